@@ -0,0 +1,422 @@
+//! Edge-avoiding À-Trous wavelet filter.
+//!
+//! Implements the filter of Dammertz et al., "Edge-Avoiding À-Trous Wavelet Transform for fast
+//! Global Illumination Filtering" (HPG 2010). Each iteration applies the 5x5 B3-spline kernel
+//! with holes of `2^i` pixels between the taps, so a few iterations cover a large radius.
+//! Every tap is weighted by how similar its color, albedo, normal and depth are to the center.
+//!
+//! The filter runs on the linear radiance of the render, the display transform is applied to
+//! the filtered radiance afterwards.
+
+use anyhow::{Result, anyhow};
+use engine_config::tone_mapping::color_map;
+use engine_config::{Aov, ToneMapping};
+use frame_buffer::frame_iterator::{Frame, FrameLayer, RADIANCE_LAYER};
+
+/// Name of the layer holding the noisy input of a denoised [`Frame`].
+pub const RAW_LAYER: &str = "raw";
+
+/// AOV layers guiding the filter. Albedo and normal are required, depth is optional.
+pub const GUIDE_AOVS: [Aov; 3] = [Aov::Albedo, Aov::Normal, Aov::Depth];
+
+/// B3-spline weights of the À-Trous kernel.
+const KERNEL: [f32; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+/// Albedo below this value is not divided out, the lighting there can't be recovered.
+const MIN_ALBEDO: f32 = 0.01;
+
+/// Parameters of the edge-stopping functions.
+///
+/// Smaller sigmas preserve more detail, larger ones remove more noise.
+#[derive(Copy, Clone, Debug)]
+pub struct DenoiseSettings {
+    /// Number of filter iterations, the kernel radius is `2^(iterations + 1)` pixels.
+    pub iterations: u32,
+    /// Color similarity of the lighting, halved after every iteration.
+    pub sigma_color: f32,
+    /// Similarity of the albedo guide.
+    pub sigma_albedo: f32,
+    /// Similarity of the normal guide.
+    pub sigma_normal: f32,
+    /// Depth difference relative to the depth of the center pixel, scaled with the step width.
+    pub sigma_depth: f32,
+    /// Exposure in stops applied to the filtered radiance, the same as in the render.
+    pub exposure: f32,
+    /// Tone mapping operator applied to the filtered radiance, the same as in the render.
+    pub tone_mapping: ToneMapping,
+}
+
+impl Default for DenoiseSettings {
+    fn default() -> Self {
+        Self {
+            iterations: 5,
+            sigma_color: 1.0,
+            sigma_albedo: 0.1,
+            sigma_normal: 0.3,
+            sigma_depth: 0.05,
+            exposure: 0.0,
+            tone_mapping: ToneMapping::default(),
+        }
+    }
+}
+
+impl DenoiseSettings {
+    /// Sets the display transform of the render, applied to the filtered radiance.
+    ///
+    /// # Arguments
+    ///
+    /// * `exposure` - Exposure in stops
+    /// * `tone_mapping` - Tone mapping operator
+    pub fn with_tone_mapping(mut self, exposure: f32, tone_mapping: ToneMapping) -> Self {
+        self.exposure = exposure;
+        self.tone_mapping = tone_mapping;
+        self
+    }
+
+    /// Exposure and tone mapping of linear radiance, like in the render.
+    fn tone_map(&self, color: [f32; 3]) -> [f32; 3] {
+        let exposure = self.exposure.exp2();
+        self.tone_mapping.apply(color.map(|c| c * exposure))
+    }
+}
+
+/// Guide values of a single pixel.
+struct Guide {
+    albedo: [f32; 3],
+    normal: [f32; 3],
+    depth: Option<f32>,
+    covered: bool,
+}
+
+/// Denoises a frame with the edge-avoiding À-Trous filter.
+///
+/// The filter works on the lighting: the linear radiance of the [`RADIANCE_LAYER`] is divided
+/// by the albedo, filtered and multiplied with the albedo again. The result is exposed, tone
+/// mapped and encoded like the render. Background pixels (coverage 0) are kept.
+///
+/// # Arguments
+///
+/// * `frame` - Frame with the `radiance`, `albedo` and `normal` layers, and optionally `depth`
+/// * `settings` - Parameters of the edge-stopping functions and the display transform
+///
+/// # Returns
+///
+/// * `Ok(Frame)` - The denoised frame, carrying the layers of `frame` with the filtered radiance
+///   and the noisy input as [`RAW_LAYER`]
+/// * `Err(_)` - If a required layer is missing or the frame is invalid
+pub fn denoise(frame: &Frame, settings: &DenoiseSettings) -> Result<Frame> {
+    frame.validate()?;
+    let radiance = required_layer(frame, RADIANCE_LAYER)?;
+    let albedo = required_layer(frame, Aov::Albedo.name())?;
+    let normal = required_layer(frame, Aov::Normal.name())?;
+    let depth = frame.layer(Aov::Depth.name());
+
+    let (width, height) = (frame.width, frame.height);
+    let guides: Vec<Guide> = (0..width * height)
+        .map(|i| Guide {
+            albedo: rgb(&albedo.data, i),
+            normal: rgb(&normal.data, i),
+            depth: depth.map(|d| d.data[i * 4]),
+            covered: albedo.data[i * 4 + 3] > 0.0,
+        })
+        .collect();
+
+    let mut lighting: Vec<[f32; 3]> = (0..width * height)
+        .map(|i| demodulate(rgb(&radiance.data, i), guides[i].albedo))
+        .collect();
+
+    for iteration in 0..settings.iterations {
+        let pass = Pass {
+            lighting: &lighting,
+            guides: &guides,
+            width,
+            height,
+            step: 1usize << iteration,
+            sigma_color: settings.sigma_color / (1u32 << iteration) as f32,
+            settings,
+        };
+        lighting = pass.run();
+    }
+
+    let filtered: Vec<[f32; 3]> = lighting
+        .iter()
+        .zip(&guides)
+        .map(|(light, guide)| remodulate(*light, guide.albedo))
+        .collect();
+    let pixels = filtered
+        .iter()
+        .zip(&guides)
+        .zip(frame.pixels.chunks_exact(4))
+        .flat_map(|((color, guide), pixel)| {
+            if !guide.covered {
+                return [pixel[0], pixel[1], pixel[2], pixel[3]];
+            }
+            let [r, g, b, _] = color_map(settings.tone_map(*color), 1.0);
+            // The alpha of a shadow catcher is kept as rendered
+            [r, g, b, pixel[3]]
+        })
+        .collect();
+
+    let raw = FrameLayer::new(
+        RAW_LAYER,
+        frame.pixels.iter().map(|p| *p as f32 / 255.0).collect(),
+    );
+    let radiance = FrameLayer::new(
+        RADIANCE_LAYER,
+        filtered
+            .iter()
+            .zip(radiance.data.chunks_exact(4))
+            .flat_map(|(color, pixel)| [color[0], color[1], color[2], pixel[3]])
+            .collect(),
+    );
+    let mut layers = frame.layers.clone();
+    layers.retain(|l| l.name != RAW_LAYER && l.name != RADIANCE_LAYER);
+    layers.push(radiance);
+    layers.push(raw);
+
+    Ok(Frame::new(width, height, pixels).with_layers(layers))
+}
+
+/// One iteration of the filter over the whole image.
+struct Pass<'a> {
+    lighting: &'a [[f32; 3]],
+    guides: &'a [Guide],
+    width: usize,
+    height: usize,
+    /// Distance between the kernel taps in pixels.
+    step: usize,
+    sigma_color: f32,
+    settings: &'a DenoiseSettings,
+}
+
+impl Pass<'_> {
+    /// Filters the image, split into bands of rows that are processed on all cores.
+    fn run(&self) -> Vec<[f32; 3]> {
+        let mut filtered = self.lighting.to_vec();
+        let threads = std::thread::available_parallelism()
+            .map_or(1, |n| n.get())
+            .min(self.height)
+            .max(1);
+        let band = (self.height.div_ceil(threads) * self.width).max(1);
+
+        std::thread::scope(|scope| {
+            for (index, pixels) in filtered.chunks_mut(band).enumerate() {
+                scope.spawn(move || {
+                    for (i, pixel) in pixels.iter_mut().enumerate() {
+                        let p = index * band + i;
+                        if self.guides[p].covered {
+                            *pixel = self.filter(p % self.width, p / self.width);
+                        }
+                    }
+                });
+            }
+        });
+        filtered
+    }
+
+    /// Weighted average of the taps around a covered pixel.
+    fn filter(&self, x: usize, y: usize) -> [f32; 3] {
+        let (width, height, step, settings) = (self.width, self.height, self.step, self.settings);
+        let center = &self.guides[y * width + x];
+        let center_color = self.lighting[y * width + x].map(f32::sqrt);
+
+        let mut sum = [0.0; 3];
+        let mut weight_sum = 0.0;
+        for (ky, ky_weight) in KERNEL.iter().enumerate() {
+            let qy = y as isize + (ky as isize - 2) * step as isize;
+            if qy < 0 || qy >= height as isize {
+                continue;
+            }
+            for (kx, kx_weight) in KERNEL.iter().enumerate() {
+                let qx = x as isize + (kx as isize - 2) * step as isize;
+                if qx < 0 || qx >= width as isize {
+                    continue;
+                }
+                let q = qy as usize * width + qx as usize;
+                let other = &self.guides[q];
+                if !other.covered {
+                    continue;
+                }
+
+                // Colors are compared in the square root encoding to tame fireflies
+                let color = self.lighting[q];
+                let color_distance = distance_squared(center_color, color.map(f32::sqrt));
+                let mut exponent = color_distance / (self.sigma_color * self.sigma_color)
+                    + distance_squared(center.albedo, other.albedo)
+                        / (settings.sigma_albedo * settings.sigma_albedo)
+                    + distance_squared(center.normal, other.normal)
+                        / (settings.sigma_normal * settings.sigma_normal);
+                if let (Some(center_depth), Some(other_depth)) = (center.depth, other.depth) {
+                    let scale = settings.sigma_depth * center_depth.abs() * step as f32;
+                    let relative = (center_depth - other_depth) / scale.max(1e-6);
+                    exponent += relative * relative;
+                }
+
+                let weight = ky_weight * kx_weight * (-exponent).exp();
+                for c in 0..3 {
+                    sum[c] += weight * color[c];
+                }
+                weight_sum += weight;
+            }
+        }
+
+        // The center tap always contributes with weight 9/64
+        sum.map(|s| s / weight_sum)
+    }
+}
+
+/// Returns `true` if the frame carries the layers required by [`denoise`].
+pub fn has_guides(frame: &Frame) -> bool {
+    [RADIANCE_LAYER, Aov::Albedo.name(), Aov::Normal.name()]
+        .into_iter()
+        .all(|name| frame.layer(name).is_some())
+}
+
+fn required_layer<'a>(frame: &'a Frame, name: &str) -> Result<&'a FrameLayer> {
+    frame
+        .layer(name)
+        .ok_or_else(|| anyhow!("Denoising requires the {name} layer"))
+}
+
+fn rgb(data: &[f32], pixel: usize) -> [f32; 3] {
+    [data[pixel * 4], data[pixel * 4 + 1], data[pixel * 4 + 2]]
+}
+
+fn demodulate(color: [f32; 3], albedo: [f32; 3]) -> [f32; 3] {
+    [0, 1, 2].map(|c| {
+        if albedo[c] > MIN_ALBEDO {
+            color[c] / albedo[c]
+        } else {
+            color[c]
+        }
+    })
+}
+
+fn remodulate(lighting: [f32; 3], albedo: [f32; 3]) -> [f32; 3] {
+    [0, 1, 2].map(|c| {
+        if albedo[c] > MIN_ALBEDO {
+            lighting[c] * albedo[c]
+        } else {
+            lighting[c]
+        }
+    })
+}
+
+fn distance_squared(a: [f32; 3], b: [f32; 3]) -> f32 {
+    (0..3).map(|c| (a[c] - b[c]) * (a[c] - b[c])).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Settings that display the radiance with the square root encoding only
+    fn linear() -> DenoiseSettings {
+        DenoiseSettings::default().with_tone_mapping(0.0, ToneMapping::None)
+    }
+
+    /// Linear radiance layer of gray display values without tone mapping
+    fn radiance(pixels: &[u8]) -> FrameLayer {
+        FrameLayer::new(
+            RADIANCE_LAYER,
+            pixels
+                .chunks_exact(4)
+                .flat_map(|p| {
+                    let [r, g, b] = [0, 1, 2].map(|c| (p[c] as f32 / 255.0).powi(2));
+                    [r, g, b, p[3] as f32 / 255.0]
+                })
+                .collect(),
+        )
+    }
+
+    /// 4x1 frame, left half and right half have different normals
+    fn frame(pixels: Vec<u8>) -> Frame {
+        let up = [0.0, 1.0, 0.0, 1.0];
+        let side = [1.0, 0.0, 0.0, 1.0];
+        let radiance = radiance(&pixels);
+        Frame::new(4, 1, pixels).with_layers(vec![
+            radiance,
+            FrameLayer::new("albedo", [[1.0; 4]; 4].concat()),
+            FrameLayer::new("normal", [up, up, side, side].concat()),
+        ])
+    }
+
+    /// 4x2 frame with uniform lighting on two textures, the last pixel is background
+    fn textured(lighting: f32, settings: &DenoiseSettings) -> Frame {
+        let textures = [[0.8, 0.4, 0.2, 1.0], [0.2, 0.6, 0.9, 1.0]];
+        let albedo: Vec<[f32; 4]> = (0..8)
+            .map(|i| match i {
+                7 => [0.0; 4],
+                i => textures[i % 4 / 2],
+            })
+            .collect();
+        let radiance: Vec<[f32; 4]> = albedo
+            .iter()
+            .map(|a| [lighting * a[0], lighting * a[1], lighting * a[2], a[3]])
+            .collect();
+        let pixels: Vec<u8> = radiance
+            .iter()
+            .flat_map(|r| color_map(settings.tone_map([r[0], r[1], r[2]]), r[3]))
+            .collect();
+        Frame::new(4, 2, pixels).with_layers(vec![
+            FrameLayer::new(RADIANCE_LAYER, radiance.concat()),
+            FrameLayer::new("albedo", albedo.concat()),
+            FrameLayer::new("normal", [[0.0, 1.0, 0.0, 1.0]; 8].concat()),
+        ])
+    }
+
+    #[test]
+    fn smooths_noise_but_keeps_normal_edges() {
+        let noisy = frame(vec![
+            100, 100, 100, 255, 200, 200, 200, 255, 0, 0, 0, 255, 0, 0, 0, 255,
+        ]);
+
+        let denoised = denoise(&noisy, &linear()).unwrap();
+
+        // The two left pixels move towards each other
+        assert!(denoised.pixels[0] > 100 && denoised.pixels[4] < 200);
+        assert!(denoised.pixels[4].abs_diff(denoised.pixels[0]) < 50);
+        // Nothing leaks across the edge in the normals
+        assert_eq!(denoised.pixels[8], 0);
+        assert_eq!(denoised.layer(RAW_LAYER).unwrap().data[0], 100.0 / 255.0);
+        // The radiance layer carries the filtered radiance
+        let radiance = &denoised.layer(RADIANCE_LAYER).unwrap().data;
+        assert!(radiance[0] > (100.0f32 / 255.0).powi(2));
+    }
+
+    #[test]
+    fn keeps_noise_free_frames() {
+        let settings = linear();
+        let frame = textured(0.5, &settings);
+
+        let denoised = denoise(&frame, &settings).unwrap();
+
+        assert_eq!(denoised.pixels, frame.pixels);
+    }
+
+    #[test]
+    fn keeps_noise_free_tone_mapped_frames() {
+        // Reinhard is not a power law, the lighting can't be recovered from its display output
+        let settings = DenoiseSettings::default().with_tone_mapping(0.5, ToneMapping::Reinhard);
+        let frame = textured(3.0, &settings);
+
+        let denoised = denoise(&frame, &settings).unwrap();
+
+        for (denoised, pixel) in denoised.pixels.iter().zip(&frame.pixels) {
+            assert!(denoised.abs_diff(*pixel) <= 1, "{denoised} != {pixel}");
+        }
+    }
+
+    #[test]
+    fn requires_guide_layers() {
+        let frame = Frame::new(1, 1, vec![0, 0, 0, 255]);
+        assert!(!has_guides(&frame));
+        assert!(denoise(&frame, &DenoiseSettings::default()).is_err());
+
+        // Guides without the radiance are not enough
+        let mut frame = textured(0.5, &linear());
+        frame.layers.retain(|l| l.name != RADIANCE_LAYER);
+        assert!(!has_guides(&frame));
+        assert!(denoise(&frame, &linear()).is_err());
+    }
+}
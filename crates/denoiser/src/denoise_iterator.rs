@@ -0,0 +1,135 @@
+//! Denoising wrapper around a [`FrameIterator`].
+
+use anyhow::Result;
+use frame_buffer::frame_iterator::{Frame, FrameIterator};
+
+use crate::atrous::{DenoiseSettings, denoise, has_guides};
+
+/// Passes between two denoised frames of a progressive render.
+pub const DEFAULT_DENOISE_INTERVAL: u32 = 16;
+
+/// [`FrameIterator`] that denoises the frames of another iterator.
+///
+/// Denoising a large frame takes far longer than a render pass, so only the first frame,
+/// every [`interval`](Self::with_interval)-th frame and the final frame are denoised. The
+/// frames in between repeat the last denoised frame.
+///
+/// Frames without the guide layers are passed through unchanged, so a render
+/// without AOVs still shows up.
+pub struct DenoisingFrameIterator {
+    /// The iterator producing the noisy frames.
+    inner: Box<dyn FrameIterator>,
+    /// Parameters passed to [`denoise`].
+    settings: DenoiseSettings,
+    /// Frames between two denoised frames.
+    interval: u32,
+    /// Frames returned so far.
+    frame_index: u32,
+    /// The last denoised frame, shown until the next one is ready.
+    last_denoised: Option<Frame>,
+    /// Whether the missing guide layers were already reported.
+    warned: bool,
+}
+
+impl DenoisingFrameIterator {
+    /// Creates a new [`DenoisingFrameIterator`] wrapping `inner`, denoising every
+    /// [`DEFAULT_DENOISE_INTERVAL`] frames.
+    pub fn new(inner: Box<dyn FrameIterator>, settings: DenoiseSettings) -> Self {
+        Self {
+            inner,
+            settings,
+            interval: DEFAULT_DENOISE_INTERVAL,
+            frame_index: 0,
+            last_denoised: None,
+            warned: false,
+        }
+    }
+
+    /// Sets the number of frames between two denoised frames, 1 denoises every frame.
+    pub fn with_interval(mut self, interval: u32) -> Self {
+        self.interval = interval.max(1);
+        self
+    }
+}
+
+impl FrameIterator for DenoisingFrameIterator {
+    /// Delegates to the wrapped [`FrameIterator`].
+    fn has_next(&self) -> bool {
+        self.inner.has_next()
+    }
+
+    /// Returns the next frame of the wrapped [`FrameIterator`], denoised if it has the guide layers
+    /// and is due, or the last denoised frame otherwise.
+    fn next(&mut self) -> Result<Frame> {
+        let frame = self.inner.next()?;
+        let index = self.frame_index;
+        self.frame_index += 1;
+        if !has_guides(&frame) {
+            if !self.warned {
+                log::warn!("Frame has no radiance, albedo and normal layers, skipping denoising");
+                self.warned = true;
+            }
+            return Ok(frame);
+        }
+
+        let due = index.is_multiple_of(self.interval) || !self.inner.has_next();
+        match &self.last_denoised {
+            Some(last) if !due => Ok(last.clone()),
+            _ => {
+                let denoised = denoise(&frame, &self.settings)?;
+                self.last_denoised = Some(denoised.clone());
+                Ok(denoised)
+            }
+        }
+    }
+
+    /// Delegates to the wrapped [`FrameIterator`].
+    fn destroy(&mut self) {
+        self.inner.destroy()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use engine_config::ToneMapping;
+    use frame_buffer::frame_iterator::{FrameLayer, RADIANCE_LAYER};
+
+    /// Yields 1x1 frames with the given gray values, without tone mapping.
+    struct Frames(Vec<u8>);
+
+    impl FrameIterator for Frames {
+        fn has_next(&self) -> bool {
+            !self.0.is_empty()
+        }
+
+        fn next(&mut self) -> Result<Frame> {
+            let value = self.0.remove(0);
+            let radiance = (value as f32 / 255.0).powi(2);
+            Ok(
+                Frame::new(1, 1, vec![value, value, value, 255]).with_layers(vec![
+                    FrameLayer::new(RADIANCE_LAYER, vec![radiance, radiance, radiance, 1.0]),
+                    FrameLayer::new("albedo", vec![1.0; 4]),
+                    FrameLayer::new("normal", vec![0.0, 1.0, 0.0, 1.0]),
+                ]),
+            )
+        }
+
+        fn destroy(&mut self) {}
+    }
+
+    #[test]
+    fn denoises_every_interval_and_the_final_frame() {
+        let inner = Box::new(Frames(vec![10, 20, 30, 40, 50]));
+        let settings = DenoiseSettings::default().with_tone_mapping(0.0, ToneMapping::None);
+        let mut frames = DenoisingFrameIterator::new(inner, settings).with_interval(3);
+
+        let mut values = Vec::new();
+        while frames.has_next() {
+            values.push(frames.next().unwrap().pixels[0]);
+        }
+
+        // Frames 0 and 3 are due, the final frame is always denoised
+        assert_eq!(values, [10, 10, 10, 40, 50]);
+    }
+}
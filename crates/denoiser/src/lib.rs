@@ -0,0 +1,41 @@
+//! # Denoiser
+//!
+//! `denoiser` removes the noise of progressive renders at low sample counts.
+//! It runs on the CPU on finished [`Frame`](frame_buffer::frame_iterator::Frame)s,
+//! so it works with every rendering engine that provides the guide layers.
+//!
+//! ## Features
+//!
+//! - **Edge-Avoiding À-Trous Filter**: [`denoise`] blurs with a growing 5x5 B-spline kernel
+//!   and stops at edges of the first-hit albedo, normal and depth layers.
+//! - **Albedo Demodulation**: Textures are divided out before filtering and multiplied back
+//!   afterwards, so only the lighting is blurred.
+//! - **Linear Radiance**: The filter runs on the linear radiance of the render, exposure and tone
+//!   mapping of the [`DenoiseSettings`] are applied to the result.
+//! - **Progressive Rendering**: [`DenoisingFrameIterator`] denoises the frames of another
+//!   [`FrameIterator`](frame_buffer::frame_iterator::FrameIterator) every few passes and at the end.
+//! - **Parallel**: The rows of every filter iteration are split across all cores.
+//!
+//! ## Required Layers
+//!
+//! The frame has to carry the `radiance` layer
+//! ([`RADIANCE_LAYER`](frame_buffer::frame_iterator::RADIANCE_LAYER)) and the `albedo` and
+//! `normal` AOV layers ([`GUIDE_AOVS`] lists them together with the optional `depth` layer).
+//! The noisy input is kept as the `raw` layer of the denoised frame.
+//!
+//! ## Usage Example
+//!
+//! ```rust, ignore
+//! use denoiser::{denoise, DenoiseSettings};
+//! use engine_config::ToneMapping;
+//!
+//! let settings = DenoiseSettings::default().with_tone_mapping(0.0, ToneMapping::Reinhard);
+//! let denoised = denoise(&frame, &settings)?;
+//! let raw = denoised.layer(denoiser::RAW_LAYER).unwrap();
+//! ```
+
+pub mod atrous;
+pub mod denoise_iterator;
+
+pub use atrous::{DenoiseSettings, GUIDE_AOVS, RAW_LAYER, denoise, has_guides};
+pub use denoise_iterator::{DEFAULT_DENOISE_INTERVAL, DenoisingFrameIterator};
@@ -1,5 +1,5 @@
 //! Axis-Aligned Bounding Box (AABB) utilities.
-use glam::Vec3;
+use glam::{Mat4, Vec3};
 
 /// An axis-aligned bounding box defined by minimum and maximum corners.
 ///
@@ -49,4 +49,56 @@ impl AABB {
     pub fn centroid(&self) -> Vec3 {
         (self.min + self.max) * 0.5
     }
+
+    /// Returns the bounding box of this box after an affine transform.
+    ///
+    /// All eight corners are transformed, so the result stays conservative
+    /// under rotation.
+    pub fn transformed(&self, transform: Mat4) -> AABB {
+        let mut aabb = AABB::empty();
+        for corner in 0..8 {
+            let p = Vec3::new(
+                if corner & 1 == 0 {
+                    self.min.x
+                } else {
+                    self.max.x
+                },
+                if corner & 2 == 0 {
+                    self.min.y
+                } else {
+                    self.max.y
+                },
+                if corner & 4 == 0 {
+                    self.min.z
+                } else {
+                    self.max.z
+                },
+            );
+            aabb.expand(transform.transform_point3(p));
+        }
+        aabb
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_4;
+
+    #[test]
+    fn transformed_bounds_enclose_rotated_corners() {
+        let cube = AABB {
+            min: Vec3::splat(-1.0),
+            max: Vec3::splat(1.0),
+        };
+        let transform =
+            Mat4::from_translation(Vec3::new(3.0, 0.0, -2.0)) * Mat4::from_rotation_z(FRAC_PI_4);
+        let aabb = cube.transformed(transform);
+
+        // The corners on the diagonals of the xy square move out to sqrt(2)
+        let expected_min = Vec3::new(3.0 - 2f32.sqrt(), -(2f32.sqrt()), -3.0);
+        let expected_max = Vec3::new(3.0 + 2f32.sqrt(), 2f32.sqrt(), -1.0);
+        assert!(aabb.min.abs_diff_eq(expected_min, 1e-5), "{aabb:?}");
+        assert!(aabb.max.abs_diff_eq(expected_max, 1e-5), "{aabb:?}");
+    }
 }
@@ -85,20 +85,55 @@ impl BVH {
     /// The construction uses a median split along the longest axis
     /// of the node's bounding box.
     pub fn new(triangles: &[GPUTriangle]) -> Self {
-        let mut indices: Vec<u32> = (0..triangles.len() as u32).collect();
+        let bounds: Vec<AABB> = triangles.iter().map(triangle_aabb).collect();
+        let centroids: Vec<Vec3> = triangles.iter().map(triangle_centroid).collect();
+        Self::build(&bounds, &centroids, MAX_LEAF_SIZE)
+    }
+
+    /// Builds a new BVH over arbitrary primitives given by their bounding boxes,
+    /// e.g. the instances of a top-level BVH.
+    ///
+    /// Primitives are split by the centroids of their boxes.
+    pub fn from_bounds(bounds: &[AABB], max_leaf_size: usize) -> Self {
+        let centroids: Vec<Vec3> = bounds.iter().map(AABB::centroid).collect();
+        Self::build(bounds, &centroids, max_leaf_size.max(1))
+    }
+
+    /// Returns the bounding box of the whole hierarchy.
+    pub fn bounds(&self) -> AABB {
+        self.nodes.first().map_or(AABB::empty(), |root| AABB {
+            min: root.aabb_min,
+            max: root.aabb_max,
+        })
+    }
+
+    fn build(bounds: &[AABB], centroids: &[Vec3], max_leaf_size: usize) -> Self {
+        let mut indices: Vec<u32> = (0..bounds.len() as u32).collect();
         let mut nodes = Vec::new();
 
-        build_node(triangles, &mut indices, &mut nodes, 0, triangles.len());
+        let primitives = Primitives {
+            bounds,
+            centroids,
+            max_leaf_size,
+        };
+        build_node(&primitives, &mut indices, &mut nodes, 0, bounds.len());
 
         Self { nodes, indices }
     }
 }
 
+/// Bounding boxes and split positions of the primitives a BVH is built over.
+struct Primitives<'a> {
+    bounds: &'a [AABB],
+    centroids: &'a [Vec3],
+    max_leaf_size: usize,
+}
+
 /// Recursively builds a BVH node.
 ///
 /// Returns the index of the newly created node.
 fn build_node(
-    triangles: &[GPUTriangle],
+    primitives: &Primitives,
     indices: &mut [u32],
     nodes: &mut Vec<BVHNode>,
     first: usize,
@@ -108,14 +143,12 @@ fn build_node(
     nodes.push(BVHNode::default());
 
     let mut aabb = AABB::empty();
-    for i in first..first + count {
-        let tri = &triangles[indices[i] as usize];
-        aabb.expand(tri.v0); //Growing Box to include all Vertices, but keeping it minimal
-        aabb.expand(tri.v1);
-        aabb.expand(tri.v2);
+    for i in &indices[first..first + count] {
+        //Growing Box to include all primitives, but keeping it minimal
+        aabb = aabb.union(&primitives.bounds[*i as usize]);
     }
 
-    if count <= MAX_LEAF_SIZE {
+    if count <= primitives.max_leaf_size {
         //checks if the current Node is a Leaf
         //both left and right are 0 as they do not have any nodes underneath them, therefore referencing the root as default
         nodes[node_index as usize] = BVHNode::leaf(aabb.min, aabb.max, first as u32, count as u32);
@@ -136,19 +169,28 @@ fn build_node(
 
     let mid = first + count / 2; //partial sorting, better performance than actual sorting
     indices[first..first + count].select_nth_unstable_by(mid - first, |a, b| {
-        let ca = triangle_centroid(&triangles[*a as usize])[axis];
-        let cb = triangle_centroid(&triangles[*b as usize])[axis];
+        let ca = primitives.centroids[*a as usize][axis];
+        let cb = primitives.centroids[*b as usize][axis];
         ca.partial_cmp(&cb).unwrap()
     });
 
-    let left = build_node(triangles, indices, nodes, first, mid - first);
-    let right = build_node(triangles, indices, nodes, mid, first + count - mid);
+    let left = build_node(primitives, indices, nodes, first, mid - first);
+    let right = build_node(primitives, indices, nodes, mid, first + count - mid);
 
     nodes[node_index as usize] = BVHNode::internal(aabb.min, aabb.max, left, right);
 
     node_index
 }
 
+/// Computes the bounding box of a triangle.
+fn triangle_aabb(tri: &GPUTriangle) -> AABB {
+    let mut aabb = AABB::empty();
+    aabb.expand(tri.v0);
+    aabb.expand(tri.v1);
+    aabb.expand(tri.v2);
+    aabb
+}
+
 /// Computes the centroid of a triangle.
 fn triangle_centroid(tri: &GPUTriangle) -> Vec3 {
     (tri.v0 + tri.v1 + tri.v2) / 3.0
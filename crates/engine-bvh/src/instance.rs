@@ -0,0 +1,250 @@
+//! Mesh instances and the two-level BVH built over them.
+use std::ops::Range;
+
+use glam::Mat4;
+use bytemuck::{Pod, Zeroable};
+
+use crate::aabb::AABB;
+use crate::bvh::{BVH, BVHNode};
+use crate::triangle::GPUTriangle;
+
+/// Maximum number of instances stored in a leaf of the top-level BVH.
+///
+/// Every instance in a leaf starts a traversal of its bottom-level BVH,
+/// so top-level leaves are kept small.
+const MAX_INSTANCES_PER_LEAF: usize = 2;
+
+/// A placement of a mesh's bottom-level BVH in the scene.
+///
+/// The layout is `#[repr(C)]` and matches the `Instance` struct of the shaders.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+pub struct Instance {
+    /// Column major transform from mesh space to world space.
+    pub object_to_world: [[f32; 4]; 4],
+    /// Inverse of `object_to_world`, used to move rays into mesh space.
+    pub world_to_object: [[f32; 4]; 4],
+    /// Index of the root node of the instanced bottom-level BVH.
+    pub blas_root: u32,
+    pub _pad: [u32; 3],
+}
+
+impl Instance {
+    /// Creates an instance of the bottom-level BVH rooted at `blas_root`.
+    pub fn new(object_to_world: Mat4, blas_root: u32) -> Self {
+        Self {
+            object_to_world: object_to_world.to_cols_array_2d(),
+            world_to_object: object_to_world.inverse().to_cols_array_2d(),
+            blas_root,
+            _pad: [0; 3],
+        }
+    }
+}
+
+/// Bottom-level BVHs of several meshes and a top-level BVH over their instances,
+/// packed into flat buffers for the GPU.
+///
+/// `nodes` holds every bottom-level BVH followed by the top-level BVH. `indices`
+/// holds the triangle indices of the bottom-level leaves followed by the instance
+/// indices of the top-level leaves, all relative to the start of their buffer.
+#[derive(Default)]
+pub struct TwoLevelBVH {
+    /// All BVH nodes, the top-level BVH last.
+    pub nodes: Vec<BVHNode>,
+    /// Triangle and instance indices referenced by leaf nodes.
+    pub indices: Vec<u32>,
+    /// The instances referenced by the top-level leaves.
+    pub instances: Vec<Instance>,
+    /// Index of the root node of the top-level BVH.
+    pub root: u32,
+}
+
+impl TwoLevelBVH {
+    /// Builds one bottom-level BVH per mesh and a top-level BVH over the given instances.
+    ///
+    /// ## Parameters
+    /// 'triangles': the triangles of all meshes <br>
+    /// 'meshes': the range of `triangles` that makes up each mesh <br>
+    /// 'instances': the mesh index and the mesh to world transform of each instance
+    ///
+    /// Instances of meshes without triangles are left out.
+    pub fn new(
+        triangles: &[GPUTriangle],
+        meshes: &[Range<usize>],
+        instances: &[(usize, Mat4)],
+    ) -> Self {
+        let mut nodes = Vec::new();
+        let mut indices = Vec::new();
+
+        // (root node, bounds) of each bottom-level BVH
+        let blases: Vec<Option<(u32, AABB)>> = meshes
+            .iter()
+            .map(|range| {
+                if range.is_empty() {
+                    return None;
+                }
+                let blas = BVH::new(&triangles[range.clone()]);
+                let root = nodes.len() as u32;
+                let bounds = blas.bounds();
+                append(&mut nodes, &mut indices, blas, range.start as u32);
+                Some((root, bounds))
+            })
+            .collect();
+
+        let (gpu_instances, bounds): (Vec<Instance>, Vec<AABB>) = instances
+            .iter()
+            .filter_map(|(mesh, transform)| {
+                let (root, bounds) = (*blases.get(*mesh)?)?;
+                Some((
+                    Instance::new(*transform, root),
+                    bounds.transformed(*transform),
+                ))
+            })
+            .unzip();
+
+        if gpu_instances.is_empty() {
+            return Self::default();
+        }
+
+        let root = nodes.len() as u32;
+        let tlas = BVH::from_bounds(&bounds, MAX_INSTANCES_PER_LEAF);
+        append(&mut nodes, &mut indices, tlas, 0);
+
+        Self {
+            nodes,
+            indices,
+            instances: gpu_instances,
+            root,
+        }
+    }
+}
+
+/// Appends a BVH to the flat buffers, moving its child and primitive references
+/// to their new positions and its primitive indices by `primitive_offset`.
+fn append(nodes: &mut Vec<BVHNode>, indices: &mut Vec<u32>, bvh: BVH, primitive_offset: u32) {
+    let node_offset = nodes.len() as u32;
+    let index_offset = indices.len() as u32;
+    nodes.extend(bvh.nodes.into_iter().map(|mut node| {
+        if node.primitive_count > 0 {
+            node.first_primitive += index_offset;
+        } else {
+            node.left += node_offset;
+            node.right += node_offset;
+        }
+        node
+    }));
+    indices.extend(bvh.indices.into_iter().map(|i| i + primitive_offset));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::Vec3;
+
+    /// A small triangle in the z = 0 plane starting at `x`.
+    fn triangle(x: f32) -> GPUTriangle {
+        GPUTriangle {
+            v0: Vec3::new(x, 0.0, 0.0),
+            v1: Vec3::new(x + 0.5, 0.0, 0.0),
+            v2: Vec3::new(x, 0.5, 0.0),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn two_meshes_are_packed_before_the_top_level_bvh() {
+        // Mesh 0 fits into one leaf, mesh 1 needs an inner node over two leaves
+        let triangles: Vec<GPUTriangle> = (0..131).map(|i| triangle(i as f32)).collect();
+        let meshes = [0..2, 2..131];
+        let transform = Mat4::from_translation(Vec3::new(0.0, 0.0, 5.0));
+        let bvh = TwoLevelBVH::new(&triangles, &meshes, &[(1, transform)]);
+
+        assert_eq!(bvh.nodes.len(), 5);
+        assert_eq!(bvh.root, 4);
+        assert_eq!(bvh.indices.len(), 132);
+
+        let mesh0 = bvh.nodes[0];
+        assert_eq!((mesh0.first_primitive, mesh0.primitive_count), (0, 2));
+        assert_eq!(&bvh.indices[..2], &[0, 1]);
+
+        let mesh1 = bvh.nodes[1];
+        assert_eq!(mesh1.primitive_count, 0);
+        assert_eq!((mesh1.left, mesh1.right), (2, 3));
+        let (left, right) = (bvh.nodes[2], bvh.nodes[3]);
+        assert_eq!((left.first_primitive, left.primitive_count), (2, 64));
+        assert_eq!((right.first_primitive, right.primitive_count), (66, 65));
+        let mut mesh1_indices = bvh.indices[2..131].to_vec();
+        mesh1_indices.sort_unstable();
+        assert_eq!(mesh1_indices, (2..131).collect::<Vec<u32>>());
+
+        let tlas = bvh.nodes[4];
+        assert_eq!((tlas.first_primitive, tlas.primitive_count), (131, 1));
+        assert_eq!(bvh.indices[131], 0);
+        assert_eq!(tlas.aabb_min, Vec3::new(2.0, 0.0, 5.0));
+        assert_eq!(tlas.aabb_max, Vec3::new(130.5, 0.5, 5.0));
+
+        assert_eq!(bvh.instances.len(), 1);
+        assert_eq!(bvh.instances[0].blas_root, 1);
+        assert_eq!(
+            bvh.instances[0].object_to_world,
+            transform.to_cols_array_2d()
+        );
+    }
+
+    #[test]
+    fn top_level_children_are_offset_past_the_meshes() {
+        let triangles = [triangle(0.0)];
+        let instances: Vec<(usize, Mat4)> = (0..3)
+            .map(|i| {
+                (
+                    0,
+                    Mat4::from_translation(Vec3::new(i as f32 * 10.0, 0.0, 0.0)),
+                )
+            })
+            .collect();
+        let meshes = std::slice::from_ref(&(0..1));
+        let bvh = TwoLevelBVH::new(&triangles, meshes, &instances);
+
+        // One mesh leaf, then the top-level root over a leaf of one and a leaf of two instances
+        assert_eq!(bvh.nodes.len(), 4);
+        assert_eq!(bvh.root, 1);
+        let root = bvh.nodes[1];
+        assert_eq!(root.primitive_count, 0);
+        assert_eq!((root.left, root.right), (2, 3));
+        assert_eq!(
+            (bvh.nodes[2].first_primitive, bvh.nodes[2].primitive_count),
+            (1, 1)
+        );
+        assert_eq!(
+            (bvh.nodes[3].first_primitive, bvh.nodes[3].primitive_count),
+            (2, 2)
+        );
+        let mut instance_indices = bvh.indices[1..].to_vec();
+        instance_indices.sort_unstable();
+        assert_eq!(instance_indices, [0, 1, 2]);
+        assert!(bvh.instances.iter().all(|instance| instance.blas_root == 0));
+    }
+
+    #[test]
+    fn instances_of_empty_meshes_are_left_out() {
+        let triangles = [triangle(0.0)];
+        let meshes = [0..0, 0..1];
+        let instances = [
+            (0, Mat4::IDENTITY),
+            (1, Mat4::IDENTITY),
+            (7, Mat4::IDENTITY),
+        ];
+        let bvh = TwoLevelBVH::new(&triangles, &meshes, &instances);
+
+        // The empty mesh has no nodes, so the other mesh starts the buffer
+        assert_eq!(bvh.nodes.len(), 2);
+        assert_eq!(bvh.root, 1);
+        assert_eq!(bvh.instances.len(), 1);
+        assert_eq!(bvh.instances[0].blas_root, 0);
+        assert_eq!(bvh.indices, [0, 0]);
+
+        let empty = TwoLevelBVH::new(&triangles, &meshes, &[(0, Mat4::IDENTITY)]);
+        assert!(empty.nodes.is_empty() && empty.indices.is_empty() && empty.instances.is_empty());
+        assert_eq!(empty.root, 0);
+    }
+}
@@ -4,12 +4,15 @@
 //! - **Triangle Representation**: [`GPUTriangle`] stores vertex positions and indices in a GPU-friendly layout.
 //! - **Bounding Boxes**: [`AABB`] provides axis-aligned bounding boxes with utility methods like union and centroid computation.
 //! - **Acceleration Structure**: [`BVH`] organizes triangles into a BVH for fast ray intersection queries.
+//! - **Instancing**: [`TwoLevelBVH`] places per-mesh bottom-level BVHs into the scene through [`Instance`] transforms,
+//!   found by a top-level BVH over the instances.
 //!
 //! ## Core Modules
 //!
 //! - [`triangle`]: Defines the [`GPUTriangle`] type for GPU-compatible triangles.
 //! - [`aabb`]: Defines [`AABB`] and related utilities for axis-aligned bounding boxes.
 //! - [`bvh`]: Contains [`BVH`] and [`BVHNode`] for constructing acceleration structures.
+//! - [`instance`]: Contains [`Instance`] and [`TwoLevelBVH`] for building one BVH per mesh and sharing it between instances.
 //!
 //! ## Usage Example
 //!
@@ -40,8 +43,12 @@
 //!
 //! The BVH is built recursively using a median split along the longest axis
 //! of each node's bounding box, producing leaf nodes with up to `MAX_LEAF_SIZE` triangles.
+//! A [`TwoLevelBVH`] packs the bottom-level BVHs and the top-level BVH into one node buffer,
+//! so a traversal moves from an instance leaf into the bottom-level root of that instance.
 pub mod triangle;
 
 pub mod aabb;
 
 pub mod bvh;
+
+pub mod instance;
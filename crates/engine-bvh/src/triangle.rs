@@ -1,5 +1,5 @@
 //! GPU-friendly triangle representation.
-use glam::Vec3;
+use glam::{Vec3, Vec4};
 use bytemuck::{Pod, Zeroable};
 
 /// A triangle structure optimized for GPU usage.
@@ -21,9 +21,21 @@ pub struct GPUTriangle {
     pub v2: Vec3,
     /// Index of the third vertex.
     pub v2_index: u32,
+    /// Vertex normal at the first vertex, zero if the triangle is flat shaded.
+    pub n0: Vec3,
     /// Index of the mesh this triangle belongs to.
     pub mesh_index: u32,
+    /// Vertex normal at the second vertex.
+    pub n1: Vec3,
     pub _pad0: u32,
+    /// Vertex normal at the third vertex.
+    pub n2: Vec3,
     pub _pad1: u32,
-    pub _pad2: u32,
+    /// Tangent at the first vertex, `w` is the handedness of the bitangent.
+    /// Zero if the triangle has no texture coordinates.
+    pub t0: Vec4,
+    /// Tangent at the second vertex.
+    pub t1: Vec4,
+    /// Tangent at the third vertex.
+    pub t2: Vec4,
 }
@@ -0,0 +1,139 @@
+//! Arbitrary output variables rendered alongside the beauty pass.
+//!
+//! This module defines the [`Aov`] enum, naming one auxiliary layer, and the [`AovSet`]
+//! bit set that selects which layers the rendering engine writes.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Auxiliary output layer, taken from the first hit of a camera ray through the pixel center.
+///
+/// Every layer stores four floats per pixel, the fourth one is the coverage
+/// (1.0 if the ray hit an object, 0.0 for the background).
+///
+/// The discriminants are the bit positions in [`AovSet`] and the shader's `aov_flags`.
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Aov {
+    /// Distance to the hit along the camera's view axis.
+    Depth = 0,
+    /// World space shading normal, facing the camera.
+    Normal = 1,
+    /// Base color of the surface including its texture.
+    Albedo = 2,
+    /// Kind of the hit object in `x` (1 ground, 2 sphere, 3 mesh, 4 light) and its index in `y`.
+    ObjectId = 3,
+    /// Texture coordinates in `x` and `y`.
+    Uv = 4,
+}
+
+impl Aov {
+    /// All layers, in the order they are packed on the GPU.
+    pub const ALL: [Aov; 5] = [Aov::Depth, Aov::Normal, Aov::Albedo, Aov::ObjectId, Aov::Uv];
+
+    /// Returns the lowercase name used for frame layers, file names and on the command line.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Aov::Depth => "depth",
+            Aov::Normal => "normal",
+            Aov::Albedo => "albedo",
+            Aov::ObjectId => "object_id",
+            Aov::Uv => "uv",
+        }
+    }
+
+    fn bit(&self) -> u32 {
+        1 << (*self as u32)
+    }
+}
+
+impl fmt::Display for Aov {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Aov {
+    type Err = String;
+
+    /// Parses a layer from its name, ignoring case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Aov::ALL
+            .into_iter()
+            .find(|a| a.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("unknown AOV: {s}"))
+    }
+}
+
+/// Set of [`Aov`] layers requested from the rendering engine.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let aovs = AovSet::empty().with(Aov::Depth).with(Aov::Normal);
+/// assert!(aovs.contains(Aov::Normal));
+/// assert_eq!(aovs.len(), 2);
+/// ```
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AovSet(u32);
+
+impl AovSet {
+    /// Creates a set without any layers.
+    pub fn empty() -> Self {
+        Self(0)
+    }
+
+    /// Creates a set with all layers.
+    pub fn all() -> Self {
+        Aov::ALL.into_iter().collect()
+    }
+
+    /// Returns the set with `aov` added, for method chaining.
+    pub fn with(mut self, aov: Aov) -> Self {
+        self.insert(aov);
+        self
+    }
+
+    /// Adds `aov` to the set.
+    pub fn insert(&mut self, aov: Aov) {
+        self.0 |= aov.bit();
+    }
+
+    /// Removes `aov` from the set.
+    pub fn remove(&mut self, aov: Aov) {
+        self.0 &= !aov.bit();
+    }
+
+    /// Returns `true` if `aov` is in the set.
+    pub fn contains(&self, aov: Aov) -> bool {
+        self.0 & aov.bit() != 0
+    }
+
+    /// Returns `true` if no layer is requested.
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns the number of requested layers.
+    pub fn len(&self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    /// Returns the requested layers in GPU packing order.
+    pub fn iter(&self) -> impl Iterator<Item = Aov> + '_ {
+        Aov::ALL.into_iter().filter(|a| self.contains(*a))
+    }
+
+    /// Returns the raw bit mask passed to the shaders.
+    pub fn bits(&self) -> u32 {
+        self.0
+    }
+}
+
+impl FromIterator<Aov> for AovSet {
+    fn from_iter<I: IntoIterator<Item = Aov>>(iter: I) -> Self {
+        iter.into_iter().fold(Self::empty(), AovSet::with)
+    }
+}
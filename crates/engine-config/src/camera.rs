@@ -1,10 +1,51 @@
 //! Camera configuration and projection parameters.
 //!
 //! This module defines the [`Camera`] struct, which controls the viewpoint, orientation,
-//! and field of view for the rendered scene.
+//! and field of view for the rendered scene, and the [`Projection`] enum selecting how
+//! image positions are mapped to primary rays.
 
 use bytemuck::{Pod, Zeroable};
 
+/// Mapping from image positions to primary rays.
+///
+/// The discriminants are passed to the GPU shaders in [`Camera::projection`].
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Projection {
+    /// Pinhole or thin lens camera looking through the view pane.
+    #[default]
+    Perspective = 0,
+    /// Parallel rays along the view direction, `ortho_width` units wide.
+    Orthographic = 1,
+    /// Full 360x180 degree panorama around the camera position.
+    Equirectangular = 2,
+    /// Perspective views of the left and right eye next to each other.
+    StereoSideBySide = 3,
+    /// Perspective views of the left and right eye, the left one on top.
+    StereoTopBottom = 4,
+}
+
+impl TryFrom<u32> for Projection {
+    type Error = u32;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Projection::Perspective),
+            1 => Ok(Projection::Orthographic),
+            2 => Ok(Projection::Equirectangular),
+            3 => Ok(Projection::StereoSideBySide),
+            4 => Ok(Projection::StereoTopBottom),
+            other => Err(other),
+        }
+    }
+}
+
+impl From<Projection> for u32 {
+    fn from(value: Projection) -> Self {
+        value as u32
+    }
+}
+
 /// Camera configuration for scene rendering.
 ///
 /// `Camera` defines the position, direction, and projection parameters used to generate
@@ -21,6 +62,18 @@ use bytemuck::{Pod, Zeroable};
 /// - **Wider pane or shorter distance**: Wider field of view
 /// - **Narrower pane or longer distance**: Narrower field of view (telephoto effect)
 ///
+/// # Depth of Field
+///
+/// With an `aperture_radius` above zero the camera acts as a thin lens: rays start on the
+/// aperture and converge on the plane `focus_distance` units in front of the camera.
+/// `aperture_blades` shapes the aperture as a regular polygon (0 = circular).
+///
+/// # Projections
+///
+/// `projection` selects a [`Projection`]. Orthographic cameras cover `ortho_width` units
+/// horizontally, stereo cameras place the eyes `interocular_distance` units apart along
+/// the camera's horizontal axis. The lens only applies to perspective and stereo cameras.
+///
 /// # Memory Layout
 ///
 /// The struct uses `#[repr(C)]` and padding fields to ensure proper alignment for GPU buffers.
@@ -41,14 +94,17 @@ pub struct Camera {
     /// - The aspect ratio determines the height automatically
     pub pane_width: f32,
 
-    /// Padding for GPU alignment.
-    _pad0: [f32; 2],
+    /// Radius of the lens aperture in world units (0 = pinhole camera).
+    pub aperture_radius: f32,
+
+    /// Distance from the camera to the plane in focus, along `dir`.
+    pub focus_distance: f32,
 
     /// Camera position in world space (x, y, z).
     pub pos: [f32; 3],
 
-    /// Padding for GPU alignment.
-    _pad1: f32,
+    /// Number of aperture blades (0 = circular aperture, otherwise at least 3).
+    pub aperture_blades: u32,
 
     /// Camera forward direction.
     ///
@@ -58,6 +114,18 @@ pub struct Camera {
 
     /// Padding for GPU alignment.
     _pad2: f32,
+
+    /// Projection mode, see [`Projection`].
+    pub projection: u32,
+
+    /// Width of the view of an orthographic camera in world units.
+    pub ortho_width: f32,
+
+    /// Distance between the eyes of a stereo camera in world units.
+    pub interocular_distance: f32,
+
+    /// Padding for GPU alignment.
+    _pad3: u32,
 }
 
 impl Default for Camera {
@@ -68,15 +136,22 @@ impl Default for Camera {
     /// - Direction: (0, 0, 1) - looking down positive Z axis
     /// - Pane distance: 50.0
     /// - Pane width: 100.0
+    /// - Pinhole (no depth of field)
+    /// - Perspective projection
     fn default() -> Self {
         Self {
             pane_distance: 50.0,
             pane_width: 100.0,
-            _pad0: [0.0; 2],
+            aperture_radius: 0.0,
+            focus_distance: 1.0,
             pos: [2.0, 2.0, 0.0],
-            _pad1: 0.0,
+            aperture_blades: 0,
             dir: [0.0, 0.0, 1.0],
             _pad2: 0.0,
+            projection: Projection::default().into(),
+            ortho_width: 10.0,
+            interocular_distance: 0.065,
+            _pad3: 0,
         }
     }
 }
@@ -103,4 +178,50 @@ impl Camera {
             ..Default::default()
         }
     }
+
+    /// Sets the thin lens parameters for depth of field.
+    ///
+    /// # Arguments
+    ///
+    /// * `aperture_radius` - Radius of the aperture in world units (0 disables depth of field)
+    /// * `focus_distance` - Distance to the plane in focus along the view direction
+    /// * `aperture_blades` - Number of aperture blades (0 = circular)
+    ///
+    /// # Returns
+    ///
+    /// Self with the lens settings updated, for method chaining.
+    pub fn with_lens(
+        mut self,
+        aperture_radius: f32,
+        focus_distance: f32,
+        aperture_blades: u32,
+    ) -> Self {
+        self.aperture_radius = aperture_radius;
+        self.focus_distance = focus_distance;
+        self.aperture_blades = aperture_blades;
+        self
+    }
+
+    /// Sets the projection mode.
+    ///
+    /// # Arguments
+    ///
+    /// * `projection` - How image positions are mapped to primary rays
+    /// * `ortho_width` - Width of the view of an orthographic camera in world units
+    /// * `interocular_distance` - Distance between the eyes of a stereo camera in world units
+    ///
+    /// # Returns
+    ///
+    /// Self with the projection settings updated, for method chaining.
+    pub fn with_projection(
+        mut self,
+        projection: Projection,
+        ortho_width: f32,
+        interocular_distance: f32,
+    ) -> Self {
+        self.projection = projection.into();
+        self.ortho_width = ortho_width;
+        self.interocular_distance = interocular_distance;
+        self
+    }
 }
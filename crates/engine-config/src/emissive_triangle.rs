@@ -0,0 +1,95 @@
+//! Emissive mesh triangles used as area lights.
+//!
+//! This module defines the [`EmissiveTriangle`] struct, one entry of the light list the
+//! shaders sample for direct lighting from emissive geometry.
+
+use bytemuck::{Pod, Zeroable};
+use crate::Vec3;
+
+/// A mesh triangle with an emissive material, sampled as an area light.
+///
+/// The list of emissive triangles is importance sampled by power: `cdf` holds the running sum
+/// of [`power`](EmissiveTriangle::power) up to and including this triangle, so the last entry
+/// holds the total power of all triangles.
+///
+/// # Memory Layout
+///
+/// The struct uses `#[repr(C)]` to ensure consistent memory layout for GPU buffers.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct EmissiveTriangle {
+    /// First vertex position in world space.
+    pub v0: Vec3,
+    /// Unnormalized cumulative power up to and including this triangle.
+    pub cdf: f32,
+    /// Second vertex position in world space.
+    pub v1: Vec3,
+    /// Surface area of the triangle.
+    pub area: f32,
+    /// Third vertex position in world space.
+    pub v2: Vec3,
+    /// Index of the mesh triangle, whose texture coordinates and maps shade the light.
+    pub triangle: u32,
+    /// Emitted radiance, the emissive color of the material.
+    pub emission: Vec3,
+    pub _pad1: u32,
+}
+
+impl EmissiveTriangle {
+    /// Creates a new emissive triangle, the `cdf` and the `triangle` index are left at 0.
+    ///
+    /// # Arguments
+    ///
+    /// * `v0`, `v1`, `v2` - Vertex positions in world space
+    /// * `emission` - Emitted radiance [r, g, b]
+    pub fn new(v0: [f32; 3], v1: [f32; 3], v2: [f32; 3], emission: [f32; 3]) -> Self {
+        let e1 = [v1[0] - v0[0], v1[1] - v0[1], v1[2] - v0[2]];
+        let e2 = [v2[0] - v0[0], v2[1] - v0[1], v2[2] - v0[2]];
+        let cross = [
+            e1[1] * e2[2] - e1[2] * e2[1],
+            e1[2] * e2[0] - e1[0] * e2[2],
+            e1[0] * e2[1] - e1[1] * e2[0],
+        ];
+        let area = 0.5 * (cross[0] * cross[0] + cross[1] * cross[1] + cross[2] * cross[2]).sqrt();
+        Self {
+            v0: Vec3(v0),
+            cdf: 0.0,
+            v1: Vec3(v1),
+            area,
+            v2: Vec3(v2),
+            triangle: 0,
+            emission: Vec3(emission),
+            _pad1: 0,
+        }
+    }
+
+    /// Returns the emitted power used to pick the triangle: luminance of the emission times area.
+    ///
+    /// The shaders compute the same value to find the probability of a triangle hit by a ray.
+    pub fn power(&self) -> f32 {
+        let e = self.emission;
+        (0.2126 * e.x() + 0.7152 * e.y() + 0.0722 * e.z()).max(0.0) * self.area
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn power_is_luminance_times_area() {
+        let light = EmissiveTriangle::new(
+            [1.0, 0.0, 0.0],
+            [1.0, 0.0, 3.0],
+            [1.0, 4.0, 0.0],
+            [1.0, 0.0, 0.0],
+        );
+
+        assert_eq!(light.area, 6.0);
+        assert!((light.power() - 0.2126 * 6.0).abs() < 1e-6);
+        assert_eq!((light.cdf, light.triangle), (0.0, 0));
+        // Negative emission doesn't make a triangle more likely to be picked
+        let dark = EmissiveTriangle::new([0.0; 3], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [-1.0; 3]);
+        assert_eq!(dark.power(), 0.0);
+    }
+}
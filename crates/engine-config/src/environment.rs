@@ -0,0 +1,60 @@
+//! Environment map for image based lighting.
+//!
+//! This module defines the [`EnvironmentMap`] struct, which stores an equirectangular
+//! HDR image that surrounds the scene and replaces the flat sky color.
+
+use serde::{Deserialize, Serialize};
+
+/// Equirectangular HDR environment image.
+///
+/// The image covers the full sphere of directions: `u` maps to the azimuth around the
+/// y axis and `v` maps to the polar angle, starting at the zenith (`+y`).
+/// Rotation and intensity are set per render in [`Uniforms`](crate::Uniforms).
+///
+/// # Data Format
+///
+/// `rgb_data` stores linear radiance as three `f32` per pixel, row by row.
+///
+/// # Sampling
+///
+/// The rendering engine builds a luminance CDF over the image at upload time, so bright
+/// regions such as the sun are sampled directly instead of being found by chance.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EnvironmentMap {
+    /// Width of the image in pixels.
+    pub width: u32,
+    /// Height of the image in pixels.
+    pub height: u32,
+    /// Linear RGB radiance.
+    ///
+    /// Length should be `width * height * 3`.
+    pub rgb_data: Vec<f32>,
+}
+
+impl EnvironmentMap {
+    /// Creates a new environment map with specified dimensions and pixel data.
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - Width of the image in pixels
+    /// * `height` - Height of the image in pixels
+    /// * `rgb_data` - Linear RGB radiance (length should be width * height * 3)
+    ///
+    /// # Returns
+    ///
+    /// A new `EnvironmentMap` with the specified configuration.
+    pub fn new(width: u32, height: u32, rgb_data: Vec<f32>) -> Self {
+        Self {
+            width,
+            height,
+            rgb_data,
+        }
+    }
+
+    /// Returns `true` if the pixel data matches the dimensions of the image.
+    pub fn is_valid(&self) -> bool {
+        self.width > 0
+            && self.height > 0
+            && self.rgb_data.len() == (self.width as usize) * (self.height as usize) * 3
+    }
+}
@@ -18,10 +18,16 @@
 //! - [`Camera`]: Camera position, orientation, and projection settings
 //! - [`Sphere`]: Sphere primitive with material
 //! - [`Mesh`]: Triangle mesh reference with material
-//! - [`PointLight`]: Point light source
+//! - [`EmissiveTriangle`]: Mesh triangle with an emissive material, sampled as area light
+//! - [`PointLight`]: Light source of any [`LightType`] (point, directional, spot, rect, disc)
 //! - [`Material`]: Surface material properties (diffuse, specular, emissive, etc.)
+//! - [`Medium`]: Homogeneous participating medium filling the scene or the interior of objects
+//! - [`Pattern`]: Procedural base color input of a material (triplanar, checker, noise, ...)
 //! - [`Vec3`]: 3D vector for positions, directions, and colors
-//! - [`TextureData`]: Texture image data
+//! - [`TextureData`]: Texture image data with its [`WrapMode`]
+//! - [`EnvironmentMap`]: Equirectangular HDR image lighting the scene
+//! - [`ToneMapping`]: Operator mapping linear radiance to the display range
+//! - [`AovSet`]: Auxiliary output layers (depth, normal, albedo, object ID, UV)
 //!
 //! ## Architecture
 //!
@@ -52,24 +58,38 @@
 //!     .build();
 //! ```
 
+pub mod aov;
 pub mod camera;
+pub mod emissive_triangle;
+pub mod environment;
 pub mod material;
+pub mod medium;
 pub mod mesh;
+pub mod pattern;
 pub mod point_lights;
 pub mod render_config;
 pub mod renderer;
 pub mod sphere;
 pub mod texture;
+pub mod tile_order;
+pub mod tone_mapping;
 pub mod uniforms;
 pub mod vec3;
 
+pub use aov::{Aov, AovSet};
+pub use emissive_triangle::EmissiveTriangle;
 pub use render_config::{RenderConfig, RenderConfigBuilder, RenderConfigBuilderError};
 pub use sphere::{Sphere, SphereError};
-pub use texture::TextureData;
+pub use texture::{ColorSpace, TextureData, WrapMode};
+pub use tile_order::TileOrder;
+pub use tone_mapping::ToneMapping;
 pub use uniforms::Uniforms;
 pub use vec3::Vec3;
-pub use camera::Camera;
-pub use point_lights::PointLight;
+pub use camera::{Camera, Projection};
+pub use environment::EnvironmentMap;
+pub use point_lights::{LightType, PointLight};
 pub use renderer::Renderer;
 pub use material::Material;
+pub use medium::Medium;
+pub use pattern::{Pattern, PatternKind};
 pub use mesh::Mesh;
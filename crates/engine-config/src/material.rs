@@ -6,6 +6,8 @@
 use bytemuck::{Pod, Zeroable};
 use core::fmt;
 use crate::Vec3;
+use crate::medium::Medium;
+use crate::pattern::{Pattern, PatternKind};
 
 /// Material properties for surfaces and lights.
 ///
@@ -13,7 +15,8 @@ use crate::Vec3;
 /// - Phong reflection model components (ambient, diffuse, specular)
 /// - Emissive lighting for area lights
 /// - Physical properties (index of refraction, opacity)
-/// - Texture mapping
+/// - Principled metallic/roughness parameters
+/// - Texture mapping and procedural patterns
 ///
 /// The material model is based on the Wavefront OBJ/MTL format with extensions.
 ///
@@ -27,10 +30,44 @@ use crate::Vec3;
 /// - `4`: Refraction + reflection (glass)
 /// - `5`: Fresnel reflection + ray-traced reflection
 /// - `6`: Transparency + Fresnel + ray-traced refraction
+/// - `7`: Transparency + Fresnel + ray-traced refraction and reflection
+///
+/// The path tracer renders models `4`, `6` and `7` as dielectrics using `ior`.
+///
+/// # Principled BSDF
+///
+/// All other surfaces are shaded with a metallic/roughness BSDF: a Lambertian diffuse
+/// lobe with sheen, a GGX specular lobe and a GGX clearcoat lobe. `diffuse` is the base
+/// color, the Phong field `specular` is not read by the path tracer and `shininess` only
+/// together with a specular exponent map. The conversion from Phong values happens on the
+/// scene side.
+///
+/// # Surface Detail
+///
+/// A normal map or a height map perturbs the shading normal of meshes with texture
+/// coordinates. The tangent frame comes from the per-vertex tangents of the mesh.
+///
+/// # Texture Maps
+///
+/// Besides the diffuse texture, a material can reference roughness, metallic, specular,
+/// specular exponent and emission maps. An opacity map cuts holes into meshes for foliage
+/// and decals, see [`with_map_textures`](Material::with_map_textures).
+///
+/// # Procedural Patterns
+///
+/// A pattern replaces the diffuse texture lookup with a procedural node evaluated at the
+/// world position, such as a triplanar projection of the texture or noise.
+/// See [`with_pattern`](Material::with_pattern).
+///
+/// # Interior Medium
+///
+/// A closed object can be filled with a homogeneous medium. Dielectrics with a medium become
+/// murky or colored glass, other materials with a medium are invisible boundaries of a volume
+/// like smoke. See [`with_medium`](Material::with_medium).
 ///
 /// # Memory Layout
 ///
-/// The struct uses padding (`_pad0`, `_pad1`, `_pad2`) to satisfy GPU alignment requirements.
+/// The struct uses padding (`_pad0` to `_pad6`) to satisfy GPU alignment requirements.
 #[repr(C)]
 #[derive(Copy, Clone, Debug, Pod, Zeroable)]
 pub struct Material {
@@ -62,6 +99,62 @@ pub struct Material {
     pub texture_index: i32,
     /// Padding for GPU alignment.
     pub _pad2: u32,
+    /// Metalness (Pm): 0.0 = dielectric, 1.0 = metal tinted by the base color.
+    pub metallic: f32,
+    /// Perceptual roughness (Pr) in [0, 1]. The GGX alpha is `roughness²`.
+    pub roughness: f32,
+    /// Specular level of non-metals in [0, 1]. 0.5 corresponds to an F0 of 0.04.
+    pub specular_level: f32,
+    /// Sheen weight (Ps), a grazing-angle lobe for cloth-like surfaces.
+    pub sheen: f32,
+    /// Clearcoat weight (Pc) in [0, 1].
+    pub clearcoat: f32,
+    /// Clearcoat roughness (Pcr) in [0, 1].
+    pub clearcoat_roughness: f32,
+    /// Index of the roughness map (map_Pr), -1 = none.
+    pub roughness_texture: i32,
+    /// Index of the metallic map (map_Pm), -1 = none.
+    pub metallic_texture: i32,
+    /// Index of the tangent space normal map (norm), -1 = none.
+    pub normal_texture: i32,
+    /// Index of the height map (bump / map_Bump), -1 = none.
+    pub bump_texture: i32,
+    /// Bump multiplier (-bm), scales the normal map tilt and the height map slope.
+    pub bump_scale: f32,
+    /// Padding for GPU alignment.
+    pub _pad3: u32,
+    /// Absorption coefficient of the interior medium [r, g, b].
+    pub medium_absorption: [f32; 3],
+    /// Henyey-Greenstein asymmetry of the interior medium.
+    pub medium_anisotropy: f32,
+    /// Scattering coefficient of the interior medium [r, g, b].
+    pub medium_scattering: [f32; 3],
+    /// Padding for GPU alignment.
+    pub _pad4: u32,
+    /// First color of the procedural pattern [r, g, b].
+    pub pattern_color_a: [f32; 3],
+    /// Repetitions of the procedural pattern per scene unit.
+    pub pattern_scale: f32,
+    /// Second color of the procedural pattern [r, g, b].
+    pub pattern_color_b: [f32; 3],
+    /// Kind of the procedural pattern ([`PatternKind`] discriminant), 0 = none.
+    pub pattern_kind: u32,
+    /// Noise octaves of the procedural pattern.
+    pub pattern_octaves: u32,
+    /// Triplanar blend sharpness of the procedural pattern.
+    pub pattern_sharpness: f32,
+    /// Index of the alpha cutout map (map_d), -1 = none.
+    pub opacity_texture: i32,
+    /// Index of the specular color map (map_Ks), -1 = none.
+    pub specular_texture: i32,
+    /// Index of the emission map (map_Ke), -1 = none.
+    pub emissive_texture: i32,
+    /// Index of the specular exponent map (map_Ns), -1 = none.
+    pub shininess_texture: i32,
+    /// Padding for GPU alignment.
+    pub _pad5: u32,
+    /// Padding for GPU alignment.
+    pub _pad6: u32,
 }
 
 impl Default for Material {
@@ -75,6 +168,10 @@ impl Default for Material {
     /// - IOR: 1.0 (air)
     /// - Fully opaque
     /// - Illumination model 1 (diffuse only)
+    /// - Non-metallic, roughness 0.5, specular level 0.5, no sheen or clearcoat
+    /// - No interior medium
+    /// - No procedural pattern
+    /// - No texture maps
     fn default() -> Self {
         Self {
             ambient: [0.0, 0.0, 0.0],
@@ -89,6 +186,34 @@ impl Default for Material {
             illum: 1,
             texture_index: -1,
             _pad2: 0,
+            metallic: 0.0,
+            roughness: 0.5,
+            specular_level: 0.5,
+            sheen: 0.0,
+            clearcoat: 0.0,
+            clearcoat_roughness: 0.03,
+            roughness_texture: -1,
+            metallic_texture: -1,
+            normal_texture: -1,
+            bump_texture: -1,
+            bump_scale: 1.0,
+            _pad3: 0,
+            medium_absorption: [0.0; 3],
+            medium_anisotropy: 0.0,
+            medium_scattering: [0.0; 3],
+            _pad4: 0,
+            pattern_color_a: [0.8; 3],
+            pattern_scale: 1.0,
+            pattern_color_b: [0.2; 3],
+            pattern_kind: 0,
+            pattern_octaves: 4,
+            pattern_sharpness: 4.0,
+            opacity_texture: -1,
+            specular_texture: -1,
+            emissive_texture: -1,
+            shininess_texture: -1,
+            _pad5: 0,
+            _pad6: 0,
         }
     }
 }
@@ -144,6 +269,148 @@ impl Material {
             }),
         }
     }
+
+    /// Sets the parameters of the principled BSDF.
+    ///
+    /// # Arguments
+    ///
+    /// * `metallic` - Metalness (0.0-1.0)
+    /// * `roughness` - Perceptual roughness (0.0-1.0)
+    /// * `specular_level` - Specular level of non-metals (0.0-1.0)
+    /// * `sheen` - Sheen weight
+    /// * `clearcoat` - Clearcoat weight (0.0-1.0)
+    /// * `clearcoat_roughness` - Clearcoat roughness (0.0-1.0)
+    pub fn with_principled(
+        mut self,
+        metallic: f32,
+        roughness: f32,
+        specular_level: f32,
+        sheen: f32,
+        clearcoat: f32,
+        clearcoat_roughness: f32,
+    ) -> Self {
+        self.metallic = metallic;
+        self.roughness = roughness;
+        self.specular_level = specular_level;
+        self.sheen = sheen;
+        self.clearcoat = clearcoat;
+        self.clearcoat_roughness = clearcoat_roughness;
+        self
+    }
+
+    /// Sets the roughness and metallic maps, -1 for none.
+    /// The sampled red channel multiplies the scalar value.
+    pub fn with_pbr_textures(mut self, roughness_texture: i32, metallic_texture: i32) -> Self {
+        self.roughness_texture = roughness_texture;
+        self.metallic_texture = metallic_texture;
+        self
+    }
+
+    /// Sets the normal and height maps, -1 for none.
+    ///
+    /// # Arguments
+    ///
+    /// * `normal_texture` - Tangent space normal map, OpenGL convention (green = +V)
+    /// * `bump_texture` - Height map, the red channel is the height
+    /// * `bump_scale` - Bump multiplier, 1.0 leaves the maps unchanged
+    pub fn with_bump_textures(
+        mut self,
+        normal_texture: i32,
+        bump_texture: i32,
+        bump_scale: f32,
+    ) -> Self {
+        self.normal_texture = normal_texture;
+        self.bump_texture = bump_texture;
+        self.bump_scale = bump_scale;
+        self
+    }
+
+    /// Sets the alpha cutout, specular, emission and specular exponent maps, -1 for none.
+    ///
+    /// # Arguments
+    ///
+    /// * `opacity_texture` - Cutout mask, texels with an opacity below 0.5 are not hit by any
+    ///   ray. The alpha channel is read if the texture has one, the red channel otherwise
+    /// * `specular_texture` - Color map, its mean multiplies the specular level
+    /// * `emissive_texture` - Color map multiplying the emissive color
+    /// * `shininess_texture` - The red channel multiplies `shininess`, the product replaces the
+    ///   roughness as `1 - sqrt(Ns / 1000)`. Ignored if the material has a roughness map
+    pub fn with_map_textures(
+        mut self,
+        opacity_texture: i32,
+        specular_texture: i32,
+        emissive_texture: i32,
+        shininess_texture: i32,
+    ) -> Self {
+        self.opacity_texture = opacity_texture;
+        self.specular_texture = specular_texture;
+        self.emissive_texture = emissive_texture;
+        self.shininess_texture = shininess_texture;
+        self
+    }
+
+    /// Fills the interior of the object with a homogeneous medium.
+    ///
+    /// The medium replaces the absorption dielectrics derive from their diffuse color.
+    /// Non-dielectric materials with a medium have no surface, rays and shadow rays pass
+    /// through them and only interact with the volume.
+    pub fn with_medium(mut self, medium: Medium) -> Self {
+        self.medium_absorption = medium.absorption;
+        self.medium_anisotropy = medium.anisotropy;
+        self.medium_scattering = medium.scattering;
+        self
+    }
+
+    /// Returns the interior medium, a vacuum if the material has none.
+    pub fn medium(&self) -> Medium {
+        Medium::new(
+            self.medium_absorption,
+            self.medium_scattering,
+            self.medium_anisotropy,
+        )
+    }
+
+    /// Replaces the diffuse texture lookup with a procedural pattern.
+    ///
+    /// A triplanar pattern projects the diffuse texture, or the checkerboard if there is none.
+    /// The other kinds ignore the texture.
+    pub fn with_pattern(mut self, pattern: Pattern) -> Self {
+        self.pattern_color_a = pattern.colors[0];
+        self.pattern_scale = pattern.scale;
+        self.pattern_color_b = pattern.colors[1];
+        self.pattern_kind = pattern.kind.into();
+        self.pattern_octaves = pattern.octaves;
+        self.pattern_sharpness = pattern.sharpness;
+        self
+    }
+
+    /// Returns the procedural pattern, of kind [`PatternKind::None`] if the material has none.
+    pub fn pattern(&self) -> Pattern {
+        Pattern {
+            kind: PatternKind::try_from(self.pattern_kind).unwrap_or_default(),
+            scale: self.pattern_scale,
+            colors: [self.pattern_color_a, self.pattern_color_b],
+            octaves: self.pattern_octaves,
+            sharpness: self.pattern_sharpness,
+        }
+    }
+
+    /// Creates the material of the ground plane when no other material is assigned.
+    ///
+    /// A fully rough grey without specular reflection. Untextured ground materials show
+    /// the checkerboard if it is enabled.
+    pub fn ground() -> Self {
+        Self {
+            diffuse: Vec3::new(0.5, 0.5, 0.5),
+            specular: [0.0; 3],
+            shininess: 0.0,
+            illum: 0,
+            roughness: 1.0,
+            specular_level: 0.0,
+            clearcoat_roughness: 0.0,
+            ..Self::default()
+        }
+    }
 }
 
 /// Errors that can occur when creating materials.
@@ -0,0 +1,70 @@
+//! Homogeneous participating media.
+//!
+//! This module defines the [`Medium`] struct, which describes a volume of constant density
+//! that absorbs and scatters light, such as fog, smoke or the interior of colored glass.
+
+use serde::{Deserialize, Serialize};
+
+/// A homogeneous participating medium.
+///
+/// The coefficients are given per unit length and per color channel. Light travelling the
+/// distance `t` through the medium is attenuated by `exp(-(absorption + scattering) * t)`,
+/// the scattered part changes its direction according to the Henyey-Greenstein phase function.
+///
+/// A medium is used for the global medium the scene is filled with
+/// ([`Uniforms::with_medium`](crate::Uniforms::with_medium)) and for the interior of objects
+/// ([`Material::with_medium`](crate::Material::with_medium)).
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Medium {
+    /// Absorption coefficient `σa` [r, g, b].
+    #[serde(default)]
+    pub absorption: [f32; 3],
+    /// Scattering coefficient `σs` [r, g, b].
+    #[serde(default)]
+    pub scattering: [f32; 3],
+    /// Henyey-Greenstein asymmetry `g` in (-1, 1): negative values scatter backwards,
+    /// 0 is isotropic and positive values scatter forwards.
+    #[serde(default)]
+    pub anisotropy: f32,
+}
+
+impl Medium {
+    /// Creates a new medium.
+    ///
+    /// # Arguments
+    ///
+    /// * `absorption` - Absorption coefficient per unit length [r, g, b]
+    /// * `scattering` - Scattering coefficient per unit length [r, g, b]
+    /// * `anisotropy` - Henyey-Greenstein asymmetry in (-1, 1)
+    pub fn new(absorption: [f32; 3], scattering: [f32; 3], anisotropy: f32) -> Self {
+        Self {
+            absorption,
+            scattering,
+            anisotropy,
+        }
+    }
+
+    /// Returns the extinction coefficient `σt = σa + σs` [r, g, b].
+    pub fn extinction(&self) -> [f32; 3] {
+        [
+            self.absorption[0] + self.scattering[0],
+            self.absorption[1] + self.scattering[1],
+            self.absorption[2] + self.scattering[2],
+        ]
+    }
+
+    /// Returns `true` if the medium neither absorbs nor scatters light.
+    pub fn is_vacuum(&self) -> bool {
+        self.extinction().iter().all(|c| *c == 0.0)
+    }
+
+    /// Returns `true` if all coefficients are finite and non-negative and the anisotropy
+    /// lies in (-1, 1).
+    pub fn is_valid(&self) -> bool {
+        self.absorption
+            .iter()
+            .chain(self.scattering.iter())
+            .all(|c| c.is_finite() && *c >= 0.0)
+            && self.anisotropy.abs() < 1.0
+    }
+}
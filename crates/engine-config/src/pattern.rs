@@ -0,0 +1,180 @@
+//! Procedural texture inputs.
+//!
+//! This module defines the [`Pattern`] struct and the [`PatternKind`] enum, which describe a
+//! procedural node that is evaluated in the shaders instead of the diffuse texture lookup.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Procedural node that provides the base color of a material.
+///
+/// The discriminants are passed to the GPU shaders through the material.
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PatternKind {
+    /// No pattern, the diffuse texture is sampled with the UVs of the surface.
+    #[default]
+    None = 0,
+    /// The diffuse texture projected along the three world axes and blended by the normal.
+    /// Works on meshes without texture coordinates.
+    Triplanar = 1,
+    /// 3D checkerboard of the two colors.
+    Checker = 2,
+    /// Linear blend from the first to the second color along the world y axis.
+    Gradient = 3,
+    /// Fractal Perlin noise (fBm) blending the two colors.
+    Noise = 4,
+    /// Voronoi cells, the distance to the closest feature point blends the two colors.
+    Voronoi = 5,
+}
+
+impl PatternKind {
+    /// All kinds, in the order they are presented to the user.
+    pub const ALL: [PatternKind; 6] = [
+        PatternKind::None,
+        PatternKind::Triplanar,
+        PatternKind::Checker,
+        PatternKind::Gradient,
+        PatternKind::Noise,
+        PatternKind::Voronoi,
+    ];
+
+    /// Returns the lowercase name used in scene files and MTL files.
+    pub fn name(&self) -> &'static str {
+        match self {
+            PatternKind::None => "none",
+            PatternKind::Triplanar => "triplanar",
+            PatternKind::Checker => "checker",
+            PatternKind::Gradient => "gradient",
+            PatternKind::Noise => "noise",
+            PatternKind::Voronoi => "voronoi",
+        }
+    }
+}
+
+impl From<PatternKind> for u32 {
+    fn from(value: PatternKind) -> Self {
+        value as u32
+    }
+}
+
+impl TryFrom<u32> for PatternKind {
+    type Error = String;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        PatternKind::ALL
+            .into_iter()
+            .find(|k| *k as u32 == value)
+            .ok_or_else(|| format!("unknown pattern kind: {value}"))
+    }
+}
+
+impl fmt::Display for PatternKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for PatternKind {
+    type Err = String;
+
+    /// Parses a kind from its name, ignoring case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PatternKind::ALL
+            .into_iter()
+            .find(|k| k.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("unknown pattern kind: {s}"))
+    }
+}
+
+/// A procedural base color input of a material.
+///
+/// Patterns are evaluated at the world position of a hit, `scale` is the number of
+/// repetitions (checker squares, noise features, Voronoi cells) per scene unit. For the
+/// gradient it is the inverse of the height over which the colors blend, starting at `y = 0`.
+/// The result is multiplied with the diffuse color like a texture.
+///
+/// See [`Material::with_pattern`](crate::Material::with_pattern).
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Pattern {
+    /// Kind of the procedural node.
+    pub kind: PatternKind,
+    /// Repetitions per scene unit. Must be positive.
+    pub scale: f32,
+    /// The two colors the pattern blends [r, g, b]. Not used by triplanar projection.
+    pub colors: [[f32; 3]; 2],
+    /// Number of noise octaves, 1 to [`Pattern::MAX_OCTAVES`].
+    pub octaves: u32,
+    /// Exponent of the triplanar blend weights, higher values give sharper transitions.
+    pub sharpness: f32,
+}
+
+impl Pattern {
+    /// Largest number of noise octaves the shaders evaluate.
+    pub const MAX_OCTAVES: u32 = 8;
+
+    /// Creates a pattern with 4 noise octaves and a triplanar sharpness of 4.
+    ///
+    /// # Arguments
+    ///
+    /// * `kind` - Kind of the procedural node
+    /// * `scale` - Repetitions per scene unit
+    /// * `colors` - The two colors the pattern blends [r, g, b]
+    pub fn new(kind: PatternKind, scale: f32, colors: [[f32; 3]; 2]) -> Self {
+        Self {
+            kind,
+            scale,
+            colors,
+            ..Default::default()
+        }
+    }
+
+    /// Sets the number of noise octaves.
+    pub fn with_octaves(mut self, octaves: u32) -> Self {
+        self.octaves = octaves;
+        self
+    }
+
+    /// Sets the exponent of the triplanar blend weights.
+    pub fn with_sharpness(mut self, sharpness: f32) -> Self {
+        self.sharpness = sharpness;
+        self
+    }
+
+    /// Returns `true` if the scale is positive, the colors are finite and non-negative,
+    /// the octaves are in range and the sharpness is at least 1.
+    pub fn is_valid(&self) -> bool {
+        self.scale.is_finite()
+            && self.scale > 0.0
+            && self
+                .colors
+                .iter()
+                .flatten()
+                .all(|c| c.is_finite() && *c >= 0.0)
+            && (1..=Self::MAX_OCTAVES).contains(&self.octaves)
+            && self.sharpness.is_finite()
+            && self.sharpness >= 1.0
+    }
+}
+
+impl Default for Pattern {
+    /// Creates an empty pattern.
+    ///
+    /// Default values:
+    /// - Kind: none
+    /// - Scale: 1.0
+    /// - Colors: light and dark grey
+    /// - Octaves: 4
+    /// - Sharpness: 4.0
+    fn default() -> Self {
+        Self {
+            kind: PatternKind::None,
+            scale: 1.0,
+            colors: [[0.8; 3], [0.2; 3]],
+            octaves: 4,
+            sharpness: 4.0,
+        }
+    }
+}
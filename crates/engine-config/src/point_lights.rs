@@ -1,21 +1,63 @@
-//! Point light sources for scene illumination.
+//! Light sources for scene illumination.
 //!
-//! This module defines the [`PointLight`] struct, representing spherical area lights
-//! that emit light in all directions.
+//! This module defines the [`PointLight`] struct, the GPU representation of every light,
+//! and the [`LightType`] enum selecting how a light emits and is sampled.
 
 use bytemuck::{Pod, Zeroable};
 use crate::{Material, Vec3};
 
-/// A spherical area light source.
+/// Kind of a [`PointLight`].
 ///
-/// `PointLight` represents a spherical emissive object that illuminates the scene.
-/// Unlike traditional point lights, these are actual geometric spheres with radius,
-/// allowing for soft shadows and area lighting effects.
+/// The discriminants are passed to the GPU shaders in [`PointLight::light_type`].
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum LightType {
+    /// Sphere emitting in all directions.
+    #[default]
+    Point = 0,
+    /// Infinitely distant sun, `luminosity` is the irradiance on a surface facing it.
+    Directional = 1,
+    /// Sphere emitting into a cone around `direction`.
+    Spot = 2,
+    /// One-sided rectangle facing `direction`.
+    Rect = 3,
+    /// One-sided disc facing `direction`.
+    Disc = 4,
+}
+
+impl TryFrom<u32> for LightType {
+    type Error = u32;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(LightType::Point),
+            1 => Ok(LightType::Directional),
+            2 => Ok(LightType::Spot),
+            3 => Ok(LightType::Rect),
+            4 => Ok(LightType::Disc),
+            other => Err(other),
+        }
+    }
+}
+
+impl From<LightType> for u32 {
+    fn from(value: LightType) -> Self {
+        value as u32
+    }
+}
+
+/// A light source.
+///
+/// `PointLight` represents an emissive object that illuminates the scene. Point and spot
+/// lights are actual geometric spheres with radius, allowing for soft shadows and area
+/// lighting effects. Rect and disc lights are flat one-sided emitters, directional lights
+/// have no geometry and only cover a small cone of directions.
 ///
 /// # Implementation Note
 ///
-/// Despite the name "PointLight", these are actually spherical area lights. The name
-/// is retained for compability reasons.
+/// Despite the name "PointLight", these are actually area lights of any [`LightType`].
+/// The name is retained for compability reasons. Fields that don't apply to the
+/// light's type are ignored by the shaders.
 ///
 /// # Memory Layout
 ///
@@ -30,6 +72,21 @@ pub struct PointLight {
     pub radius: f32,
     /// Material properties (primarily emissive component for light emission).
     pub material: Material,
+    /// Normalized direction the light emits into: the spot axis, the normal of rect and
+    /// disc lights and the direction sunlight travels.
+    pub direction: Vec3,
+    /// [`LightType`] as `u32`.
+    pub light_type: u32,
+    /// Normalized direction of the rect light's width, perpendicular to `direction`.
+    pub tangent: Vec3,
+    /// Cosine of the spot angle up to which the full intensity is emitted.
+    pub cos_inner: f32,
+    /// Half width and half height of a rect light.
+    pub half_size: [f32; 2],
+    /// Cosine of the spot angle beyond which nothing is emitted,
+    /// or of the angular radius of a directional light.
+    pub cos_outer: f32,
+    pub _pad0: u32,
 }
 
 impl Default for PointLight {
@@ -43,11 +100,7 @@ impl Default for PointLight {
     fn default() -> Self {
         let color = Vec3::new(1.0, 1.0, 1.0);
         let luminosity = 150.0;
-        Self {
-            center: Vec3::new(2.0, 2.0, 1.0),
-            radius: 0.5,
-            material: Material::emissive(color, luminosity),
-        }
+        Self::new([2.0, 2.0, 1.0], 0.5, luminosity, color.0)
     }
 }
 
@@ -83,6 +136,23 @@ impl Material {
             illum: 0,
             texture_index: -1,
             _pad2: 0,
+            metallic: 0.0,
+            roughness: 1.0,
+            specular_level: 0.0,
+            sheen: 0.0,
+            clearcoat: 0.0,
+            clearcoat_roughness: 0.0,
+            roughness_texture: -1,
+            metallic_texture: -1,
+            normal_texture: -1,
+            bump_texture: -1,
+            bump_scale: 1.0,
+            _pad3: 0,
+            medium_absorption: [0.0; 3],
+            medium_anisotropy: 0.0,
+            medium_scattering: [0.0; 3],
+            _pad4: 0,
+            ..Default::default()
         }
     }
 }
@@ -105,6 +175,129 @@ impl PointLight {
             center: Vec3(position),
             radius,
             material: Material::emissive(Vec3(color), luminosity),
+            direction: Vec3::new(0.0, -1.0, 0.0),
+            light_type: LightType::Point.into(),
+            tangent: Vec3::new(1.0, 0.0, 0.0),
+            cos_inner: 1.0,
+            half_size: [0.0, 0.0],
+            cos_outer: 1.0,
+            _pad0: 0,
+        }
+    }
+
+    /// Creates a directional light, like the sun.
+    ///
+    /// # Arguments
+    ///
+    /// * `direction` - Direction the light travels in [x, y, z], normalized
+    /// * `angle` - Angular diameter in degrees, 0 for perfectly sharp shadows
+    /// * `luminosity` - Irradiance on a surface facing the light
+    /// * `color` - RGB color of the emitted light [r, g, b] (each 0.0-1.0)
+    pub fn directional(direction: [f32; 3], angle: f32, luminosity: f32, color: [f32; 3]) -> Self {
+        Self {
+            direction: Vec3(direction),
+            light_type: LightType::Directional.into(),
+            cos_outer: (angle * 0.5).to_radians().cos(),
+            ..Self::new([0.0; 3], 0.0, luminosity, color)
+        }
+    }
+
+    /// Creates a spot light, a sphere light emitting into a cone.
+    ///
+    /// The intensity falls off smoothly between `inner_angle` and `outer_angle`.
+    ///
+    /// # Arguments
+    ///
+    /// * `position` - World space position [x, y, z]
+    /// * `radius` - Radius of the light sphere (affects shadow softness)
+    /// * `direction` - Axis of the cone [x, y, z], normalized
+    /// * `angles` - Inner and outer angle between the axis and the cone in degrees
+    /// * `luminosity` - Brightness multiplier (higher = brighter)
+    /// * `color` - RGB color of the emitted light [r, g, b] (each 0.0-1.0)
+    pub fn spot(
+        position: [f32; 3],
+        radius: f32,
+        direction: [f32; 3],
+        angles: [f32; 2],
+        luminosity: f32,
+        color: [f32; 3],
+    ) -> Self {
+        let outer = angles[1].clamp(0.0, 180.0);
+        let inner = angles[0].clamp(0.0, outer);
+        Self {
+            direction: Vec3(direction),
+            light_type: LightType::Spot.into(),
+            cos_inner: inner.to_radians().cos(),
+            cos_outer: outer.to_radians().cos(),
+            ..Self::new(position, radius, luminosity, color)
+        }
+    }
+
+    /// Creates a one-sided rectangular area light.
+    ///
+    /// # Arguments
+    ///
+    /// * `position` - World space position of the center [x, y, z]
+    /// * `direction` - Normal of the emitting side [x, y, z], normalized
+    /// * `tangent` - Direction of the width [x, y, z], normalized and perpendicular to `direction`
+    /// * `size` - Width and height
+    /// * `luminosity` - Brightness multiplier (higher = brighter)
+    /// * `color` - RGB color of the emitted light [r, g, b] (each 0.0-1.0)
+    pub fn rect(
+        position: [f32; 3],
+        direction: [f32; 3],
+        tangent: [f32; 3],
+        size: [f32; 2],
+        luminosity: f32,
+        color: [f32; 3],
+    ) -> Self {
+        Self {
+            direction: Vec3(direction),
+            tangent: Vec3(tangent),
+            light_type: LightType::Rect.into(),
+            half_size: [size[0] * 0.5, size[1] * 0.5],
+            ..Self::new(position, 0.0, luminosity, color)
+        }
+    }
+
+    /// Creates a one-sided disc shaped area light.
+    ///
+    /// # Arguments
+    ///
+    /// * `position` - World space position of the center [x, y, z]
+    /// * `direction` - Normal of the emitting side [x, y, z], normalized
+    /// * `radius` - Radius of the disc
+    /// * `luminosity` - Brightness multiplier (higher = brighter)
+    /// * `color` - RGB color of the emitted light [r, g, b] (each 0.0-1.0)
+    pub fn disc(
+        position: [f32; 3],
+        direction: [f32; 3],
+        radius: f32,
+        luminosity: f32,
+        color: [f32; 3],
+    ) -> Self {
+        Self {
+            direction: Vec3(direction),
+            light_type: LightType::Disc.into(),
+            ..Self::new(position, radius, luminosity, color)
+        }
+    }
+
+    /// Returns `true` if the light has a known type and a size the shaders can sample.
+    pub fn is_valid(&self) -> bool {
+        let direction = self.direction;
+        let has_direction = direction.x() * direction.x()
+            + direction.y() * direction.y()
+            + direction.z() * direction.z()
+            > 0.0;
+        match LightType::try_from(self.light_type) {
+            Ok(LightType::Point) => self.radius > 0.0,
+            Ok(LightType::Spot) | Ok(LightType::Disc) => self.radius > 0.0 && has_direction,
+            Ok(LightType::Rect) => {
+                self.half_size[0] > 0.0 && self.half_size[1] > 0.0 && has_direction
+            }
+            Ok(LightType::Directional) => has_direction,
+            Err(_) => false,
         }
     }
 }
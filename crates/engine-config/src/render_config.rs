@@ -7,6 +7,7 @@
 use crate::*;
 use core::fmt;
 use engine_bvh::bvh::BVHNode;
+use engine_bvh::instance::Instance;
 use engine_bvh::triangle::GPUTriangle;
 
 /// Complete scene configuration for rendering.
@@ -52,8 +53,16 @@ pub struct RenderConfig {
     pub bvh_indices: Change<Vec<u32>>,
     /// Triangles in BVH-compatible format.
     pub bvh_triangles: Change<Vec<GPUTriangle>>,
+    /// Mesh instances placed by the top-level BVH.
+    pub instances: Change<Vec<Instance>>,
+    /// Emissive mesh triangles with their power distribution, sampled as area lights.
+    pub emissive_triangles: Change<Vec<EmissiveTriangle>>,
     /// Texture image data for material mapping.
     pub textures: Change<Vec<TextureData>>,
+    /// Environment map lighting the scene, replaces the sky color.
+    pub environment: Change<EnvironmentMap>,
+    /// Auxiliary output layers written alongside the beauty pass.
+    pub aovs: Change<AovSet>,
 }
 
 /// Change tracking enum for resource lifecycle management.
@@ -198,6 +207,38 @@ impl Validate for RenderConfig {
                 if is_zero(&u.camera.dir) {
                     return Err(RenderConfigBuilderError::InvalidCameraDirection);
                 }
+                if u.camera.aperture_radius < 0.0
+                    || (u.camera.aperture_radius > 0.0 && u.camera.focus_distance <= 0.0)
+                {
+                    return Err(RenderConfigBuilderError::InvalidLens);
+                }
+                if Projection::try_from(u.camera.projection).is_err()
+                    || u.camera.ortho_width.is_nan()
+                    || u.camera.ortho_width <= 0.0
+                    || u.camera.interocular_distance.is_nan()
+                    || u.camera.interocular_distance < 0.0
+                {
+                    return Err(RenderConfigBuilderError::InvalidProjection);
+                }
+                if !u.exposure.is_finite()
+                    || !ToneMapping::ALL
+                        .iter()
+                        .any(|t| u32::from(*t) == u.tone_mapping)
+                {
+                    return Err(RenderConfigBuilderError::InvalidToneMapping);
+                }
+                if !u.adaptive_threshold.is_finite()
+                    || u.adaptive_threshold < 0.0
+                    || u.adaptive_min_samples == 0
+                {
+                    return Err(RenderConfigBuilderError::InvalidAdaptiveSampling);
+                }
+                if !u.medium().is_valid() {
+                    return Err(RenderConfigBuilderError::InvalidMedium);
+                }
+                if TileOrder::from_u32(u.tile_order).is_none() {
+                    return Err(RenderConfigBuilderError::InvalidTileOrder);
+                }
                 // TODO: Add more Uniforms validation as needed
             }
             Change::Delete => {
@@ -208,7 +249,10 @@ impl Validate for RenderConfig {
 
         match &self.spheres {
             Change::Update(spheres) | Change::Create(spheres) => {
-                if spheres.iter().any(|s| s.radius <= 0.0) {
+                if spheres
+                    .iter()
+                    .any(|s| s.radius <= 0.0 || !is_valid_material(&s.material))
+                {
                     return Err(RenderConfigBuilderError::InvalidSpheres);
                 }
                 // TODO: Add more Sphere validation as needed
@@ -232,8 +276,11 @@ impl Validate for RenderConfig {
         }
 
         match &self.meshes {
-            Change::Update(_meshes) | Change::Create(_meshes) => {
-                // TODO: Mesh validation
+            Change::Update(meshes) | Change::Create(meshes) => {
+                if meshes.iter().any(|m| !is_valid_material(&m.material)) {
+                    return Err(RenderConfigBuilderError::InvalidMeshes);
+                }
+                // TODO: More mesh validation
             }
             Change::Delete => {
                 todo!("Implement meshes Deletion")
@@ -243,7 +290,7 @@ impl Validate for RenderConfig {
 
         match &self.lights {
             Change::Update(lights) | Change::Create(lights) => {
-                if lights.iter().any(|l| l.radius <= 0.0) {
+                if lights.iter().any(|l| !l.is_valid()) {
                     return Err(RenderConfigBuilderError::InvalidLights);
                 }
             }
@@ -263,6 +310,15 @@ impl Validate for RenderConfig {
             Change::Keep => {}
         }
 
+        match &self.environment {
+            Change::Update(environment) | Change::Create(environment) => {
+                if !environment.is_valid() {
+                    return Err(RenderConfigBuilderError::InvalidEnvironment);
+                }
+            }
+            Change::Delete | Change::Keep => {}
+        }
+
         Ok(self)
     }
 }
@@ -319,7 +375,11 @@ pub struct RenderConfigBuilder {
     pub bvh_nodes: Option<Change<Vec<BVHNode>>>,
     pub bvh_indices: Option<Change<Vec<u32>>>,
     pub bvh_triangles: Option<Change<Vec<GPUTriangle>>>,
+    pub instances: Option<Change<Vec<Instance>>>,
+    pub emissive_triangles: Option<Change<Vec<EmissiveTriangle>>>,
     pub textures: Option<Change<Vec<TextureData>>>,
+    pub environment: Option<Change<EnvironmentMap>>,
+    pub aovs: Option<Change<AovSet>>,
 }
 
 impl RenderConfigBuilder {
@@ -334,7 +394,11 @@ impl RenderConfigBuilder {
             bvh_nodes: None,
             bvh_indices: None,
             bvh_triangles: None,
+            instances: None,
+            emissive_triangles: None,
             textures: None,
+            environment: None,
+            aovs: None,
         }
     }
 
@@ -530,6 +594,54 @@ impl RenderConfigBuilder {
         self
     }
 
+    /// Updates the mesh instances (`Change::Update`).
+    pub fn instances(mut self, instances: Vec<Instance>) -> Self {
+        self.instances = Some(Change::Update(instances));
+        self
+    }
+
+    /// Creates the mesh instances (`Change::Create`).
+    pub fn instances_create(mut self, instances: Vec<Instance>) -> Self {
+        self.instances = Some(Change::Create(instances));
+        self
+    }
+
+    /// Keeps the mesh instances unchanged (`Change::Keep`).
+    pub fn instances_no_change(mut self) -> Self {
+        self.instances = Some(Change::Keep);
+        self
+    }
+
+    /// Deletes the mesh instances (`Change::Delete`).
+    pub fn instances_delete(mut self) -> Self {
+        self.instances = Some(Change::Delete);
+        self
+    }
+
+    /// Updates the emissive triangles (`Change::Update`).
+    pub fn emissive_triangles(mut self, triangles: Vec<EmissiveTriangle>) -> Self {
+        self.emissive_triangles = Some(Change::Update(triangles));
+        self
+    }
+
+    /// Creates the emissive triangles (`Change::Create`).
+    pub fn emissive_triangles_create(mut self, triangles: Vec<EmissiveTriangle>) -> Self {
+        self.emissive_triangles = Some(Change::Create(triangles));
+        self
+    }
+
+    /// Keeps the emissive triangles unchanged (`Change::Keep`).
+    pub fn emissive_triangles_no_change(mut self) -> Self {
+        self.emissive_triangles = Some(Change::Keep);
+        self
+    }
+
+    /// Deletes the emissive triangles (`Change::Delete`).
+    pub fn emissive_triangles_delete(mut self) -> Self {
+        self.emissive_triangles = Some(Change::Delete);
+        self
+    }
+
     /// Updates textures (`Change::Update`).
     pub fn textures(mut self, textures: Vec<TextureData>) -> Self {
         self.textures = Some(Change::Update(textures));
@@ -554,6 +666,54 @@ impl RenderConfigBuilder {
         self
     }
 
+    /// Updates the environment map (`Change::Update`).
+    pub fn environment(mut self, environment: EnvironmentMap) -> Self {
+        self.environment = Some(Change::Update(environment));
+        self
+    }
+
+    /// Creates the environment map (`Change::Create`).
+    pub fn environment_create(mut self, environment: EnvironmentMap) -> Self {
+        self.environment = Some(Change::Create(environment));
+        self
+    }
+
+    /// Keeps the environment map unchanged (`Change::Keep`).
+    pub fn environment_no_change(mut self) -> Self {
+        self.environment = Some(Change::Keep);
+        self
+    }
+
+    /// Deletes the environment map (`Change::Delete`), the sky color is used instead.
+    pub fn environment_delete(mut self) -> Self {
+        self.environment = Some(Change::Delete);
+        self
+    }
+
+    /// Updates the requested AOV layers (`Change::Update`).
+    pub fn aovs(mut self, aovs: AovSet) -> Self {
+        self.aovs = Some(Change::Update(aovs));
+        self
+    }
+
+    /// Creates the requested AOV layers (`Change::Create`).
+    pub fn aovs_create(mut self, aovs: AovSet) -> Self {
+        self.aovs = Some(Change::Create(aovs));
+        self
+    }
+
+    /// Keeps the requested AOV layers unchanged (`Change::Keep`).
+    pub fn aovs_no_change(mut self) -> Self {
+        self.aovs = Some(Change::Keep);
+        self
+    }
+
+    /// Deletes the AOV layers (`Change::Delete`), only the beauty pass is rendered.
+    pub fn aovs_delete(mut self) -> Self {
+        self.aovs = Some(Change::Delete);
+        self
+    }
+
     /// Builds the [`RenderConfig`] from the builder.
     ///
     /// Fields not explicitly set will default to `Change::Keep`.
@@ -590,6 +750,18 @@ impl RenderConfigBuilder {
         if self.bvh_triangles.is_none() {
             log::info!("RenderConfigBuilder: bvh_triangles not set, defaulting to NoChange");
         }
+        if self.instances.is_none() {
+            log::info!("RenderConfigBuilder: instances not set, defaulting to NoChange");
+        }
+        if self.emissive_triangles.is_none() {
+            log::info!("RenderConfigBuilder: emissive_triangles not set, defaulting to NoChange");
+        }
+        if self.environment.is_none() {
+            log::info!("RenderConfigBuilder: environment not set, defaulting to NoChange");
+        }
+        if self.aovs.is_none() {
+            log::info!("RenderConfigBuilder: aovs not set, defaulting to NoChange");
+        }
 
         RenderConfig {
             uniforms: self.uniforms.unwrap_or(Change::Keep),
@@ -601,6 +773,10 @@ impl RenderConfigBuilder {
             bvh_nodes: self.bvh_nodes.unwrap_or(Change::Keep),
             bvh_indices: self.bvh_indices.unwrap_or(Change::Keep),
             bvh_triangles: self.bvh_triangles.unwrap_or(Change::Keep),
+            instances: self.instances.unwrap_or(Change::Keep),
+            emissive_triangles: self.emissive_triangles.unwrap_or(Change::Keep),
+            environment: self.environment.unwrap_or(Change::Keep),
+            aovs: self.aovs.unwrap_or(Change::Keep),
         }
     }
 }
@@ -614,6 +790,18 @@ pub enum RenderConfigBuilderError {
     PaneWidthOutOfBounds,
     /// Camera direction is zero vector (invalid).
     InvalidCameraDirection,
+    /// Aperture radius is negative or the focus distance is not positive.
+    InvalidLens,
+    /// The projection is unknown, the orthographic width is not positive or the interocular distance is negative.
+    InvalidProjection,
+    /// Exposure is not finite or the tone mapping operator is unknown.
+    InvalidToneMapping,
+    /// Adaptive sampling threshold is negative or not finite, or no minimum samples are taken.
+    InvalidAdaptiveSampling,
+    /// Medium coefficients are negative or not finite, or the anisotropy is outside (-1, 1).
+    InvalidMedium,
+    /// The tile order is unknown.
+    InvalidTileOrder,
     /// Uniforms are invalid or missing.
     InvalidUniforms,
     /// Spheres contain invalid data (e.g., non-positive radius).
@@ -626,6 +814,8 @@ pub enum RenderConfigBuilderError {
     InvalidLights,
     /// Textures contain invalid data.
     InvalidTextures,
+    /// Environment map data does not match its dimensions.
+    InvalidEnvironment,
     /// Attempted to delete a non-existent resource.
     CannotDeleteNonexistent,
 }
@@ -642,12 +832,23 @@ impl fmt::Display for RenderConfigBuilderError {
             RenderConfigBuilderError::InvalidCameraDirection => {
                 write!(f, "Invalid camera direction")
             }
+            RenderConfigBuilderError::InvalidLens => write!(f, "Invalid lens parameters"),
+            RenderConfigBuilderError::InvalidProjection => write!(f, "Invalid camera projection"),
+            RenderConfigBuilderError::InvalidToneMapping => {
+                write!(f, "Invalid exposure or tone mapping")
+            }
+            RenderConfigBuilderError::InvalidAdaptiveSampling => {
+                write!(f, "Invalid adaptive sampling parameters")
+            }
+            RenderConfigBuilderError::InvalidMedium => write!(f, "Invalid medium"),
+            RenderConfigBuilderError::InvalidTileOrder => write!(f, "Invalid tile order"),
             RenderConfigBuilderError::InvalidUniforms => write!(f, "Invalid Uniforms"),
             RenderConfigBuilderError::InvalidSpheres => write!(f, "Invalid Spheres"),
             RenderConfigBuilderError::InvalidUVs => write!(f, "Invalid UVs"),
             RenderConfigBuilderError::InvalidMeshes => write!(f, "Invalid Meshes"),
             RenderConfigBuilderError::InvalidLights => write!(f, "Invalid Lights"),
             RenderConfigBuilderError::InvalidTextures => write!(f, "Invalid Textures"),
+            RenderConfigBuilderError::InvalidEnvironment => write!(f, "Invalid Environment"),
             RenderConfigBuilderError::CannotDeleteNonexistent => {
                 write!(f, "Cannot delete none existent")
             }
@@ -673,6 +874,14 @@ fn is_zero(v: &[f32; 3]) -> bool {
     len_sq < f32::EPSILON
 }
 
+/// Checks the interior medium and the procedural pattern of a material.
+///
+/// Materials without a pattern are not checked for pattern parameters.
+fn is_valid_material(material: &Material) -> bool {
+    let pattern = material.pattern();
+    material.medium().is_valid() && (pattern.kind == PatternKind::None || pattern.is_valid())
+}
+
 #[cfg(test)]
 mod tests {
     // TODO: Write tests for new CRUD style RenderConfigBuilder
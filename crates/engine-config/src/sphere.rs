@@ -40,6 +40,9 @@ pub struct Sphere {
     pub radius: f32,
     /// Material properties determining visual appearance.
     pub material: Material,
+    /// Rotation of the texture mapping, as columns padded to `vec4` like a WGSL `mat3x3`.
+    /// The columns are the world directions of the local x, y (north pole) and z axes.
+    pub orientation: [[f32; 4]; 3],
 }
 
 /// Errors that can occur when creating a sphere.
@@ -63,6 +66,11 @@ impl Sphere {
     pub const DEFAULT_CENTER: Vec3 = Vec3::ZERO;
     pub const DEFAULT_RADIUS: f32 = 1.0;
     pub const DEFAULT_COLOR: Vec3 = Vec3::COLOR_RED;
+    const IDENTITY: [[f32; 4]; 3] = [
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+    ];
 
     /// Creates a new sphere with validation.
     ///
@@ -84,8 +92,19 @@ impl Sphere {
             center,
             radius,
             material,
+            orientation: Self::IDENTITY,
         })
     }
+
+    /// Sets the orientation of the texture mapping.
+    ///
+    /// # Arguments
+    ///
+    /// * `columns` - World directions of the local x, y and z axes of the sphere
+    pub fn with_orientation(mut self, columns: [[f32; 3]; 3]) -> Self {
+        self.orientation = columns.map(|[x, y, z]| [x, y, z, 0.0]);
+        self
+    }
 }
 
 impl Default for Sphere {
@@ -95,11 +114,13 @@ impl Default for Sphere {
     /// - Center: origin (0, 0, 0)
     /// - Radius: 1.0
     /// - Material: default material
+    /// - Orientation: unrotated
     fn default() -> Self {
         Sphere {
             center: Self::DEFAULT_CENTER,
             radius: Self::DEFAULT_RADIUS,
             material: Material::default(),
+            orientation: Self::IDENTITY,
         }
     }
 }
@@ -18,6 +18,12 @@ use serde::{Deserialize, Serialize};
 /// - Bits 16-23: Blue channel
 /// - Bits 24-31: Alpha channel
 ///
+/// # Sampling
+///
+/// Textures are filtered bilinearly between the levels of a mip chain that is generated
+/// at upload time. [`WrapMode`] selects how UVs outside of [0, 1] are handled, and
+/// [`ColorSpace`] how the texels are averaged into the lower levels.
+///
 /// # Serialization
 ///
 /// This struct derives `Serialize` and `Deserialize` to support saving and loading
@@ -33,8 +39,41 @@ pub struct TextureData {
     /// Length should be `width * height`.
     /// Each `u32` represents one pixel in RGBA8888 format.
     pub rgba_data: Vec<u32>,
+    /// How texture coordinates outside of [0, 1] are mapped onto the texture.
+    #[serde(default)]
+    pub wrap_mode: WrapMode,
+    /// Encoding of the texels, color maps are gamma encoded and data maps linear.
+    #[serde(default)]
+    pub color_space: ColorSpace,
+}
+
+/// Addressing mode for texture coordinates outside of [0, 1].
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WrapMode {
+    /// Tiles the texture.
+    #[default]
+    Repeat = 0,
+    /// Extends the edge texels (MTL `-clamp on`).
+    Clamp = 1,
+    /// Tiles the texture, flipping every other tile (MTL `-mirror on`).
+    Mirror = 2,
 }
 
+/// Encoding of the texel values of a texture.
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ColorSpace {
+    /// Gamma encoded colors, decoded with a gamma of 2.2 like in the shaders.
+    #[default]
+    Srgb = 0,
+    /// Data stored as is, such as roughness, normal and height maps.
+    Linear = 1,
+}
+
+/// Gamma of [`ColorSpace::Srgb`] textures.
+const TEXTURE_GAMMA: f32 = 2.2;
+
 impl TextureData {
     /// Creates a new texture with specified dimensions and pixel data.
     ///
@@ -57,6 +96,128 @@ impl TextureData {
             width,
             height,
             rgba_data,
+            wrap_mode: WrapMode::default(),
+            color_space: ColorSpace::default(),
         }
     }
+
+    /// Sets the addressing mode for texture coordinates outside of [0, 1].
+    pub fn with_wrap_mode(mut self, wrap_mode: WrapMode) -> Self {
+        self.wrap_mode = wrap_mode;
+        self
+    }
+
+    /// Sets the encoding of the texels.
+    pub fn with_color_space(mut self, color_space: ColorSpace) -> Self {
+        self.color_space = color_space;
+        self
+    }
+
+    /// Builds the mip chain down to 1x1 by averaging 2x2 texel blocks.
+    ///
+    /// The colors of [`ColorSpace::Srgb`] textures are averaged in linear space and encoded
+    /// again, so high contrast textures keep their brightness at lower levels. Alpha and
+    /// [`ColorSpace::Linear`] textures are averaged as stored.
+    ///
+    /// # Returns
+    ///
+    /// The texels of all levels, starting with the full resolution image, each level half
+    /// the size of the previous one. Textures with fewer texels than their size only get
+    /// the full resolution level.
+    pub fn mip_chain(&self) -> Vec<Vec<u32>> {
+        let (mut width, mut height) = (self.width as usize, self.height as usize);
+        let mut levels = vec![self.rgba_data.clone()];
+        if width == 0 || height == 0 || self.rgba_data.len() < width * height {
+            return levels;
+        }
+
+        // Linear value of every byte, the alpha channel is always linear
+        let decode: Vec<f32> = (0..=255u8)
+            .map(|byte| {
+                let value = byte as f32 / 255.0;
+                match self.color_space {
+                    ColorSpace::Srgb => value.powf(TEXTURE_GAMMA),
+                    ColorSpace::Linear => value,
+                }
+            })
+            .collect();
+        let encode = |value: f32, channel: usize| {
+            let value = match self.color_space {
+                ColorSpace::Srgb if channel < 3 => value.powf(1.0 / TEXTURE_GAMMA),
+                _ => value,
+            };
+            (value * 255.0).round() as u8
+        };
+
+        while width > 1 || height > 1 {
+            let level = levels.last().unwrap();
+            let (next_width, next_height) = ((width / 2).max(1), (height / 2).max(1));
+            let mut next = Vec::with_capacity(next_width * next_height);
+            for y in 0..next_height {
+                for x in 0..next_width {
+                    let mut sum = [0.0f32; 4];
+                    for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                        let sx = (x * 2 + dx).min(width - 1);
+                        let sy = (y * 2 + dy).min(height - 1);
+                        let texel = level[sy * width + sx].to_le_bytes();
+                        for (channel, (sum, byte)) in sum.iter_mut().zip(texel).enumerate() {
+                            *sum += if channel < 3 {
+                                decode[byte as usize]
+                            } else {
+                                byte as f32 / 255.0
+                            };
+                        }
+                    }
+                    let mut texel = [0u8; 4];
+                    for (channel, value) in texel.iter_mut().enumerate() {
+                        *value = encode(sum[channel] / 4.0, channel);
+                    }
+                    next.push(u32::from_le_bytes(texel));
+                }
+            }
+            levels.push(next);
+            (width, height) = (next_width, next_height);
+        }
+        levels
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: u32 = 0xffff_ffff;
+    const BLACK: u32 = 0xff00_0000;
+
+    #[test]
+    fn mip_chain_halves_down_to_one_texel() {
+        let texture = TextureData::new(5, 2, vec![WHITE; 10]);
+        let sizes: Vec<usize> = texture.mip_chain().iter().map(Vec::len).collect();
+        assert_eq!(sizes, [10, 2, 1]);
+        assert_eq!(
+            TextureData::new(4, 4, vec![]).mip_chain(),
+            [Vec::<u32>::new()]
+        );
+    }
+
+    #[test]
+    fn srgb_textures_are_averaged_in_linear_space() {
+        let checker = vec![BLACK, WHITE, WHITE, BLACK];
+        let srgb = TextureData::new(2, 2, checker.clone()).mip_chain();
+        // Half the light of white, encoded with the gamma of 2.2
+        let half = (0.5f32.powf(1.0 / 2.2) * 255.0).round() as u8;
+        assert_eq!(srgb[1][0].to_le_bytes(), [half, half, half, 255]);
+
+        let data = TextureData::new(2, 2, checker)
+            .with_color_space(ColorSpace::Linear)
+            .mip_chain();
+        assert_eq!(data[1][0].to_le_bytes(), [128, 128, 128, 255]);
+    }
+
+    #[test]
+    fn alpha_is_averaged_as_stored() {
+        let cutout = vec![0x00ff_ffff, WHITE];
+        let levels = TextureData::new(2, 1, cutout).mip_chain();
+        assert_eq!(levels[1][0].to_le_bytes(), [255, 255, 255, 128]);
+    }
 }
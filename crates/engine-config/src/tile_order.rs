@@ -0,0 +1,68 @@
+//! Order in which the tiles of a tiled render are rendered.
+//!
+//! This module defines the [`TileOrder`] enum, which decides the sequence in which
+//! the image tiles are rendered when the image does not fit into one tile.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Sequence in which the tiles of an image are rendered.
+///
+/// The discriminants are stored in the [`Uniforms`](crate::Uniforms), the tiles
+/// themselves are scheduled on the CPU.
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TileOrder {
+    /// Row by row from the top, every row from left to right.
+    #[default]
+    Rows = 0,
+    /// Column by column from the left, every column from top to bottom.
+    Columns = 1,
+    /// Outwards from the center of the image, so the subject is visible first.
+    Spiral = 2,
+}
+
+impl TileOrder {
+    /// All orders, in the order they are presented to the user.
+    pub const ALL: [TileOrder; 3] = [TileOrder::Rows, TileOrder::Columns, TileOrder::Spiral];
+
+    /// Returns the lowercase name used in scene files and on the command line.
+    pub fn name(&self) -> &'static str {
+        match self {
+            TileOrder::Rows => "rows",
+            TileOrder::Columns => "columns",
+            TileOrder::Spiral => "spiral",
+        }
+    }
+
+    /// Returns the order stored in the uniforms, or `None` for an unknown value.
+    pub fn from_u32(value: u32) -> Option<Self> {
+        TileOrder::ALL.into_iter().find(|o| u32::from(*o) == value)
+    }
+}
+
+impl From<TileOrder> for u32 {
+    fn from(value: TileOrder) -> Self {
+        value as u32
+    }
+}
+
+impl fmt::Display for TileOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for TileOrder {
+    type Err = String;
+
+    /// Parses an order from its name, ignoring case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        TileOrder::ALL
+            .into_iter()
+            .find(|o| o.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("unknown tile order: {s}"))
+    }
+}
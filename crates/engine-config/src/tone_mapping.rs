@@ -0,0 +1,188 @@
+//! Tone mapping operators for the display transform.
+//!
+//! This module defines the [`ToneMapping`] enum, which selects how the accumulated linear
+//! radiance is compressed into the displayable range before 8-bit quantization.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Operator that maps linear radiance to the displayable `[0, 1]` range.
+///
+/// The exposure from [`Uniforms`](crate::Uniforms) is applied before the operator,
+/// gamma encoding and the clamp to 8 bit happen after it.
+///
+/// The discriminants are passed to the GPU shaders through the uniform buffer.
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ToneMapping {
+    /// No compression, values above 1.0 are clamped.
+    None = 0,
+    /// Simple Reinhard operator `c / (c + 1)`, applied per channel.
+    #[default]
+    Reinhard = 1,
+    /// Fitted ACES reference rendering and output device transform.
+    Aces = 2,
+    /// AgX base transform, desaturates very bright colors instead of shifting their hue.
+    AgX = 3,
+}
+
+impl ToneMapping {
+    /// All operators, in the order they are presented to the user.
+    pub const ALL: [ToneMapping; 4] = [
+        ToneMapping::None,
+        ToneMapping::Reinhard,
+        ToneMapping::Aces,
+        ToneMapping::AgX,
+    ];
+
+    /// Applies the operator to an exposed linear color. The result is still linear,
+    /// [`color_map`] encodes it for display. Mirrors `tone_map` of the shaders.
+    pub fn apply(&self, color: [f32; 3]) -> [f32; 3] {
+        let color = color.map(|c| c.max(0.0));
+        match self {
+            ToneMapping::None => color,
+            ToneMapping::Reinhard => color.map(|c| c / (c + 1.0)),
+            ToneMapping::Aces => tone_map_aces(color),
+            ToneMapping::AgX => tone_map_agx(color),
+        }
+    }
+
+    /// Returns the lowercase name used in scene files and on the command line.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ToneMapping::None => "none",
+            ToneMapping::Reinhard => "reinhard",
+            ToneMapping::Aces => "aces",
+            ToneMapping::AgX => "agx",
+        }
+    }
+}
+
+impl TryFrom<u32> for ToneMapping {
+    type Error = u32;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        ToneMapping::ALL
+            .into_iter()
+            .find(|t| u32::from(*t) == value)
+            .ok_or(value)
+    }
+}
+
+impl From<ToneMapping> for u32 {
+    fn from(value: ToneMapping) -> Self {
+        value as u32
+    }
+}
+
+impl fmt::Display for ToneMapping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for ToneMapping {
+    type Err = String;
+
+    /// Parses an operator from its name, ignoring case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ToneMapping::ALL
+            .into_iter()
+            .find(|t| t.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("unknown tone mapping operator: {s}"))
+    }
+}
+
+/// Encodes a tone mapped color and its alpha as RGBA8 with the square root as display gamma,
+/// like `color_map` of the shaders.
+pub fn color_map(color: [f32; 3], alpha: f32) -> [u8; 4] {
+    let encode = |c: f32| (c.max(0.0).sqrt().clamp(0.0, 1.0) * 255.999) as u8;
+    let [r, g, b] = color.map(encode);
+    [r, g, b, (alpha.clamp(0.0, 1.0) * 255.999) as u8]
+}
+
+/// Product of the matrix with the columns `m` and `v`.
+fn mul(m: [[f32; 3]; 3], v: [f32; 3]) -> [f32; 3] {
+    [0, 1, 2].map(|row| m[0][row] * v[0] + m[1][row] * v[1] + m[2][row] * v[2])
+}
+
+/// Stephen Hill's fit of the ACES reference rendering and output device transforms.
+fn tone_map_aces(color: [f32; 3]) -> [f32; 3] {
+    let input = [
+        [0.59719, 0.07600, 0.02840],
+        [0.35458, 0.90834, 0.13383],
+        [0.04823, 0.01566, 0.83777],
+    ];
+    let output = [
+        [1.60475, -0.10208, -0.00327],
+        [-0.53108, 1.10813, -0.07276],
+        [-0.07367, -0.00605, 1.07602],
+    ];
+    let fitted = mul(input, color).map(|v| {
+        let a = v * (v + 0.0245786) - 0.000090537;
+        let b = v * (0.983729 * v + 0.432951) + 0.238081;
+        a / b
+    });
+    mul(output, fitted)
+}
+
+/// Polynomial fit of the AgX default contrast curve.
+fn agx_contrast(x: f32) -> f32 {
+    let x2 = x * x;
+    let x4 = x2 * x2;
+    15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x
+        - 0.00232
+}
+
+/// AgX base transform: log encoding in the AgX working space, sigmoid, back to linear sRGB.
+fn tone_map_agx(color: [f32; 3]) -> [f32; 3] {
+    let inset = [
+        [0.84247905, 0.042328242, 0.042375654],
+        [0.0784336, 0.87846863, 0.0784336],
+        [0.079223745, 0.07916613, 0.879143],
+    ];
+    let outset = [
+        [1.196879, -0.052896854, -0.052971635],
+        [-0.09802088, 1.1519032, -0.09804345],
+        [-0.09902974, -0.098961174, 1.1510737],
+    ];
+    let (min_ev, max_ev) = (-12.47393, 4.026069);
+    let encoded = mul(inset, color.map(|c| c.max(1e-10)))
+        .map(|v| ((v.log2() - min_ev) / (max_ev - min_ev)).clamp(0.0, 1.0));
+    // The curve outputs display encoded values, undo the display gamma for color_map
+    mul(outset, encoded.map(agx_contrast)).map(|c| c.max(0.0).powf(2.2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn operators_keep_black_and_compress_highlights() {
+        for operator in ToneMapping::ALL {
+            let black = operator.apply([0.0; 3]);
+            assert!(
+                black.iter().all(|c| c.abs() < 1e-3),
+                "{operator}: {black:?}"
+            );
+        }
+        assert_eq!(
+            ToneMapping::Reinhard.apply([1.0, 3.0, -1.0]),
+            [0.5, 0.75, 0.0]
+        );
+        // The ACES fit overshoots white by about one percent
+        for operator in [ToneMapping::Aces, ToneMapping::AgX] {
+            let bright = operator.apply([100.0; 3]);
+            assert!(bright.iter().all(|c| *c <= 1.02), "{operator}: {bright:?}");
+        }
+    }
+
+    #[test]
+    fn color_map_encodes_with_the_square_root() {
+        assert_eq!(color_map([0.25, 1.0, 4.0], 0.5), [127, 255, 255, 127]);
+        assert_eq!(ToneMapping::try_from(3), Ok(ToneMapping::AgX));
+        assert_eq!(ToneMapping::try_from(9), Err(9));
+    }
+}
@@ -6,6 +6,10 @@
 
 use bytemuck::{Pod, Zeroable};
 use crate::camera::Camera;
+use crate::material::Material;
+use crate::medium::Medium;
+use crate::tile_order::TileOrder;
+use crate::tone_mapping::ToneMapping;
 
 /// Global rendering parameters passed to GPU shaders.
 ///
@@ -16,11 +20,11 @@ use crate::camera::Camera;
 /// # Memory Layout
 ///
 /// The struct uses `#[repr(C)]` to ensure consistent memory layout across platforms.
-/// Padding fields (`_pad1`, `_pad2`) are used to satisfy GPU alignment requirements.
+/// Padding fields (`_pad1` to `_pad3`) are used to satisfy GPU alignment requirements.
 ///
 /// # Boolean Fields
 ///
-/// Note that boolean flags (`ground_enabled`, `checkerboard_enabled`, `color_hash_enabled`)
+/// Note that boolean flags (`ground_enabled`, `checkerboard_enabled`, `color_hash_enabled`, `interior_media`, `shadow_catcher`, `radiance_layer`)
 /// are stored as `u32` instead of `bool` because `bool` doesn't satisfy the `Pod` trait
 /// requirements for GPU data.
 #[repr(C)]
@@ -44,7 +48,7 @@ pub struct Uniforms {
     pub bvh_node_count: u32,
     /// Number of triangles in the BVH structure.
     pub bvh_triangle_count: u32,
-    /// Index of the root BVH node.
+    /// Index of the root node of the top-level BVH over the mesh instances.
     pub bvh_root: u32,
     /// Y-coordinate of the ground plane.
     pub ground_height: f32,
@@ -64,6 +68,47 @@ pub struct Uniforms {
     pub checkerboard_color_2: [f32; 3],
     /// Padding for GPU alignment.
     pub _pad2: u32,
+    /// Width of the environment map in pixels (0 = no environment map, use `sky_color`).
+    pub environment_width: u32,
+    /// Height of the environment map in pixels.
+    pub environment_height: u32,
+    /// Rotation of the environment map around the y axis in radians.
+    pub environment_rotation: f32,
+    /// Radiance multiplier of the environment map.
+    pub environment_intensity: f32,
+    /// Exposure in stops, the radiance is scaled by `2^exposure` before tone mapping.
+    pub exposure: f32,
+    /// Tone mapping operator, see [`ToneMapping`].
+    pub tone_mapping: u32,
+    /// Bit mask of the AOV layers to write, see [`AovSet`](crate::AovSet).
+    pub aov_flags: u32,
+    /// Noise level at which a pixel stops taking samples (0 = adaptive sampling disabled).
+    pub adaptive_threshold: f32,
+    /// Samples every pixel takes before its noise level is trusted.
+    pub adaptive_min_samples: u32,
+    /// Number of emissive mesh triangles sampled as area lights.
+    pub emissive_triangle_count: u32,
+    /// Edge length of the square tiles the image is rendered in (0 = as large as the GPU allows).
+    pub tile_size: u32,
+    /// Order in which the tiles are rendered, see [`TileOrder`].
+    pub tile_order: u32,
+    /// Absorption coefficient of the global medium [r, g, b], see [`Medium`].
+    pub medium_absorption: [f32; 3],
+    /// Henyey-Greenstein asymmetry of the global medium.
+    pub medium_anisotropy: f32,
+    /// Scattering coefficient of the global medium [r, g, b].
+    pub medium_scattering: [f32; 3],
+    /// Whether any object has an interior medium (0 = no, 1 = yes).
+    pub interior_media: u32,
+    /// Material of the ground plane, textures are mapped with one repeat per scene unit.
+    pub ground_material: Material,
+    /// Render the ground as shadow catcher (0 = disabled, 1 = enabled).
+    pub shadow_catcher: u32,
+    /// Add the linear radiance of every pixel to the frames as
+    /// [`RADIANCE_LAYER`](frame_buffer::frame_iterator::RADIANCE_LAYER) (0 = disabled, 1 = enabled).
+    pub radiance_layer: u32,
+    /// Padding for GPU alignment.
+    pub _pad3: [u32; 2],
 }
 
 impl Default for Uniforms {
@@ -76,6 +121,12 @@ impl Default for Uniforms {
     /// - Checkerboard pattern enabled (black and magenta)
     /// - Sky color: light blue (0.5, 0.7, 1.0)
     /// - Max ray depth: 5 bounces
+    /// - No environment map
+    /// - Exposure 0 with Reinhard tone mapping
+    /// - Adaptive sampling disabled, 16 samples minimum once enabled
+    /// - No participating media
+    /// - Grey ground material, see [`Material::ground`], no shadow catcher
+    /// - Tiles as large as the GPU allows, rendered row by row
     fn default() -> Self {
         Self {
             width: 400,
@@ -97,6 +148,26 @@ impl Default for Uniforms {
             _pad1: 0,
             checkerboard_color_2: [1.0, 0.0, 1.0], // Magenta
             _pad2: 0,
+            environment_width: 0,
+            environment_height: 0,
+            environment_rotation: 0.0,
+            environment_intensity: 1.0,
+            exposure: 0.0,
+            tone_mapping: ToneMapping::default().into(),
+            aov_flags: 0,
+            adaptive_threshold: 0.0,
+            adaptive_min_samples: 16,
+            emissive_triangle_count: 0,
+            tile_size: 0,
+            tile_order: TileOrder::default().into(),
+            medium_absorption: [0.0; 3],
+            medium_anisotropy: 0.0,
+            medium_scattering: [0.0; 3],
+            interior_media: 0,
+            ground_material: Material::ground(),
+            shadow_catcher: 0,
+            radiance_layer: 0,
+            _pad3: [0; 2],
         }
     }
 }
@@ -181,4 +252,238 @@ impl Uniforms {
         self.color_hash_enabled = if enabled { 1 } else { 0 };
         self
     }
+
+    /// Sets the root of the top-level BVH.
+    ///
+    /// # Arguments
+    ///
+    /// * `root` - Index of the top-level root within the BVH node buffer
+    ///
+    /// # Returns
+    ///
+    /// Self with the BVH root updated, for method chaining.
+    pub fn with_bvh_root(mut self, root: u32) -> Self {
+        self.bvh_root = root;
+        self
+    }
+
+    /// Sets the environment map parameters.
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - Width of the environment map in pixels (0 disables it)
+    /// * `height` - Height of the environment map in pixels
+    /// * `rotation` - Rotation around the y axis in radians
+    /// * `intensity` - Radiance multiplier
+    ///
+    /// # Returns
+    ///
+    /// Self with the environment settings updated, for method chaining.
+    pub fn with_environment(
+        mut self,
+        width: u32,
+        height: u32,
+        rotation: f32,
+        intensity: f32,
+    ) -> Self {
+        self.environment_width = width;
+        self.environment_height = height;
+        self.environment_rotation = rotation;
+        self.environment_intensity = intensity;
+        self
+    }
+
+    /// Sets the exposure and the tone mapping operator.
+    ///
+    /// # Arguments
+    ///
+    /// * `exposure` - Exposure in stops (0 leaves the radiance unchanged)
+    /// * `tone_mapping` - Operator applied after the exposure
+    ///
+    /// # Returns
+    ///
+    /// Self with the display settings updated, for method chaining.
+    pub fn with_tone_mapping(mut self, exposure: f32, tone_mapping: ToneMapping) -> Self {
+        self.exposure = exposure;
+        self.tone_mapping = tone_mapping.into();
+        self
+    }
+
+    /// Configures adaptive sampling.
+    ///
+    /// A pixel stops taking samples once the standard error of its mean, measured in the
+    /// gamma encoded display range, falls below `threshold`.
+    ///
+    /// # Arguments
+    ///
+    /// * `threshold` - Noise level at which a pixel is converged (0 disables adaptive sampling)
+    /// * `min_samples` - Samples every pixel takes before it may stop
+    ///
+    /// # Returns
+    ///
+    /// Self with the adaptive sampling settings updated, for method chaining.
+    pub fn with_adaptive_sampling(mut self, threshold: f32, min_samples: u32) -> Self {
+        self.adaptive_threshold = threshold;
+        self.adaptive_min_samples = min_samples;
+        self
+    }
+
+    /// Configures tiled rendering.
+    ///
+    /// Images that don't fit into the GPU buffers are always split into tiles. Every tile
+    /// accumulates all of its samples before the next one is started.
+    ///
+    /// # Arguments
+    ///
+    /// * `tile_size` - Edge length of the tiles in pixels (0 picks the largest size the GPU allows)
+    /// * `tile_order` - Order in which the tiles are rendered
+    ///
+    /// # Returns
+    ///
+    /// Self with the tile settings updated, for method chaining.
+    pub fn with_tiles(mut self, tile_size: u32, tile_order: TileOrder) -> Self {
+        self.tile_size = tile_size;
+        self.tile_order = tile_order.into();
+        self
+    }
+
+    /// Sets the participating media.
+    ///
+    /// # Arguments
+    ///
+    /// * `medium` - Global medium filling the space outside of objects
+    /// * `interior_media` - Whether any object has an interior medium
+    ///
+    /// # Returns
+    ///
+    /// Self with the media settings updated, for method chaining.
+    pub fn with_medium(mut self, medium: Medium, interior_media: bool) -> Self {
+        self.medium_absorption = medium.absorption;
+        self.medium_anisotropy = medium.anisotropy;
+        self.medium_scattering = medium.scattering;
+        self.interior_media = if interior_media { 1 } else { 0 };
+        self
+    }
+
+    /// Sets the material of the ground plane and the shadow catcher mode.
+    ///
+    /// A shadow catcher is invisible itself. It only keeps the shadows and reflections of
+    /// the other objects, over a transparent background.
+    ///
+    /// # Arguments
+    ///
+    /// * `material` - Material of the ground plane
+    /// * `shadow_catcher` - Whether to render the ground as shadow catcher
+    ///
+    /// # Returns
+    ///
+    /// Self with the ground settings updated, for method chaining.
+    pub fn with_ground(mut self, material: Material, shadow_catcher: bool) -> Self {
+        self.ground_material = material;
+        self.shadow_catcher = if shadow_catcher { 1 } else { 0 };
+        self
+    }
+
+    /// Sets whether the frames carry the linear radiance of every pixel, before exposure and
+    /// tone mapping. The denoiser filters this layer.
+    ///
+    /// # Returns
+    ///
+    /// Self with the radiance layer setting updated, for method chaining.
+    pub fn with_radiance_layer(mut self, radiance_layer: bool) -> Self {
+        self.radiance_layer = if radiance_layer { 1 } else { 0 };
+        self
+    }
+
+    /// Returns the global medium.
+    pub fn medium(&self) -> Medium {
+        Medium::new(
+            self.medium_absorption,
+            self.medium_scattering,
+            self.medium_anisotropy,
+        )
+    }
+
+    /// Standard error of the mean luminance of a pixel, scaled to the square root encoding of
+    /// the display. Mirrors `sample_error` of the shaders.
+    ///
+    /// # Arguments
+    ///
+    /// * `luminance_sum` - Sum of the luminance of all samples
+    /// * `luminance_squared_sum` - Sum of the squared luminance of all samples
+    /// * `samples` - Number of samples taken
+    pub fn sample_error(
+        &self,
+        luminance_sum: f32,
+        luminance_squared_sum: f32,
+        samples: f32,
+    ) -> f32 {
+        let scale = self.exposure.exp2();
+        let mean = luminance_sum / samples * scale;
+        let variance = (luminance_squared_sum / samples * scale * scale - mean * mean).max(0.0);
+        (variance / samples).sqrt() / (2.0 * mean.sqrt() + ADAPTIVE_ERROR_FLOOR)
+    }
+
+    /// Returns `true` if a pixel took the minimum samples and its [`sample_error`](Self::sample_error)
+    /// is below the adaptive threshold. Mirrors `pixel_converged` of the shaders.
+    pub fn pixel_converged(
+        &self,
+        luminance_sum: f32,
+        luminance_squared_sum: f32,
+        samples: f32,
+    ) -> bool {
+        samples >= self.adaptive_min_samples as f32
+            && self.sample_error(luminance_sum, luminance_squared_sum, samples)
+                < self.adaptive_threshold
+    }
+
+    /// Applies the exposure and the tone mapping operator to linear radiance, mirrors
+    /// `tone_map` of the shaders. Unknown operators leave the exposed color as is.
+    pub fn tone_map(&self, color: [f32; 3]) -> [f32; 3] {
+        let exposed = color.map(|c| c * self.exposure.exp2());
+        ToneMapping::try_from(self.tone_mapping).map_or(exposed, |t| t.apply(exposed))
+    }
+}
+
+/// Keeps the adaptive sampling error of nearly black pixels finite, `ADAPTIVE_ERROR_FLOOR` of
+/// the shaders.
+pub const ADAPTIVE_ERROR_FLOOR: f32 = 0.05;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Luminance sums of `n` samples alternating between `a` and `b`.
+    fn sums(a: f32, b: f32, n: u32) -> (f32, f32, f32) {
+        let values = (0..n).map(|i| if i % 2 == 0 { a } else { b });
+        let sum = values.clone().sum();
+        let squared_sum = values.map(|v| v * v).sum();
+        (sum, squared_sum, n as f32)
+    }
+
+    #[test]
+    fn noise_free_pixels_converge_after_the_minimum_samples() {
+        let uniforms = Uniforms::default().with_adaptive_sampling(0.01, 16);
+        let (sum, squared_sum, _) = sums(0.5, 0.5, 16);
+
+        assert_eq!(uniforms.sample_error(sum, squared_sum, 16.0), 0.0);
+        assert!(uniforms.pixel_converged(sum, squared_sum, 16.0));
+        // Not even a noise-free pixel stops before the minimum
+        let (sum, squared_sum, _) = sums(0.5, 0.5, 8);
+        assert!(!uniforms.pixel_converged(sum, squared_sum, 8.0));
+    }
+
+    #[test]
+    fn sample_error_falls_with_the_square_root_of_the_samples() {
+        let uniforms = Uniforms::default().with_adaptive_sampling(0.01, 16);
+        let (sum, squared_sum, n) = sums(0.0, 1.0, 16);
+        let error_16 = uniforms.sample_error(sum, squared_sum, n);
+        let (sum, squared_sum, n) = sums(0.0, 1.0, 64);
+        let error_64 = uniforms.sample_error(sum, squared_sum, n);
+
+        // Standard deviation 0.5 around the mean 0.5
+        assert!((error_16 - 0.125 / (2.0 * 0.5f32.sqrt() + ADAPTIVE_ERROR_FLOOR)).abs() < 1e-6);
+        assert!((error_16 / error_64 - 2.0).abs() < 1e-4);
+        assert!(!uniforms.pixel_converged(sum, squared_sum, n));
+    }
 }
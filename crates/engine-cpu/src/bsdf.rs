@@ -0,0 +1,557 @@
+//! Principled BSDF, dielectrics and the sampling helpers they share.
+
+use crate::color::luminance;
+use crate::intersect::Hit;
+use crate::medium::{Medium, hg_phase};
+use crate::sampler::Sampler;
+use crate::scene::Scene;
+use engine_config::Material;
+use glam::{Mat3, Vec2, Vec3};
+use std::f32::consts::PI;
+
+/// Uniform direction on the unit sphere.
+pub fn random_unit_vector(rng: &mut Sampler) -> Vec3 {
+    let u = rng.sample_2d();
+    let z = 1.0 - 2.0 * u.x;
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * PI * u.y;
+    Vec3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+/// Cosine-weighted hemisphere sampling.
+pub fn scatter_lambertian(normal: Vec3, rng: &mut Sampler) -> Vec3 {
+    let direction = normal + random_unit_vector(rng);
+    if direction.abs().max_element() < 1e-8 {
+        return normal;
+    }
+    direction.normalize()
+}
+
+pub fn reflect(v: Vec3, n: Vec3) -> Vec3 {
+    v - 2.0 * v.dot(n) * n
+}
+
+/// Refraction of the unit vector `v` through a surface with normal `n` facing against it,
+/// zero on total internal reflection like WGSL's `refract`.
+pub fn refract(v: Vec3, n: Vec3, eta: f32) -> Vec3 {
+    let n_dot_v = n.dot(v);
+    let k = 1.0 - eta * eta * (1.0 - n_dot_v * n_dot_v);
+    if k < 0.0 {
+        return Vec3::ZERO;
+    }
+    eta * v - (eta * n_dot_v + k.sqrt()) * n
+}
+
+/// Orthonormal basis with `w` as the third column (Duff et al. 2017).
+pub fn build_onb(w: Vec3) -> Mat3 {
+    let sign = if w.z >= 0.0 { 1.0 } else { -1.0 };
+    let a = -1.0 / (sign + w.z);
+    let b = w.x * w.y * a;
+    let t = Vec3::new(1.0 + sign * w.x * w.x * a, sign * b, -sign * w.x);
+    let bt = Vec3::new(b, sign + w.y * w.y * a, -w.y);
+    Mat3::from_cols(t, bt, w)
+}
+
+/// Shading normal with the normal map and height map of the material applied. Only hits
+/// with a tangent frame are perturbed, other surfaces keep their normal.
+pub fn shading_normal(scene: &Scene, hit: &Hit) -> Vec3 {
+    let material = &hit.material;
+    let tangent = hit.tangent.truncate();
+    if (material.normal_texture < 0 && material.bump_texture < 0)
+        || tangent.length_squared() < 1e-12
+    {
+        return hit.normal;
+    }
+    let n = hit.normal;
+    let t = (tangent - n * n.dot(tangent)).normalize();
+    let b = n.cross(t) * if hit.tangent.w < 0.0 { -1.0 } else { 1.0 };
+
+    let mut mapped = n;
+    if material.normal_texture >= 0 {
+        // Tangent space normal, OpenGL convention (green = +V)
+        let m = scene.fetch_texel(material.normal_texture, hit.uv, hit.footprint) * 2.0 - 1.0;
+        let tilted = Vec3::new(
+            m.x * material.bump_scale,
+            m.y * material.bump_scale,
+            m.z.max(1e-3),
+        );
+        mapped = (t * tilted.x + b * tilted.y + n * tilted.z).normalize();
+    }
+    if let Some(texture) = scene.texture(material.bump_texture) {
+        // Height differences to the neighbouring texels tilt the normal
+        let (width, height) = texture.size();
+        let texel = Vec2::new(1.0 / width as f32, 1.0 / height as f32);
+        let h = texture.fetch(hit.uv, hit.footprint).x;
+        let dh_du = texture
+            .fetch(hit.uv + Vec2::new(texel.x, 0.0), hit.footprint)
+            .x
+            - h;
+        let dh_dv = texture
+            .fetch(hit.uv + Vec2::new(0.0, texel.y), hit.footprint)
+            .x
+            - h;
+        mapped = (mapped - material.bump_scale * (dh_du * t + dh_dv * b)).normalize();
+    }
+    mapped
+}
+
+/// Emitted radiance of a hit, the emissive color scaled by the emission map.
+pub fn surface_emission(scene: &Scene, hit: &Hit) -> Vec3 {
+    let emissive = Vec3::from(hit.material.emissive);
+    if hit.material.emissive_texture < 0 {
+        return emissive;
+    }
+    emissive * scene.sample_texture(hit.material.emissive_texture, hit.uv, hit.footprint)
+}
+
+/// Illumination models 4, 6 and 7 describe glass-like surfaces that refract.
+pub fn is_dielectric(material: &Material) -> bool {
+    matches!(material.illum, 4 | 6 | 7)
+}
+
+/// Schlick's approximation of the Fresnel reflectance.
+fn reflectance(cos_theta: f32, eta: f32) -> f32 {
+    let r0 = ((1.0 - eta) / (1.0 + eta)).powi(2);
+    r0 + (1.0 - r0) * (1.0 - cos_theta).powi(5)
+}
+
+/// Picks reflection or refraction weighted by the Fresnel term.
+///
+/// `normal` has to face against `dir`, `eta` is the ratio of the incident to the
+/// transmitted IOR.
+pub fn scatter_dielectric(dir: Vec3, normal: Vec3, eta: f32, rng: &mut Sampler) -> Vec3 {
+    let unit_dir = dir.normalize();
+    let cos_theta = (-unit_dir).dot(normal).min(1.0);
+    let sin2_theta = (1.0 - cos_theta * cos_theta).max(0.0);
+    let sin2_transmitted = eta * eta * sin2_theta;
+
+    // Total internal reflection
+    if sin2_transmitted > 1.0 {
+        return reflect(unit_dir, normal);
+    }
+
+    // Schlick is only valid for the angle on the optically thinner side
+    let cos_fresnel = if eta > 1.0 {
+        (1.0 - sin2_transmitted).sqrt()
+    } else {
+        cos_theta
+    };
+    if reflectance(cos_fresnel, eta) > rng.sample_1d() {
+        return reflect(unit_dir, normal);
+    }
+    refract(unit_dir, normal, eta)
+}
+
+/// Beer-Lambert absorption for a path segment of length `distance` inside a dielectric.
+///
+/// Kd is read as the transmittance after one scene unit, a black Kd is treated as clear.
+pub fn dielectric_absorption(material: &Material, distance: f32) -> Vec3 {
+    let diffuse = Vec3::from(material.diffuse.0);
+    if diffuse.max_element() <= 0.0 {
+        return Vec3::ONE;
+    }
+    let sigma_a = -diffuse.clamp(Vec3::splat(1e-4), Vec3::ONE).ln();
+    (-sigma_a * distance).exp()
+}
+
+/// Resolved parameters of the principled BSDF at a hit.
+#[derive(Copy, Clone, Debug)]
+pub struct Principled {
+    pub base_color: Vec3,
+    pub metallic: f32,
+    pub alpha: f32,
+    pub specular: f32,
+    pub sheen: f32,
+    pub clearcoat: f32,
+    pub clearcoat_alpha: f32,
+}
+
+impl Principled {
+    /// Resolves the parameters at a hit, `base_color` already includes the texture.
+    pub fn from_hit(scene: &Scene, hit: &Hit, base_color: Vec3) -> Self {
+        let m = &hit.material;
+        let mut metallic = m.metallic;
+        let mut roughness = m.roughness;
+        let mut specular = m.specular_level;
+        if m.metallic_texture >= 0 {
+            metallic *= scene
+                .fetch_texel(m.metallic_texture, hit.uv, hit.footprint)
+                .x;
+        }
+        if m.roughness_texture >= 0 {
+            roughness *= scene
+                .fetch_texel(m.roughness_texture, hit.uv, hit.footprint)
+                .x;
+        } else if m.shininess_texture >= 0 {
+            // Same conversion from the specular exponent as on the scene side
+            let exponent = m.shininess
+                * scene
+                    .fetch_texel(m.shininess_texture, hit.uv, hit.footprint)
+                    .x;
+            roughness = 1.0 - (exponent / 1000.0).clamp(0.0, 1.0).sqrt();
+        }
+        if m.specular_texture >= 0 {
+            let tint = scene.sample_texture(m.specular_texture, hit.uv, hit.footprint);
+            specular *= (tint.x + tint.y + tint.z) / 3.0;
+        }
+        // alpha is clamped so near-perfect mirrors stay numerically stable
+        Self {
+            base_color,
+            metallic: metallic.clamp(0.0, 1.0),
+            alpha: (roughness * roughness).max(1e-3),
+            specular,
+            sheen: m.sheen,
+            clearcoat: m.clearcoat,
+            clearcoat_alpha: (m.clearcoat_roughness * m.clearcoat_roughness).max(1e-3),
+        }
+    }
+
+    /// Parameters of points inside a medium, which scatter with the phase function instead.
+    fn none() -> Self {
+        Self {
+            base_color: Vec3::ZERO,
+            metallic: 0.0,
+            alpha: 1.0,
+            specular: 0.0,
+            sheen: 0.0,
+            clearcoat: 0.0,
+            clearcoat_alpha: 1.0,
+        }
+    }
+
+    fn specular_f0(&self) -> Vec3 {
+        Vec3::splat(0.08 * self.specular).lerp(self.base_color, self.metallic)
+    }
+
+    /// Probabilities of picking the diffuse, specular and clearcoat lobe, they only depend
+    /// on `wo`.
+    fn lobe_probabilities(&self, n_dot_v: f32) -> Vec3 {
+        let f0 = self.specular_f0();
+        let w_diffuse = (1.0 - self.metallic) * (luminance(self.base_color) + self.sheen);
+        let w_specular = if f0.max_element() > 0.0 {
+            luminance(fresnel_schlick(f0, n_dot_v))
+        } else {
+            0.0
+        };
+        let w_clearcoat = 0.25 * self.clearcoat * fresnel_schlick(Vec3::splat(0.04), n_dot_v).x;
+        let total = w_diffuse + w_specular + w_clearcoat;
+        if total <= 0.0 {
+            return Vec3::X;
+        }
+        Vec3::new(w_diffuse, w_specular, w_clearcoat) / total
+    }
+
+    /// Evaluates the BSDF, returns f · cos θi and the sampling pdf.
+    ///
+    /// `n` has to face `wo`, both `wo` and `wi` point away from the surface.
+    pub fn eval(&self, n: Vec3, wo: Vec3, wi: Vec3) -> (Vec3, f32) {
+        let n_dot_l = n.dot(wi);
+        let n_dot_v = n.dot(wo);
+        if n_dot_l <= 0.0 || n_dot_v <= 0.0 {
+            return (Vec3::ZERO, 0.0);
+        }
+
+        let h = (wo + wi).normalize();
+        let n_dot_h = n.dot(h).max(0.0);
+        let l_dot_h = wi.dot(h).clamp(0.0, 1.0);
+        let probs = self.lobe_probabilities(n_dot_v);
+
+        // Diffuse with sheen at grazing angles
+        let diffuse_weight = 1.0 - self.metallic;
+        let mut f = diffuse_weight
+            * (self.base_color / PI + Vec3::splat(self.sheen * (1.0 - l_dot_h).powi(5)));
+        let mut pdf = probs.x * n_dot_l / PI;
+
+        // Specular, a black F0 disables the lobe
+        if probs.y > 0.0 {
+            let d = ggx_d(n_dot_h, self.alpha);
+            let g = smith_g1(n_dot_l, self.alpha) * smith_g1(n_dot_v, self.alpha);
+            f += fresnel_schlick(self.specular_f0(), l_dot_h) * d * g / (4.0 * n_dot_l * n_dot_v);
+            pdf += probs.y * ggx_vndf_pdf(n_dot_v, n_dot_h, self.alpha);
+        }
+
+        // Clearcoat, a colorless specular layer with a fixed IOR of 1.5
+        if probs.z > 0.0 {
+            let d = ggx_d(n_dot_h, self.clearcoat_alpha);
+            let g =
+                smith_g1(n_dot_l, self.clearcoat_alpha) * smith_g1(n_dot_v, self.clearcoat_alpha);
+            let fc = fresnel_schlick(Vec3::splat(0.04), l_dot_h).x;
+            f += Vec3::splat(0.25 * self.clearcoat * fc * d * g / (4.0 * n_dot_l * n_dot_v));
+            pdf += probs.z * ggx_vndf_pdf(n_dot_v, n_dot_h, self.clearcoat_alpha);
+        }
+
+        (f * n_dot_l, pdf)
+    }
+
+    /// Samples an incoming direction by picking one lobe. The result may point below the
+    /// surface.
+    pub fn sample(&self, n: Vec3, wo: Vec3, rng: &mut Sampler) -> Vec3 {
+        let probs = self.lobe_probabilities(n.dot(wo));
+        let r = rng.sample_1d();
+        if r < probs.x {
+            return scatter_lambertian(n, rng);
+        }
+
+        let alpha = if r < probs.x + probs.y {
+            self.alpha
+        } else {
+            self.clearcoat_alpha
+        };
+        let onb = build_onb(n);
+        let wo_local = onb.transpose() * wo;
+        let u = rng.sample_2d();
+        let h = onb * sample_ggx_vndf(wo_local, alpha, u.x, u.y);
+        reflect(-wo, h)
+    }
+}
+
+fn fresnel_schlick(f0: Vec3, cos_theta: f32) -> Vec3 {
+    f0 + (Vec3::ONE - f0) * (1.0 - cos_theta.clamp(0.0, 1.0)).powi(5)
+}
+
+/// GGX / Trowbridge-Reitz normal distribution.
+fn ggx_d(n_dot_h: f32, alpha: f32) -> f32 {
+    let a2 = alpha * alpha;
+    let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    a2 / (PI * d * d)
+}
+
+/// Smith masking function for GGX.
+fn smith_g1(n_dot_v: f32, alpha: f32) -> f32 {
+    let a2 = alpha * alpha;
+    2.0 * n_dot_v / (n_dot_v + (a2 + (1.0 - a2) * n_dot_v * n_dot_v).sqrt())
+}
+
+/// Solid angle pdf of a reflected direction when sampling visible GGX normals.
+fn ggx_vndf_pdf(n_dot_v: f32, n_dot_h: f32, alpha: f32) -> f32 {
+    smith_g1(n_dot_v, alpha) * ggx_d(n_dot_h, alpha) / (4.0 * n_dot_v)
+}
+
+/// Samples a visible GGX normal in tangent space, `v` is the outgoing direction
+/// (Heitz 2018).
+fn sample_ggx_vndf(v: Vec3, alpha: f32, u1: f32, u2: f32) -> Vec3 {
+    let vh = Vec3::new(alpha * v.x, alpha * v.y, v.z).normalize();
+    let lensq = vh.x * vh.x + vh.y * vh.y;
+    let t1 = if lensq > 0.0 {
+        Vec3::new(-vh.y, vh.x, 0.0) / lensq.sqrt()
+    } else {
+        Vec3::X
+    };
+    let t2 = vh.cross(t1);
+
+    let r = u1.sqrt();
+    let phi = 2.0 * PI * u2;
+    let p1 = r * phi.cos();
+    let s = 0.5 * (1.0 + vh.z);
+    let p2 = (1.0 - s) * (1.0 - p1 * p1).max(0.0).sqrt() + s * r * phi.sin();
+
+    let nh = p1 * t1 + p2 * t2 + (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt() * vh;
+    Vec3::new(alpha * nh.x, alpha * nh.y, nh.z.max(1e-6)).normalize()
+}
+
+/// Scattering vertex for next-event estimation: a principled surface or a point inside a
+/// medium.
+#[derive(Copy, Clone, Debug)]
+pub struct ScatterPoint {
+    pub pos: Vec3,
+    /// Surface normal on the side of `wo`, zero inside a medium.
+    pub normal: Vec3,
+    pub wo: Vec3,
+    pub p: Principled,
+    /// Medium shadow rays start in.
+    pub medium: Medium,
+    pub in_medium: bool,
+}
+
+impl ScatterPoint {
+    pub fn surface(pos: Vec3, normal: Vec3, wo: Vec3, p: Principled, medium: Medium) -> Self {
+        Self {
+            pos,
+            normal,
+            wo,
+            p,
+            medium,
+            in_medium: false,
+        }
+    }
+
+    pub fn in_medium(pos: Vec3, wo: Vec3, medium: Medium) -> Self {
+        Self {
+            pos,
+            normal: Vec3::ZERO,
+            wo,
+            p: Principled::none(),
+            medium,
+            in_medium: true,
+        }
+    }
+
+    /// BSDF or phase function value towards `wi` and its sampling pdf.
+    pub fn eval(&self, wi: Vec3) -> (Vec3, f32) {
+        if self.in_medium {
+            let phase = hg_phase((-self.wo).dot(wi), self.medium.g);
+            return (Vec3::splat(phase), phase);
+        }
+        self.p.eval(self.normal, self.wo, wi)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn principled(base_color: Vec3, metallic: f32, roughness: f32) -> Principled {
+        Principled {
+            base_color,
+            metallic,
+            alpha: (roughness * roughness).max(1e-3),
+            specular: 0.5,
+            sheen: 0.0,
+            clearcoat: 0.0,
+            clearcoat_alpha: 1.0,
+        }
+    }
+
+    /// Outgoing direction at `degrees` from the normal `Z`.
+    fn view(degrees: f32) -> Vec3 {
+        let theta = degrees.to_radians();
+        Vec3::new(theta.sin(), 0.0, theta.cos())
+    }
+
+    /// Directional albedo, estimated by sampling the BSDF.
+    fn sampled_albedo(p: &Principled, wo: Vec3, samples: u32) -> Vec3 {
+        let total: Vec3 = (0..samples)
+            .map(|i| {
+                let mut rng = Sampler::new(11, i);
+                let wi = p.sample(Vec3::Z, wo, &mut rng);
+                let (f, pdf) = p.eval(Vec3::Z, wo, wi);
+                if pdf > 0.0 { f / pdf } else { Vec3::ZERO }
+            })
+            .sum();
+        total / samples as f32
+    }
+
+    /// Directional albedo, estimated with uniformly distributed directions on the hemisphere.
+    fn uniform_albedo(p: &Principled, wo: Vec3, samples: u32) -> Vec3 {
+        let total: Vec3 = (0..samples)
+            .map(|i| {
+                let mut rng = Sampler::new(11, i);
+                let wi = random_unit_vector(&mut rng);
+                let wi = Vec3::new(wi.x, wi.y, wi.z.abs());
+                p.eval(Vec3::Z, wo, wi).0 * 2.0 * PI
+            })
+            .sum();
+        total / samples as f32
+    }
+
+    #[test]
+    fn white_lambertian_reflects_all_light() {
+        let mut p = principled(Vec3::ONE, 0.0, 1.0);
+        p.specular = 0.0;
+        for degrees in [0.0, 45.0, 80.0] {
+            let albedo = sampled_albedo(&p, view(degrees), 256);
+            assert!(albedo.abs_diff_eq(Vec3::ONE, 1e-4), "{degrees}°: {albedo}");
+        }
+    }
+
+    #[test]
+    fn principled_conserves_energy() {
+        let mut clearcoat = principled(Vec3::splat(0.5), 0.0, 0.6);
+        clearcoat.clearcoat = 1.0;
+        clearcoat.clearcoat_alpha = 0.01;
+        let materials = [
+            principled(Vec3::splat(0.8), 0.0, 0.5),
+            principled(Vec3::ONE, 1.0, 0.3),
+            principled(Vec3::ONE, 1.0, 1.0),
+            clearcoat,
+        ];
+        for (i, p) in materials.iter().enumerate() {
+            for degrees in [0.0, 30.0, 60.0, 85.0] {
+                let albedo = sampled_albedo(p, view(degrees), 4096);
+                assert!(albedo.max_element() <= 1.0, "{i} at {degrees}°: {albedo}");
+            }
+        }
+        // A smooth white metal only loses little energy to masking
+        let albedo = sampled_albedo(&materials[1], view(0.0), 4096);
+        assert!(albedo.min_element() > 0.9, "{albedo}");
+    }
+
+    #[test]
+    fn sampling_pdf_matches_the_evaluated_bsdf() {
+        let mut clearcoat = principled(Vec3::new(0.2, 0.5, 0.8), 0.3, 0.7);
+        clearcoat.clearcoat = 1.0;
+        clearcoat.clearcoat_alpha = 0.25;
+        for p in [principled(Vec3::splat(0.8), 1.0, 0.6), clearcoat] {
+            let wo = view(40.0);
+            let sampled = sampled_albedo(&p, wo, 8192);
+            let uniform = uniform_albedo(&p, wo, 8192);
+            assert!(
+                sampled.abs_diff_eq(uniform, 0.005),
+                "sampled {sampled}, uniform {uniform}"
+            );
+        }
+    }
+
+    #[test]
+    fn schlick_fresnel_at_normal_and_grazing_incidence() {
+        assert!((reflectance(1.0, 1.5) - 0.04).abs() < 1e-6);
+        assert!((reflectance(1.0, 1.0 / 1.5) - 0.04).abs() < 1e-6);
+        assert_eq!(reflectance(0.0, 1.5), 1.0);
+
+        let f0 = Vec3::new(0.9, 0.6, 0.3);
+        assert!(fresnel_schlick(f0, 1.0).abs_diff_eq(f0, 1e-6));
+        assert!(fresnel_schlick(f0, 0.0).abs_diff_eq(Vec3::ONE, 1e-6));
+        let f = fresnel_schlick(f0, 0.5);
+        assert!(f.cmpgt(f0).all() && f.cmplt(Vec3::ONE).all());
+    }
+
+    #[test]
+    fn dielectric_splits_light_by_fresnel_and_snell() {
+        let normal = Vec3::Z;
+        let eta = 1.0 / 1.5;
+
+        // Head-on, 4 % are reflected
+        let samples = 4096;
+        let reflected = (0..samples)
+            .filter(|i| {
+                let mut rng = Sampler::new(3, *i);
+                scatter_dielectric(-Vec3::Z, normal, eta, &mut rng).z > 0.0
+            })
+            .count();
+        let fraction = reflected as f32 / samples as f32;
+        assert!((fraction - 0.04).abs() < 0.005, "{fraction}");
+
+        // Refracted rays bend towards the normal by Snell's law
+        let dir = -view(50.0);
+        let refracted = refract(dir, normal, eta);
+        let sin_in = dir.truncate().length();
+        let sin_out = refracted.truncate().length();
+        assert!((sin_out - eta * sin_in).abs() < 1e-5);
+        assert!((refracted.length() - 1.0).abs() < 1e-5);
+
+        // From the inside beyond the critical angle, everything is reflected
+        let grazing = -view(60.0);
+        for i in 0..64 {
+            let mut rng = Sampler::new(3, i);
+            let out = scatter_dielectric(grazing, normal, 1.5, &mut rng);
+            assert!(out.abs_diff_eq(reflect(grazing, normal), 1e-6));
+        }
+    }
+
+    #[test]
+    fn onb_is_orthonormal() {
+        for w in [
+            Vec3::Z,
+            -Vec3::Z,
+            Vec3::X,
+            Vec3::new(1.0, -2.0, 0.5).normalize(),
+        ] {
+            let onb = build_onb(w);
+            assert!(
+                (onb * onb.transpose()).abs_diff_eq(Mat3::IDENTITY, 1e-5),
+                "{w}"
+            );
+            assert!(onb.z_axis.abs_diff_eq(w, 1e-6));
+        }
+    }
+}
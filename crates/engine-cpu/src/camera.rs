@@ -0,0 +1,132 @@
+//! Primary rays of the camera projections and the thin lens.
+
+use crate::sampler::Sampler;
+use engine_config::{Projection, Uniforms};
+use glam::{Vec2, Vec3};
+use std::f32::consts::PI;
+
+/// Orthonormal frame of the camera.
+///
+/// The frames are mirrored horizontally like on the GPU, `right` points to the left of the
+/// frame.
+#[derive(Copy, Clone, Debug)]
+pub struct CameraFrame {
+    pub forward: Vec3,
+    pub right: Vec3,
+    pub up: Vec3,
+}
+
+impl CameraFrame {
+    pub fn new(uniforms: &Uniforms) -> Self {
+        let forward = Vec3::from(uniforms.camera.dir).normalize();
+        let right = Vec3::Y.cross(forward).normalize();
+        let up = forward.cross(right);
+        Self { forward, right, up }
+    }
+}
+
+/// Primary ray through an image pixel.
+#[derive(Copy, Clone, Debug)]
+pub struct CameraRay {
+    pub origin: Vec3,
+    pub direction: Vec3,
+    /// Angle between the rays of neighbouring pixels, the initial spread of the ray cone.
+    pub spread: f32,
+    /// Perspective rays may start on the lens aperture instead.
+    pub lens: bool,
+}
+
+/// Primary ray through the pixel (x, y) in shader coordinates, offset by `jitter` pixels.
+pub fn camera_ray(
+    uniforms: &Uniforms,
+    frame: &CameraFrame,
+    x: u32,
+    y: u32,
+    jitter: Vec2,
+) -> CameraRay {
+    let camera = &uniforms.camera;
+    let (width, height) = (uniforms.width, uniforms.height);
+    let projection = Projection::try_from(camera.projection).unwrap_or_default();
+    let pos = Vec3::from(camera.pos);
+
+    if projection == Projection::Equirectangular {
+        // Longitude around the up axis with the view direction in the center, latitude from
+        // the top
+        let phi = ((x as f32 + 0.5 + jitter.x) / width as f32 * 2.0 - 1.0) * PI;
+        let theta = (0.5 - (y as f32 + 0.5 + jitter.y) / height as f32) * PI;
+        let direction = theta.cos() * (phi.sin() * frame.right + phi.cos() * frame.forward)
+            + theta.sin() * frame.up;
+        return CameraRay {
+            origin: pos,
+            direction,
+            spread: 2.0 * PI / width as f32,
+            lens: false,
+        };
+    }
+
+    if projection == Projection::Orthographic {
+        let aspect = width as f32 / height as f32;
+        let u = ((x as f32 + jitter.x) / (width - 1) as f32) * 2.0 - 1.0;
+        let v = 1.0 - ((y as f32 + jitter.y) / (height - 1) as f32) * 2.0;
+        let half_width = camera.ortho_width * 0.5;
+        return CameraRay {
+            origin: pos + u * half_width * frame.right + v * half_width / aspect * frame.up,
+            direction: frame.forward,
+            spread: 0.0,
+            lens: false,
+        };
+    }
+
+    // Stereo pairs split the image into one perspective view per eye, the left eye is shown
+    // on the left (the upper x range in shader coordinates) or on top
+    let (mut view_x, mut view_y, mut view_width, mut view_height) = (x, y, width, height);
+    let mut eye = 0.0;
+    if projection == Projection::StereoSideBySide {
+        let half = width / 2;
+        let left = x >= half;
+        view_x = if left { x - half } else { x };
+        view_width = if left { width - half } else { half };
+        eye = if left { 0.5 } else { -0.5 };
+    } else if projection == Projection::StereoTopBottom {
+        let half = height / 2;
+        let left = y < half;
+        view_y = if left { y } else { y - half };
+        view_height = if left { half } else { height - half };
+        eye = if left { 0.5 } else { -0.5 };
+    }
+
+    let view_aspect = view_width as f32 / view_height as f32;
+    let fov = camera.pane_width / (2.0 * camera.pane_distance * view_aspect);
+    let view_u = (((view_x as f32 + jitter.x) / (view_width - 1) as f32) * 2.0 - 1.0) * view_aspect;
+    let view_v = 1.0 - ((view_y as f32 + jitter.y) / (view_height - 1) as f32) * 2.0;
+    CameraRay {
+        origin: pos + eye * camera.interocular_distance * frame.right,
+        direction: (fov * view_u * frame.right + fov * view_v * frame.up + frame.forward)
+            .normalize(),
+        spread: camera.pane_width / (camera.pane_distance * view_width as f32),
+        lens: true,
+    }
+}
+
+/// Point on the unit aperture: a disk, or a regular polygon with `blades` corners on the unit
+/// circle.
+pub fn sample_aperture(blades: u32, rng: &mut Sampler) -> Vec2 {
+    let u = rng.sample_2d();
+    if blades < 3 {
+        let r = u.x.sqrt();
+        let phi = 2.0 * PI * u.y;
+        return r * Vec2::new(phi.cos(), phi.sin());
+    }
+
+    // One of the triangles fanning out from the center, then a uniform point inside it
+    let n = blades as f32;
+    let k = (rng.sample_1d() * n).floor().min(n - 1.0);
+    let a0 = 2.0 * PI * k / n;
+    let a1 = 2.0 * PI * (k + 1.0) / n;
+    let (mut s, mut t) = (u.x, u.y);
+    if s + t > 1.0 {
+        s = 1.0 - s;
+        t = 1.0 - t;
+    }
+    s * Vec2::new(a0.cos(), a0.sin()) + t * Vec2::new(a1.cos(), a1.sin())
+}
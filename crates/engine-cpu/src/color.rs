@@ -0,0 +1,20 @@
+//! Exposure, tone mapping and display encoding of linear radiance.
+
+use engine_config::Uniforms;
+use glam::Vec3;
+
+/// Relative luminance of a linear sRGB color.
+pub fn luminance(c: Vec3) -> f32 {
+    c.dot(Vec3::new(0.2126, 0.7152, 0.0722))
+}
+
+/// Applies the exposure and the tone mapping operator of `uniforms`, see
+/// [`Uniforms::tone_map`]. The result is still linear, [`color_map`] encodes it for display.
+pub fn tone_map(color: Vec3, uniforms: &Uniforms) -> Vec3 {
+    Vec3::from(uniforms.tone_map(color.to_array()))
+}
+
+/// Encodes a tone mapped color and its alpha as RGBA8 with the square root as display gamma.
+pub fn color_map(color: Vec3, alpha: f32) -> [u8; 4] {
+    engine_config::tone_mapping::color_map(color.to_array(), alpha)
+}
@@ -0,0 +1,338 @@
+//! Progressive, tile-parallel rendering on the CPU.
+
+use crate::camera::CameraFrame;
+use crate::color::{color_map, luminance, tone_map};
+use crate::integrator::{pixel_aovs, sample_pixel};
+use crate::scene::Scene;
+use anyhow::Result;
+use engine_config::{Aov, RenderConfig};
+use glam::{Vec3, Vec4};
+use log::info;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Edge length of the tiles if the uniforms leave it to the engine.
+const DEFAULT_TILE_SIZE: u32 = 32;
+
+/// Square region of the frame, in frame coordinates.
+#[derive(Copy, Clone, Debug)]
+struct Tile {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+/// Everything a worker computed for one tile in a pass, merged into the film afterwards.
+struct TileResult {
+    tile: Tile,
+    accumulation: Vec<Vec3>,
+    stats: Vec<Vec3>,
+    pixels: Vec<[u8; 4]>,
+    /// AOV values of every pixel, only computed in the first pass.
+    aovs: Vec<Vec<Vec4>>,
+    unconverged_pixels: u32,
+}
+
+/// Renders [`RenderConfig`] scenes on the CPU with the same passes as the GPU engines.
+///
+/// Every pass takes one sample of every pixel of the whole image. The image is split into
+/// square tiles of [`Uniforms::tile_size`](engine_config::Uniforms::tile_size), which are
+/// handed out to one worker thread per core. The tile order has no effect, the frame is
+/// only returned once all tiles of a pass are done.
+///
+/// The frames are mirrored horizontally relative to the shader coordinates like the frames of
+/// the GPU engines, and a pixel takes the same samples as on the GPU.
+pub struct CpuRenderer {
+    scene: Scene,
+    initialized: bool,
+    /// Passes of the current render, one sample each.
+    total_passes: u32,
+    current_pass: u32,
+    /// Radiance sum of every pixel, in frame order.
+    accumulation: Vec<Vec3>,
+    /// Luminance sum of squares, sample count and alpha sum of every pixel.
+    stats: Vec<Vec3>,
+    /// RGBA8 frame of the current render.
+    image: Vec<u8>,
+    /// AOV layers of the current render, four floats per pixel.
+    aov_layers: Vec<(Aov, Vec<f32>)>,
+    /// Pixels that still need samples after the last pass.
+    unconverged_pixels: u32,
+}
+
+impl CpuRenderer {
+    /// Creates a renderer for the initial configuration, which must create all required
+    /// fields.
+    pub fn new(rc: RenderConfig) -> Result<Self> {
+        let mut renderer = Self {
+            scene: Scene::default(),
+            initialized: false,
+            total_passes: 0,
+            current_pass: 0,
+            accumulation: Vec::new(),
+            stats: Vec::new(),
+            image: Vec::new(),
+            aov_layers: Vec::new(),
+            unconverged_pixels: 0,
+        };
+        renderer.update(rc)?;
+        Ok(renderer)
+    }
+
+    /// Applies the changes of a new configuration to the scene.
+    pub fn update(&mut self, rc: RenderConfig) -> Result<()> {
+        self.scene.update(rc, self.initialized)?;
+        self.initialized = true;
+        Ok(())
+    }
+
+    pub fn width(&self) -> u32 {
+        self.scene.uniforms.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.scene.uniforms.height
+    }
+
+    /// Returns `true` if pixels stop taking samples once they are converged.
+    pub fn is_adaptive(&self) -> bool {
+        self.scene.uniforms.adaptive_threshold > 0.0
+    }
+
+    /// Returns `true` if the frames carry the linear radiance layer.
+    pub fn has_radiance_layer(&self) -> bool {
+        self.scene.uniforms.radiance_layer != 0
+    }
+
+    /// Index of the pass the next [`render_pass`](Self::render_pass) renders.
+    pub fn current_pass(&self) -> u32 {
+        self.current_pass
+    }
+
+    pub fn total_passes(&self) -> u32 {
+        self.total_passes
+    }
+
+    /// Starts a new render, must be called before the first pass.
+    pub fn begin_render(&mut self) {
+        let pixels = (self.width() * self.height()) as usize;
+        self.total_passes = self.scene.uniforms.total_samples;
+        self.current_pass = 0;
+        self.accumulation = vec![Vec3::ZERO; pixels];
+        self.stats = vec![Vec3::ZERO; pixels];
+        self.image = [0, 0, 0, 255].repeat(pixels);
+        self.aov_layers = self
+            .scene
+            .aovs
+            .iter()
+            .map(|aov| (aov, vec![0.0; pixels * 4]))
+            .collect();
+        self.unconverged_pixels = 0;
+    }
+
+    /// Returns `true` while the current render has passes left.
+    pub fn has_next_pass(&self) -> bool {
+        self.current_pass < self.total_passes
+    }
+
+    /// Renders the next pass over all tiles.
+    ///
+    /// With adaptive sampling the render ends early once all pixels converged.
+    pub fn render_pass(&mut self) -> Result<()> {
+        let tiles = self.tiles();
+        let next_tile = AtomicUsize::new(0);
+        let workers = std::thread::available_parallelism()
+            .map_or(1, |n| n.get())
+            .min(tiles.len())
+            .max(1);
+
+        let results: Vec<TileResult> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..workers)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut results = Vec::new();
+                        loop {
+                            let index = next_tile.fetch_add(1, Ordering::Relaxed);
+                            let Some(tile) = tiles.get(index) else {
+                                break;
+                            };
+                            results.push(self.render_tile(*tile));
+                        }
+                        results
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().expect("render worker panicked"))
+                .collect()
+        });
+
+        self.unconverged_pixels = 0;
+        for result in results {
+            self.merge_tile(result);
+        }
+        self.current_pass += 1;
+
+        if self.is_adaptive() && self.unconverged_pixels == 0 {
+            info!("All pixels converged after {} samples", self.current_pass);
+            self.current_pass = self.total_passes;
+        }
+        Ok(())
+    }
+
+    /// Renders all passes of a new render.
+    pub fn render(&mut self) -> Result<()> {
+        self.begin_render();
+        while self.has_next_pass() {
+            self.render_pass()?;
+        }
+        Ok(())
+    }
+
+    /// Returns the RGBA8 pixels of the current render. The alpha channel is 255 except over a
+    /// shadow catcher, where the colors are not premultiplied.
+    pub fn read_pixels(&self) -> Vec<u8> {
+        self.image.clone()
+    }
+
+    /// Returns one entry per requested AOV layer with four floats per pixel, written in the
+    /// first pass.
+    pub fn read_aovs(&self) -> Vec<(Aov, Vec<f32>)> {
+        self.aov_layers.clone()
+    }
+
+    /// Returns the samples taken per pixel, empty without adaptive sampling.
+    pub fn samples(&self) -> Vec<f32> {
+        if !self.is_adaptive() {
+            return Vec::new();
+        }
+        self.stats.iter().map(|s| s.y).collect()
+    }
+
+    /// Returns the linear radiance and alpha of every pixel, four floats per pixel, empty
+    /// unless the radiance layer is requested. The radiance is not premultiplied.
+    pub fn read_radiance(&self) -> Vec<f32> {
+        if !self.has_radiance_layer() {
+            return Vec::new();
+        }
+        self.accumulation
+            .iter()
+            .zip(&self.stats)
+            .flat_map(|(color, stats)| {
+                let n = stats.y.max(1.0);
+                let alpha = stats.z / n;
+                (*color / n / alpha.max(1e-4)).extend(alpha).to_array()
+            })
+            .collect()
+    }
+
+    /// Pixels that still needed samples after the last pass, only counted with adaptive
+    /// sampling.
+    pub fn unconverged_pixels(&self) -> u32 {
+        self.unconverged_pixels
+    }
+
+    fn tiles(&self) -> Vec<Tile> {
+        let size = match self.scene.uniforms.tile_size {
+            0 => DEFAULT_TILE_SIZE,
+            size => size,
+        };
+        let (width, height) = (self.width(), self.height());
+        let mut tiles = Vec::new();
+        for y in (0..height).step_by(size as usize) {
+            for x in (0..width).step_by(size as usize) {
+                tiles.push(Tile {
+                    x,
+                    y,
+                    width: size.min(width - x),
+                    height: size.min(height - y),
+                });
+            }
+        }
+        tiles
+    }
+
+    /// Converged pixels stop taking samples, the same test as in the shaders.
+    fn pixel_converged(&self, color_sum: Vec3, stats: Vec3) -> bool {
+        self.scene
+            .uniforms
+            .pixel_converged(luminance(color_sum), stats.x, stats.y)
+    }
+
+    /// Takes the next sample of every pixel of a tile.
+    fn render_tile(&self, tile: Tile) -> TileResult {
+        let scene = &self.scene;
+        let width = self.width();
+        let frame = CameraFrame::new(&scene.uniforms);
+        let adaptive = self.is_adaptive();
+        let first_pass = self.current_pass == 0 && !scene.aovs.is_empty();
+
+        let pixels = (tile.width * tile.height) as usize;
+        let mut result = TileResult {
+            tile,
+            accumulation: Vec::with_capacity(pixels),
+            stats: Vec::with_capacity(pixels),
+            pixels: Vec::with_capacity(pixels),
+            aovs: Vec::new(),
+            unconverged_pixels: 0,
+        };
+
+        for fy in tile.y..tile.y + tile.height {
+            for fx in tile.x..tile.x + tile.width {
+                let index = (fy * width + fx) as usize;
+                // The frames are mirrored horizontally relative to the shader coordinates
+                let (x, y) = (width - 1 - fx, fy);
+
+                if first_pass {
+                    result.aovs.push(pixel_aovs(scene, &frame, x, y));
+                }
+
+                let mut color = self.accumulation[index];
+                let mut stats = self.stats[index];
+                // Converged pixels keep their samples from earlier passes
+                if !(adaptive && self.pixel_converged(color, stats)) {
+                    let sample = sample_pixel(scene, &frame, x, y, stats.y as u32);
+                    color += sample.truncate();
+                    let sample_luminance = luminance(sample.truncate());
+                    stats += Vec3::new(sample_luminance * sample_luminance, 1.0, sample.w);
+                    if adaptive && !self.pixel_converged(color, stats) {
+                        result.unconverged_pixels += 1;
+                    }
+                }
+
+                // The alpha is stored unpremultiplied
+                let n = stats.y.max(1.0);
+                let alpha = stats.z / n;
+                let final_color = color / n / alpha.max(1e-4);
+                result.accumulation.push(color);
+                result.stats.push(stats);
+                result
+                    .pixels
+                    .push(color_map(tone_map(final_color, &scene.uniforms), alpha));
+            }
+        }
+        result
+    }
+
+    /// Copies the results of a tile into the film and the frame.
+    fn merge_tile(&mut self, result: TileResult) {
+        let tile = result.tile;
+        let width = self.width() as usize;
+        self.unconverged_pixels += result.unconverged_pixels;
+
+        let frame_indices = (tile.y..tile.y + tile.height).flat_map(|fy| {
+            (tile.x..tile.x + tile.width).map(move |fx| fy as usize * width + fx as usize)
+        });
+        for (i, index) in frame_indices.enumerate() {
+            self.accumulation[index] = result.accumulation[i];
+            self.stats[index] = result.stats[i];
+            self.image[index * 4..index * 4 + 4].copy_from_slice(&result.pixels[i]);
+            if let Some(values) = result.aovs.get(i) {
+                for ((_, layer), value) in self.aov_layers.iter_mut().zip(values) {
+                    layer[index * 4..index * 4 + 4].copy_from_slice(&value.to_array());
+                }
+            }
+        }
+    }
+}
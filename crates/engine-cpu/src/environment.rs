@@ -0,0 +1,146 @@
+//! Importance sampled environment map.
+
+use crate::color::luminance;
+use crate::sampler::Sampler;
+use engine_config::EnvironmentMap;
+use glam::{Vec2, Vec3};
+use std::f32::consts::PI;
+
+/// Equirectangular environment map with the CDFs for importance sampling.
+///
+/// Pixels are picked proportional to their luminance times the sine of their polar angle,
+/// first the row through the marginal CDF, then the pixel through the CDF of the row.
+pub struct Environment {
+    width: usize,
+    height: usize,
+    pixels: Vec<Vec3>,
+    /// Conditional CDF of every pixel within its row.
+    column_cdf: Vec<f32>,
+    /// Marginal CDF of the rows.
+    row_cdf: Vec<f32>,
+    /// Sum of all pixel weights.
+    total: f32,
+}
+
+impl Environment {
+    /// Builds the sampling distribution of a map, `None` if the map has no valid pixels.
+    pub fn new(environment: &EnvironmentMap) -> Option<Self> {
+        if !environment.is_valid() {
+            return None;
+        }
+        let (width, height) = (environment.width as usize, environment.height as usize);
+        let pixels: Vec<Vec3> = environment
+            .rgb_data
+            .chunks_exact(3)
+            .map(Vec3::from_slice)
+            .collect();
+
+        let mut column_cdf = Vec::with_capacity(width * height);
+        let mut row_weights = Vec::with_capacity(height);
+        for (y, row) in pixels.chunks_exact(width).enumerate() {
+            let sin_theta = (PI * (y as f32 + 0.5) / height as f32).sin();
+            let weights: Vec<f32> = row
+                .iter()
+                .map(|c| luminance(*c).max(0.0) * sin_theta)
+                .collect();
+            let row_weight: f32 = weights.iter().sum();
+
+            let mut cumulative = 0.0;
+            for (x, weight) in weights.iter().enumerate() {
+                cumulative += weight;
+                column_cdf.push(if row_weight > 0.0 {
+                    cumulative / row_weight
+                } else {
+                    (x + 1) as f32 / width as f32
+                });
+            }
+            row_weights.push(row_weight);
+        }
+
+        let total: f32 = row_weights.iter().sum();
+        let mut cumulative = 0.0;
+        let row_cdf = row_weights
+            .iter()
+            .enumerate()
+            .map(|(y, weight)| {
+                cumulative += weight;
+                if total > 0.0 {
+                    cumulative / total
+                } else {
+                    (y + 1) as f32 / height as f32
+                }
+            })
+            .collect();
+
+        Some(Self {
+            width,
+            height,
+            pixels,
+            column_cdf,
+            row_cdf,
+            total,
+        })
+    }
+
+    /// Pixel of the map seen in direction `dir`.
+    fn index(&self, dir: Vec3, rotation: f32) -> usize {
+        let uv = environment_uv(dir, rotation);
+        let x = ((uv.x * self.width as f32) as usize).min(self.width - 1);
+        let y = ((uv.y * self.height as f32) as usize).min(self.height - 1);
+        y * self.width + x
+    }
+
+    /// Radiance arriving from `dir`, without filtering so it matches the sampling pdf.
+    pub fn radiance(&self, dir: Vec3, rotation: f32) -> Vec3 {
+        self.pixels[self.index(dir, rotation)]
+    }
+
+    /// Solid angle pdf of sampling `dir` with [`sample`](Self::sample).
+    pub fn pdf(&self, dir: Vec3, rotation: f32) -> f32 {
+        let sin_theta = (1.0 - dir.y * dir.y).max(0.0).sqrt();
+        if self.total <= 0.0 || sin_theta <= 0.0 {
+            return 0.0;
+        }
+        let index = self.index(dir, rotation);
+        let sin_row = (PI * ((index / self.width) as f32 + 0.5) / self.height as f32).sin();
+        let weight = luminance(self.pixels[index]).max(0.0) * sin_row;
+        weight * (self.width * self.height) as f32 / (self.total * 2.0 * PI * PI * sin_theta)
+    }
+
+    /// Samples a direction, returns it with its solid angle pdf.
+    pub fn sample(&self, rotation: f32, rng: &mut Sampler) -> (Vec3, f32) {
+        let r = rng.sample_2d();
+        let y = self
+            .row_cdf
+            .partition_point(|cdf| *cdf < r.x)
+            .min(self.height - 1);
+        let row = &self.column_cdf[y * self.width..(y + 1) * self.width];
+        let x = row.partition_point(|cdf| *cdf < r.y).min(self.width - 1);
+
+        let jitter = rng.sample_2d();
+        let uv = Vec2::new(
+            (x as f32 + jitter.x) / self.width as f32,
+            (y as f32 + jitter.y) / self.height as f32,
+        );
+        let dir = environment_dir(uv, rotation);
+        (dir, self.pdf(dir, rotation))
+    }
+}
+
+/// Equirectangular coordinates of a direction, v = 0 is the zenith.
+fn environment_uv(dir: Vec3, rotation: f32) -> Vec2 {
+    let phi = dir.z.atan2(dir.x) - rotation;
+    let theta = dir.y.clamp(-1.0, 1.0).acos();
+    let u = phi / (2.0 * PI);
+    Vec2::new(u - u.floor(), theta / PI)
+}
+
+fn environment_dir(uv: Vec2, rotation: f32) -> Vec3 {
+    let phi = uv.x * 2.0 * PI + rotation;
+    let theta = uv.y * PI;
+    Vec3::new(
+        theta.sin() * phi.cos(),
+        theta.cos(),
+        theta.sin() * phi.sin(),
+    )
+}
@@ -0,0 +1,333 @@
+//! Path tracing integrator, the CPU counterpart of the path tracer shader.
+//!
+//! Paths are traced exactly like on the GPU: principled surfaces, dielectrics and media with
+//! next-event estimation and multiple importance sampling, the shadow catcher alpha and
+//! ray cones for texture filtering.
+
+use crate::bsdf::{
+    Principled, ScatterPoint, dielectric_absorption, is_dielectric, scatter_dielectric,
+    shading_normal, surface_emission,
+};
+use crate::camera::{CameraFrame, camera_ray, sample_aperture};
+use crate::color::luminance;
+use crate::intersect::{Object, intersect_scene};
+use crate::lights::{
+    directional_radiance, emissive_triangle_pdf, light_emission, light_pdf, power_heuristic,
+    sample_direct_light,
+};
+use crate::medium::{hg_phase, is_medium_boundary, material_medium, medium_behind, sample_hg};
+use crate::pattern::surface_base_color;
+use crate::sampler::Sampler;
+use crate::scene::Scene;
+use engine_config::{Aov, Projection};
+use glam::{Vec2, Vec3, Vec4};
+
+/// Distance of a miss, free flight treats it as a surface far away.
+const MISS_DISTANCE: f32 = 1e20;
+
+/// Takes one sample of the pixel (x, y) in shader coordinates, `sample_index` is the number
+/// of samples the pixel already took. Returns the radiance premultiplied by the alpha and
+/// the alpha.
+pub fn sample_pixel(scene: &Scene, frame: &CameraFrame, x: u32, y: u32, sample_index: u32) -> Vec4 {
+    let uniforms = &scene.uniforms;
+    let mut rng = Sampler::new(y * uniforms.width + x, sample_index);
+
+    let offset = rng.sample_2d() - 0.5;
+    let ray = camera_ray(uniforms, frame, x, y, offset);
+    let mut origin = ray.origin;
+    let mut direction = ray.direction;
+
+    // Thin lens: start on the aperture and pass through the point the pinhole ray hits on
+    // the focus plane
+    let camera = &uniforms.camera;
+    if ray.lens && camera.aperture_radius > 0.0 {
+        let focus_point =
+            ray.origin + direction * (camera.focus_distance / direction.dot(frame.forward));
+        let lens = sample_aperture(camera.aperture_blades, &mut rng) * camera.aperture_radius;
+        origin = ray.origin + lens.x * frame.right + lens.y * frame.up;
+        direction = (focus_point - origin).normalize();
+    }
+
+    trace_ray(scene, origin, direction, rng, ray.spread)
+}
+
+/// Radiance along a camera ray and its alpha, which is only below 1 over a shadow catcher.
+/// The color is premultiplied by the alpha.
+fn trace_ray(
+    scene: &Scene,
+    origin0: Vec3,
+    direction0: Vec3,
+    mut rng: Sampler,
+    pixel_spread: f32,
+) -> Vec4 {
+    let mut origin = origin0;
+    let mut direction = direction0;
+
+    let mut color = Vec3::ZERO;
+    let mut attenuation = Vec3::ONE;
+    // Solid angle pdf of the last principled bounce, 0 for camera rays, glass and dissolve
+    let mut prev_bsdf_pdf = 0.0;
+    // Ray cone for texture filtering: width at the current vertex and spread angle
+    let mut cone_width = 0.0;
+    let mut cone_spread = pixel_spread;
+    let mut medium = scene.global_medium();
+    // Last scattering vertex and the distance travelled since, medium boundaries in between
+    // do not scatter. Needed for the light pdfs of the MIS weights.
+    let mut vertex_pos = origin0;
+    let mut vertex_dist = 0.0;
+    // Shadow catcher: the path met nothing but the catcher so far, the background behind it
+    // is transparent
+    let mut catcher_path = scene.shadow_catcher_enabled();
+    let mut alpha = if catcher_path { 0.0 } else { 1.0 };
+
+    for depth in 0..scene.uniforms.max_depth {
+        rng.start_bounce(depth + 1);
+        let closest_hit = intersect_scene(scene, origin, direction);
+        let hit_catcher =
+            catcher_path && matches!(closest_hit, Some(ref hit) if hit.object == Object::Ground);
+
+        // Free flight through the medium
+        let mut scatter_dist = None;
+        if medium.is_present() {
+            let t_max = closest_hit.as_ref().map_or(MISS_DISTANCE, |hit| hit.t);
+            let (weight, dist) = medium.sample_free_flight(t_max, &mut rng);
+            attenuation *= weight;
+            scatter_dist = dist;
+        }
+
+        // The first object seen directly or in the catcher covers the shadow and the background
+        let hit_object = closest_hit
+            .as_ref()
+            .is_some_and(|hit| !hit_catcher && !is_medium_boundary(hit));
+        if catcher_path && (scatter_dist.is_some() || hit_object) {
+            let coverage = luminance(attenuation).clamp(0.0, 1.0);
+            alpha = coverage + (1.0 - coverage) * alpha;
+            catcher_path = false;
+        }
+
+        let scattered;
+        let mut albedo = Vec3::ONE;
+        // Vertex for direct light sampling, if the scattering event has one
+        let mut vertex = None;
+        let mut absorbed = false;
+        let next_origin;
+
+        if let Some(dist) = scatter_dist {
+            // Scattering inside the medium, the phase function is sampled exactly
+            let pos = origin + dist * direction;
+            scattered = sample_hg(direction, medium.g, rng.sample_2d());
+            vertex = Some(ScatterPoint::in_medium(pos, -direction, medium));
+            prev_bsdf_pdf = hg_phase(direction.dot(scattered), medium.g);
+            cone_width += cone_spread * dist;
+            next_origin = pos;
+        } else {
+            let Some(mut hit) = closest_hit else {
+                // Sky
+                if catcher_path {
+                    break;
+                }
+                // The environment map was also sampled directly at the previous principled
+                // vertex
+                let mut sky_weight = 1.0;
+                if let Some(environment) = &scene.environment
+                    && prev_bsdf_pdf > 0.0
+                {
+                    let env_pdf = environment.pdf(direction, scene.uniforms.environment_rotation);
+                    sky_weight = power_heuristic(prev_bsdf_pdf, env_pdf);
+                }
+                color += attenuation * scene.environment_radiance(direction) * sky_weight;
+                color += attenuation * directional_radiance(scene, direction, prev_bsdf_pdf);
+                break;
+            };
+
+            // Medium boundaries have no surface, the ray continues in the medium behind them
+            if is_medium_boundary(&hit) {
+                medium = medium_behind(scene, &hit, direction);
+                cone_width += cone_spread * hit.t;
+                vertex_dist += hit.t;
+                origin = hit.pos + 0.001 * direction;
+                continue;
+            }
+            let light_t = vertex_dist + hit.t;
+
+            // Add emitted light. Lights reached by a principled bounce were already sampled
+            // directly at the previous vertex, so both strategies are MIS-weighted.
+            if let Object::Light(k) = hit.object {
+                let light = &scene.lights[k];
+                let mut emission_weight = 1.0;
+                if prev_bsdf_pdf > 0.0 {
+                    emission_weight = power_heuristic(
+                        prev_bsdf_pdf,
+                        light_pdf(vertex_pos, light, direction, light_t),
+                    );
+                }
+                color +=
+                    attenuation * light_emission(light, vertex_pos, direction) * emission_weight;
+            } else if !hit_catcher {
+                // Emissive mesh triangles are sampled directly as well
+                let mut emission_weight = 1.0;
+                let emission = Vec3::from(hit.material.emissive);
+                if matches!(hit.object, Object::Mesh(_))
+                    && prev_bsdf_pdf > 0.0
+                    && scene.emissive_triangles_enabled()
+                    && luminance(emission) > 0.0
+                {
+                    let cos_light = direction.dot(hit.geometric_normal).abs();
+                    emission_weight = power_heuristic(
+                        prev_bsdf_pdf,
+                        emissive_triangle_pdf(scene, emission, light_t, cos_light),
+                    );
+                }
+                color += attenuation * surface_emission(scene, &hit) * emission_weight;
+            }
+            prev_bsdf_pdf = 0.0;
+
+            // Texture footprint of the ray cone, stretched at grazing angles
+            cone_width += cone_spread * hit.t;
+            let cos_incident = direction.dot(hit.normal).abs().max(0.05);
+            hit.footprint = cone_width * hit.uv_density / cos_incident;
+
+            let mapped_normal = shading_normal(scene, &hit);
+
+            if is_dielectric(&hit.material) {
+                // Glass / water: Fresnel-weighted reflection or refraction
+                let front_face = direction.dot(hit.normal) < 0.0;
+                let mut normal = mapped_normal;
+                let mut eta = 1.0 / hit.material.ior.max(1e-3);
+                let interior = material_medium(&hit.material);
+                if !front_face {
+                    // Leaving the medium, the segment that just ended ran inside it. An interior
+                    // medium replaces the absorption derived from Kd and was handled by free
+                    // flight.
+                    normal = -normal;
+                    eta = hit.material.ior;
+                    if !interior.is_present() {
+                        attenuation *= dielectric_absorption(&hit.material, hit.t);
+                    }
+                }
+                scattered = scatter_dielectric(direction, normal, eta, &mut rng);
+                // Refraction enters the interior medium or leaves to the global one
+                if scattered.dot(normal) < 0.0 {
+                    medium = if front_face {
+                        interior
+                    } else {
+                        scene.global_medium()
+                    };
+                }
+            } else if hit.material.opacity < 1.0 && rng.sample_1d() > hit.material.opacity {
+                // Dissolve (d < 1): the ray passes through unchanged
+                scattered = direction;
+            } else {
+                // Principled metallic/roughness BSDF
+                let p = Principled::from_hit(scene, &hit, surface_base_color(scene, &hit));
+
+                let wo = -direction;
+                let facing = if hit.normal.dot(wo) < 0.0 { -1.0 } else { 1.0 };
+                let mut normal = mapped_normal * facing;
+                // A mapped normal facing away from the viewer would end the path, use the
+                // surface normal
+                if normal.dot(wo) <= 1e-4 {
+                    normal = hit.normal * facing;
+                }
+
+                // Sampled first, the number of dimensions used by the lights depends on the
+                // scene
+                scattered = p.sample(normal, wo, &mut rng);
+                vertex = Some(ScatterPoint::surface(hit.pos, normal, wo, p, medium));
+
+                let (f, pdf) = p.eval(normal, wo, scattered);
+                if pdf <= 0.0 {
+                    absorbed = true;
+                } else {
+                    albedo = f / pdf;
+                    prev_bsdf_pdf = pdf;
+                    // Rough lobes widen the ray cone
+                    cone_spread += p.alpha.sqrt();
+                }
+            }
+
+            // Offset to the side the scattered ray leaves on
+            let side = if scattered.dot(hit.normal) < 0.0 {
+                -1.0
+            } else {
+                1.0
+            };
+            next_origin = hit.pos + 0.001 * side * hit.normal;
+        }
+
+        // Direct light from the environment map, emissive triangles and the lights
+        if let Some(vertex) = vertex {
+            let (direct, unoccluded) = sample_direct_light(scene, &vertex, &mut rng);
+            if hit_catcher {
+                // The catcher keeps the light the objects take away, as the alpha of a black
+                // shadow. Lights reached by its bounce get the full weight, since their direct
+                // samples are dropped.
+                let lit = luminance(unoccluded);
+                if lit > 0.0 {
+                    alpha = (1.0 - luminance(direct) / lit).clamp(0.0, 1.0);
+                }
+                prev_bsdf_pdf = 0.0;
+            } else {
+                color += attenuation * direct;
+            }
+        }
+        if absorbed {
+            break;
+        }
+
+        attenuation *= albedo;
+
+        origin = next_origin;
+        direction = scattered.normalize();
+        vertex_pos = origin;
+        vertex_dist = 0.0;
+    }
+    color.extend(alpha)
+}
+
+/// Auxiliary outputs of the first hit of a pinhole ray through the center of the pixel
+/// (x, y), one value per enabled AOV in the order of [`Aov::ALL`]. `w` holds the coverage.
+pub fn pixel_aovs(scene: &Scene, frame: &CameraFrame, x: u32, y: u32) -> Vec<Vec4> {
+    let ray = camera_ray(&scene.uniforms, frame, x, y, Vec2::ZERO);
+    // Panoramas have no view axis, their depth is the distance along the ray
+    let depth_axis = if scene.uniforms.camera.projection == u32::from(Projection::Equirectangular) {
+        ray.direction
+    } else {
+        frame.forward
+    };
+    let hit = intersect_scene(scene, ray.origin, ray.direction);
+    let coverage = if hit.is_some() { 1.0 } else { 0.0 };
+
+    let mut depth = 0.0;
+    let mut normal = Vec3::ZERO;
+    let mut albedo = Vec3::ZERO;
+    let mut object = Vec2::ZERO;
+    let mut uv = Vec2::ZERO;
+    if let Some(hit) = &hit {
+        // Distance along the view axis, not along the ray
+        depth = hit.t * ray.direction.dot(depth_axis);
+        normal = shading_normal(scene, hit);
+        if normal.dot(ray.direction) > 0.0 {
+            normal = -normal;
+        }
+        albedo = if is_dielectric(&hit.material) {
+            Vec3::ONE
+        } else {
+            surface_base_color(scene, hit)
+        };
+        object = Vec2::new(hit.object.kind_id() as f32, hit.object.index() as f32);
+        uv = hit.uv;
+    }
+
+    scene
+        .aovs
+        .iter()
+        .map(|aov| match aov {
+            Aov::Depth => Vec3::splat(depth).extend(coverage),
+            Aov::Normal => normal.extend(coverage),
+            Aov::Albedo => albedo.extend(coverage),
+            Aov::ObjectId => object.extend(0.0).extend(coverage),
+            Aov::Uv => uv.extend(0.0).extend(coverage),
+        })
+        .collect()
+}
@@ -0,0 +1,610 @@
+//! Ray intersection with the ground, the mesh BVH, the spheres and the lights.
+
+use crate::scene::Scene;
+use engine_bvh::triangle::GPUTriangle;
+use engine_config::{LightType, Material, PointLight, Sphere};
+use glam::{Mat3, Mat4, Vec2, Vec3, Vec4};
+use std::f32::consts::PI;
+
+/// Entries of the BVH traversal stack, the same limit as in the shaders.
+const BVH_STACK_SIZE: usize = 64;
+
+/// Rays do not hit surfaces closer than this, so they can leave the surface they start on.
+const T_MIN: f32 = 0.001;
+
+/// Kind of the hit object and its index within that kind.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Object {
+    Ground,
+    Sphere(usize),
+    Mesh(usize),
+    Light(usize),
+}
+
+impl Object {
+    /// Kind of the object as written to the object ID layer, 0 is the background.
+    pub fn kind_id(&self) -> u32 {
+        match self {
+            Object::Ground => 1,
+            Object::Sphere(_) => 2,
+            Object::Mesh(_) => 3,
+            Object::Light(_) => 4,
+        }
+    }
+
+    pub fn index(&self) -> usize {
+        match self {
+            Object::Ground => 0,
+            Object::Sphere(i) | Object::Mesh(i) | Object::Light(i) => *i,
+        }
+    }
+}
+
+/// Surface hit by a ray.
+#[derive(Copy, Clone, Debug)]
+pub struct Hit {
+    pub t: f32,
+    pub pos: Vec3,
+    pub normal: Vec3,
+    /// Face normal of mesh triangles, zero for other objects.
+    pub geometric_normal: Vec3,
+    pub uv: Vec2,
+    /// Tangent and bitangent sign, zero if the surface has no tangent frame.
+    pub tangent: Vec4,
+    /// Texture space units per world unit at the hit, 0 if unknown.
+    pub uv_density: f32,
+    /// Width of the ray cone in texture space, used to pick the mip level.
+    pub footprint: f32,
+    pub use_texture: bool,
+    pub object: Object,
+    pub material: Material,
+}
+
+impl Hit {
+    fn new(t: f32, pos: Vec3, normal: Vec3, object: Object, material: Material) -> Self {
+        Self {
+            t,
+            pos,
+            normal,
+            geometric_normal: Vec3::ZERO,
+            uv: Vec2::ZERO,
+            tangent: Vec4::ZERO,
+            uv_density: 0.0,
+            footprint: 0.0,
+            use_texture: false,
+            object,
+            material,
+        }
+    }
+}
+
+/// Closest triangle hit during BVH traversal.
+struct TriangleHit {
+    t: f32,
+    triangle: usize,
+    instance: usize,
+    barycentrics: Vec2,
+}
+
+/// Closest hit along the ray over the ground, the BVH triangles, the spheres and the lights.
+pub fn intersect_scene(scene: &Scene, origin: Vec3, dir: Vec3) -> Option<Hit> {
+    let mut closest: Option<Hit> = None;
+    let t_max = |closest: &Option<Hit>| closest.as_ref().map_or(f32::INFINITY, |h| h.t);
+
+    if scene.ground_enabled() {
+        let t = intersect_ground(scene, origin, dir);
+        if t > T_MIN {
+            let pos = origin + t * dir;
+            let mut hit = Hit::new(
+                t,
+                pos,
+                Vec3::Y,
+                Object::Ground,
+                scene.uniforms.ground_material,
+            );
+            hit.uv = Vec2::new(pos.x, pos.z);
+            // u runs along x and v along z
+            hit.tangent = Vec4::new(1.0, 0.0, 0.0, -1.0);
+            hit.uv_density = 1.0;
+            hit.use_texture = true;
+            closest = Some(hit);
+        }
+    }
+
+    if let Some(tri_hit) = intersect_bvh(scene, origin, dir, t_max(&closest)) {
+        closest = Some(mesh_hit(scene, origin, dir, &tri_hit));
+    }
+
+    for (k, sphere) in scene.spheres.iter().enumerate() {
+        let t = intersect_sphere(origin, dir, sphere);
+        if t > T_MIN && t < t_max(&closest) {
+            let pos = origin + t * dir;
+            let normal = (pos - Vec3::from(sphere.center.0)).normalize();
+            let mut hit = Hit::new(t, pos, normal, Object::Sphere(k), sphere.material);
+            hit.use_texture = sphere.material.texture_index >= 0;
+            closest = Some(hit);
+        }
+    }
+    if let Some(hit) = closest.as_mut()
+        && let Object::Sphere(k) = hit.object
+    {
+        sphere_mapping(hit, &scene.spheres[k]);
+    }
+
+    for (k, light) in scene.lights.iter().enumerate() {
+        let t = intersect_light(origin, dir, light);
+        if t > T_MIN && t < t_max(&closest) {
+            let pos = origin + t * dir;
+            let normal = if is_planar(light) {
+                Vec3::from(light.direction.0)
+            } else {
+                (pos - Vec3::from(light.center.0)).normalize()
+            };
+            closest = Some(Hit::new(t, pos, normal, Object::Light(k), light.material));
+        }
+    }
+
+    closest
+}
+
+/// Whether anything blocks the ray before `max_dist`.
+pub fn occluded(scene: &Scene, origin: Vec3, dir: Vec3, max_dist: f32) -> bool {
+    if scene.ground_enabled() {
+        let t = intersect_ground(scene, origin, dir);
+        if t > T_MIN && t < max_dist {
+            return true;
+        }
+    }
+    if intersect_bvh(scene, origin, dir, max_dist).is_some() {
+        return true;
+    }
+    let blocks = |t: f32| t > T_MIN && t < max_dist;
+    scene
+        .spheres
+        .iter()
+        .any(|s| blocks(intersect_sphere(origin, dir, s)))
+        || scene
+            .lights
+            .iter()
+            .any(|l| blocks(intersect_light(origin, dir, l)))
+}
+
+fn intersect_ground(scene: &Scene, origin: Vec3, dir: Vec3) -> f32 {
+    if dir.y.abs() < 1e-6 {
+        return -1.0;
+    }
+    let t = (scene.uniforms.ground_height - origin.y) / dir.y;
+    if t > 0.0 { t } else { -1.0 }
+}
+
+/// Nearest hit distance beyond [`T_MIN`], -1 if missed.
+pub fn intersect_sphere_at(origin: Vec3, dir: Vec3, center: Vec3, radius: f32) -> f32 {
+    let oc = origin - center;
+    let a = dir.dot(dir);
+    let half_b = oc.dot(dir);
+    let c = oc.dot(oc) - radius * radius;
+    let discriminant = half_b * half_b - a * c;
+    if discriminant < 0.0 {
+        return -1.0;
+    }
+
+    let sqrtd = discriminant.sqrt();
+    let mut root = (-half_b - sqrtd) / a;
+    if root <= T_MIN {
+        root = (-half_b + sqrtd) / a;
+        if root <= T_MIN {
+            return -1.0;
+        }
+    }
+    root
+}
+
+pub fn intersect_sphere(origin: Vec3, dir: Vec3, sphere: &Sphere) -> f32 {
+    intersect_sphere_at(origin, dir, Vec3::from(sphere.center.0), sphere.radius)
+}
+
+pub fn is_planar(light: &PointLight) -> bool {
+    light.light_type == u32::from(LightType::Rect) || light.light_type == u32::from(LightType::Disc)
+}
+
+/// Hit distance on the plane of a rect or disc light, -1 outside of its shape. Both sides
+/// are solid.
+fn intersect_planar_light(origin: Vec3, dir: Vec3, light: &PointLight) -> f32 {
+    let normal = Vec3::from(light.direction.0);
+    let denom = dir.dot(normal);
+    if denom.abs() < 1e-8 {
+        return -1.0;
+    }
+    let center = Vec3::from(light.center.0);
+    let t = (center - origin).dot(normal) / denom;
+    if t <= T_MIN {
+        return -1.0;
+    }
+
+    let local = origin + t * dir - center;
+    if light.light_type == u32::from(LightType::Rect) {
+        let tangent = Vec3::from(light.tangent.0);
+        let bitangent = normal.cross(tangent);
+        if local.dot(tangent).abs() > light.half_size[0]
+            || local.dot(bitangent).abs() > light.half_size[1]
+        {
+            return -1.0;
+        }
+    } else if local.dot(local) > light.radius * light.radius {
+        return -1.0;
+    }
+    t
+}
+
+/// Hit distance on the geometry of any light, -1 if missed. Directional lights have no
+/// geometry.
+pub fn intersect_light(origin: Vec3, dir: Vec3, light: &PointLight) -> f32 {
+    match LightType::try_from(light.light_type) {
+        Ok(LightType::Point | LightType::Spot) => {
+            intersect_sphere_at(origin, dir, Vec3::from(light.center.0), light.radius)
+        }
+        Ok(LightType::Rect | LightType::Disc) => intersect_planar_light(origin, dir, light),
+        _ => -1.0,
+    }
+}
+
+/// Möller–Trumbore intersection, returns the distance and the barycentrics (u, v).
+fn intersect_triangle(
+    origin: Vec3,
+    dir: Vec3,
+    v0: Vec3,
+    v1: Vec3,
+    v2: Vec3,
+) -> Option<(f32, Vec2)> {
+    let edge1 = v1 - v0;
+    let edge2 = v2 - v0;
+    let h = dir.cross(edge2);
+    let a = edge1.dot(h);
+    if a.abs() < 1e-6 {
+        return None;
+    }
+
+    let f = 1.0 / a;
+    let s = origin - v0;
+    let u = f * s.dot(h);
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+
+    let q = s.cross(edge1);
+    let v = f * dir.dot(q);
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    let t = f * edge2.dot(q);
+    (t > 0.0).then_some((t, Vec2::new(u, v)))
+}
+
+fn intersect_aabb(origin: Vec3, dir: Vec3, aabb_min: Vec3, aabb_max: Vec3) -> bool {
+    let inv_dir = dir.recip();
+    let t0s = (aabb_min - origin) * inv_dir;
+    let t1s = (aabb_max - origin) * inv_dir;
+    let tmin = t0s.min(t1s).max_element();
+    let tmax = t0s.max(t1s).min_element();
+    tmax >= tmin.max(0.0)
+}
+
+/// Texture coordinates of a vertex, zero if the UV buffer does not cover it.
+fn vertex_uv(scene: &Scene, index: u32) -> Vec2 {
+    let i = index as usize * 2;
+    match scene.uvs.get(i..i + 2) {
+        Some(uv) => Vec2::new(uv[0], uv[1]),
+        None => Vec2::ZERO,
+    }
+}
+
+pub fn triangle_uv(scene: &Scene, tri: &GPUTriangle, b: Vec2) -> Vec2 {
+    (1.0 - b.x - b.y) * vertex_uv(scene, tri.v0_index)
+        + b.x * vertex_uv(scene, tri.v1_index)
+        + b.y * vertex_uv(scene, tri.v2_index)
+}
+
+/// Whether the opacity map of the triangle's material cuts the point away.
+pub fn is_cut_out(scene: &Scene, tri: &GPUTriangle, b: Vec2) -> bool {
+    let index = scene
+        .meshes
+        .get(tri.mesh_index as usize)
+        .map_or(-1, |mesh| mesh.material.opacity_texture);
+    index >= 0 && scene.fetch_opacity(index, triangle_uv(scene, tri, b)) < 0.5
+}
+
+/// Traverses the top-level BVH over the mesh instances and, from its leaves, the
+/// bottom-level BVHs of the instanced meshes, which are tested with the ray moved into the
+/// space of their instance.
+fn intersect_bvh(scene: &Scene, origin: Vec3, dir: Vec3, t_max: f32) -> Option<TriangleHit> {
+    if scene.bvh_nodes.is_empty() {
+        return None;
+    }
+
+    // Node index and instance of each entry, `None` for the top level in world space
+    let mut stack = [(0usize, None::<usize>); BVH_STACK_SIZE];
+    stack[0] = (scene.uniforms.bvh_root as usize, None);
+    let mut sp = 1;
+    let mut closest: Option<TriangleHit> = None;
+
+    while sp > 0 {
+        sp -= 1;
+        let (node_index, instance) = stack[sp];
+        let Some(node) = scene.bvh_nodes.get(node_index) else {
+            continue;
+        };
+
+        // The direction is not normalized in mesh space, so t is the same in both spaces
+        let (local_origin, local_dir) = match instance.and_then(|i| scene.instances.get(i)) {
+            Some(inst) => {
+                let world_to_object = Mat4::from_cols_array_2d(&inst.world_to_object);
+                (
+                    world_to_object.transform_point3(origin),
+                    world_to_object.transform_vector3(dir),
+                )
+            }
+            None => (origin, dir),
+        };
+
+        if !intersect_aabb(local_origin, local_dir, node.aabb_min, node.aabb_max) {
+            continue;
+        }
+
+        if node.primitive_count == 0 {
+            for child in [node.left, node.right] {
+                if (child as usize) < scene.bvh_nodes.len() && sp < BVH_STACK_SIZE {
+                    stack[sp] = (child as usize, instance);
+                    sp += 1;
+                }
+            }
+            continue;
+        }
+
+        let first = node.first_primitive as usize;
+        let last = (first + node.primitive_count as usize).min(scene.bvh_indices.len());
+        for &primitive in scene.bvh_indices.get(first..last).unwrap_or_default() {
+            let primitive = primitive as usize;
+
+            // Top-level leaves hold instances, continue in their bottom-level BVH
+            let Some(instance) = instance else {
+                if let Some(inst) = scene.instances.get(primitive)
+                    && sp < BVH_STACK_SIZE
+                {
+                    stack[sp] = (inst.blas_root as usize, Some(primitive));
+                    sp += 1;
+                }
+                continue;
+            };
+
+            let Some(tri) = scene.bvh_triangles.get(primitive) else {
+                continue;
+            };
+            let Some((t, barycentrics)) =
+                intersect_triangle(local_origin, local_dir, tri.v0, tri.v1, tri.v2)
+            else {
+                continue;
+            };
+
+            // Cut out texels are skipped like misses, which also lets shadow rays through
+            let t_closest = closest.as_ref().map_or(t_max, |h| h.t);
+            if t > T_MIN && t < t_closest && !is_cut_out(scene, tri, barycentrics) {
+                closest = Some(TriangleHit {
+                    t,
+                    triangle: primitive,
+                    instance,
+                    barycentrics,
+                });
+            }
+        }
+    }
+    closest
+}
+
+/// Interpolates the vertex normals of a triangle, triangles without vertex normals fall back
+/// to the flat face normal.
+fn triangle_normal(tri: &GPUTriangle, u: f32, v: f32, w: f32) -> Vec3 {
+    let shading = w * tri.n0 + u * tri.n1 + v * tri.n2;
+    if shading.length_squared() < 1e-12 {
+        return (tri.v1 - tri.v0).cross(tri.v2 - tri.v0).normalize();
+    }
+    shading.normalize()
+}
+
+/// Resolves the surface at a triangle hit in world space.
+fn mesh_hit(scene: &Scene, origin: Vec3, dir: Vec3, tri_hit: &TriangleHit) -> Hit {
+    let tri = &scene.bvh_triangles[tri_hit.triangle];
+    let (object_to_world, world_to_object) =
+        scene
+            .instances
+            .get(tri_hit.instance)
+            .map_or((Mat4::IDENTITY, Mat4::IDENTITY), |inst| {
+                (
+                    Mat4::from_cols_array_2d(&inst.object_to_world),
+                    Mat4::from_cols_array_2d(&inst.world_to_object),
+                )
+            });
+    let (u, v) = (tri_hit.barycentrics.x, tri_hit.barycentrics.y);
+    let w = 1.0 - u - v;
+
+    // Normals move to world space with the inverse transpose of the instance transform
+    let normal_to_world = Mat3::from_mat4(world_to_object).transpose();
+    let face = (tri.v1 - tri.v0).cross(tri.v2 - tri.v0);
+    let normal = (normal_to_world * triangle_normal(tri, u, v, w)).normalize();
+    let pos = origin + tri_hit.t * dir;
+
+    let mut hit = Hit::new(
+        tri_hit.t,
+        pos,
+        normal,
+        Object::Mesh(tri.mesh_index as usize),
+        empty_material(),
+    );
+    hit.geometric_normal = (normal_to_world * face).normalize();
+
+    let uv0 = vertex_uv(scene, tri.v0_index);
+    let uv1 = vertex_uv(scene, tri.v1_index);
+    let uv2 = vertex_uv(scene, tri.v2_index);
+    hit.uv = w * uv0 + u * uv1 + v * uv2;
+    let uv_area = ((uv1 - uv0).perp_dot(uv2 - uv0)).abs();
+    let edge1 = object_to_world.transform_vector3(tri.v1 - tri.v0);
+    let edge2 = object_to_world.transform_vector3(tri.v2 - tri.v0);
+    let world_area = edge1.cross(edge2).length();
+    hit.uv_density = (uv_area / world_area.max(1e-12)).sqrt();
+    let tangent = w * tri.t0 + u * tri.t1 + v * tri.t2;
+    hit.tangent = object_to_world
+        .transform_vector3(tangent.truncate())
+        .extend(tangent.w);
+
+    if scene.uniforms.color_hash_enabled != 0 {
+        hit.material.diffuse =
+            engine_config::Vec3(hash_to_color(tri_hit.triangle as u32 + 1).into());
+    } else if let Some(mesh) = scene.meshes.get(tri.mesh_index as usize) {
+        hit.material = mesh.material;
+        hit.use_texture = hit.material.texture_index >= 0;
+    }
+    hit
+}
+
+/// Material of surfaces without one: black, fully rough and without specular reflection.
+fn empty_material() -> Material {
+    Material {
+        diffuse: engine_config::Vec3::ZERO,
+        specular: [0.0; 3],
+        shininess: 0.0,
+        illum: 0,
+        roughness: 1.0,
+        specular_level: 0.0,
+        clearcoat_roughness: 0.0,
+        pattern_color_a: [0.0; 3],
+        pattern_color_b: [0.0; 3],
+        pattern_octaves: 1,
+        pattern_sharpness: 1.0,
+        ..Material::default()
+    }
+}
+
+/// Debug color of a triangle in color hash mode.
+fn hash_to_color(n: u32) -> Vec3 {
+    let h = n.wrapping_mul(2654435761);
+    Vec3::new(
+        (h % 41) as f32 / 40.0,
+        (h % 29) as f32 / 28.0,
+        (h % 19) as f32 / 18.0,
+    )
+}
+
+/// Spherical UVs of a sphere hit with the tangent frame along them.
+///
+/// In the local frame of the sphere v runs from the south pole (-y) to the north pole (+y)
+/// and u runs around the y axis, starting and ending at the seam at -z.
+fn sphere_mapping(hit: &mut Hit, sphere: &Sphere) {
+    let [c0, c1, c2] = sphere.orientation;
+    let orientation = Mat3::from_cols(
+        Vec3::new(c0[0], c0[1], c0[2]),
+        Vec3::new(c1[0], c1[1], c1[2]),
+        Vec3::new(c2[0], c2[1], c2[2]),
+    );
+    let d = orientation.transpose() * hit.normal;
+    let ring = Vec2::new(d.x, d.z).length();
+    hit.uv = Vec2::new(
+        0.5 + d.x.atan2(d.z) / (2.0 * PI),
+        0.5 + d.y.clamp(-1.0, 1.0).asin() / PI,
+    );
+    // dP/du points along the ring, any direction at the poles
+    let tangent = if ring > 1e-6 {
+        Vec3::new(d.z, 0.0, -d.x) / ring
+    } else {
+        Vec3::X
+    };
+    hit.tangent = (orientation * tangent).extend(1.0);
+    // u covers 2 pi r cos(latitude) and v covers pi r
+    hit.uv_density = 1.0 / (PI * sphere.radius * (2.0 * ring.max(1e-3)).sqrt());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use engine_bvh::instance::TwoLevelBVH;
+    use engine_config::Mesh;
+    use std::f32::consts::FRAC_PI_2;
+
+    #[test]
+    fn triangle_hits_report_distance_and_barycentrics() {
+        let (v0, v1, v2) = (Vec3::ZERO, Vec3::X, Vec3::Y);
+        let (t, b) = intersect_triangle(Vec3::new(0.25, 0.5, 3.0), -Vec3::Z, v0, v1, v2).unwrap();
+        assert!((t - 3.0).abs() < 1e-6);
+        assert!(b.abs_diff_eq(Vec2::new(0.25, 0.5), 1e-6));
+
+        // Outside the edges, parallel to the plane and behind the origin
+        assert!(intersect_triangle(Vec3::new(0.6, 0.6, 3.0), -Vec3::Z, v0, v1, v2).is_none());
+        assert!(intersect_triangle(Vec3::new(0.2, 0.2, 3.0), Vec3::X, v0, v1, v2).is_none());
+        assert!(intersect_triangle(Vec3::new(0.2, 0.2, 3.0), Vec3::Z, v0, v1, v2).is_none());
+    }
+
+    #[test]
+    fn sphere_hits_from_outside_and_inside() {
+        let center = Vec3::new(0.0, 0.0, 5.0);
+        assert!((intersect_sphere_at(Vec3::ZERO, Vec3::Z, center, 1.0) - 4.0).abs() < 1e-5);
+        // From the inside the far side is hit
+        assert!((intersect_sphere_at(center, Vec3::Z, center, 1.0) - 1.0).abs() < 1e-5);
+        // Passing by and behind the origin
+        assert_eq!(
+            intersect_sphere_at(Vec3::new(1.5, 0.0, 0.0), Vec3::Z, center, 1.0),
+            -1.0
+        );
+        assert_eq!(intersect_sphere_at(Vec3::ZERO, -Vec3::Z, center, 1.0), -1.0);
+    }
+
+    #[test]
+    fn instances_place_the_mesh_in_world_space() {
+        let triangle = GPUTriangle {
+            v0: Vec3::new(-1.0, -1.0, 0.0),
+            v1: Vec3::new(1.0, -1.0, 0.0),
+            v2: Vec3::new(0.0, 1.0, 0.0),
+            ..Default::default()
+        };
+        // One copy 5 units ahead, one turned to face the x axis and scaled up
+        let ahead = Mat4::from_translation(Vec3::new(0.0, 0.0, 5.0));
+        let turned = Mat4::from_translation(Vec3::new(10.0, 0.0, 0.0))
+            * Mat4::from_rotation_y(FRAC_PI_2)
+            * Mat4::from_scale(Vec3::splat(2.0));
+        let bvh = TwoLevelBVH::new(
+            &[triangle],
+            std::slice::from_ref(&(0..1)),
+            &[(0, ahead), (0, turned)],
+        );
+
+        let material = Material {
+            diffuse: engine_config::Vec3::new(0.2, 0.4, 0.6),
+            ..Default::default()
+        };
+        let mut scene = Scene {
+            meshes: vec![Mesh::new(0, 1, material)],
+            bvh_triangles: vec![triangle],
+            bvh_nodes: bvh.nodes,
+            bvh_indices: bvh.indices,
+            instances: bvh.instances,
+            ..Default::default()
+        };
+        scene.uniforms.bvh_root = bvh.root;
+        scene.uniforms.color_hash_enabled = 0;
+
+        // The mesh itself is not placed at the origin, only its instances are
+        let hit = intersect_scene(&scene, Vec3::new(0.0, 0.0, -10.0), Vec3::Z).unwrap();
+        assert!((hit.t - 15.0).abs() < 1e-4);
+        assert_eq!(hit.object, Object::Mesh(0));
+        assert_eq!(hit.material.diffuse, material.diffuse);
+        assert!(hit.normal.abs().abs_diff_eq(Vec3::Z, 1e-5));
+
+        // 1.5 units off the axis is outside the small copy but inside the scaled one
+        let hit = intersect_scene(&scene, Vec3::new(5.0, 1.5, 0.0), Vec3::X).unwrap();
+        assert!((hit.t - 5.0).abs() < 1e-4);
+        assert!(hit.normal.abs().abs_diff_eq(Vec3::X, 1e-5));
+        assert!(hit.geometric_normal.abs().abs_diff_eq(Vec3::X, 1e-5));
+        assert!(intersect_scene(&scene, Vec3::new(0.0, 1.5, 0.0), Vec3::Z).is_none());
+
+        assert!(occluded(&scene, Vec3::ZERO, Vec3::Z, 6.0));
+        assert!(!occluded(&scene, Vec3::ZERO, Vec3::Z, 4.0));
+    }
+}
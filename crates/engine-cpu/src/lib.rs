@@ -0,0 +1,333 @@
+//! # Engine CPU
+//!
+//! `engine-cpu` renders [`RenderConfig`] scenes on the CPU for the RenderBaby project. It needs
+//! no GPU, so scenes can be rendered and tested on machines and CI runners without one, and it
+//! serves as a ground-truth reference for the path tracing shader.
+//!
+//! ## Features
+//!
+//! - **No GPU Required**: Traces the scene directly from the [`RenderConfig`] and the `engine_bvh`
+//!   acceleration structures.
+//! - **Progressive Rendering**: Supports progressive rendering through [`CpuFrameIterator`] with one
+//!   sample per pixel and frame, like the GPU engines.
+//! - **Parallel Tiles**: Every pass is split into tiles which are rendered on all cores.
+//! - **Same Light Transport**: Ports the path tracing shader, including its materials, lights,
+//!   media, AOV layers and adaptive sampling, and uses the same random sequences per pixel.
+//!
+//! ## Architecture
+//!
+//! This crate implements the [`Renderer`] trait from `engine_config`. It encapsulates a
+//! [`CpuRenderer`] which holds the scene and the accumulated samples. The light transport lives in
+//! the `integrator` module, split into the same parts as the shader.
+
+mod bsdf;
+mod camera;
+mod color;
+mod cpu_renderer;
+mod environment;
+mod integrator;
+mod intersect;
+mod lights;
+mod medium;
+mod pattern;
+mod sampler;
+mod scene;
+mod texture;
+
+pub use cpu_renderer::CpuRenderer;
+
+use anyhow::Result;
+use chrono::Local;
+pub use engine_config::RenderConfig;
+use engine_config::Renderer;
+use frame_buffer::frame_iterator::{Frame, FrameIterator, FrameLayer, RADIANCE_LAYER, samples_layer};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// A CPU path tracing renderer.
+///
+/// The `Engine` struct serves as the main entry point for the CPU backend.
+/// It holds a thread-safe reference to the [`CpuRenderer`] and implements the [`Renderer`] trait
+/// to integrate with the rest of the application.
+pub struct Engine {
+    /// Shared access to the renderer, holding the scene and the accumulated samples.
+    renderer: Arc<Mutex<CpuRenderer>>,
+}
+
+impl Renderer for Engine {
+    /// Renders a scene synchronously.
+    ///
+    /// This method blocks until all passes are complete and returns the final frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `rc` - The render configuration containing the scene description and settings.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Frame>` - The rendered frame containing pixel data, or an error if rendering fails.
+    fn render(&mut self, rc: RenderConfig) -> Result<Frame> {
+        let mut renderer = self.renderer.lock().unwrap();
+
+        renderer.update(rc)?;
+        renderer.render()?;
+        Ok(read_frame(&renderer))
+    }
+
+    /// Creates a frame iterator for progressive rendering.
+    ///
+    /// This method applies the configuration, starts a new render and returns an iterator
+    /// that yields one frame per pass.
+    ///
+    /// # Arguments
+    ///
+    /// * `rc` - The render configuration containing the scene description and settings.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Box<dyn FrameIterator>>` - A boxed iterator yielding frames, or an error if the configuration is invalid.
+    fn frame_iterator(&mut self, rc: RenderConfig) -> Result<Box<dyn FrameIterator>> {
+        {
+            let mut renderer = self.renderer.lock().unwrap();
+            renderer.update(rc)?;
+            renderer.begin_render();
+        }
+        Ok(Box::new(CpuFrameIterator::new(Arc::clone(&self.renderer))))
+    }
+}
+
+impl Engine {
+    /// Creates a new `Engine` instance.
+    ///
+    /// # Arguments
+    ///
+    /// * `rc` - The initial render configuration.
+    ///
+    /// # Returns
+    ///
+    /// * `Self` - A new instance of the CPU engine.
+    pub fn new(rc: RenderConfig) -> Self {
+        let renderer = CpuRenderer::new(rc).unwrap();
+        Self {
+            renderer: Arc::new(Mutex::new(renderer)),
+        }
+    }
+}
+
+/// Builds the frame of the current render with its AOV layers, with adaptive sampling the
+/// samples layer and, if requested, the radiance layer.
+fn read_frame(renderer: &CpuRenderer) -> Frame {
+    let mut layers: Vec<FrameLayer> = renderer
+        .read_aovs()
+        .into_iter()
+        .map(|(aov, data)| FrameLayer::new(aov.name(), data))
+        .collect();
+    if renderer.is_adaptive() {
+        layers.push(samples_layer(&renderer.samples()));
+    }
+    if renderer.has_radiance_layer() {
+        layers.push(FrameLayer::new(RADIANCE_LAYER, renderer.read_radiance()));
+    }
+    Frame::new(
+        renderer.width() as usize,
+        renderer.height() as usize,
+        renderer.read_pixels(),
+    )
+    .with_layers(layers)
+}
+
+/// A frame iterator for progressive rendering on the CPU.
+///
+/// Every `next()` call renders one pass over all tiles and returns the accumulated frame.
+pub struct CpuFrameIterator {
+    /// Shared access to the renderer.
+    renderer: Arc<Mutex<CpuRenderer>>,
+    /// Flag indicating if the rendering session has been initialized (e.g., render timer started).
+    initialized: bool,
+    /// Timer to track the duration of the rendering process.
+    render_time: Option<Instant>,
+}
+
+impl CpuFrameIterator {
+    /// Creates a new `CpuFrameIterator`.
+    ///
+    /// # Arguments
+    ///
+    /// * `renderer` - Shared access to the renderer.
+    fn new(renderer: Arc<Mutex<CpuRenderer>>) -> Self {
+        Self {
+            renderer,
+            initialized: false,
+            render_time: None,
+        }
+    }
+}
+
+impl FrameIterator for CpuFrameIterator {
+    /// Checks if there are more passes to render.
+    fn has_next(&self) -> bool {
+        self.renderer.lock().unwrap().has_next_pass()
+    }
+
+    /// Renders the next pass and returns the accumulated frame.
+    fn next(&mut self) -> Result<Frame> {
+        if !self.has_next() {
+            anyhow::bail!("No more frames available");
+        }
+
+        let mut renderer = self.renderer.lock().unwrap();
+
+        if !self.initialized {
+            self.render_time = Some(Instant::now());
+            log::info!("Render started at {}", Local::now());
+            self.initialized = true;
+        }
+
+        let sample = renderer.current_pass() + 1;
+        renderer.render_pass()?;
+        let frame = read_frame(&renderer);
+
+        log::info!(
+            "[ENGINE-CPU] Sample: {} / {}",
+            sample,
+            renderer.total_passes()
+        );
+
+        if !renderer.has_next_pass()
+            && let Some(timer) = self.render_time
+        {
+            log::info!("[ENGINE-CPU] Render finished in {:?}", timer.elapsed());
+        }
+        Ok(frame)
+    }
+
+    /// Cancels the current rendering iterator.
+    fn destroy(&mut self) {
+        log::info!("[ENGINE-CPU] Cancelled Render Iterator.")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use engine_config::{Aov, AovSet, Material, PointLight, Sphere, ToneMapping, Uniforms, Vec3};
+    use frame_buffer::frame_iterator::SAMPLES_LAYER;
+
+    fn furnace_config(total_samples: u32) -> RenderConfig {
+        let uniforms = Uniforms {
+            width: 16,
+            height: 12,
+            total_samples,
+            color_hash_enabled: 0,
+            ground_enabled: 0,
+            sky_color: [0.5; 3],
+            tone_mapping: ToneMapping::None.into(),
+            ..Default::default()
+        };
+        let material = Material {
+            diffuse: Vec3::new(1.0, 1.0, 1.0),
+            specular_level: 0.0,
+            ..Default::default()
+        };
+        let sphere = Sphere::new(Vec3::new(2.0, 2.0, 5.0), 1.5, material).unwrap();
+        RenderConfig::builder()
+            .uniforms_create(uniforms)
+            .spheres_create(vec![sphere])
+            .uvs_create(vec![])
+            .meshes_create(vec![])
+            .lights_create(vec![])
+            .textures_create(vec![])
+            .build()
+    }
+
+    #[test]
+    fn white_furnace_matches_sky() {
+        let mut engine = Engine::new(furnace_config(16));
+        let frame = engine.render(furnace_config(16)).unwrap();
+        frame.validate().unwrap();
+
+        // A white diffuse sphere disappears in a uniform sky, sqrt(0.5) after display encoding
+        for pixel in frame.pixels.chunks(4) {
+            for channel in &pixel[..3] {
+                assert!(channel.abs_diff(181) <= 2, "pixel {pixel:?}");
+            }
+            assert_eq!(pixel[3], 255);
+        }
+    }
+
+    #[test]
+    fn sunlit_ground_follows_lamberts_cosine_law() {
+        let mut uniforms = Uniforms {
+            width: 8,
+            height: 8,
+            total_samples: 4,
+            color_hash_enabled: 0,
+            checkerboard_enabled: 0,
+            sky_color: [0.0; 3],
+            tone_mapping: ToneMapping::None.into(),
+            radiance_layer: 1,
+            ..Default::default()
+        };
+        uniforms.ground_material = Material {
+            diffuse: Vec3::new(0.5, 0.5, 0.5),
+            specular_level: 0.0,
+            ..Default::default()
+        };
+        // A narrow view down onto the ground, which is all the camera sees
+        uniforms.camera.pos = [0.0, 0.0, 0.0];
+        uniforms.camera.dir = [0.0, -1.0, 1.0];
+        uniforms.camera.pane_distance = 1.0;
+        uniforms.camera.pane_width = 0.2;
+        uniforms.camera.aperture_radius = 0.0;
+        // A sharp sun with an irradiance of π, 36.87° from the zenith
+        let sun = PointLight::directional([0.6, -0.8, 0.0], 0.0, std::f32::consts::PI, [1.0; 3]);
+        let rc = RenderConfig::builder()
+            .uniforms_create(uniforms)
+            .spheres_create(vec![])
+            .uvs_create(vec![])
+            .meshes_create(vec![])
+            .lights_create(vec![sun])
+            .textures_create(vec![])
+            .build();
+
+        let mut engine = Engine::new(rc.clone());
+        let frame = engine.render(rc).unwrap();
+
+        // Lambertian radiance albedo · E · cos θ / π, without noise from the sharp sun
+        let expected = color::color_map(glam::Vec3::splat(0.5 * 0.8), 1.0);
+        for pixel in frame.pixels.chunks(4) {
+            for (channel, expected) in pixel.iter().zip(expected) {
+                assert!(channel.abs_diff(expected) <= 1, "pixel {pixel:?}");
+            }
+        }
+        let radiance = frame.layer(RADIANCE_LAYER).unwrap();
+        for pixel in radiance.data.chunks(4) {
+            assert!(
+                (pixel[0] - 0.4).abs() < 1e-4 && pixel[3] == 1.0,
+                "{pixel:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn frame_iterator_yields_one_frame_per_sample() {
+        let mut engine = Engine::new(furnace_config(3));
+        let rc = RenderConfig::builder()
+            .aovs_create(AovSet::empty().with(Aov::Depth).with(Aov::ObjectId))
+            .build();
+        let mut frames = engine.frame_iterator(rc).unwrap();
+
+        let mut count = 0;
+        while frames.has_next() {
+            let frame = frames.next().unwrap();
+            frame.validate().unwrap();
+            assert_eq!((frame.width, frame.height), (16, 12));
+            assert!(frame.layer(Aov::Depth.name()).is_some());
+            assert!(frame.layer(Aov::ObjectId.name()).is_some());
+            assert!(frame.layer(SAMPLES_LAYER).is_none());
+            count += 1;
+        }
+        assert_eq!(count, 3);
+        assert!(frames.next().is_err());
+    }
+}
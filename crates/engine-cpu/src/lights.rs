@@ -0,0 +1,618 @@
+//! Next-event estimation: light sampling, MIS weights and shadow rays.
+
+use crate::bsdf::{ScatterPoint, build_onb};
+use crate::color::luminance;
+use crate::intersect::{intersect_scene, intersect_sphere_at, is_cut_out, occluded, triangle_uv};
+use crate::medium::{Medium, is_medium_boundary, medium_behind};
+use crate::sampler::Sampler;
+use crate::scene::Scene;
+use engine_config::{LightType, PointLight};
+use glam::{Vec2, Vec3};
+use std::f32::consts::PI;
+
+/// Medium boundaries a shadow ray passes through before it is treated as blocked.
+const MAX_MEDIUM_BOUNDARIES: u32 = 8;
+
+/// Distance of lights at infinity.
+const FAR: f32 = 1e20;
+
+fn light_type(light: &PointLight) -> Option<LightType> {
+    LightType::try_from(light.light_type).ok()
+}
+
+fn center(light: &PointLight) -> Vec3 {
+    Vec3::from(light.center.0)
+}
+
+fn direction(light: &PointLight) -> Vec3 {
+    Vec3::from(light.direction.0)
+}
+
+fn emissive(light: &PointLight) -> Vec3 {
+    Vec3::from(light.material.emissive)
+}
+
+pub fn power_heuristic(pdf_a: f32, pdf_b: f32) -> f32 {
+    let a2 = pdf_a * pdf_a;
+    let b2 = pdf_b * pdf_b;
+    if a2 + b2 <= 0.0 {
+        return 0.0;
+    }
+    a2 / (a2 + b2)
+}
+
+/// 1 - cos of the half angle of the cone a sphere light subtends from `pos`, 0 if `pos` is
+/// inside.
+fn light_cone_extent(pos: Vec3, light: &PointLight) -> f32 {
+    let dist2 = (center(light) - pos).length_squared();
+    let r2 = light.radius * light.radius;
+    if dist2 <= r2 {
+        return 0.0;
+    }
+    let sin2_max = r2 / dist2;
+    let cos_max = (1.0 - sin2_max).max(0.0).sqrt();
+    // Equal to 1 - cos_max, but stable for small or distant lights
+    sin2_max / (1.0 + cos_max)
+}
+
+fn sphere_light_pdf(pos: Vec3, light: &PointLight) -> f32 {
+    let extent = light_cone_extent(pos, light);
+    if extent <= 0.0 {
+        return 0.0;
+    }
+    1.0 / (2.0 * PI * extent)
+}
+
+/// Solid angle pdf of sampling `dir` (hit at distance `t`) towards a rect or disc light by
+/// area.
+fn planar_light_pdf(light: &PointLight, dir: Vec3, t: f32) -> f32 {
+    let cos_light = dir.dot(direction(light)).abs();
+    if cos_light <= 1e-6 {
+        return 0.0;
+    }
+    t * t / (planar_light_area(light) * cos_light)
+}
+
+fn planar_light_area(light: &PointLight) -> f32 {
+    if light_type(light) == Some(LightType::Rect) {
+        return 4.0 * light.half_size[0] * light.half_size[1];
+    }
+    PI * light.radius * light.radius
+}
+
+/// Solid angle pdf of next-event estimation reaching the light from `origin` along `dir`.
+pub fn light_pdf(origin: Vec3, light: &PointLight, dir: Vec3, t: f32) -> f32 {
+    match light_type(light) {
+        Some(LightType::Point | LightType::Spot) => sphere_light_pdf(origin, light),
+        Some(LightType::Rect | LightType::Disc) => planar_light_pdf(light, dir, t),
+        _ => 0.0,
+    }
+}
+
+/// Smooth spot falloff between the inner and the outer cone for light leaving along
+/// `out_dir`.
+fn spot_falloff(light: &PointLight, out_dir: Vec3) -> f32 {
+    let cos_angle = out_dir.dot(direction(light));
+    if light.cos_inner <= light.cos_outer {
+        return if cos_angle >= light.cos_outer {
+            1.0
+        } else {
+            0.0
+        };
+    }
+    let x = ((cos_angle - light.cos_outer) / (light.cos_inner - light.cos_outer)).clamp(0.0, 1.0);
+    x * x * (3.0 - 2.0 * x)
+}
+
+/// Radiance of a light seen from `origin` along `dir`. The spot falloff depends on the
+/// direction from the center to `origin`, so it is the same for light and BSDF sampling.
+pub fn light_emission(light: &PointLight, origin: Vec3, dir: Vec3) -> Vec3 {
+    match light_type(light) {
+        Some(LightType::Spot) => {
+            emissive(light) * spot_falloff(light, (origin - center(light)).normalize())
+        }
+        // One-sided
+        Some(LightType::Rect | LightType::Disc) if dir.dot(direction(light)) >= 0.0 => Vec3::ZERO,
+        _ => emissive(light),
+    }
+}
+
+/// 1 - cos of the angular radius of a directional light, 0 for a perfectly sharp one.
+fn directional_extent(light: &PointLight) -> f32 {
+    let cos_max = light.cos_outer.clamp(-1.0, 1.0);
+    if cos_max < 1.0 {
+        (1.0 - cos_max * cos_max) / (1.0 + cos_max)
+    } else {
+        0.0
+    }
+}
+
+/// Radiance of the directional lights visible along an escaping ray, MIS-weighted against
+/// the previous principled bounce.
+pub fn directional_radiance(scene: &Scene, dir: Vec3, prev_bsdf_pdf: f32) -> Vec3 {
+    let mut radiance = Vec3::ZERO;
+    for light in &scene.lights {
+        let extent = directional_extent(light);
+        if light_type(light) != Some(LightType::Directional) || extent <= 0.0 {
+            continue;
+        }
+        if dir.dot(-direction(light)) < light.cos_outer {
+            continue;
+        }
+        let pdf = 1.0 / (2.0 * PI * extent);
+        let weight = if prev_bsdf_pdf > 0.0 {
+            power_heuristic(prev_bsdf_pdf, pdf)
+        } else {
+            1.0
+        };
+        // The luminosity is the irradiance, spread over the solid angle of the sun
+        radiance += emissive(light) * pdf * weight;
+    }
+    radiance
+}
+
+/// Direction sampled towards a light.
+struct LightSample {
+    dir: Vec3,
+    /// Distance to the sampled point, [`FAR`] for directional lights.
+    dist: f32,
+    radiance: Vec3,
+    /// Solid angle pdf.
+    pdf: f32,
+    /// Sharp directional light, can't be reached by BSDF sampling.
+    delta: bool,
+}
+
+/// Uniformly sampled direction within `extent` (1 - cos of the half angle) around `axis`.
+fn sample_cone(axis: Vec3, extent: f32, u: Vec2) -> Vec3 {
+    let cos_theta = 1.0 - u.x * extent;
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * PI * u.y;
+    (build_onb(axis) * Vec3::new(phi.cos() * sin_theta, phi.sin() * sin_theta, cos_theta))
+        .normalize()
+}
+
+/// Uniformly sampled direction in the cone a sphere light subtends from `origin`.
+fn sample_sphere_light(light: &PointLight, origin: Vec3, u: Vec2) -> Option<LightSample> {
+    let extent = light_cone_extent(origin, light);
+    if light.radius <= 0.0 || extent <= 0.0 {
+        return None;
+    }
+    let dir = sample_cone((center(light) - origin).normalize(), extent, u);
+    let t = intersect_sphere_at(origin, dir, center(light), light.radius);
+    if t <= 0.0 {
+        return None;
+    }
+    Some(LightSample {
+        dir,
+        dist: t,
+        radiance: light_emission(light, origin, dir),
+        pdf: 1.0 / (2.0 * PI * extent),
+        delta: false,
+    })
+}
+
+/// Uniformly sampled point on the area of a rect or disc light.
+fn sample_planar_light(light: &PointLight, origin: Vec3, u: Vec2) -> Option<LightSample> {
+    let tangent = Vec3::from(light.tangent.0);
+    let bitangent = direction(light).cross(tangent);
+    let offset = if light_type(light) == Some(LightType::Rect) {
+        (2.0 * u - 1.0) * Vec2::from(light.half_size)
+    } else {
+        let r = light.radius * u.x.sqrt();
+        let phi = 2.0 * PI * u.y;
+        r * Vec2::new(phi.cos(), phi.sin())
+    };
+    let point = center(light) + offset.x * tangent + offset.y * bitangent;
+
+    let to_light = point - origin;
+    let dist = to_light.length();
+    if dist <= 1e-4 {
+        return None;
+    }
+    let dir = to_light / dist;
+    let pdf = planar_light_pdf(light, dir, dist);
+    let radiance = light_emission(light, origin, dir);
+    if pdf <= 0.0 || radiance.max_element() <= 0.0 {
+        return None;
+    }
+    Some(LightSample {
+        dir,
+        dist,
+        radiance,
+        pdf,
+        delta: false,
+    })
+}
+
+/// Uniformly sampled direction in the cone of a directional light.
+fn sample_directional_light(light: &PointLight, u: Vec2) -> Option<LightSample> {
+    let towards = -direction(light).normalize();
+    let extent = directional_extent(light);
+    if extent <= 0.0 {
+        return Some(LightSample {
+            dir: towards,
+            dist: FAR,
+            radiance: emissive(light),
+            pdf: 1.0,
+            delta: true,
+        });
+    }
+    let pdf = 1.0 / (2.0 * PI * extent);
+    Some(LightSample {
+        dir: sample_cone(towards, extent, u),
+        dist: FAR,
+        radiance: emissive(light) * pdf,
+        pdf,
+        delta: false,
+    })
+}
+
+fn sample_light(light: &PointLight, origin: Vec3, u: Vec2) -> Option<LightSample> {
+    match light_type(light)? {
+        LightType::Point | LightType::Spot => sample_sphere_light(light, origin, u),
+        LightType::Rect | LightType::Disc => sample_planar_light(light, origin, u),
+        LightType::Directional => sample_directional_light(light, u),
+    }
+}
+
+/// Unshadowed light sample for next-event estimation.
+struct LightCandidate {
+    dir: Vec3,
+    dist: f32,
+    /// MIS-weighted `Le · f / pdf` towards `wo`, before the shadow ray.
+    contribution: Vec3,
+}
+
+/// Next-event estimation for one light: a sampled direction towards it, MIS-weighted
+/// against BSDF sampling. Sharp directional lights are not weighted.
+fn light_candidate(
+    s: &ScatterPoint,
+    light: &PointLight,
+    rng: &mut Sampler,
+) -> Option<LightCandidate> {
+    if emissive(light).max_element() <= 0.0 {
+        return None;
+    }
+
+    let origin = s.pos + 0.001 * s.normal;
+    let ls = sample_light(light, origin, rng.sample_2d())?;
+    if ls.pdf <= 0.0 {
+        return None;
+    }
+    let (f, bsdf_pdf) = s.eval(ls.dir);
+    if bsdf_pdf <= 0.0 {
+        return None;
+    }
+
+    let weight = if ls.delta {
+        1.0
+    } else {
+        power_heuristic(ls.pdf, bsdf_pdf)
+    };
+    Some(LightCandidate {
+        dir: ls.dir,
+        dist: ls.dist - 0.001,
+        contribution: ls.radiance * f * weight / ls.pdf,
+    })
+}
+
+fn emissive_total_power(scene: &Scene) -> f32 {
+    scene.emissive_triangles.last().map_or(0.0, |tri| tri.cdf)
+}
+
+/// Index of the emissive triangle whose share of the total power contains `u` in [0, 1), a
+/// binary search over the running sums.
+fn pick_emissive_triangle(scene: &Scene, u: f32) -> usize {
+    let r = u * emissive_total_power(scene);
+    scene
+        .emissive_triangles
+        .partition_point(|tri| tri.cdf <= r)
+        .min(scene.emissive_triangles.len() - 1)
+}
+
+/// Solid angle pdf of sampling a point at distance `t` on an emissive mesh triangle, whose
+/// face normal makes `cos_light` with the ray. Triangles are picked proportional to their
+/// power, so the area cancels out.
+pub fn emissive_triangle_pdf(scene: &Scene, emission: Vec3, t: f32, cos_light: f32) -> f32 {
+    let total = emissive_total_power(scene);
+    if total <= 0.0 || cos_light <= 1e-6 {
+        return 0.0;
+    }
+    luminance(emission) * t * t / (total * cos_light)
+}
+
+/// Next-event estimation for emissive mesh triangles: picks one triangle by power and a
+/// uniform point on it, MIS-weighted against BSDF sampling. Emission is two-sided.
+fn emissive_triangle_candidate(
+    scene: &Scene,
+    s: &ScatterPoint,
+    rng: &mut Sampler,
+) -> Option<LightCandidate> {
+    if !scene.emissive_triangles_enabled() {
+        return None;
+    }
+
+    let tri = &scene.emissive_triangles[pick_emissive_triangle(scene, rng.sample_1d())];
+    let (v0, v1, v2) = (
+        Vec3::from(tri.v0.0),
+        Vec3::from(tri.v1.0),
+        Vec3::from(tri.v2.0),
+    );
+
+    let u = rng.sample_2d();
+    let su = u.x.sqrt();
+    let b0 = 1.0 - su;
+    let b1 = u.y * su;
+    let point = b0 * v0 + b1 * v1 + (1.0 - b0 - b1) * v2;
+
+    // The maps are looked up at the sampled point, the triangle was picked by its untextured
+    // power
+    let emission_base = Vec3::from(tri.emission.0);
+    let mut emission = emission_base;
+    if let Some(mesh_tri) = scene.bvh_triangles.get(tri.triangle as usize) {
+        let barycentrics = Vec2::new(b1, 1.0 - b0 - b1);
+        if is_cut_out(scene, mesh_tri, barycentrics) {
+            return None;
+        }
+        let emissive_texture = scene
+            .meshes
+            .get(mesh_tri.mesh_index as usize)
+            .map_or(-1, |mesh| mesh.material.emissive_texture);
+        if emissive_texture >= 0 {
+            let uv = triangle_uv(scene, mesh_tri, barycentrics);
+            emission *= scene.sample_texture(emissive_texture, uv, 0.0);
+        }
+    }
+
+    let origin = s.pos + 0.001 * s.normal;
+    let to_light = point - origin;
+    let dist = to_light.length();
+    if dist <= 1e-4 {
+        return None;
+    }
+    let light_dir = to_light / dist;
+    let face_normal = (v1 - v0).cross(v2 - v0).normalize();
+    let light_pdf =
+        emissive_triangle_pdf(scene, emission_base, dist, light_dir.dot(face_normal).abs());
+    if light_pdf <= 0.0 {
+        return None;
+    }
+
+    let (f, bsdf_pdf) = s.eval(light_dir);
+    if bsdf_pdf <= 0.0 {
+        return None;
+    }
+
+    let weight = power_heuristic(light_pdf, bsdf_pdf);
+    Some(LightCandidate {
+        dir: light_dir,
+        dist: dist - 0.001,
+        contribution: emission * f * weight / light_pdf,
+    })
+}
+
+/// Next-event estimation for the environment map: an importance sampled direction,
+/// MIS-weighted against BSDF sampling.
+fn environment_candidate(
+    scene: &Scene,
+    s: &ScatterPoint,
+    rng: &mut Sampler,
+) -> Option<LightCandidate> {
+    let environment = scene.environment.as_ref()?;
+    let (light_dir, env_pdf) = environment.sample(scene.uniforms.environment_rotation, rng);
+    if env_pdf <= 0.0 {
+        return None;
+    }
+
+    let (f, bsdf_pdf) = s.eval(light_dir);
+    if bsdf_pdf <= 0.0 {
+        return None;
+    }
+
+    let weight = power_heuristic(env_pdf, bsdf_pdf);
+    Some(LightCandidate {
+        dir: light_dir,
+        dist: FAR,
+        contribution: scene.environment_radiance(light_dir) * f * weight / env_pdf,
+    })
+}
+
+/// Direct light at a vertex: one sample of the environment map, one emissive triangle and
+/// one sample per light, in this order.
+///
+/// Returns the scattered radiance towards `wo` and the light that would arrive without
+/// shadows, for the shadow catcher.
+pub fn sample_direct_light(scene: &Scene, s: &ScatterPoint, rng: &mut Sampler) -> (Vec3, Vec3) {
+    let mut direct = Vec3::ZERO;
+    let mut unoccluded = Vec3::ZERO;
+    let origin = s.pos + 0.001 * s.normal;
+
+    let mut add = |candidate: Option<LightCandidate>| {
+        if let Some(c) = candidate
+            && c.contribution.max_element() > 0.0
+        {
+            direct += c.contribution * shadow_transmittance(scene, origin, c.dir, c.dist, s.medium);
+            unoccluded += c.contribution;
+        }
+    };
+    add(environment_candidate(scene, s, rng));
+    add(emissive_triangle_candidate(scene, s, rng));
+    for light in &scene.lights {
+        add(light_candidate(s, light, rng));
+    }
+
+    (direct, unoccluded)
+}
+
+/// Transmittance of a shadow ray starting in `medium` up to `max_dist`, zero if a surface
+/// blocks it.
+///
+/// The ray passes through medium boundaries and is attenuated by every medium on its way,
+/// so lights at infinity are fully extinguished by a global medium.
+pub fn shadow_transmittance(
+    scene: &Scene,
+    origin: Vec3,
+    dir: Vec3,
+    max_dist: f32,
+    medium: Medium,
+) -> Vec3 {
+    if !scene.media_enabled() {
+        return if occluded(scene, origin, dir, max_dist) {
+            Vec3::ZERO
+        } else {
+            Vec3::ONE
+        };
+    }
+
+    let mut transmittance = Vec3::ONE;
+    let mut current = medium;
+    let mut pos = origin;
+    let mut remaining = max_dist;
+    for _ in 0..MAX_MEDIUM_BOUNDARIES {
+        let hit = match intersect_scene(scene, pos, dir) {
+            Some(hit) if hit.t < remaining => hit,
+            _ => return transmittance * current.transmittance(remaining),
+        };
+        if !is_medium_boundary(&hit) {
+            return Vec3::ZERO;
+        }
+        transmittance *= current.transmittance(hit.t);
+        current = medium_behind(scene, &hit, dir);
+        pos = hit.pos + 0.001 * dir;
+        remaining -= hit.t + 0.001;
+    }
+    Vec3::ZERO
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use engine_config::EmissiveTriangle;
+
+    /// Midpoints of an `n` x `n` grid over [0, 1)².
+    fn grid(n: u32) -> impl Iterator<Item = Vec2> {
+        (0..n * n).map(move |i| {
+            Vec2::new(
+                ((i % n) as f32 + 0.5) / n as f32,
+                ((i / n) as f32 + 0.5) / n as f32,
+            )
+        })
+    }
+
+    /// Solid angle covered by the samples of a light, the mean of 1 / pdf.
+    fn sampled_solid_angle(light: &PointLight, origin: Vec3) -> f32 {
+        let n = 64;
+        let total: f32 = grid(n)
+            .map(|u| {
+                let sample = sample_light(light, origin, u).expect("light sample");
+                let t = crate::intersect::intersect_light(origin, sample.dir, light);
+                assert!((t - sample.dist).abs() < 1e-3, "{t} != {}", sample.dist);
+                assert!((light_pdf(origin, light, sample.dir, t) - sample.pdf).abs() < 1e-3);
+                1.0 / sample.pdf
+            })
+            .sum();
+        total / (n * n) as f32
+    }
+
+    #[test]
+    fn area_light_samples_cover_their_solid_angle() {
+        // Disc of radius 1 at distance 2: 2π (1 - cos θ)
+        let disc = PointLight::disc([0.0, 0.0, 2.0], [0.0, 0.0, -1.0], 1.0, 1.0, [1.0; 3]);
+        let expected = 2.0 * PI * (1.0 - 2.0 / 5f32.sqrt());
+        let solid_angle = sampled_solid_angle(&disc, Vec3::ZERO);
+        assert!(
+            (solid_angle - expected).abs() < 0.01 * expected,
+            "{solid_angle}"
+        );
+
+        // 2 x 2 rect at distance 1: 4 asin(ab / sqrt((a² + d²)(b² + d²))) = 2π / 3
+        let rect = PointLight::rect(
+            [0.0, 0.0, 1.0],
+            [0.0, 0.0, -1.0],
+            [1.0, 0.0, 0.0],
+            [2.0, 2.0],
+            1.0,
+            [1.0; 3],
+        );
+        let solid_angle = sampled_solid_angle(&rect, Vec3::ZERO);
+        assert!((solid_angle - 2.0 * PI / 3.0).abs() < 0.02, "{solid_angle}");
+
+        // Sphere of radius 1 at distance 4, sampled uniformly in its cone
+        let sphere = PointLight::new([0.0, 0.0, 4.0], 1.0, 1.0, [1.0; 3]);
+        let expected = 2.0 * PI * (1.0 - 15f32.sqrt() / 4.0);
+        let solid_angle = sampled_solid_angle(&sphere, Vec3::ZERO);
+        assert!((solid_angle - expected).abs() < 1e-4, "{solid_angle}");
+    }
+
+    #[test]
+    fn planar_lights_emit_to_one_side() {
+        let disc = PointLight::disc([0.0, 0.0, 2.0], [0.0, 0.0, -1.0], 1.0, 3.0, [1.0; 3]);
+        assert_eq!(light_emission(&disc, Vec3::ZERO, Vec3::Z), Vec3::splat(3.0));
+        assert_eq!(
+            light_emission(&disc, Vec3::new(0.0, 0.0, 4.0), -Vec3::Z),
+            Vec3::ZERO
+        );
+    }
+
+    #[test]
+    fn spot_light_falls_off_between_its_cones() {
+        let spot = PointLight::spot([0.0; 3], 0.1, [0.0, -1.0, 0.0], [20.0, 40.0], 1.0, [1.0; 3]);
+        let at = |degrees: f32| {
+            let theta = degrees.to_radians();
+            spot_falloff(&spot, Vec3::new(theta.sin(), -theta.cos(), 0.0))
+        };
+        assert_eq!(at(0.0), 1.0);
+        assert_eq!(at(19.0), 1.0);
+        assert_eq!(at(41.0), 0.0);
+        assert!(at(25.0) > at(30.0) && at(30.0) > at(35.0) && at(35.0) > 0.0);
+    }
+
+    #[test]
+    fn emissive_triangles_are_picked_by_power() {
+        let mut scene = Scene::default();
+        let triangle = |cdf| EmissiveTriangle {
+            cdf,
+            ..EmissiveTriangle::new([0.0; 3], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [1.0; 3])
+        };
+        // Powers 1, 0, 2 and 3
+        scene.emissive_triangles = vec![triangle(1.0), triangle(1.0), triangle(3.0), triangle(6.0)];
+
+        let n = 600;
+        let mut picks = [0; 4];
+        for i in 0..n {
+            picks[pick_emissive_triangle(&scene, (i as f32 + 0.5) / n as f32)] += 1;
+        }
+        assert_eq!(picks, [100, 0, 200, 300]);
+        assert_eq!(pick_emissive_triangle(&scene, 0.0), 0);
+        assert_eq!(pick_emissive_triangle(&scene, 0.99999), 3);
+    }
+
+    #[test]
+    fn emissive_triangle_pdf_is_uniform_over_the_area() {
+        let mut scene = Scene::default();
+        let mut tri = EmissiveTriangle::new(
+            [0.0, 0.0, 0.0],
+            [2.0, 0.0, 0.0],
+            [0.0, 3.0, 0.0],
+            [4.0, 2.0, 1.0],
+        );
+        tri.cdf = tri.power();
+        scene.emissive_triangles = vec![tri];
+
+        // One triangle is always picked, the pdf is 1 / area converted to solid angle
+        let emission = Vec3::from(tri.emission.0);
+        let pdf = emissive_triangle_pdf(&scene, emission, 2.0, 0.5);
+        assert!((pdf - 2.0 * 2.0 / (3.0 * 0.5)).abs() < 1e-4, "{pdf}");
+        assert_eq!(emissive_triangle_pdf(&scene, emission, 2.0, 0.0), 0.0);
+    }
+
+    #[test]
+    fn power_heuristic_weights_sum_to_one() {
+        for (a, b) in [(1.0, 1.0), (0.3, 2.0), (5.0, 0.01)] {
+            assert!((power_heuristic(a, b) + power_heuristic(b, a) - 1.0).abs() < 1e-6);
+        }
+        assert_eq!(power_heuristic(0.0, 0.0), 0.0);
+        assert_eq!(power_heuristic(1.0, 0.0), 1.0);
+    }
+}
@@ -0,0 +1,197 @@
+//! Homogeneous participating media.
+
+use crate::bsdf::build_onb;
+use crate::intersect::{Hit, Object};
+use crate::sampler::Sampler;
+use crate::scene::Scene;
+use glam::{Vec2, Vec3};
+use std::f32::consts::PI;
+
+/// Homogeneous participating medium, coefficients per unit length.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Medium {
+    pub absorption: Vec3,
+    pub scattering: Vec3,
+    /// Henyey-Greenstein asymmetry.
+    pub g: f32,
+}
+
+impl From<engine_config::Medium> for Medium {
+    fn from(m: engine_config::Medium) -> Self {
+        Self {
+            absorption: Vec3::from(m.absorption),
+            scattering: Vec3::from(m.scattering),
+            g: m.anisotropy,
+        }
+    }
+}
+
+impl Medium {
+    /// Whether the medium absorbs or scatters light at all.
+    pub fn is_present(&self) -> bool {
+        (self.absorption + self.scattering).max_element() > 0.0
+    }
+
+    pub fn transmittance(&self, distance: f32) -> Vec3 {
+        (-(self.absorption + self.scattering) * distance).exp()
+    }
+
+    /// Free-flight sampling up to the next surface at `t_max`.
+    ///
+    /// The distance is sampled proportional to the scattering coefficient of a random color
+    /// channel, the weight is MIS-combined over all channels (one-sample balance heuristic).
+    /// Returns the path weight and the scattering distance, `None` if the ray reaches the
+    /// surface. Absorption alone never scatters.
+    pub fn sample_free_flight(&self, t_max: f32, rng: &mut Sampler) -> (Vec3, Option<f32>) {
+        let channel = ((rng.sample_1d() * 3.0) as usize).min(2);
+        let u = rng.sample_1d();
+        let mut t = t_max;
+        if self.scattering[channel] > 0.0 {
+            t = (-(1.0 - u).ln() / self.scattering[channel]).min(t_max);
+        }
+        let transmittance = self.transmittance(t);
+        let survival = (-self.scattering * t).exp();
+        if t < t_max {
+            let pdf = (self.scattering * survival).dot(Vec3::splat(1.0 / 3.0));
+            return (self.scattering * transmittance / pdf, Some(t));
+        }
+        let pdf = survival.dot(Vec3::splat(1.0 / 3.0));
+        (transmittance / pdf, None)
+    }
+}
+
+/// Interior medium of a material.
+pub fn material_medium(material: &engine_config::Material) -> Medium {
+    Medium::from(material.medium())
+}
+
+/// Non-dielectric objects with an interior medium have no surface of their own (smoke, fog
+/// volumes), rays pass through their boundary and only change the medium they travel in.
+pub fn is_medium_boundary(hit: &Hit) -> bool {
+    let object = matches!(hit.object, Object::Sphere(_) | Object::Mesh(_));
+    object
+        && !crate::bsdf::is_dielectric(&hit.material)
+        && material_medium(&hit.material).is_present()
+}
+
+/// Medium a ray continues in after crossing the boundary of `hit`: the object's interior
+/// medium when entering it, the global medium when leaving it.
+pub fn medium_behind(scene: &Scene, hit: &Hit, direction: Vec3) -> Medium {
+    if direction.dot(hit.normal) < 0.0 {
+        material_medium(&hit.material)
+    } else {
+        scene.global_medium()
+    }
+}
+
+/// Henyey-Greenstein phase function for the angle between the propagation directions before
+/// and after scattering, `g > 0` scatters forwards.
+pub fn hg_phase(cos_theta: f32, g: f32) -> f32 {
+    let denom = (1.0 + g * g - 2.0 * g * cos_theta).max(1e-6);
+    (1.0 - g * g) / (4.0 * PI * denom * denom.sqrt())
+}
+
+/// Samples a new propagation direction from the Henyey-Greenstein phase function.
+pub fn sample_hg(direction: Vec3, g: f32, u: Vec2) -> Vec3 {
+    let mut cos_theta = 1.0 - 2.0 * u.x;
+    if g.abs() > 1e-3 {
+        let sq = (1.0 - g * g) / (1.0 - g + 2.0 * g * u.x);
+        cos_theta = ((1.0 + g * g - sq * sq) / (2.0 * g)).clamp(-1.0, 1.0);
+    }
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * PI * u.y;
+    build_onb(direction) * Vec3::new(phi.cos() * sin_theta, phi.sin() * sin_theta, cos_theta)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ASYMMETRIES: [f32; 5] = [-0.7, -0.2, 0.0, 0.4, 0.9];
+
+    #[test]
+    fn hg_phase_is_normalized() {
+        let steps = 20000;
+        for g in ASYMMETRIES {
+            // Integrated over the sphere, 2π ∫ p(cos θ) d cos θ
+            let integral: f32 = (0..steps)
+                .map(|i| {
+                    let cos_theta = -1.0 + (i as f32 + 0.5) * 2.0 / steps as f32;
+                    2.0 * PI * hg_phase(cos_theta, g) * 2.0 / steps as f32
+                })
+                .sum();
+            assert!((integral - 1.0).abs() < 1e-3, "g = {g}: {integral}");
+        }
+    }
+
+    #[test]
+    fn sampled_directions_have_the_mean_cosine_g() {
+        let direction = Vec3::new(1.0, 2.0, -2.0).normalize();
+        let n = 64;
+        for g in ASYMMETRIES {
+            let mut mean_cos = 0.0;
+            for i in 0..n {
+                for j in 0..n {
+                    let u = Vec2::new((i as f32 + 0.5) / n as f32, (j as f32 + 0.5) / n as f32);
+                    let scattered = sample_hg(direction, g, u);
+                    assert!((scattered.length() - 1.0).abs() < 1e-4);
+                    mean_cos += scattered.dot(direction);
+                }
+            }
+            mean_cos /= (n * n) as f32;
+            assert!((mean_cos - g).abs() < 2e-3, "g = {g}: {mean_cos}");
+        }
+    }
+
+    #[test]
+    fn transmittance_follows_beer_lambert() {
+        let medium = Medium {
+            absorption: Vec3::new(0.1, 0.2, 0.0),
+            scattering: Vec3::new(0.1, 0.0, 0.5),
+            g: 0.0,
+        };
+        assert!(medium.is_present());
+        assert!(!Medium::default().is_present());
+        let expected = Vec3::new((-0.6f32).exp(), (-0.6f32).exp(), (-1.5f32).exp());
+        assert!(medium.transmittance(3.0).abs_diff_eq(expected, 1e-6));
+    }
+
+    #[test]
+    fn free_flight_weights_are_unbiased() {
+        // Paths reaching the surface carry its transmittance, the scattered ones the light
+        // scattered on the way, ∫ σs T(t) dt
+        let medium = Medium {
+            absorption: Vec3::splat(0.1),
+            scattering: Vec3::new(0.2, 0.5, 1.0),
+            g: 0.0,
+        };
+        let t_max = 2.0;
+        let samples = 16384;
+        let mut surface = Vec3::ZERO;
+        let mut scattered = Vec3::ZERO;
+        for i in 0..samples {
+            let mut rng = Sampler::new(9, i);
+            match medium.sample_free_flight(t_max, &mut rng) {
+                (weight, None) => surface += weight,
+                (weight, Some(t)) => {
+                    assert!(t < t_max);
+                    scattered += weight;
+                }
+            }
+        }
+        surface /= samples as f32;
+        scattered /= samples as f32;
+
+        assert!(
+            surface.abs_diff_eq(medium.transmittance(t_max), 0.01),
+            "{surface}"
+        );
+        let extinction = medium.absorption + medium.scattering;
+        let expected_scattered =
+            medium.scattering / extinction * (Vec3::ONE - (-extinction * t_max).exp());
+        assert!(
+            scattered.abs_diff_eq(expected_scattered, 0.01),
+            "{scattered}"
+        );
+    }
+}
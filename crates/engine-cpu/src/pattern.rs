@@ -0,0 +1,206 @@
+//! Base color of surfaces: the material color times its texture or procedural pattern.
+
+use crate::intersect::Hit;
+use crate::sampler::{hash, hash_combine};
+use crate::scene::Scene;
+use engine_config::{Material, PatternKind};
+use glam::{Vec2, Vec3};
+
+/// Base color of a hit: the diffuse color times the procedural pattern or the texture.
+pub fn surface_base_color(scene: &Scene, hit: &Hit) -> Vec3 {
+    let diffuse = Vec3::from(hit.material.diffuse.0);
+    if hit.material.pattern_kind != u32::from(PatternKind::None) {
+        return diffuse * sample_pattern(scene, hit);
+    }
+    if hit.use_texture {
+        return diffuse * scene.sample_texture(hit.material.texture_index, hit.uv, hit.footprint);
+    }
+    diffuse
+}
+
+/// Procedural pattern of the material at the world position of the hit.
+fn sample_pattern(scene: &Scene, hit: &Hit) -> Vec3 {
+    let m = &hit.material;
+    let p = hit.pos * m.pattern_scale;
+    // Width of the ray cone in pattern space, 0 if the surface has no UV density
+    let width = if hit.uv_density > 0.0 {
+        m.pattern_scale * hit.footprint / hit.uv_density
+    } else {
+        0.0
+    };
+
+    let t = match PatternKind::try_from(m.pattern_kind) {
+        Ok(PatternKind::Triplanar) => return sample_triplanar(scene, m, p, hit.normal, width),
+        Ok(PatternKind::Checker) => {
+            // Not filtered along the normal, flat faces on a cell boundary would turn grey
+            let n = hit.normal;
+            checker_pattern(p, width * (Vec3::ONE - n * n).max(Vec3::ZERO).powf(0.5))
+        }
+        Ok(PatternKind::Gradient) => p.y.clamp(0.0, 1.0),
+        Ok(PatternKind::Noise) => (0.5 + fbm(p, m.pattern_octaves, width)).clamp(0.0, 1.0),
+        Ok(PatternKind::Voronoi) => voronoi_distance(p).clamp(0.0, 1.0),
+        _ => 0.0,
+    };
+    Vec3::from(m.pattern_color_a).lerp(Vec3::from(m.pattern_color_b), t)
+}
+
+/// Diffuse texture projected along the x, y and z axes, blended by the normal.
+fn sample_triplanar(scene: &Scene, m: &Material, p: Vec3, normal: Vec3, width: f32) -> Vec3 {
+    let w = normal.abs().powf(m.pattern_sharpness);
+    let w = w / (w.x + w.y + w.z).max(1e-6);
+    let mut color = Vec3::ZERO;
+    if w.x > 1e-3 {
+        color += w.x * scene.sample_texture(m.texture_index, Vec2::new(p.z, p.y), width);
+    }
+    if w.y > 1e-3 {
+        color += w.y * scene.sample_texture(m.texture_index, Vec2::new(p.x, p.z), width);
+    }
+    if w.z > 1e-3 {
+        color += w.z * scene.sample_texture(m.texture_index, Vec2::new(p.x, p.y), width);
+    }
+    color
+}
+
+fn fract3(v: Vec3) -> Vec3 {
+    v - v.floor()
+}
+
+/// 3D checkerboard, 0 or 1 per unit cell, box filtered over `width` per axis.
+fn checker_pattern(p: Vec3, width: Vec3) -> f32 {
+    let w = width.max(Vec3::splat(1e-3));
+    // The offset keeps faces on integer coordinates inside one cell
+    let q = p + 1e-3;
+    let i = 2.0
+        * ((fract3((q - 0.5 * w) * 0.5) - 0.5).abs() - (fract3((q + 0.5 * w) * 0.5) - 0.5).abs())
+        / w;
+    0.5 - 0.5 * i.x * i.y * i.z
+}
+
+/// Random point in [0, 1)³ for a lattice cell.
+fn lattice_hash(cell: Vec3) -> Vec3 {
+    let c = cell.as_ivec3();
+    let h = hash(hash_combine(
+        hash_combine(hash(c.x as u32), c.y as u32),
+        c.z as u32,
+    ));
+    let h2 = hash(h);
+    let h3 = hash(h2);
+    Vec3::new((h >> 8) as f32, (h2 >> 8) as f32, (h3 >> 8) as f32) / 16777216.0
+}
+
+/// Contribution of one lattice corner to the gradient noise.
+fn noise_corner(cell: Vec3, f: Vec3, corner: Vec3) -> f32 {
+    let gradient = lattice_hash(cell + corner) * 2.0 - 1.0;
+    gradient.dot(f - corner)
+}
+
+fn mix(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+/// Perlin gradient noise, roughly in [-1, 1].
+fn gradient_noise(p: Vec3) -> f32 {
+    let cell = p.floor();
+    let f = p - cell;
+    let u = f * f * f * (f * (f * 6.0 - 15.0) + 10.0);
+    let corner = |x: f32, y: f32, z: f32| noise_corner(cell, f, Vec3::new(x, y, z));
+    let x00 = mix(corner(0.0, 0.0, 0.0), corner(1.0, 0.0, 0.0), u.x);
+    let x10 = mix(corner(0.0, 1.0, 0.0), corner(1.0, 1.0, 0.0), u.x);
+    let x01 = mix(corner(0.0, 0.0, 1.0), corner(1.0, 0.0, 1.0), u.x);
+    let x11 = mix(corner(0.0, 1.0, 1.0), corner(1.0, 1.0, 1.0), u.x);
+    mix(mix(x00, x10, u.y), mix(x01, x11, u.y), u.z)
+}
+
+/// Fractal sum of noise octaves, each with twice the frequency and half the amplitude.
+/// Octaves finer than the filter width fade out to their mean.
+fn fbm(p: Vec3, octaves: u32, width: f32) -> f32 {
+    let mut sum = 0.0;
+    let mut total = 0.0;
+    let mut amplitude = 1.0;
+    let mut frequency = 1.0;
+    for _ in 0..octaves {
+        let fade = (1.5 - frequency * width * 2.0).clamp(0.0, 1.0);
+        sum += amplitude * fade * gradient_noise(p * frequency);
+        total += amplitude * amplitude;
+        amplitude *= 0.5;
+        frequency *= 2.0;
+    }
+    sum / f32::sqrt(total)
+}
+
+/// Distance to the closest of the random feature points, one per unit cell.
+fn voronoi_distance(p: Vec3) -> f32 {
+    let cell = p.floor();
+    let mut closest = 8.0f32;
+    for z in -1..=1 {
+        for y in -1..=1 {
+            for x in -1..=1 {
+                let neighbour = cell + Vec3::new(x as f32, y as f32, z as f32);
+                let feature = neighbour + lattice_hash(neighbour);
+                closest = closest.min(p.distance(feature));
+            }
+        }
+    }
+    closest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checker_alternates_between_cells_and_averages_out() {
+        let sharp = Vec3::splat(1e-3);
+        let cell = checker_pattern(Vec3::splat(0.5), sharp);
+        assert!(cell.abs() < 1e-4 || (cell - 1.0).abs() < 1e-4, "{cell}");
+        for step in [Vec3::X, Vec3::Y, Vec3::Z] {
+            let neighbour = checker_pattern(Vec3::splat(0.5) + step, sharp);
+            assert!((cell + neighbour - 1.0).abs() < 1e-4, "{cell} {neighbour}");
+        }
+        // Two cells along every axis average to grey
+        let blurred = checker_pattern(Vec3::splat(0.3), Vec3::splat(2.0));
+        assert!((blurred - 0.5).abs() < 1e-4, "{blurred}");
+    }
+
+    #[test]
+    fn gradient_noise_vanishes_on_the_lattice() {
+        for i in -3..3 {
+            let p = Vec3::new(i as f32, (i * 7) as f32, (i * 3 - 2) as f32);
+            assert_eq!(gradient_noise(p), 0.0);
+        }
+        let mut mean = 0.0;
+        let n = 4096;
+        for i in 0..n {
+            let p = Vec3::new(i as f32 * 0.173, i as f32 * 0.0917, i as f32 * 0.0411);
+            let noise = gradient_noise(p);
+            assert!(noise.abs() <= 1.5, "{noise} at {p}");
+            mean += noise / n as f32;
+        }
+        assert!(mean.abs() < 0.05, "{mean}");
+    }
+
+    #[test]
+    fn fbm_fades_octaves_finer_than_the_filter() {
+        let p = Vec3::new(0.3, 1.7, -2.2);
+        assert_eq!(fbm(p, 4, 1.0), 0.0);
+        // An octave keeps its full amplitude up to a quarter of its period
+        assert_eq!(fbm(p, 4, 0.0), fbm(p, 4, 0.25 / 8.0));
+        // Half of the first octave is left, the finer ones are gone
+        let first = gradient_noise(p) / f32::sqrt(1.0 + 0.25 + 0.0625);
+        assert!((fbm(p, 3, 0.5) - first * 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn voronoi_distance_is_zero_at_feature_points() {
+        for cell in [Vec3::ZERO, Vec3::new(-2.0, 5.0, 1.0)] {
+            let feature = cell + lattice_hash(cell);
+            assert_eq!(voronoi_distance(feature), 0.0);
+        }
+        // Every point has a feature point within its own cell
+        for i in 0..256 {
+            let p = Vec3::new(i as f32 * 0.37, i as f32 * -0.21, i as f32 * 0.13);
+            let distance = voronoi_distance(p);
+            assert!((0.0..=3f32.sqrt()).contains(&distance), "{distance} at {p}");
+        }
+    }
+}
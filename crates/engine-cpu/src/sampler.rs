@@ -0,0 +1,205 @@
+//! Low-discrepancy sampler shared with the GPU shaders.
+//!
+//! The sequences are bit for bit the ones of the path tracer shader, so a pixel takes the
+//! same samples on the CPU and on the GPU.
+
+use glam::Vec2;
+
+/// PCG hash.
+pub fn hash(seed: u32) -> u32 {
+    let state = seed.wrapping_mul(747796405).wrapping_add(2891336453);
+    let word = ((state >> ((state >> 28) + 4)) ^ state).wrapping_mul(277803737);
+    (word >> 22) ^ word
+}
+
+/// Mixes `value` into `seed`.
+pub fn hash_combine(seed: u32, value: u32) -> u32 {
+    seed ^ value.wrapping_add(seed << 6).wrapping_add(seed >> 2)
+}
+
+/// Owen-scrambled Sobol points, after Burley, "Practical Hash-based Owen Scrambling"
+/// (JCGT 2020).
+///
+/// Every draw takes its own dimension, the bounce in the upper and a running slot in the
+/// lower 16 bits. Per dimension the sample index is shuffled and the 2D Sobol point is
+/// scrambled with seeds hashed from the pixel and the dimension.
+#[derive(Clone, Copy, Debug)]
+pub struct Sampler {
+    /// Index of the sample within the pixel.
+    index: u32,
+    /// Hash of the pixel.
+    seed: u32,
+    /// Dimension of the next draw.
+    dimension: u32,
+}
+
+impl Sampler {
+    /// Creates the sampler of sample `sample_index` of the pixel `pixel_index`.
+    pub fn new(pixel_index: u32, sample_index: u32) -> Self {
+        Self {
+            index: sample_index,
+            seed: hash(pixel_index),
+            dimension: 0,
+        }
+    }
+
+    /// Continues with the dimensions of a bounce, bounce 0 is the camera.
+    pub fn start_bounce(&mut self, bounce: u32) {
+        self.dimension = bounce << 16;
+    }
+
+    /// Returns the next 2D point in [0, 1)².
+    pub fn sample_2d(&mut self) -> Vec2 {
+        let seed = hash(hash_combine(self.seed, self.dimension));
+        self.dimension = self.dimension.wrapping_add(1);
+
+        let index = nested_uniform_scramble(self.index, seed);
+        let (x, y) = sobol_2d(index);
+        let x = nested_uniform_scramble(x, hash_combine(seed, 0));
+        let y = nested_uniform_scramble(y, hash_combine(seed, 1));
+        // The upper 24 bits fit into the mantissa, so the result stays below 1.0
+        Vec2::new((x >> 8) as f32, (y >> 8) as f32) / 16777216.0
+    }
+
+    /// Returns the next number in [0, 1).
+    pub fn sample_1d(&mut self) -> f32 {
+        self.sample_2d().x
+    }
+}
+
+fn laine_karras_permutation(x0: u32, seed: u32) -> u32 {
+    let mut x = x0.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50b47c);
+    x ^= x.wrapping_mul(0xb82f1e52);
+    x ^= x.wrapping_mul(0xc7afe638);
+    x ^= x.wrapping_mul(0x8d22f6e6);
+    x
+}
+
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    laine_karras_permutation(x.reverse_bits(), seed).reverse_bits()
+}
+
+/// First two Sobol dimensions: van der Corput and its (0,2)-sequence partner.
+fn sobol_2d(index: u32) -> (u32, u32) {
+    let mut y = 0;
+    let mut v = 0x80000000u32;
+    let mut i = index;
+    while i != 0 {
+        if i & 1 != 0 {
+            y ^= v;
+        }
+        i >>= 1;
+        v ^= v >> 1;
+    }
+    (index.reverse_bits(), y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Asserts that every cell of a `columns` x `rows` grid over [0, 1)² holds one point.
+    fn assert_stratified(points: &[Vec2], columns: usize, rows: usize) {
+        let mut cells = vec![0; columns * rows];
+        for p in points {
+            assert!(p.cmpge(Vec2::ZERO).all() && p.cmplt(Vec2::ONE).all(), "{p}");
+            let cell = (p.y * rows as f32) as usize * columns + (p.x * columns as f32) as usize;
+            cells[cell] += 1;
+        }
+        assert!(cells.iter().all(|n| *n == 1), "{columns}x{rows}: {cells:?}");
+    }
+
+    #[test]
+    fn first_samples_of_a_pixel_are_stratified() {
+        for (pixel, bounce, draw) in [(0, 0, 0), (1234, 0, 3), (77, 5, 1)] {
+            let points: Vec<Vec2> = (0..16)
+                .map(|i| {
+                    let mut rng = Sampler::new(pixel, i);
+                    rng.start_bounce(bounce);
+                    for _ in 0..draw {
+                        rng.sample_2d();
+                    }
+                    rng.sample_2d()
+                })
+                .collect();
+            // Every elementary interval of a (0, 4, 2)-net
+            for (columns, rows) in [(16, 1), (8, 2), (4, 4), (2, 8), (1, 16)] {
+                assert_stratified(&points, columns, rows);
+            }
+        }
+    }
+
+    #[test]
+    fn dimensions_and_pixels_are_decorrelated() {
+        let mut rng = Sampler::new(5, 3);
+        let first = rng.sample_2d();
+        let second = rng.sample_2d();
+        assert_ne!(first, second);
+        assert_ne!(first, Sampler::new(6, 3).sample_2d());
+
+        // The same pixel, sample and dimension always draw the same point
+        assert_eq!(first, Sampler::new(5, 3).sample_2d());
+        let mut bounce = Sampler::new(5, 3);
+        bounce.start_bounce(1);
+        assert_ne!(first, bounce.sample_2d());
+    }
+
+    #[test]
+    fn sobol_starts_with_the_van_der_corput_sequence() {
+        assert_eq!(sobol_2d(0), (0, 0));
+        assert_eq!(sobol_2d(1), (0x80000000, 0x80000000));
+        assert_eq!(sobol_2d(2), (0x40000000, 0xc0000000));
+        assert_eq!(sobol_2d(3), (0xc0000000, 0x40000000));
+    }
+
+    type Integrand = fn(Vec2) -> f32;
+
+    /// The PCG sequence the path tracer drew its numbers from before the Sobol sampler.
+    fn hash_sample_2d(pixel: u32, sample: u32) -> Vec2 {
+        let mut seed = hash(pixel.wrapping_add(hash(sample)));
+        let mut next = || {
+            seed = hash(seed);
+            seed as f32 / 4294967296.0
+        };
+        Vec2::new(next(), next())
+    }
+
+    /// RMSE of the `spp`-sample estimates of `integrand` over 1024 pixels.
+    fn rmse(spp: u32, expected: f32, integrand: Integrand, draw: fn(u32, u32) -> Vec2) -> f32 {
+        let pixels = 1024;
+        let squared_error: f32 = (0..pixels)
+            .map(|pixel| {
+                let sum: f32 = (0..spp).map(|i| integrand(draw(pixel, i))).sum();
+                (sum / spp as f32 - expected).powi(2)
+            })
+            .sum();
+        (squared_error / pixels as f32).sqrt()
+    }
+
+    /// Measured RMSE over 1024 pixels, Sobol / hash:
+    ///
+    /// | integrand | 16 spp          | 64 spp          |
+    /// |-----------|-----------------|-----------------|
+    /// | smooth    | 0.0266 / 0.0737 | 0.0042 / 0.0361 |
+    /// | edge      | 0.0589 / 0.1073 | 0.0192 / 0.0533 |
+    #[test]
+    fn converges_faster_than_the_hash_sampler() {
+        use std::f32::consts::PI;
+        let sobol = |pixel, i| Sampler::new(pixel, i).sample_2d();
+        // A smooth integrand like a cosine lobe, and a hard edge like a shadow boundary
+        let cases: [(Integrand, f32); 2] = [
+            (|p| (PI * p.x).sin() * (PI * p.y).sin(), 4.0 / (PI * PI)),
+            (|p| (p.length_squared() < 1.0) as u32 as f32, PI / 4.0),
+        ];
+        for (integrand, expected) in cases {
+            let [sobol_16, sobol_64] = [16, 64].map(|spp| rmse(spp, expected, integrand, sobol));
+            let [hash_16, hash_64] =
+                [16, 64].map(|spp| rmse(spp, expected, integrand, hash_sample_2d));
+            assert!(sobol_16 < 0.6 * hash_16, "16 spp: {sobol_16} vs {hash_16}");
+            assert!(sobol_64 < 0.4 * hash_64, "64 spp: {sobol_64} vs {hash_64}");
+            // Random sampling only halves the error with four times the samples
+            assert!(sobol_64 < sobol_16 / 2.5, "{sobol_16} -> {sobol_64}");
+        }
+    }
+}
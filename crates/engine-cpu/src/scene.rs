@@ -0,0 +1,184 @@
+//! Scene data of the CPU renderer.
+//!
+//! The [`Scene`] keeps the contents of the last [`RenderConfig`]s, the same data the GPU
+//! engines hold in their buffers, and applies the [`Change`]s of new ones.
+
+use crate::environment::Environment;
+use crate::medium::Medium;
+use crate::texture::Texture;
+use anyhow::Result;
+use engine_bvh::bvh::BVHNode;
+use engine_bvh::instance::Instance;
+use engine_bvh::triangle::GPUTriangle;
+use engine_config::render_config::{Change, Validate, ValidateInit};
+use engine_config::{AovSet, EmissiveTriangle, Mesh, PointLight, RenderConfig, Sphere, Uniforms};
+use glam::{Vec2, Vec3};
+
+/// Scene description and render settings, read by the render threads.
+pub struct Scene {
+    pub uniforms: Uniforms,
+    pub spheres: Vec<Sphere>,
+    /// Texture coordinates, two floats per vertex.
+    pub uvs: Vec<f32>,
+    pub meshes: Vec<Mesh>,
+    pub lights: Vec<PointLight>,
+    pub bvh_nodes: Vec<BVHNode>,
+    pub bvh_indices: Vec<u32>,
+    pub bvh_triangles: Vec<GPUTriangle>,
+    pub instances: Vec<Instance>,
+    /// Emissive mesh triangles, `cdf` holds the running sum of their power.
+    pub emissive_triangles: Vec<EmissiveTriangle>,
+    pub textures: Vec<Texture>,
+    pub environment: Option<Environment>,
+    pub aovs: AovSet,
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Self {
+            uniforms: Uniforms::default(),
+            spheres: Vec::new(),
+            uvs: Vec::new(),
+            meshes: Vec::new(),
+            lights: Vec::new(),
+            bvh_nodes: Vec::new(),
+            bvh_indices: Vec::new(),
+            bvh_triangles: Vec::new(),
+            instances: Vec::new(),
+            emissive_triangles: Vec::new(),
+            textures: Vec::new(),
+            environment: None,
+            aovs: AovSet::empty(),
+        }
+    }
+}
+
+impl Scene {
+    /// Applies a render configuration.
+    ///
+    /// The first configuration has to create all required fields, later ones are validated
+    /// and may keep, update or delete them, like the buffers of the GPU engines.
+    pub fn update(&mut self, rc: RenderConfig, initialized: bool) -> Result<()> {
+        if initialized {
+            rc.validate()?;
+        } else {
+            rc.validate_init()?;
+        }
+
+        apply(&mut self.uniforms, rc.uniforms);
+        apply(&mut self.spheres, rc.spheres);
+        apply(&mut self.uvs, rc.uvs);
+        apply(&mut self.meshes, rc.meshes);
+        apply(&mut self.lights, rc.lights);
+        apply(&mut self.bvh_nodes, rc.bvh_nodes);
+        apply(&mut self.bvh_indices, rc.bvh_indices);
+        apply(&mut self.bvh_triangles, rc.bvh_triangles);
+        apply(&mut self.instances, rc.instances);
+        apply(&mut self.emissive_triangles, rc.emissive_triangles);
+        apply(&mut self.aovs, rc.aovs);
+
+        match rc.textures {
+            Change::Keep => {}
+            Change::Create(textures) | Change::Update(textures) => {
+                self.textures = textures.iter().map(Texture::new).collect();
+            }
+            Change::Delete => self.textures.clear(),
+        }
+        match rc.environment {
+            Change::Keep => {}
+            Change::Create(environment) | Change::Update(environment) => {
+                self.environment = Environment::new(&environment);
+            }
+            Change::Delete => self.environment = None,
+        }
+        Ok(())
+    }
+
+    pub fn ground_enabled(&self) -> bool {
+        self.uniforms.ground_enabled > 0
+    }
+
+    /// The ground only keeps the shadows and reflections of the other objects, over a
+    /// transparent background.
+    pub fn shadow_catcher_enabled(&self) -> bool {
+        self.ground_enabled() && self.uniforms.shadow_catcher > 0
+    }
+
+    pub fn emissive_triangles_enabled(&self) -> bool {
+        !self.emissive_triangles.is_empty() && self.uniforms.color_hash_enabled == 0
+    }
+
+    pub fn global_medium(&self) -> Medium {
+        Medium::from(self.uniforms.medium())
+    }
+
+    /// Shadow rays only have to look for medium boundaries if the scene contains any media.
+    pub fn media_enabled(&self) -> bool {
+        self.global_medium().is_present() || self.uniforms.interior_media != 0
+    }
+
+    /// Radiance arriving from `dir`, falls back to the sky color.
+    pub fn environment_radiance(&self, dir: Vec3) -> Vec3 {
+        match &self.environment {
+            Some(environment) => {
+                environment.radiance(dir, self.uniforms.environment_rotation)
+                    * self.uniforms.environment_intensity
+            }
+            None => Vec3::from(self.uniforms.sky_color),
+        }
+    }
+
+    /// Color texture lookup, converted from sRGB to linear. Without a texture the missing
+    /// texture checkerboard is returned, or white if the checkerboard is disabled.
+    pub fn sample_texture(&self, index: i32, uv: Vec2, footprint: f32) -> Vec3 {
+        if index < 0 {
+            if self.uniforms.checkerboard_enabled == 0 {
+                return Vec3::ONE;
+            }
+            // Magenta/black checkerboard, box filtered over the footprint
+            let n = 10.0;
+            let p = uv * n;
+            let w = Vec2::splat((footprint * n).max(1e-4));
+            let i = 2.0
+                * ((fract2((p - 0.5 * w) * 0.5) - 0.5).abs()
+                    - (fract2((p + 0.5 * w) * 0.5) - 0.5).abs())
+                / w;
+            let odd = 0.5 - 0.5 * i.x * i.y;
+            return Vec3::from(self.uniforms.checkerboard_color_1)
+                .lerp(Vec3::from(self.uniforms.checkerboard_color_2), odd);
+        }
+        self.fetch_texel(index, uv, footprint).powf(2.2)
+    }
+
+    /// Raw texel lookup without color conversion, for data maps such as roughness.
+    pub fn fetch_texel(&self, index: i32, uv: Vec2, footprint: f32) -> Vec3 {
+        self.texture(index)
+            .map_or(Vec3::ZERO, |texture| texture.fetch(uv, footprint))
+    }
+
+    /// Opacity of an alpha cutout map at full resolution.
+    pub fn fetch_opacity(&self, index: i32, uv: Vec2) -> f32 {
+        self.texture(index)
+            .map_or(1.0, |texture| texture.opacity(uv))
+    }
+
+    pub fn texture(&self, index: i32) -> Option<&Texture> {
+        usize::try_from(index)
+            .ok()
+            .and_then(|i| self.textures.get(i))
+    }
+}
+
+/// Applies a change to the scene data, deleted data is reset to its default.
+fn apply<T: Default>(field: &mut T, change: Change<T>) {
+    match change {
+        Change::Keep => {}
+        Change::Create(value) | Change::Update(value) => *field = value,
+        Change::Delete => *field = T::default(),
+    }
+}
+
+/// Fractional part, `v - floor(v)` like in the shaders.
+fn fract2(v: Vec2) -> Vec2 {
+    v - v.floor()
+}
@@ -0,0 +1,188 @@
+//! Texture lookups with mip mapping.
+//!
+//! Textures are box filtered into the same mip chains as on the GPU and sampled the same
+//! way: bilinear within a level, trilinear between the two levels closest to the footprint.
+
+use engine_config::{TextureData, WrapMode};
+use glam::{Vec2, Vec3, Vec4};
+
+/// One level of a mip chain.
+struct Level {
+    width: usize,
+    height: usize,
+    texels: Vec<Vec4>,
+}
+
+/// A texture with its mip chain, texels are unpacked to floats in [0, 1].
+pub struct Texture {
+    levels: Vec<Level>,
+    wrap_mode: WrapMode,
+    /// Opacity maps read the alpha channel if set, the red channel otherwise.
+    has_alpha: bool,
+}
+
+impl Texture {
+    /// Unpacks the mip chain of a texture, the same levels as on the GPU, see
+    /// [`TextureData::mip_chain`].
+    ///
+    /// Textures without texels are black.
+    pub fn new(data: &TextureData) -> Self {
+        let (width, height) = (data.width as usize, data.height as usize);
+        if width == 0 || height == 0 || data.rgba_data.len() < width * height {
+            return Self {
+                levels: vec![Level {
+                    width: 1,
+                    height: 1,
+                    texels: vec![Vec4::ZERO],
+                }],
+                wrap_mode: data.wrap_mode,
+                has_alpha: false,
+            };
+        }
+
+        let mut size = (width, height);
+        let mut levels = Vec::new();
+        for texels in data.mip_chain() {
+            levels.push(unpack_level(size.0, size.1, &texels[..size.0 * size.1]));
+            size = ((size.0 / 2).max(1), (size.1 / 2).max(1));
+        }
+
+        Self {
+            levels,
+            wrap_mode: data.wrap_mode,
+            has_alpha: data.rgba_data.iter().any(|p| p >> 24 != 255),
+        }
+    }
+
+    /// Returns the width and height of the full resolution level.
+    pub fn size(&self) -> (usize, usize) {
+        (self.levels[0].width, self.levels[0].height)
+    }
+
+    /// Raw texel lookup without color conversion, filtered over a footprint of `footprint`
+    /// texture space units.
+    pub fn fetch(&self, uv: Vec2, footprint: f32) -> Vec3 {
+        let (width, height) = self.size();
+        let texels = footprint * width.max(height) as f32;
+        let lod = texels
+            .max(1e-8)
+            .log2()
+            .clamp(0.0, (self.levels.len() - 1) as f32);
+        let level = lod as usize;
+
+        let fine = self.sample_level(level, uv).truncate();
+        if level + 1 >= self.levels.len() {
+            return fine;
+        }
+        fine.lerp(self.sample_level(level + 1, uv).truncate(), lod.fract())
+    }
+
+    /// Opacity of an alpha cutout map, looked up at full resolution.
+    pub fn opacity(&self, uv: Vec2) -> f32 {
+        let texel = self.sample_level(0, uv);
+        if self.has_alpha { texel.w } else { texel.x }
+    }
+
+    /// Bilinear lookup in one mip level.
+    fn sample_level(&self, level: usize, uv: Vec2) -> Vec4 {
+        let level = &self.levels[level];
+        let (width, height) = (level.width as i32, level.height as i32);
+
+        // Texel centers are at +0.5, V is flipped because the image data starts at the top
+        let p = Vec2::new(uv.x * width as f32, (1.0 - uv.y) * height as f32) - 0.5;
+        let base = p.floor();
+        let f = p - base;
+
+        let x0 = wrap_texel(base.x as i32, width, self.wrap_mode);
+        let x1 = wrap_texel(base.x as i32 + 1, width, self.wrap_mode);
+        let y0 = wrap_texel(base.y as i32, height, self.wrap_mode);
+        let y1 = wrap_texel(base.y as i32 + 1, height, self.wrap_mode);
+        let texel = |x: usize, y: usize| level.texels[y * level.width + x];
+
+        let top = texel(x0, y0).lerp(texel(x1, y0), f.x);
+        let bottom = texel(x0, y1).lerp(texel(x1, y1), f.x);
+        top.lerp(bottom, f.y)
+    }
+}
+
+fn unpack_level(width: usize, height: usize, texels: &[u32]) -> Level {
+    Level {
+        width,
+        height,
+        texels: texels.iter().map(|t| unpack_texel(*t)).collect(),
+    }
+}
+
+/// Unpacks an RGBA8 texel, red is stored in the lowest byte.
+fn unpack_texel(pixel: u32) -> Vec4 {
+    let [r, g, b, a] = pixel.to_le_bytes();
+    Vec4::new(r as f32, g as f32, b as f32, a as f32) / 255.0
+}
+
+/// Maps a texel coordinate into [0, size) for the wrap mode.
+fn wrap_texel(i: i32, size: i32, wrap_mode: WrapMode) -> usize {
+    let wrapped = match wrap_mode {
+        WrapMode::Clamp => i.clamp(0, size - 1),
+        WrapMode::Mirror => {
+            let m = i.rem_euclid(2 * size);
+            if m >= size { 2 * size - 1 - m } else { m }
+        }
+        WrapMode::Repeat => i.rem_euclid(size),
+    };
+    wrapped as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use engine_config::ColorSpace;
+
+    const BLACK: u32 = 0xff00_0000;
+    const WHITE: u32 = 0xffff_ffff;
+    const RED: u32 = 0xff00_00ff;
+
+    fn texture(width: u32, height: u32, rgba_data: Vec<u32>, wrap_mode: WrapMode) -> Texture {
+        let data = TextureData::new(width, height, rgba_data)
+            .with_wrap_mode(wrap_mode)
+            .with_color_space(ColorSpace::Linear);
+        Texture::new(&data)
+    }
+
+    #[test]
+    fn texel_coordinates_wrap_by_mode() {
+        let wrapped = |mode| [-5, -1, 0, 3, 4, 5, 9].map(|i| wrap_texel(i, 4, mode));
+        assert_eq!(wrapped(WrapMode::Repeat), [3, 3, 0, 3, 0, 1, 1]);
+        assert_eq!(wrapped(WrapMode::Clamp), [0, 0, 0, 3, 3, 3, 3]);
+        assert_eq!(wrapped(WrapMode::Mirror), [3, 0, 0, 3, 3, 2, 1]);
+    }
+
+    #[test]
+    fn first_row_of_the_image_is_the_top() {
+        let red_top_left = texture(2, 2, vec![RED, BLACK, BLACK, BLACK], WrapMode::Clamp);
+        assert_eq!(red_top_left.fetch(Vec2::new(0.25, 0.75), 0.0), Vec3::X);
+        assert_eq!(red_top_left.fetch(Vec2::new(0.25, 0.25), 0.0), Vec3::ZERO);
+        assert_eq!(red_top_left.opacity(Vec2::new(0.25, 0.75)), 1.0);
+        assert_eq!(red_top_left.opacity(Vec2::new(0.75, 0.75)), 0.0);
+    }
+
+    #[test]
+    fn footprint_selects_the_mip_level() {
+        let checker = texture(2, 2, vec![BLACK, WHITE, WHITE, BLACK], WrapMode::Repeat);
+        let uv = Vec2::new(0.25, 0.75);
+        assert_eq!(checker.fetch(uv, 0.0), Vec3::ZERO);
+        // The 1x1 level is the RGBA8 average, 128
+        let grey = Vec3::splat(128.0 / 255.0);
+        assert_eq!(checker.fetch(uv, 1.0), grey);
+        assert_eq!(checker.fetch(uv, 100.0), grey);
+        // Halfway between the levels in log2 of the footprint
+        let between = checker.fetch(uv, f32::sqrt(2.0) / 2.0);
+        assert!(between.abs_diff_eq(grey * 0.5, 1e-5), "{between}");
+    }
+
+    #[test]
+    fn textures_without_texels_are_black() {
+        let empty = texture(4, 4, vec![], WrapMode::Repeat);
+        assert_eq!(empty.size(), (1, 1));
+        assert_eq!(empty.fetch(Vec2::splat(0.5), 0.0), Vec3::ZERO);
+    }
+}
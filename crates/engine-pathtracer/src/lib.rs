@@ -6,7 +6,7 @@
 //! ## Features
 //!
 //! - **GPU Acceleration**: Utilizes `wgpu` for hardware-accelerated ray tracing via compute shaders.
-//! - **Progressive Rendering**: Supports progressive rendering through [`RaytracerFrameIterator`], allowing for interactive updates and improved image quality over time.
+//! - **Progressive Rendering**: Supports progressive rendering through [`GpuFrameIterator`], allowing for interactive updates and improved image quality over time.
 //! - **Scene Support**: Handles complex scenes with:
 //!   - Spheres and Triangle Meshes (accelerated via BVH).
 //!   - Point Lights and Global Illumination.
@@ -14,9 +14,11 @@
 //!
 //! ## Architecture
 //!
-//! This crate implements the [`Renderer`] trait from `engine_config`. It encapsulates a
-//! [`GpuWrapper`] which manages the underlying GPU resources (buffers, bind groups, pipelines).
-//! The core ray tracing logic resides in the associated WGSL shader (`shader.wgsl`).
+//! This crate implements the [`Renderer`] trait from `engine_config` through the [`GpuEngine`] of
+//! `engine-wgpu-wrapper`, which manages the underlying GPU resources (buffers, bind groups,
+//! pipelines) and the progressive passes.
+//! The core ray tracing logic resides in the associated WGSL shader (`shader.wgsl`), which is
+//! appended to the shared WGSL of `engine-wgpu-wrapper` (`common.wgsl`).
 //!
 //! Path Tracer Module
 #![doc = include_str!("shader_docs.md")]
@@ -24,82 +26,35 @@
 use anyhow::Result;
 pub use engine_config::RenderConfig;
 use engine_config::Renderer;
-use engine_wgpu_wrapper::{GpuWrapper};
-use frame_buffer::frame_iterator::{FrameIterator, Frame};
-use std::time::Instant;
-use chrono::Local;
+pub use engine_wgpu_wrapper::{GpuEngine, GpuFrameIterator};
+use frame_buffer::frame_iterator::{Frame, FrameIterator};
 
-use std::sync::{Arc, Mutex};
-
-/// A wgpu-based ray tracing renderer.
+/// A wgpu-based path tracing renderer.
 ///
-/// The `Engine` struct serves as the main entry point for the ray tracing backend.
-/// It holds a thread-safe reference to the [`GpuWrapper`] and implements the [`Renderer`] trait
+/// The `Engine` struct serves as the main entry point for the path tracing backend.
+/// It runs the path tracing shader on a [`GpuEngine`] and implements the [`Renderer`] trait
 /// to integrate with the rest of the application.
 pub struct Engine {
-    /// Shared access to the GPU wrapper, managing device, queue, and resources.
-    gpu_wrapper: Arc<Mutex<GpuWrapper>>,
+    /// The GPU engine running this crate's shader.
+    gpu_engine: GpuEngine,
 }
 
 impl Renderer for Engine {
-    /// Renders a scene synchronously.
-    ///
-    /// This method blocks until the entire rendering process (all passes) is complete.
-    /// It updates the GPU resources with the provided configuration, dispatches the compute shader,
-    /// and returns the final frame.
-    ///
-    /// # Arguments
-    ///
-    /// * `rc` - The render configuration containing the scene description and settings.
-    ///
-    /// # Returns
-    ///
-    /// * `Result<Frame>` - The rendered frame containing pixel data, or an error if rendering fails.
+    /// Renders a scene synchronously, see [`GpuEngine`].
     fn render(&mut self, rc: RenderConfig) -> Result<Frame> {
-        let mut gpu_wrapper = self.gpu_wrapper.lock().unwrap();
-
-        gpu_wrapper.update(rc)?;
-        gpu_wrapper.update_uniforms();
-        gpu_wrapper.dispatch_compute()?;
-        let pixels = gpu_wrapper.read_pixels()?;
-
-        Ok(Frame::new(
-            gpu_wrapper.get_width() as usize,
-            gpu_wrapper.get_height() as usize,
-            pixels,
-        ))
+        self.gpu_engine.render(rc)
     }
 
-    /// Creates a frame iterator for progressive rendering.
-    ///
-    /// This method prepares the GPU for a new rendering session and returns an iterator
-    /// that yields frames progressively. This is useful for interactive viewing where
-    /// intermediate results are displayed while the image converges.
-    ///
-    /// # Arguments
-    ///
-    /// * `rc` - The render configuration containing the scene description and settings.
-    ///
-    /// # Returns
-    ///
-    /// * `Result<Box<dyn FrameIterator>>` - A boxed iterator yielding frames, or an error if initialization fails.
+    /// Creates a [`GpuFrameIterator`] for progressive rendering, see [`GpuEngine`].
     fn frame_iterator(&mut self, rc: RenderConfig) -> Result<Box<dyn FrameIterator>> {
-        {
-            let mut gpu_wrapper = self.gpu_wrapper.lock().unwrap();
-            gpu_wrapper.update(rc)?;
-            gpu_wrapper.update_uniforms();
-            gpu_wrapper.prh_mut().current_pass = 0;
-        }
-        Ok(Box::new(RaytracerFrameIterator::new(Arc::clone(
-            &self.gpu_wrapper,
-        ))))
+        self.gpu_engine.frame_iterator(rc)
     }
 }
 
 impl Engine {
     /// Creates a new `Engine` instance.
     ///
-    /// Initializes the underlying `GpuWrapper` with the ray tracing compute shader.
+    /// Initializes the underlying [`GpuEngine`] with the path tracing compute shader.
     ///
     /// # Arguments
     ///
@@ -107,128 +62,116 @@ impl Engine {
     ///
     /// # Returns
     ///
-    /// * `Self` - A new instance of the ray tracing engine.
+    /// * `Self` - A new instance of the path tracing engine.
     pub fn new(rc: RenderConfig) -> Self {
         // Embed the shader source code into the binary at compile time.
         // This ensures the shader is available regardless of the execution environment.
         let shader_source = include_str!("shader.wgsl");
-        let wrapper = GpuWrapper::new(rc, shader_source).unwrap();
         Self {
-            gpu_wrapper: Arc::new(Mutex::new(wrapper)),
+            gpu_engine: GpuEngine::new(rc, shader_source, "ENGINE-PATHTRACER").unwrap(),
         }
     }
 }
 
-/// A frame iterator for progressive ray tracing.
-///
-/// This struct manages the state of a progressive rendering session. It handles
-/// multi-pass rendering where each `next()` call computes a portion of the total samples,
-/// accumulating results to reduce noise over time.
-pub struct RaytracerFrameIterator {
-    /// Shared access to the GPU wrapper.
-    gpu_wrapper: Arc<Mutex<GpuWrapper>>,
-    /// Flag indicating if the rendering session has been initialized (e.g., accumulation buffer cleared).
-    initialized: bool,
-    /// Timer to track the duration of the rendering process.
-    render_time: Option<Instant>,
-}
-
-impl RaytracerFrameIterator {
-    /// Creates a new `RaytracerFrameIterator`.
-    ///
-    /// # Arguments
-    ///
-    /// * `gpu_wrapper` - Shared access to the GPU wrapper.
-    fn new(gpu_wrapper: Arc<Mutex<GpuWrapper>>) -> Self {
-        Self {
-            gpu_wrapper,
-            initialized: false,
-            render_time: None,
-        }
-    }
-}
-
-impl FrameIterator for RaytracerFrameIterator {
-    /// Checks if there are more passes to render.
-    fn has_next(&self) -> bool {
-        let wrapper = self.gpu_wrapper.lock().unwrap();
-        wrapper.prh().current_pass < wrapper.prh().total_passes
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use engine_config::{Material, PointLight, Sphere, ToneMapping, Uniforms, Vec3};
+    use engine_wgpu_wrapper::shader_with_common;
+    use frame_buffer::frame_iterator::RADIANCE_LAYER;
+    use wgpu::naga;
+
+    /// A glossy and a metal sphere on the checkered ground, lit by a soft sun and the sky
+    fn comparison_config() -> RenderConfig {
+        let uniforms = Uniforms {
+            width: 32,
+            height: 24,
+            total_samples: 16,
+            color_hash_enabled: 0,
+            sky_color: [0.3, 0.4, 0.6],
+            tone_mapping: ToneMapping::None.into(),
+            radiance_layer: 1,
+            ..Default::default()
+        };
+        let plastic = Material {
+            diffuse: Vec3::new(0.8, 0.2, 0.1),
+            roughness: 0.3,
+            ..Default::default()
+        };
+        let metal = Material {
+            diffuse: Vec3::new(0.9, 0.8, 0.5),
+            metallic: 1.0,
+            roughness: 0.2,
+            ..Default::default()
+        };
+        let spheres = vec![
+            Sphere::new(Vec3::new(-1.2, 1.0, 6.0), 1.0, plastic).unwrap(),
+            Sphere::new(Vec3::new(1.2, 1.0, 6.0), 1.0, metal).unwrap(),
+        ];
+        let sun = PointLight::directional([0.4, -0.8, 0.4], 2.0, 3.0, [1.0, 0.95, 0.9]);
+        RenderConfig::builder()
+            .uniforms_create(uniforms)
+            .spheres_create(spheres)
+            .uvs_create(vec![])
+            .meshes_create(vec![])
+            .lights_create(vec![sun])
+            .textures_create(vec![])
+            .build()
     }
 
-    /// Computes the next frame in the progressive sequence.
-    ///
-    /// This method:
-    /// 1. Initializes the accumulation buffer on the first call.
-    /// 2. Updates the progressive render helper uniforms.
-    /// 3. Dispatches the compute shader for the current pass.
-    /// 4. Reads back the result and increments the pass counter.
-    ///
-    /// # Returns
-    ///
-    /// * `Result<Frame>` - The current accumulated frame.
-    fn next(&mut self) -> Result<Frame> {
-        if !self.has_next() {
-            // Stop render time
-            if let Some(timer) = self.render_time {
-                let duration = timer.elapsed();
-                log::info!("Render finished in {:?}", duration);
-            }
-            anyhow::bail!("No more frames available");
-        }
-
-        let mut gpu_wrapper = self.gpu_wrapper.lock().unwrap();
-
-        if !self.initialized {
-            // Start render time
-            self.render_time = Some(Instant::now());
-            log::info!("Render started at {}", Local::now());
-
-            gpu_wrapper.queue().write_buffer(
-                &gpu_wrapper.buffer_wrapper().accumulation,
-                0,
-                &vec![0u8; (gpu_wrapper.get_width() * gpu_wrapper.get_height() * 16) as usize],
-            );
-            self.initialized = true;
-        }
-
-        gpu_wrapper.queue().write_buffer(
-            &gpu_wrapper.buffer_wrapper().progressive_render,
-            0,
-            bytemuck::cast_slice(&[*gpu_wrapper.prh()]),
+    /// Renders the same scene with the shader and with the CPU port, which draw the same samples
+    /// per pixel. Floating point differences let a few paths diverge, so the images are compared
+    /// by their mean and by the share of pixels that differ visibly.
+    ///
+    /// Run with `cargo test -p engine-pathtracer --lib -- --ignored`.
+    #[test]
+    #[ignore = "needs a GPU adapter, compiling the shader takes minutes on software rasterizers"]
+    fn shader_matches_the_cpu_reference() {
+        let radiance = |frame: Frame| frame.layer(RADIANCE_LAYER).unwrap().data.clone();
+        let gpu = radiance(
+            Engine::new(comparison_config())
+                .render(comparison_config())
+                .unwrap(),
         );
-
-        gpu_wrapper.dispatch_compute_progressive(
-            gpu_wrapper.prh().current_pass,
-            gpu_wrapper.prh().total_passes,
-        )?;
-
-        let pixels = gpu_wrapper.read_pixels()?;
-
-        let frame = Frame::new(
-            gpu_wrapper.get_width() as usize,
-            gpu_wrapper.get_height() as usize,
-            pixels,
+        let cpu = radiance(
+            engine_cpu::Engine::new(comparison_config())
+                .render(comparison_config())
+                .unwrap(),
         );
+        assert_eq!(gpu.len(), cpu.len());
 
-        gpu_wrapper.prh_mut().current_pass += 1;
-
-        log::info!(
-            "[ENGINE-RAYTRACER] Sample: {} / {}",
-            gpu_wrapper.prh().current_pass,
-            gpu_wrapper.prh().total_passes,
+        let mean = |data: &[f32]| data.iter().sum::<f32>() / data.len() as f32;
+        let (gpu_mean, cpu_mean) = (mean(&gpu), mean(&cpu));
+        assert!(
+            (gpu_mean - cpu_mean).abs() < 0.005 * cpu_mean,
+            "mean radiance: GPU {gpu_mean}, CPU {cpu_mean}"
         );
 
-        if gpu_wrapper.prh().current_pass == gpu_wrapper.prh().total_passes
-            && let Some(timer) = self.render_time
-        {
-            let duration = timer.elapsed();
-            log::info!("[ENGINE-RAYTRACER] Render finished in {:?}", duration);
-        }
-        Ok(frame)
+        // Compared after the square root encoding of the display, like the eye would
+        let differing = gpu
+            .chunks_exact(4)
+            .zip(cpu.chunks_exact(4))
+            .filter(|(g, c)| {
+                (0..4).any(|i| (g[i].max(0.0).sqrt() - c[i].max(0.0).sqrt()).abs() > 0.02)
+            })
+            .count();
+        let pixels = gpu.len() / 4;
+        assert!(
+            differing * 100 <= pixels,
+            "{differing} of {pixels} pixels differ"
+        );
     }
 
-    /// Cancels the current rendering iterator.
-    fn destroy(&mut self) {
-        log::info!("[ENGINE-RAYTRACER] Cancelled Render Iterator.")
+    #[test]
+    fn shader_validates_after_the_common_wgsl() {
+        let source = shader_with_common(include_str!("shader.wgsl"));
+        let module = naga::front::wgsl::parse_str(&source)
+            .unwrap_or_else(|e| panic!("{}", e.emit_to_string(&source)));
+        naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::default(),
+        )
+        .validate(&module)
+        .unwrap();
     }
 }
@@ -1,723 +1,877 @@
-struct Camera {
-    pane_distance: f32,
-    pane_width: f32,
-    _pad0: vec2<f32>,
-    pos: vec3<f32>,
-    _pad1: f32,
-    dir: vec3<f32>,
-    _pad2: f32,
-};
-
-struct ProgressiveRenderHelper {
-    total_passes: u32,
-    current_pass: u32,
-    total_samples: u32,
-    samples_per_pass: u32,
-}
-
-struct Uniforms {
-    width: u32,
-    height: u32,
-    total_passes: u32,
-    color_hash_enabled: u32,
-    camera: Camera,
-    spheres_count: u32,
-    triangles_count: u32,
-    bvh_node_count: u32,
-    bvh_triangle_count: u32,
-    bvh_root: u32,
-    ground_height: f32,
-    ground_enabled: u32,
-    checkerboard_enabled: u32,
-    sky_color: vec3<f32>,
-    max_depth: u32,
-    checkerboard_color_1: vec3<f32>,
-    _pad1: u32,
-    checkerboard_color_2: vec3<f32>,
-    _pad2: u32,
-};
-
-struct Sphere {
-    center: vec3<f32>,
-    radius: f32,
-    material: Material,
-};
+// Appended to common.wgsl of engine-wgpu-wrapper, which declares the buffers, the scene
+// intersection, the sampler and the camera rays used below, and the entry point `main` that
+// calls `trace_ray`.
 
-struct Mesh {
-    triangle_index_start: u32,
-    triangle_count: u32,
-    _pad: vec2<u32>,
-    material: Material,
-}
-
-struct Material {
-    ambient: vec3<f32>,
-    _pad0: f32,
-    diffuse: vec3<f32>,
-    _pad1: f32,
-    specular: vec3<f32>,
-    shininess: f32,
-    emissive: vec3<f32>,
-    ior: f32,
-    opacity: f32,
-    illum: u32,
-    texture_index: i32,
-    _pad2: u32,
-}
-
-struct HitRecord {
-    hit: bool,
-    t: f32,
-    pos: vec3<f32>,
-    normal: vec3<f32>,
-    uv: vec2<f32>,
-    use_texture: bool,
-    material: Material,
+// Continues with the dimensions of a bounce, bounce 0 is the camera
+fn sampler_start_bounce(rng: ptr<function, Sampler>, bounce: u32) {
+    (*rng).dimension = bounce << 16u;
 }
 
-struct PointLight {
-    center: vec3<f32>,
-    radius: f32,
-    material: Material,
-};
-
-struct GPUTriangle {
-    v0: vec3<f32>,
-    v0_index: u32,
-    v1: vec3<f32>,
-    v1_index: u32,
-    v2: vec3<f32>,
-    v2_index: u32,
-    mesh_index: u32,
-    _pad0: u32,
-    _pad1: u32,
-    _pad2: u32,
-};
+// Uniform direction on the unit sphere
+fn random_unit_vector(rng: ptr<function, Sampler>) -> vec3<f32> {
+    let u = sample_2d(rng);
+    let z = 1.0 - 2.0 * u.x;
+    let r = sqrt(max(0.0, 1.0 - z * z));
+    let phi = 2.0 * PI * u.y;
+    return vec3<f32>(r * cos(phi), r * sin(phi), z);
+}
 
-struct BVHNode {
-    aabb_min: vec3<f32>,
-    _pad0: u32,
-    aabb_max: vec3<f32>,
-    _pad1: u32,
-    left: u32,
-    right: u32,
-    first_primitive: u32,
-    primitive_count: u32,
-};
+// Uniform direction on the hemisphere around `normal`
+fn random_in_hemisphere(normal: vec3<f32>, rng: ptr<function, Sampler>) -> vec3<f32> {
+    let in_unit_sphere = random_unit_vector(rng);
 
-struct TextureInfo {
-    offset: u32,
-    width: u32,
-    height: u32,
-    _pad: u32,
-}
-
-@group(0) @binding(0) var<uniform> uniforms: Uniforms;
-@group(0) @binding(1) var<storage, read_write> output: array<u32>;
-@group(0) @binding(2) var<storage, read> spheres: array<Sphere>;
-@group(0) @binding(3) var<storage, read_write> accumulation: array<vec4<f32>>;
-@group(0) @binding(4) var<uniform> prh: ProgressiveRenderHelper;
-@group(0) @binding(5) var<storage, read> point_lights: array<PointLight>;
-@group(0) @binding(6) var<storage, read> meshes: array<Mesh>;
-@group(0) @binding(7) var<storage, read> bvh_nodes: array<BVHNode>;
-@group(0) @binding(8) var<storage, read> bvh_indices: array<u32>;
-@group(0) @binding(9) var<storage, read> bvh_triangles: array<GPUTriangle>;
-@group(0) @binding(10) var<storage, read> uvs: array<f32>;
-@group(0) @binding(11) var<storage, read> texture_data: array<u32>;
-@group(0) @binding(12) var<storage, read> texture_info: array<TextureInfo>;
-
-fn ground_enabled() -> bool {
-    if (uniforms.ground_enabled > 0) {
-        return true;
+    if (dot(in_unit_sphere, normal) > 0.0) {
+        return in_unit_sphere;
     } else {
-        return false;
+        return -in_unit_sphere;
     }
 }
 
-fn linear_to_gamma(lin_color: f32) -> f32 {
-    if (lin_color > 0.0) {
-        return sqrt(lin_color);
-    }
-    return 0.0;
+fn near_zero(v: vec3<f32>) -> bool {
+    let s = 1e-8;
+    return (abs(v.x) < s) && (abs(v.y) < s) && (abs(v.z) < s);
 }
 
-fn color_map(color: vec3<f32>) -> u32 {
-    let r: u32 = u32(linear_to_gamma(color.x) * 255.999);
-    let g: u32 = u32(linear_to_gamma(color.y) * 255.999);
-    let b: u32 = u32(linear_to_gamma(color.z) * 255.999);
-    let a: u32 = 255u;
+// Cosine-weighted hemisphere sampling
+fn scatter_lambertian(
+    normal: vec3<f32>,
+    rng: ptr<function, Sampler>
+) -> vec3<f32> {
+    let scatter_direction = normal + random_unit_vector(rng);
+
+    if near_zero(scatter_direction) {
+        return normal;
+    }
 
-    return (a << 24u) | (b << 16u) | (g << 8u) | r;
+    return normalize(scatter_direction);
 }
 
-fn sample_texture(index: i32, uv: vec2<f32>) -> vec3<f32> {
-    if (index < 0) {
-        if (uniforms.checkerboard_enabled > 0){
-            // Missing texture pattern (Magenta/Black checkerboard)
-            let n = 10.0;
-            let u2 = i32(floor(uv.x * n));
-            let v2 = i32(floor(uv.y * n));
-            if ((u2 + v2) % 2 == 0) {
-                return uniforms.checkerboard_color_1;
-            } else {
-                return uniforms.checkerboard_color_2;
-            }
-        } else {
-            return vec3(1.0, 1.0, 1.0);
-        }
-        
-    }
-    let info = texture_info[u32(index)];
+// GGX / Trowbridge-Reitz normal distribution
+fn ggx_d(n_dot_h: f32, alpha: f32) -> f32 {
+    let a2 = alpha * alpha;
+    let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    return a2 / (PI * d * d);
+}
 
-    // Wrap UVs (repeat)
-    let u = fract(uv.x);
-    let v = fract(uv.y);
+// Smith masking function for GGX
+fn smith_g1(n_dot_v: f32, alpha: f32) -> f32 {
+    let a2 = alpha * alpha;
+    return 2.0 * n_dot_v / (n_dot_v + sqrt(a2 + (1.0 - a2) * n_dot_v * n_dot_v));
+}
 
-    // Map to pixel coordinates
-    // Flip V because standard UVs have (0,0) at bottom-left, but image data is top-left
-    let x = min(u32(u * f32(info.width)), info.width - 1u);
-    let y = min(u32((1.0 - v) * f32(info.height)), info.height - 1u);
+// Solid angle pdf of a reflected direction when sampling visible GGX normals
+fn ggx_vndf_pdf(n_dot_v: f32, n_dot_h: f32, alpha: f32) -> f32 {
+    return smith_g1(n_dot_v, alpha) * ggx_d(n_dot_h, alpha) / (4.0 * n_dot_v);
+}
 
-    let pixel_index = info.offset + y * info.width + x;
-    let pixel = texture_data[pixel_index];
+// Samples a visible GGX normal in tangent space, `v` is the outgoing direction (Heitz 2018)
+fn sample_ggx_vndf(v: vec3<f32>, alpha: f32, u1: f32, u2: f32) -> vec3<f32> {
+    let vh = normalize(vec3<f32>(alpha * v.x, alpha * v.y, v.z));
+    let lensq = vh.x * vh.x + vh.y * vh.y;
+    var t1 = vec3<f32>(1.0, 0.0, 0.0);
+    if (lensq > 0.0) {
+        t1 = vec3<f32>(-vh.y, vh.x, 0.0) / sqrt(lensq);
+    }
+    let t2 = cross(vh, t1);
 
-    // Unpack RGBA8 (Little Endian: A B G R)
-    let r = f32(pixel & 255u) / 255.0;
-    let g = f32((pixel >> 8u) & 255u) / 255.0;
-    let b = f32((pixel >> 16u) & 255u) / 255.0;
+    let r = sqrt(u1);
+    let phi = 2.0 * PI * u2;
+    let p1 = r * cos(phi);
+    let s = 0.5 * (1.0 + vh.z);
+    let p2 = (1.0 - s) * sqrt(max(0.0, 1.0 - p1 * p1)) + s * r * sin(phi);
 
-    // Convert sRGB to Linear
-    return vec3<f32>(pow(r, 2.2), pow(g, 2.2), pow(b, 2.2));
+    let nh = p1 * t1 + p2 * t2 + sqrt(max(0.0, 1.0 - p1 * p1 - p2 * p2)) * vh;
+    return normalize(vec3<f32>(alpha * nh.x, alpha * nh.y, max(1e-6, nh.z)));
 }
 
-fn intersect_sphere(ray_origin: vec3<f32>, ray_dir: vec3<f32>, sphere: Sphere) -> f32 {
-    let oc = ray_origin - sphere.center;
-    let a = dot(ray_dir, ray_dir);
-    let half_b = dot(oc, ray_dir);
-    let c = dot(oc, oc) - sphere.radius * sphere.radius;
-    let discriminant = half_b * half_b - a * c;
+// Probabilities of picking the diffuse, specular and clearcoat lobe, they only depend on `wo`
+fn principled_lobe_probabilities(p: Principled, n_dot_v: f32) -> vec3<f32> {
+    let f0 = specular_f0(p);
+    let w_diffuse = (1.0 - p.metallic) * (luminance(p.base_color) + p.sheen);
+    var w_specular = 0.0;
+    if (max(max(f0.x, f0.y), f0.z) > 0.0) {
+        w_specular = luminance(fresnel_schlick(f0, n_dot_v));
+    }
+    let w_clearcoat = 0.25 * p.clearcoat * fresnel_schlick(vec3<f32>(0.04), n_dot_v).x;
+    let total = w_diffuse + w_specular + w_clearcoat;
+    if (total <= 0.0) {
+        return vec3<f32>(1.0, 0.0, 0.0);
+    }
+    return vec3<f32>(w_diffuse, w_specular, w_clearcoat) / total;
+}
 
-    if discriminant < 0.0 {
-        return -1.0;
+// Evaluates the principled BSDF. Returns f * cos(theta_i) in xyz and the sampling pdf in w.
+// `n` has to face `wo`, both `wo` and `wi` point away from the surface.
+fn principled_eval(p: Principled, n: vec3<f32>, wo: vec3<f32>, wi: vec3<f32>) -> vec4<f32> {
+    let n_dot_l = dot(n, wi);
+    let n_dot_v = dot(n, wo);
+    if (n_dot_l <= 0.0 || n_dot_v <= 0.0) {
+        return vec4<f32>(0.0);
     }
 
-    let sqrtd = sqrt(discriminant);
-    var root = (-half_b - sqrtd) / a;
+    let h = normalize(wo + wi);
+    let n_dot_h = max(dot(n, h), 0.0);
+    let l_dot_h = clamp(dot(wi, h), 0.0, 1.0);
+    let probs = principled_lobe_probabilities(p, n_dot_v);
+
+    // Diffuse with sheen at grazing angles
+    let diffuse_weight = 1.0 - p.metallic;
+    var f = diffuse_weight * (p.base_color / PI + vec3<f32>(p.sheen * pow(1.0 - l_dot_h, 5.0)));
+    var pdf = probs.x * n_dot_l / PI;
+
+    // Specular, a black F0 disables the lobe
+    let f0 = specular_f0(p);
+    if (probs.y > 0.0) {
+        let d = ggx_d(n_dot_h, p.alpha);
+        let g = smith_g1(n_dot_l, p.alpha) * smith_g1(n_dot_v, p.alpha);
+        f += fresnel_schlick(f0, l_dot_h) * d * g / (4.0 * n_dot_l * n_dot_v);
+        pdf += probs.y * ggx_vndf_pdf(n_dot_v, n_dot_h, p.alpha);
+    }
 
-    if root <= 0.001 {
-        root = (-half_b + sqrtd) / a;
-        if root <= 0.001 {
-            return -1.0;
-        }
+    // Clearcoat, a colorless specular layer with a fixed IOR of 1.5
+    if (probs.z > 0.0) {
+        let d = ggx_d(n_dot_h, p.clearcoat_alpha);
+        let g = smith_g1(n_dot_l, p.clearcoat_alpha) * smith_g1(n_dot_v, p.clearcoat_alpha);
+        let fc = fresnel_schlick(vec3<f32>(0.04), l_dot_h).x;
+        f += vec3<f32>(0.25 * p.clearcoat * fc * d * g / (4.0 * n_dot_l * n_dot_v));
+        pdf += probs.z * ggx_vndf_pdf(n_dot_v, n_dot_h, p.clearcoat_alpha);
     }
 
-    return root;
+    return vec4<f32>(f * n_dot_l, pdf);
 }
 
-fn intersect_pointlight(ray_origin: vec3<f32>, ray_dir: vec3<f32>, pointlight: PointLight) -> f32 {
-    let oc = ray_origin - pointlight.center;
-    let a = dot(ray_dir, ray_dir);
-    let half_b = dot(oc, ray_dir);
-    let c = dot(oc, oc) - pointlight.radius * pointlight.radius;
-    let discriminant = half_b * half_b - a * c;
-
-    if discriminant < 0.0 {
-        return -1.0;
+// Samples an incoming direction by picking one lobe. The result may point below the surface.
+fn principled_sample(
+    p: Principled,
+    n: vec3<f32>,
+    wo: vec3<f32>,
+    rng: ptr<function, Sampler>
+) -> vec3<f32> {
+    let probs = principled_lobe_probabilities(p, dot(n, wo));
+    let r = sample_1d(rng);
+    if (r < probs.x) {
+        return scatter_lambertian(n, rng);
     }
 
-    let sqrtd = sqrt(discriminant);
-    var root = (-half_b - sqrtd) / a;
-
-    if root <= 0.001 {
-        root = (-half_b + sqrtd) / a;
-        if root <= 0.001 {
-            return -1.0;
-        }
+    var alpha = p.clearcoat_alpha;
+    if (r < probs.x + probs.y) {
+        alpha = p.alpha;
     }
-
-    return root;
+    let onb = build_onb(n);
+    let wo_local = transpose(onb) * wo;
+    let u = sample_2d(rng);
+    let h = onb * sample_ggx_vndf(wo_local, alpha, u.x, u.y);
+    return reflect_vector(-wo, h);
 }
 
-struct TriangleData {
-    v0: vec3<f32>,
-    v1: vec3<f32>,
-    v2: vec3<f32>,
-    _pad: u32,
-};
+// Picks reflection or refraction weighted by the Fresnel term.
+// `normal` has to face against `ray_dir`, `eta` is the ratio of the incident to the transmitted IOR.
+fn scatter_dielectric(
+    ray_dir: vec3<f32>,
+    normal: vec3<f32>,
+    eta: f32,
+    rng: ptr<function, Sampler>
+) -> vec3<f32> {
+    let unit_dir = normalize(ray_dir);
+    let cos_theta = min(dot(-unit_dir, normal), 1.0);
+    let sin2_theta = max(0.0, 1.0 - cos_theta * cos_theta);
+    let sin2_transmitted = eta * eta * sin2_theta;
+
+    // Total internal reflection
+    if (sin2_transmitted > 1.0) {
+        return reflect_vector(unit_dir, normal);
+    }
 
-fn intersect_triangle(ray_origin: vec3<f32>, ray_dir: vec3<f32>, tri: TriangleData) -> vec3<f32> {
-    let edge1 = tri.v1 - tri.v0;
-    let edge2 = tri.v2 - tri.v0;
-    let h = cross(ray_dir, edge2);
-    let a = dot(edge1, h);
+    // Schlick is only valid for the angle on the optically thinner side
+    var cos_fresnel = cos_theta;
+    if (eta > 1.0) {
+        cos_fresnel = sqrt(1.0 - sin2_transmitted);
+    }
 
-    if abs(a) < 1e-6 {
-        return vec3<f32>(-1.0, 0.0, 0.0);
+    if (reflectance(cos_fresnel, eta) > sample_1d(rng)) {
+        return reflect_vector(unit_dir, normal);
     }
+    return refract(unit_dir, normal, eta);
+}
 
-    let f = 1.0 / a;
-    let s = ray_origin - tri.v0;
-    let u = f * dot(s, h);
+// Samples a new propagation direction from the Henyey-Greenstein phase function
+fn sample_hg(direction: vec3<f32>, g: f32, u: vec2<f32>) -> vec3<f32> {
+    var cos_theta = 1.0 - 2.0 * u.x;
+    if (abs(g) > 1e-3) {
+        let sq = (1.0 - g * g) / (1.0 - g + 2.0 * g * u.x);
+        cos_theta = clamp((1.0 + g * g - sq * sq) / (2.0 * g), -1.0, 1.0);
+    }
+    let sin_theta = sqrt(max(0.0, 1.0 - cos_theta * cos_theta));
+    let phi = 2.0 * PI * u.y;
+    return build_onb(direction) * vec3<f32>(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta);
+}
 
-    if u < 0.0 || u > 1.0 {
-        return vec3<f32>(-1.0, 0.0, 0.0);
+// Free-flight sampling up to the next surface at `t_max`. The distance is sampled proportional
+// to the scattering coefficient of a random color channel, the weight is MIS-combined over all
+// channels (one-sample balance heuristic). Returns the path weight in xyz and the scattering
+// distance in w, w < 0 if the ray reaches the surface. Absorption alone never scatters, so a
+// purely absorbing medium reduces to Beer-Lambert attenuation.
+fn sample_free_flight(m: Medium, t_max: f32, rng: ptr<function, Sampler>) -> vec4<f32> {
+    let channel = min(u32(sample_1d(rng) * 3.0), 2u);
+    let u = sample_1d(rng);
+    var t = t_max;
+    if (m.scattering[channel] > 0.0) {
+        t = min(-log(1.0 - u) / m.scattering[channel], t_max);
     }
+    let transmittance = medium_transmittance(m, t);
+    let survival = exp(-m.scattering * t);
+    if (t < t_max) {
+        let pdf = dot(m.scattering * survival, vec3<f32>(1.0 / 3.0));
+        return vec4<f32>(m.scattering * transmittance / pdf, t);
+    }
+    let pdf = dot(survival, vec3<f32>(1.0 / 3.0));
+    return vec4<f32>(transmittance / pdf, -1.0);
+}
 
-    let q = cross(s, edge1);
-    let v = f * dot(ray_dir, q);
+// Scattering vertex for next-event estimation: a principled surface or a point inside a medium
+struct ScatterPoint {
+    pos: vec3<f32>,
+    // Surface normal on the side of `wo`, zero inside a medium
+    normal: vec3<f32>,
+    wo: vec3<f32>,
+    p: Principled,
+    // Medium shadow rays start in
+    medium: Medium,
+    in_medium: bool,
+};
 
-    if v < 0.0 || u + v > 1.0 {
-        return vec3<f32>(-1.0, 0.0, 0.0);
-    }
+fn surface_point(pos: vec3<f32>, normal: vec3<f32>, wo: vec3<f32>, p: Principled, medium: Medium) -> ScatterPoint {
+    return ScatterPoint(pos, normal, wo, p, medium, false);
+}
 
-    let t = f * dot(edge2, q);
+fn medium_point(pos: vec3<f32>, wo: vec3<f32>, medium: Medium) -> ScatterPoint {
+    let p = Principled(vec3<f32>(0.0), 0.0, 1.0, 0.0, 0.0, 0.0, 1.0);
+    return ScatterPoint(pos, vec3<f32>(0.0), wo, p, medium, true);
+}
 
-    if t > 0.0 {
-        return vec3<f32>(t, u, v);
+// BSDF or phase function value towards `wi` in xyz and its sampling pdf in w
+fn scatter_eval(s: ScatterPoint, wi: vec3<f32>) -> vec4<f32> {
+    if (s.in_medium) {
+        let phase = hg_phase(dot(-s.wo, wi), s.medium.g);
+        return vec4<f32>(vec3<f32>(phase), phase);
     }
-
-    return vec3<f32>(-1.0, 0.0, 0.0);
+    return principled_eval(s.p, s.normal, s.wo, wi);
 }
 
-fn intersect_bvh(ray_origin: vec3<f32>, ray_dir: vec3<f32>) -> HitRecord {
-    var hit = HitRecord(
-                        false,
-                        1e20,
-                        vec3<f32>(0.0),
-                        vec3<f32>(0.0),
-                        vec2<f32>(0.0),
-                        false,
-                        Material(
-                            vec3<f32>(0.0), 0.0,
-                            vec3<f32>(0.0), 0.0,
-                            vec3<f32>(0.0), 0.0,
-                            vec3<f32>(0.0), 0.0,
-                            0.0, 0u, -1, 0u
-                        )
-                    );
+// Orthonormal basis with `w` as the third column (Duff et al. 2017)
+fn build_onb(w: vec3<f32>) -> mat3x3<f32> {
+    let sign = select(-1.0, 1.0, w.z >= 0.0);
+    let a = -1.0 / (sign + w.z);
+    let b = w.x * w.y * a;
+    let t = vec3<f32>(1.0 + sign * w.x * w.x * a, sign * b, -sign * w.x);
+    let bt = vec3<f32>(b, sign + w.y * w.y * a, -w.y);
+    return mat3x3<f32>(t, bt, w);
+}
 
-    var stack: array<u32, 1024>;  //very large, might be too big for bad GPUs, might have to make this variable
-    var sp: i32 = 0;
+// 1 - cos of the half angle of the cone a sphere light subtends from `pos`, 0 if `pos` is inside
+fn light_cone_extent(pos: vec3<f32>, light: PointLight) -> f32 {
+    let to_center = light.center - pos;
+    let dist2 = dot(to_center, to_center);
+    let r2 = light.radius * light.radius;
+    if (dist2 <= r2) {
+        return 0.0;
+    }
+    let sin2_max = r2 / dist2;
+    let cos_max = sqrt(max(0.0, 1.0 - sin2_max));
+    // Equal to 1 - cos_max, but stable for small or distant lights
+    return sin2_max / (1.0 + cos_max);
+}
 
-    if (uniforms.bvh_node_count == 0u) {
-        return hit;
+// Solid angle pdf of sampling a direction in the cone towards a sphere light
+fn sphere_light_pdf(pos: vec3<f32>, light: PointLight) -> f32 {
+    let extent = light_cone_extent(pos, light);
+    if (extent <= 0.0) {
+        return 0.0;
     }
+    return 1.0 / (2.0 * PI * extent);
+}
 
-    stack[sp] = 0u;
-    sp = sp + 1;
+// Solid angle pdf of sampling `dir` (hit at distance `t`) towards a rect or disc light by area
+fn planar_light_pdf(light: PointLight, dir: vec3<f32>, t: f32) -> f32 {
+    let cos_light = abs(dot(dir, light.direction));
+    if (cos_light <= 1e-6) {
+        return 0.0;
+    }
+    return t * t / (planar_light_area(light) * cos_light);
+}
 
-    loop {
-        if sp == 0 {
-            break;
+// Solid angle pdf of next-event estimation reaching the light from `origin` along `dir`
+fn light_pdf(origin: vec3<f32>, light: PointLight, dir: vec3<f32>, t: f32) -> f32 {
+    switch (light.light_type) {
+        case LIGHT_POINT, LIGHT_SPOT: {
+            return sphere_light_pdf(origin, light);
         }
-        sp = sp - 1;
-        let node_idx = stack[sp];
+        case LIGHT_RECT, LIGHT_DISC: {
+            return planar_light_pdf(light, dir, t);
+        }
+        default: {
+            return 0.0;
+        }
+    }
+}
 
-        if (node_idx >= uniforms.bvh_node_count) {
+// Radiance of the directional lights visible along an escaping ray, MIS-weighted against the
+// previous principled bounce
+fn directional_radiance(direction: vec3<f32>, prev_bsdf_pdf: f32) -> vec3<f32> {
+    var radiance = vec3<f32>(0.0);
+    for (var k = 0u; k < arrayLength(&point_lights); k = k + 1u) {
+        let light = point_lights[k];
+        let extent = directional_extent(light);
+        if (light.light_type != LIGHT_DIRECTIONAL || extent <= 0.0) {
             continue;
         }
-
-        let node = bvh_nodes[node_idx];
-
-        if (!intersect_aabb(ray_origin, ray_dir, node.aabb_min, node.aabb_max)) {
+        if (dot(direction, -light.direction) < light.cos_outer) {
             continue;
         }
+        let pdf = 1.0 / (2.0 * PI * extent);
+        var weight = 1.0;
+        if (prev_bsdf_pdf > 0.0) {
+            weight = power_heuristic(prev_bsdf_pdf, pdf);
+        }
+        // The luminosity is the irradiance, spread over the solid angle of the sun
+        radiance += light.material.emissive * pdf * weight;
+    }
+    return radiance;
+}
 
-        if node.primitive_count > 0u {
-            for (var i: u32 = 0u; i < node.primitive_count; i = i + 1u) {
-                let index_count = arrayLength(&bvh_indices);
-
-                let tri_idx = node.first_primitive + i;
-                if (tri_idx >= index_count) {
-                    continue;
-                }
-
-                let bvh_tri_idx = bvh_indices[tri_idx];
-                if (bvh_tri_idx >= uniforms.bvh_triangle_count) {
-                    continue;
-                }
-
-                let tri = bvh_triangles[bvh_tri_idx];
-
-                let triangle_data = TriangleData(tri.v0, tri.v1, tri.v2, 0u);
-
-                let hit_data = intersect_triangle(ray_origin, ray_dir, triangle_data);
-                let t = hit_data.x;
-
-                if (t > 0.001 && t < hit.t) {
-                    hit.hit = true;
-                    hit.t = t;
-                    hit.pos = ray_origin + t * ray_dir;
-                    hit.normal = normalize(cross(tri.v1 - tri.v0, tri.v2 - tri.v0));
-
-                    let u = hit_data.y;
-                    let v = hit_data.z;
-                    let w = 1.0 - u - v;
-
-                    let uv0 = vec2<f32>(uvs[tri.v0_index * 2u], uvs[tri.v0_index * 2u + 1u]);
-                    let uv1 = vec2<f32>(uvs[tri.v1_index * 2u], uvs[tri.v1_index * 2u + 1u]);
-                    let uv2 = vec2<f32>(uvs[tri.v2_index * 2u], uvs[tri.v2_index * 2u + 1u]);
+fn environment_dir(uv: vec2<f32>) -> vec3<f32> {
+    let phi = uv.x * 2.0 * PI + uniforms.environment_rotation;
+    let theta = uv.y * PI;
+    return vec3<f32>(sin(theta) * cos(phi), cos(theta), sin(theta) * sin(phi));
+}
 
-                    hit.uv = w * uv0 + u * uv1 + v * uv2;
+// Solid angle pdf of sampling `dir` with `sample_environment`
+fn environment_pdf(dir: vec3<f32>) -> f32 {
+    let w = uniforms.environment_width;
+    let h = uniforms.environment_height;
+    let total = environment[w * h].z;
+    let sin_theta = sqrt(max(0.0, 1.0 - dir.y * dir.y));
+    if (total <= 0.0 || sin_theta <= 0.0) {
+        return 0.0;
+    }
+    let index = environment_index(dir);
+    let sin_row = sin(PI * (f32(index / w) + 0.5) / f32(h));
+    let weight = max(luminance(environment[index].xyz), 0.0) * sin_row;
+    return weight * f32(w * h) / (total * 2.0 * PI * PI * sin_theta);
+}
 
-                    if (uniforms.color_hash_enabled != 0u) {
-                        hit.material.diffuse = hash_to_color(bvh_tri_idx + 1u);
-                        hit.material.ambient = vec3<f32>(0.0);
-                        hit.material.specular = vec3<f32>(0.0);
-                        hit.use_texture = false;
-                    } else {
-                        hit.material = meshes[tri.mesh_index].material;
-                        // Use texture if material has a valid texture index
-                        hit.use_texture = hit.material.texture_index >= 0;
-                    }
-                }
-            }
+// Picks a pixel proportional to its weight through the marginal and the row CDF, then a point
+// inside it. Returns the direction and its solid angle pdf.
+fn sample_environment(rng: ptr<function, Sampler>) -> vec4<f32> {
+    let w = uniforms.environment_width;
+    let h = uniforms.environment_height;
+    let rows = w * h;
+
+    let r = sample_2d(rng);
+    let r_row = r.x;
+    var lo = 0u;
+    var hi = h - 1u;
+    while (lo < hi) {
+        let mid = (lo + hi) / 2u;
+        if (environment[rows + mid].x < r_row) {
+            lo = mid + 1u;
         } else {
-            if node.left < uniforms.bvh_node_count {
-                if (sp < 1024) {
-                    stack[sp] = node.left;
-                    sp = sp + 1;
-                }
-            }
-            if node.right < uniforms.bvh_node_count {
-                if (sp < 1024) {
-                    stack[sp] = node.right;
-                    sp = sp + 1;
-                }
-            }
+            hi = mid;
         }
     }
-
-    return hit;
+    let y = lo;
+
+    let r_col = r.y;
+    lo = 0u;
+    hi = w - 1u;
+    while (lo < hi) {
+        let mid = (lo + hi) / 2u;
+        if (environment[y * w + mid].w < r_col) {
+            lo = mid + 1u;
+        } else {
+            hi = mid;
+        }
+    }
+    let x = lo;
+
+    let jitter = sample_2d(rng);
+    let uv = vec2<f32>(
+        (f32(x) + jitter.x) / f32(w),
+        (f32(y) + jitter.y) / f32(h)
+    );
+    let dir = environment_dir(uv);
+    return vec4<f32>(dir, environment_pdf(dir));
 }
 
-fn hash_to_color(n: u32) -> vec3<f32> {
-    let h = n * 2654435761u;
-    let r = f32(h % 41u) / 40.0;
-    let g = f32(h % 29u) / 28.0;
-    let b = f32(h % 19u) / 18.0;
-    return vec3<f32>(r, g, b);
+fn power_heuristic(pdf_a: f32, pdf_b: f32) -> f32 {
+    let a2 = pdf_a * pdf_a;
+    let b2 = pdf_b * pdf_b;
+    if (a2 + b2 <= 0.0) {
+        return 0.0;
+    }
+    return a2 / (a2 + b2);
 }
 
-fn intersect_ground(ray_origin: vec3<f32>, ray_dir: vec3<f32>) -> f32 {
-    if (abs(ray_dir.y) < 1e-6) {
-        return -1.0;
-    }
+struct LightSample {
+    dir: vec3<f32>,
+    // Distance to the sampled point, 1e20 for directional lights
+    dist: f32,
+    radiance: vec3<f32>,
+    // Solid angle pdf, 0 if the sample is invalid
+    pdf: f32,
+    // Sharp directional light, can't be reached by BSDF sampling
+    delta: bool,
+}
 
-    let t = (uniforms.ground_height - ray_origin.y) / ray_dir.y;
+fn no_light_sample() -> LightSample {
+    return LightSample(vec3<f32>(0.0), 0.0, vec3<f32>(0.0), 0.0, false);
+}
 
-    if (t > 0.0) {
-        return t;
+// Uniformly sampled direction in the cone a sphere light subtends from `origin`
+fn sample_sphere_light(light: PointLight, origin: vec3<f32>, u: vec2<f32>) -> LightSample {
+    let extent = light_cone_extent(origin, light);
+    if (light.radius <= 0.0 || extent <= 0.0) {
+        return no_light_sample();
     }
-
-    return -1.0;
+    let cos_theta = 1.0 - u.x * extent;
+    let sin_theta = sqrt(max(0.0, 1.0 - cos_theta * cos_theta));
+    let phi = 2.0 * PI * u.y;
+    let onb = build_onb(normalize(light.center - origin));
+    let dir = normalize(onb * vec3<f32>(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta));
+
+    let t = intersect_pointlight(origin, dir, light);
+    if (t <= 0.0) {
+        return no_light_sample();
+    }
+    return LightSample(dir, t, light_emission(light, origin, dir), 1.0 / (2.0 * PI * extent), false);
 }
 
-// PCG random number generator
-fn hash(seed: u32) -> u32 {
-    var state = seed * 747796405u + 2891336453u;
-    var word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
-    return (word >> 22u) ^ word;
+// Uniformly sampled point on the area of a rect or disc light
+fn sample_planar_light(light: PointLight, origin: vec3<f32>, u: vec2<f32>) -> LightSample {
+    let bitangent = cross(light.direction, light.tangent);
+    var offset: vec2<f32>;
+    if (light.light_type == LIGHT_RECT) {
+        offset = (2.0 * u - 1.0) * light.half_size;
+    } else {
+        let r = light.radius * sqrt(u.x);
+        let phi = 2.0 * PI * u.y;
+        offset = r * vec2<f32>(cos(phi), sin(phi));
+    }
+    let point = light.center + offset.x * light.tangent + offset.y * bitangent;
+
+    let to_light = point - origin;
+    let dist = length(to_light);
+    if (dist <= 1e-4) {
+        return no_light_sample();
+    }
+    let dir = to_light / dist;
+    let pdf = planar_light_pdf(light, dir, dist);
+    let radiance = light_emission(light, origin, dir);
+    if (pdf <= 0.0 || max(max(radiance.x, radiance.y), radiance.z) <= 0.0) {
+        return no_light_sample();
+    }
+    return LightSample(dir, dist, radiance, pdf, false);
 }
 
-fn random_float(seed: ptr<function, u32>) -> f32 {
-    *seed = hash(*seed);
-    return f32(*seed) / 4294967296.0;
+// Uniformly sampled direction in the cone of a directional light
+fn sample_directional_light(light: PointLight, u: vec2<f32>) -> LightSample {
+    let towards = -normalize(light.direction);
+    let extent = directional_extent(light);
+    if (extent <= 0.0) {
+        return LightSample(towards, 1e20, light.material.emissive, 1.0, true);
+    }
+    let cos_theta = 1.0 - u.x * extent;
+    let sin_theta = sqrt(max(0.0, 1.0 - cos_theta * cos_theta));
+    let phi = 2.0 * PI * u.y;
+    let onb = build_onb(towards);
+    let dir = normalize(onb * vec3<f32>(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta));
+    let pdf = 1.0 / (2.0 * PI * extent);
+    return LightSample(dir, 1e20, light.material.emissive * pdf, pdf, false);
 }
 
-// Random vector in unit sphere (rejection sampling)
-fn random_in_unit_sphere(seed: ptr<function, u32>) -> vec3<f32> {
-    loop {
-        let p = vec3<f32>(
-            random_float(seed) * 2.0 - 1.0,
-            random_float(seed) * 2.0 - 1.0,
-            random_float(seed) * 2.0 - 1.0
-        );
-        if dot(p, p) < 1.0 {
-            return p;
+fn sample_light(light: PointLight, origin: vec3<f32>, u: vec2<f32>) -> LightSample {
+    switch (light.light_type) {
+        case LIGHT_POINT, LIGHT_SPOT: {
+            return sample_sphere_light(light, origin, u);
+        }
+        case LIGHT_RECT, LIGHT_DISC: {
+            return sample_planar_light(light, origin, u);
+        }
+        case LIGHT_DIRECTIONAL: {
+            return sample_directional_light(light, u);
+        }
+        default: {
+            return no_light_sample();
         }
     }
-    return vec3<f32>(0.0);
 }
 
-// Random unit vector on hemisphere
-fn random_unit_vector(seed: ptr<function, u32>) -> vec3<f32> {
-    return normalize(random_in_unit_sphere(seed));
+// Unshadowed light sample for next-event estimation, `contribution` is zero if it was rejected
+struct LightCandidate {
+    dir: vec3<f32>,
+    dist: f32,
+    // MIS-weighted `Le · f / pdf` towards `wo`, before the shadow ray
+    contribution: vec3<f32>,
+};
+
+fn no_candidate() -> LightCandidate {
+    return LightCandidate(vec3<f32>(0.0, 1.0, 0.0), 0.0, vec3<f32>(0.0));
 }
 
-// Cosine-weighted hemisphere sampling
-fn random_in_hemisphere(normal: vec3<f32>, seed: ptr<function, u32>) -> vec3<f32> {
-    let in_unit_sphere = random_unit_vector(seed);
+// Next-event estimation for one light: a sampled direction towards it, MIS-weighted against
+// BSDF sampling. Sharp directional lights are not weighted.
+fn light_candidate(s: ScatterPoint, light: PointLight, rng: ptr<function, Sampler>) -> LightCandidate {
+    let emissive = light.material.emissive;
+    if (max(max(emissive.x, emissive.y), emissive.z) <= 0.0) {
+        return no_candidate();
+    }
 
-    if (dot(in_unit_sphere, normal) > 0.0) {
-        return in_unit_sphere;
-    } else {
-        return -in_unit_sphere;
+    let origin = s.pos + 0.001 * s.normal;
+    let ls = sample_light(light, origin, sample_2d(rng));
+    if (ls.pdf <= 0.0) {
+        return no_candidate();
+    }
+
+    let bsdf = scatter_eval(s, ls.dir);
+    if (bsdf.w <= 0.0) {
+        return no_candidate();
     }
-}
 
-fn reflect_vector(v: vec3<f32>, n: vec3<f32>) -> vec3<f32> {
-    return v - 2.0 * dot(v, n) * n;
+    let weight = select(power_heuristic(ls.pdf, bsdf.w), 1.0, ls.delta);
+    return LightCandidate(ls.dir, ls.dist - 0.001, ls.radiance * bsdf.xyz * weight / ls.pdf);
 }
 
-fn near_zero(v: vec3<f32>) -> bool {
-    let s = 1e-8;
-    return (abs(v.x) < s) && (abs(v.y) < s) && (abs(v.z) < s);
+// Solid angle pdf of sampling a point at distance `t` on an emissive mesh triangle, whose face
+// normal makes `cos_light` with the ray. Triangles are picked proportional to their power
+// `luminance(emission) · area`, so the area cancels out.
+fn emissive_triangle_pdf(emission: vec3<f32>, t: f32, cos_light: f32) -> f32 {
+    let total = emissive_total_power();
+    if (total <= 0.0 || cos_light <= 1e-6) {
+        return 0.0;
+    }
+    return luminance(emission) * t * t / (total * cos_light);
 }
 
-fn scatter_lambertian(
-    normal: vec3<f32>,
-    seed: ptr<function, u32>
-) -> vec3<f32> {
-    let scatter_direction = normal + random_unit_vector(seed);
+// Next-event estimation for emissive mesh triangles: picks one triangle by power and a uniform
+// point on it, MIS-weighted against BSDF sampling. Emission is two-sided.
+fn emissive_triangle_candidate(s: ScatterPoint, rng: ptr<function, Sampler>) -> LightCandidate {
+    if (!emissive_triangles_enabled()) {
+        return no_candidate();
+    }
 
-    if near_zero(scatter_direction) {
-        return normal;
+    let tri = pick_emissive_triangle(sample_1d(rng));
+
+    let u = sample_2d(rng);
+    let su = sqrt(u.x);
+    let b0 = 1.0 - su;
+    let b1 = u.y * su;
+    let point = b0 * tri.v0 + b1 * tri.v1 + (1.0 - b0 - b1) * tri.v2;
+
+    // The maps are looked up at the sampled point, the triangle was picked by its untextured power
+    var emission = tri.emission;
+    let mesh_tri = bvh_triangles[tri.triangle];
+    if (is_cut_out(mesh_tri, b1, 1.0 - b0 - b1)) {
+        return no_candidate();
+    }
+    let emissive_texture = meshes[mesh_tri.mesh_index].material.emissive_texture;
+    if (emissive_texture >= 0) {
+        emission *= sample_texture(emissive_texture, triangle_uv(mesh_tri, b1, 1.0 - b0 - b1), 0.0);
     }
 
-    return normalize(scatter_direction);
-}
+    let origin = s.pos + 0.001 * s.normal;
+    let to_light = point - origin;
+    let dist = length(to_light);
+    if (dist <= 1e-4) {
+        return no_candidate();
+    }
+    let light_dir = to_light / dist;
+    let face_normal = normalize(cross(tri.v1 - tri.v0, tri.v2 - tri.v0));
+    let light_pdf = emissive_triangle_pdf(tri.emission, dist, abs(dot(light_dir, face_normal)));
+    if (light_pdf <= 0.0) {
+        return no_candidate();
+    }
 
-fn scatter_metal(
-    ray_dir: vec3<f32>,
-    hit: HitRecord,
-    fuzz: f32,
-    seed: ptr<function, u32>
-) -> vec3<f32> {
-    let reflected = reflect_vector(normalize(ray_dir), hit.normal);
-    let fuzzed = reflected + fuzz * random_unit_vector(seed);
-    return fuzzed;
+    let bsdf = scatter_eval(s, light_dir);
+    if (bsdf.w <= 0.0) {
+        return no_candidate();
+    }
+
+    let weight = power_heuristic(light_pdf, bsdf.w);
+    return LightCandidate(light_dir, dist - 0.001, emission * bsdf.xyz * weight / light_pdf);
 }
 
-fn collision(origin: vec3<f32>, light_dir: vec3<f32>, max_dist: f32) -> bool {
-    if (ground_enabled()) {
-        let t = intersect_ground(origin, light_dir);
-        if (t > 0.001 && t < max_dist) {
-            return true;
-        }
+// Next-event estimation for the environment map: an importance sampled direction,
+// MIS-weighted against BSDF sampling.
+fn environment_candidate(s: ScatterPoint, rng: ptr<function, Sampler>) -> LightCandidate {
+    if (!environment_enabled()) {
+        return no_candidate();
     }
 
-    let bvh_hit = intersect_bvh(origin, light_dir);
-    if (bvh_hit.hit && bvh_hit.t < max_dist) {
-        return true;
+    let env = sample_environment(rng);
+    if (env.w <= 0.0) {
+        return no_candidate();
     }
+    let light_dir = env.xyz;
 
-    for (var k = 0u; k < uniforms.spheres_count; k = k + 1u) {
-        let t = intersect_sphere(origin, light_dir, spheres[k]);
-        if (t > 0.001 && t < max_dist) {
-            return true;
-        }
+    let bsdf = scatter_eval(s, light_dir);
+    if (bsdf.w <= 0.0) {
+        return no_candidate();
     }
 
-    for (var k = 0u; k < arrayLength(&point_lights); k = k + 1u) {
-            let t = intersect_pointlight(origin, light_dir, point_lights[k]);
-            if (t > 0.001 && t < max_dist) {
-                return true;
-            }
+    let weight = power_heuristic(env.w, bsdf.w);
+    return LightCandidate(light_dir, 1e20, environment_radiance(light_dir) * bsdf.xyz * weight / env.w);
+}
+
+// Direct light at a vertex: one sample of the environment map, one emissive triangle and one sample
+// per light, in this order. All candidates share one shadow ray loop, which keeps the shader small
+// for drivers that inline every call.
+// Returns the scattered radiance towards `wo`.
+// `unoccluded` receives the light that would arrive without shadows, for the shadow catcher
+fn sample_direct_light(s: ScatterPoint, rng: ptr<function, Sampler>, unoccluded: ptr<function, vec3<f32>>) -> vec3<f32> {
+    var direct = vec3<f32>(0.0);
+    let origin = s.pos + 0.001 * s.normal;
+    let candidates = 2u + arrayLength(&point_lights);
+
+    for (var i = 0u; i < candidates; i = i + 1u) {
+        var c: LightCandidate;
+        if (i == 0u) {
+            c = environment_candidate(s, rng);
+        } else if (i == 1u) {
+            c = emissive_triangle_candidate(s, rng);
+        } else {
+            c = light_candidate(s, point_lights[i - 2u], rng);
         }
+        if (max(max(c.contribution.x, c.contribution.y), c.contribution.z) <= 0.0) {
+            continue;
+        }
+        direct += c.contribution * shadow_transmittance(origin, c.dir, c.dist, s.medium);
+        *unoccluded += c.contribution;
+    }
 
-    return false;
+    return direct;
 }
 
+// Radiance along a camera ray and its alpha, which is only below 1 over a shadow catcher.
+// The color is premultiplied by the alpha.
 fn trace_ray(
     origin0: vec3<f32>,
     direction0: vec3<f32>,
-    seed0: u32
-) -> vec3<f32> {
+    rng0: Sampler,
+    pixel_spread: f32
+) -> vec4<f32> {
     var origin = origin0;
     var direction = direction0;
-    var seed = seed0;
+    var rng = rng0;
 
     var color = vec3<f32>(0.0);
     var attenuation = vec3<f32>(1.0);
+    // Solid angle pdf of the last principled bounce, 0 for camera rays, glass and dissolve
+    var prev_bsdf_pdf = 0.0;
+    // Ray cone for texture filtering: width at the current vertex and spread angle
+    var cone_width = 0.0;
+    var cone_spread = pixel_spread;
+    // Medium the ray travels in
+    var medium = global_medium();
+    // Last scattering vertex and the distance travelled since, medium boundaries in between
+    // do not scatter. Needed for the light pdfs of the MIS weights.
+    var vertex_pos = origin0;
+    var vertex_dist = 0.0;
+    // Shadow catcher: the path met nothing but the catcher so far, the background behind it is transparent
+    var catcher_path = shadow_catcher_enabled();
+    var alpha = select(1.0, 0.0, catcher_path);
     
     for (var depth: u32 = 0; depth < uniforms.max_depth; depth = depth + 1) {
-        var closest_hit = HitRecord(
-            false,
-            1e20,
-            vec3<f32>(0.0),
-            vec3<f32>(0.0),
-            vec2<f32>(0.0),
-            false,
-            Material(
-                vec3<f32>(0.0), 0.0,
-                vec3<f32>(0.0), 0.0,
-                vec3<f32>(0.0), 0.0,
-                vec3<f32>(0.0), 0.0,
-                0.0, 0u, -1, 0u
-            )
-        );
-
-        // Ground
-        if (ground_enabled()) {
-            let t = intersect_ground(origin, direction);
-            if (t > 0.001 && t < closest_hit.t) {
-                closest_hit.hit = true;
-                closest_hit.t = t;
-                closest_hit.pos = origin + t * direction;
-                closest_hit.normal = vec3<f32>(0.0, 1.0, 0.0);
-                closest_hit.material.diffuse = vec3<f32>(0.5);
-                closest_hit.material.ambient = vec3<f32>(0.0);
-                closest_hit.material.specular = vec3<f32>(0.0);
-                closest_hit.uv = closest_hit.pos.xz;
-                closest_hit.use_texture = true;
-            }
+        sampler_start_bounce(&rng, depth + 1u);
+        var closest_hit = intersect_scene(origin, direction);
+        let hit_light = select(-1, i32(closest_hit.object.y), closest_hit.object.x == OBJECT_LIGHT);
+        let hit_catcher = catcher_path && closest_hit.object.x == OBJECT_GROUND;
+
+        // Free flight through the medium, a miss is treated as a surface far away
+        var scatter_dist = -1.0;
+        if (has_medium(medium)) {
+            let flight = sample_free_flight(medium, closest_hit.t, &rng);
+            attenuation *= flight.xyz;
+            scatter_dist = flight.w;
         }
 
-        // BVH Triangles
-        let bvh_hit = intersect_bvh(origin, direction);
-            if bvh_hit.hit && bvh_hit.t < closest_hit.t {
-                closest_hit = bvh_hit;
+        // The first object seen directly or in the catcher covers the shadow and the background
+        let hit_object = closest_hit.hit && !hit_catcher && !is_medium_boundary(closest_hit);
+        if (catcher_path && (scatter_dist >= 0.0 || hit_object)) {
+            let coverage = clamp(luminance(attenuation), 0.0, 1.0);
+            alpha = coverage + (1.0 - coverage) * alpha;
+            catcher_path = false;
         }
 
-        // Spheres
-        for (var k = 0u; k < uniforms.spheres_count; k = k + 1u) {
-            let sphere = spheres[k];
-            let t = intersect_sphere(origin, direction, sphere);
-
-            if (t > 0.001 && t < closest_hit.t) {
-                closest_hit.hit = true;
-                closest_hit.t = t;
-                closest_hit.pos = origin + t * direction;
-                closest_hit.normal = normalize(closest_hit.pos - sphere.center);
-                closest_hit.material = sphere.material;
-            closest_hit.use_texture = closest_hit.material.texture_index >= 0;
+        // Scatter
+        var scattered: vec3<f32>;
+        var albedo = vec3<f32>(1.0);
+        // Vertex for direct light sampling, if the scattering event has one
+        var vertex: ScatterPoint;
+        var sample_direct = false;
+        var absorbed = false;
+        var next_origin: vec3<f32>;
+
+        if (scatter_dist >= 0.0) {
+            // Scattering inside the medium, the phase function is sampled exactly
+            let pos = origin + scatter_dist * direction;
+            scattered = sample_hg(direction, medium.g, sample_2d(&rng));
+            vertex = medium_point(pos, -direction, medium);
+            sample_direct = true;
+            prev_bsdf_pdf = hg_phase(dot(direction, scattered), medium.g);
+            cone_width = cone_width + cone_spread * scatter_dist;
+            next_origin = pos;
+        } else {
+            //Sky
+            if (!closest_hit.hit) {
+                if (catcher_path) {
+                    break;
+                }
+                // The environment map was also sampled directly at the previous principled vertex
+                var sky_weight = 1.0;
+                if (environment_enabled() && prev_bsdf_pdf > 0.0) {
+                    sky_weight = power_heuristic(prev_bsdf_pdf, environment_pdf(direction));
+                }
+                color += attenuation * environment_radiance(direction) * sky_weight;
+                color += attenuation * directional_radiance(direction, prev_bsdf_pdf);
+                break;
             }
-        }
-
-        // Point Light
 
-        for (var k = 0u; k < arrayLength(&point_lights); k = k + 1u) {
-            let point_light = point_lights[k];
-            let t = intersect_pointlight(origin, direction, point_light);
-
-            if (t > 0.001 && t < closest_hit.t) {
-                closest_hit.hit = true;
-                closest_hit.t = t;
-                closest_hit.pos = origin + t * direction;
-                closest_hit.normal = normalize(closest_hit.pos - point_light.center);
-                closest_hit.material = point_light.material;
+            // Medium boundaries have no surface, the ray continues in the medium behind them
+            if (is_medium_boundary(closest_hit)) {
+                medium = medium_behind(closest_hit, direction);
+                cone_width = cone_width + cone_spread * closest_hit.t;
+                vertex_dist += closest_hit.t;
+                origin = closest_hit.pos + 0.001 * direction;
+                continue;
             }
-        }
-
-        //Sky
-        if (!closest_hit.hit) {
-            let sky = uniforms.sky_color;
-            color += attenuation * sky;
-            break;
-        }
-
-        //has to be reviewed
-        // if (depth == 0) {
-        //     color += closest_hit.material.ambient;
-        // }
-
-        let specular_strength = (closest_hit.material.specular.x +
-                                closest_hit.material.specular.y +
-                                closest_hit.material.specular.z) / 3.0;
-        let diffuse_strength = (closest_hit.material.diffuse.x +
-                                closest_hit.material.diffuse.y +
-                                closest_hit.material.diffuse.z) / 3.0;
-
-        // Only treat as metal if it has specular but negligible diffuse
-        let is_metal = specular_strength > 0.01 && diffuse_strength < 0.01;
-
-        // Add emitted light
-        color += attenuation * closest_hit.material.emissive;
+            let light_t = vertex_dist + closest_hit.t;
+
+            //has to be reviewed
+            // if (depth == 0) {
+            //     color += closest_hit.material.ambient;
+            // }
+
+            // Add emitted light. Lights reached by a principled bounce were already sampled
+            // directly at the previous vertex, so both strategies are MIS-weighted.
+            if (hit_light >= 0) {
+                let light = point_lights[u32(hit_light)];
+                var emission_weight = 1.0;
+                if (prev_bsdf_pdf > 0.0) {
+                    emission_weight = power_heuristic(prev_bsdf_pdf, light_pdf(vertex_pos, light, direction, light_t));
+                }
+                color += attenuation * light_emission(light, vertex_pos, direction) * emission_weight;
+            } else if (!hit_catcher) {
+                // Emissive mesh triangles are sampled directly as well
+                var emission_weight = 1.0;
+                let emission = closest_hit.material.emissive;
+                if (closest_hit.object.x == OBJECT_MESH && prev_bsdf_pdf > 0.0 && emissive_triangles_enabled() && luminance(emission) > 0.0) {
+                    let cos_light = abs(dot(direction, closest_hit.geometric_normal));
+                    emission_weight = power_heuristic(prev_bsdf_pdf, emissive_triangle_pdf(emission, light_t, cos_light));
+                }
+                color += attenuation * surface_emission(closest_hit) * emission_weight;
+            }
+            prev_bsdf_pdf = 0.0;
+
+            // Texture footprint of the ray cone, stretched at grazing angles
+            cone_width = cone_width + cone_spread * closest_hit.t;
+            let cos_incident = max(abs(dot(direction, closest_hit.normal)), 0.05);
+            closest_hit.footprint = cone_width * closest_hit.uv_density / cos_incident;
+
+            let mapped_normal = shading_normal(closest_hit);
+
+            if (is_dielectric(closest_hit.material)) {
+                // Glass / water: Fresnel-weighted reflection or refraction
+                let front_face = dot(direction, closest_hit.normal) < 0.0;
+                var normal = mapped_normal;
+                var eta = 1.0 / max(closest_hit.material.ior, 1e-3);
+                let interior = material_medium(closest_hit.material);
+                if (!front_face) {
+                    // Leaving the medium, the segment that just ended ran inside it. An interior
+                    // medium replaces the absorption derived from Kd and was handled by free flight.
+                    normal = -normal;
+                    eta = closest_hit.material.ior;
+                    if (!has_medium(interior)) {
+                        attenuation *= dielectric_absorption(closest_hit.material, closest_hit.t);
+                    }
+                }
+                scattered = scatter_dielectric(direction, normal, eta, &rng);
+                // Refraction enters the interior medium or leaves to the global one
+                if (dot(scattered, normal) < 0.0) {
+                    medium = global_medium();
+                    if (front_face) {
+                        medium = interior;
+                    }
+                }
+            } else if (closest_hit.material.opacity < 1.0 && sample_1d(&rng) > closest_hit.material.opacity) {
+                // Dissolve (d < 1): the ray passes through unchanged
+                scattered = direction;
+            } else {
+                // Principled metallic/roughness BSDF
+                let p = principled_from_hit(closest_hit, surface_base_color(closest_hit));
 
-        // Scatter
-        var scattered: vec3<f32>;
-        var albedo: vec3<f32>;
+                let wo = -direction;
+                var normal = mapped_normal;
+                if (dot(closest_hit.normal, wo) < 0.0) {
+                    normal = -normal;
+                }
+                // A mapped normal facing away from the viewer would end the path, use the surface normal
+                if (dot(normal, wo) <= 1e-4) {
+                    normal = select(closest_hit.normal, -closest_hit.normal, dot(closest_hit.normal, wo) < 0.0);
+                }
 
-        if (is_metal) {
-            // Metal material with glossy reflections
-            // Map Ns (0-1000) to fuzz (1.0-0.0)
-            // Higher Ns = sharper reflections (less fuzz)
-            // Lower Ns = blurrier reflections (more fuzz)
-            let fuzz = clamp(1.0 - (closest_hit.material.shininess / 1000.0), 0.0, 1.0);
-            scattered = scatter_metal(direction, closest_hit, fuzz, &seed);
+                // Sampled first, the number of dimensions used by the lights depends on the scene
+                scattered = principled_sample(p, normal, wo, &rng);
+                vertex = surface_point(closest_hit.pos, normal, wo, p, medium);
+                sample_direct = true;
+
+                let bsdf = principled_eval(p, normal, wo, scattered);
+                if (bsdf.w <= 0.0) {
+                    absorbed = true;
+                } else {
+                    albedo = bsdf.xyz / bsdf.w;
+                    prev_bsdf_pdf = bsdf.w;
+                    // Rough lobes widen the ray cone
+                    cone_spread = cone_spread + sqrt(p.alpha);
+                }
+            }
 
-            if (dot(scattered, closest_hit.normal) <= 0.0) {
-                break;
+            // Offset to the side the scattered ray leaves on
+            var offset_normal = closest_hit.normal;
+            if (dot(scattered, closest_hit.normal) < 0.0) {
+                offset_normal = -offset_normal;
             }
+            next_origin = closest_hit.pos + 0.001 * offset_normal;
+        }
 
-            albedo = closest_hit.material.specular;
-        } else {
-            scattered = scatter_lambertian(closest_hit.normal, &seed);
-            albedo = closest_hit.material.diffuse;
-            // Apply texture
-            if (closest_hit.use_texture) {
-                albedo = albedo * sample_texture(closest_hit.material.texture_index, closest_hit.uv);
+        // Direct light from the environment map, emissive triangles and the lights.
+        // Medium and surface vertices share the call, every call site adds a copy of the shadow ray loop.
+        if (sample_direct) {
+            var unoccluded = vec3<f32>(0.0);
+            let direct = sample_direct_light(vertex, &rng, &unoccluded);
+            if (hit_catcher) {
+                // The catcher keeps the light the objects take away, as the alpha of a black shadow.
+                // Lights reached by its bounce get the full weight, since their direct samples are dropped.
+                let lit = luminance(unoccluded);
+                if (lit > 0.0) {
+                    alpha = clamp(1.0 - luminance(direct) / lit, 0.0, 1.0);
+                }
+                prev_bsdf_pdf = 0.0;
+            } else {
+                color += attenuation * direct;
             }
         }
+        if (absorbed) {
+            break;
+        }
 
         // Update attenuation
         attenuation *= albedo;
 
         // Next ray
-        origin = closest_hit.pos + 0.001 * closest_hit.normal;
+        origin = next_origin;
         direction = normalize(scattered);
+        vertex_pos = origin;
+        vertex_dist = 0.0;
     }
-    return color;
+    return vec4<f32>(color, alpha);
 }
-
-fn intersect_aabb(ray_origin: vec3<f32>, ray_dir: vec3<f32>, aabb_min: vec3<f32>, aabb_max: vec3<f32>) -> bool {
-    let inv_dir = 1.0 / ray_dir;
-    let t0s = (aabb_min - ray_origin) * inv_dir;
-    let t1s = (aabb_max - ray_origin) * inv_dir;
-    let tmin = max(max(min(t0s.x, t1s.x), min(t0s.y, t1s.y)), min(t0s.z, t1s.z));
-    let tmax = min(min(max(t0s.x, t1s.x), max(t0s.y, t1s.y)), max(t0s.z, t1s.z));
-    return tmax >= max(tmin, 0.0);
-}
-
-@compute @workgroup_size(16, 16, 1)
-fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
-    let x: u32 = global_id.x;
-    let y: u32 = global_id.y;
-
-    if (x >= uniforms.width || y >= uniforms.height) {
-        return;
-    }
-
-    let pixel_index = y * uniforms.width + x;
-
-    // Load previous accumulation
-    var accumulated_color = accumulation[pixel_index].xyz;
-    var total_samples = u32(accumulation[pixel_index].w);
-
-    // Render new samples for this pass
-    for (var sample: u32 = 0u; sample < prh.samples_per_pass; sample = sample + 1u) {
-        let aspect = f32(uniforms.width) / f32(uniforms.height);
-
-        // Use pass index to ensure different samples each pass
-        let sample_offset = prh.current_pass * prh.samples_per_pass + sample;
-        var seed = hash(pixel_index + hash(sample_offset));
-
-        let offset_x = random_float(&seed) - 0.5;
-        let offset_y = random_float(&seed) - 0.5;
-
-        let u = (((f32(x) + offset_x) / f32(uniforms.width - 1u)) * 2.0 - 1.0) * aspect;
-        let v = 1.0 - ((f32(y) + offset_y) / f32(uniforms.height - 1u)) * 2.0;
-
-        let camera_pos = uniforms.camera.pos;
-        let camera_forward = normalize(uniforms.camera.dir);
-        let world_up = vec3<f32>(0.0, 1.0, 0.0);
-        let camera_right = normalize(cross(world_up, camera_forward));
-        let camera_up = cross(camera_forward, camera_right);
-
-        let fov = uniforms.camera.pane_width / (2.0 * uniforms.camera.pane_distance * aspect);
-        let ray_dir = normalize(fov * u * camera_right + fov * v * camera_up + camera_forward);
-
-        let sample_color = trace_ray(camera_pos, ray_dir, seed);
-        accumulated_color = accumulated_color + sample_color;
-        total_samples = total_samples + 1u;
-    }
-
-    // Store accumulated result
-    accumulation[pixel_index] = vec4<f32>(accumulated_color, f32(total_samples));
-
-    // Write final color (averaged + reinhard tone mapping)
-    let final_color = accumulated_color / f32(total_samples);
-    let mapped = final_color / (final_color + vec3<f32>(1.0));
-    output[pixel_index] = color_map(mapped);
-}
\ No newline at end of file
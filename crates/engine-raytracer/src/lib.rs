@@ -1,57 +1,97 @@
 //! # Engine Raytracer
 //!
-//! `engine-raytracer` provides a placeholder for a wgpu-based ray tracing implementation.
-//! This crate is not currently implemented in the production system.
+//! `engine-raytracer` provides a wgpu-based Whitted-style ray tracer for the RenderBaby project,
+//! the fast preview engine next to `engine-pathtracer`. It extends the capabilities of
+//! `engine-wgpu-wrapper` and renders the same scenes with the same buffers.
 //!
-//! ## Status
+//! ## Features
 //!
-//! **Not Implemented**: This crate exists as a placeholder for future development.
-//! The pathtracer (`engine-pathtracer`) is the primary rendering backend.
+//! - **GPU Acceleration**: Utilizes `wgpu` for hardware-accelerated ray tracing via compute shaders.
+//! - **Progressive Rendering**: Supports progressive rendering through [`GpuFrameIterator`],
+//!   further passes only anti-alias the image and the depth of field.
+//! - **Whitted Ray Tracing**:
+//!   - Recursive mirror reflections and Fresnel-weighted glass refraction on an explicit
+//!     ray stack, bounded by the maximum depth.
+//!   - Hard shadows from every light towards its center.
+//!   - Phong shading from the `specular` and `shininess` material fields.
+//!   - Exponential depth fog from the global medium.
+//!
+//! ## Architecture
+//!
+//! This crate implements the [`Renderer`] trait from `engine_config` through the [`GpuEngine`] of
+//! `engine-wgpu-wrapper`, which manages the underlying GPU resources (buffers, bind groups,
+//! pipelines) and the progressive passes.
+//! The core ray tracing logic resides in the associated WGSL shader (`shader.wgsl`), which is
+//! appended to the shared WGSL of `engine-wgpu-wrapper` (`common.wgsl`).
+//!
+//! Ray Tracer Module
+#![doc = include_str!("shader_docs.md")]
 
 use anyhow::Result;
 pub use engine_config::RenderConfig;
 use engine_config::Renderer;
-use engine_wgpu_wrapper::GpuWrapper;
+pub use engine_wgpu_wrapper::{GpuEngine, GpuFrameIterator};
 use frame_buffer::frame_iterator::{Frame, FrameIterator};
 
-/// A placeholder ray tracing renderer.
+/// A wgpu-based Whitted-style ray tracing renderer.
 ///
-/// This engine is not currently implemented. Use `engine-pathtracer` instead.
-#[allow(dead_code)]
+/// The `Engine` struct serves as the main entry point for the Whitted-style ray tracing backend.
+/// It runs the Whitted-style ray tracing shader on a [`GpuEngine`] and implements the [`Renderer`] trait
+/// to integrate with the rest of the application.
 pub struct Engine {
-    /// GPU wrapper for future implementation.
-    gpu_wrapper: GpuWrapper,
+    /// The GPU engine running this crate's shader.
+    gpu_engine: GpuEngine,
 }
 
 impl Renderer for Engine {
-    /// Not implemented.
+    /// Renders a scene synchronously, see [`GpuEngine`].
     fn render(&mut self, rc: RenderConfig) -> Result<Frame> {
-        self.render(rc)
+        self.gpu_engine.render(rc)
     }
 
-    /// Not implemented.
-    fn frame_iterator(&mut self, _rc: RenderConfig) -> Result<Box<dyn FrameIterator>> {
-        todo!()
+    /// Creates a [`GpuFrameIterator`] for progressive rendering, see [`GpuEngine`].
+    fn frame_iterator(&mut self, rc: RenderConfig) -> Result<Box<dyn FrameIterator>> {
+        self.gpu_engine.frame_iterator(rc)
     }
 }
 
 impl Engine {
-    /// Creates a new placeholder engine instance.
+    /// Creates a new `Engine` instance.
+    ///
+    /// Initializes the underlying [`GpuEngine`] with the Whitted-style ray tracing compute shader.
     ///
     /// # Arguments
     ///
-    /// * `rc` - Initial render configuration
+    /// * `rc` - The initial render configuration.
+    ///
+    /// # Returns
+    ///
+    /// * `Self` - A new instance of the Whitted-style ray tracing engine.
     pub fn new(rc: RenderConfig) -> Self {
+        // Embed the shader source code into the binary at compile time.
+        // This ensures the shader is available regardless of the execution environment.
         let shader_source = include_str!("shader.wgsl");
-        let wrapper = GpuWrapper::new(rc, shader_source).unwrap();
-
         Self {
-            gpu_wrapper: wrapper,
+            gpu_engine: GpuEngine::new(rc, shader_source, "ENGINE-RAYTRACER").unwrap(),
         }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use engine_wgpu_wrapper::shader_with_common;
+    use wgpu::naga;
 
-    /// Not implemented.
-    pub fn render(&mut self, _rc: RenderConfig) -> Result<Frame> {
-        todo!()
+    #[test]
+    fn shader_validates_after_the_common_wgsl() {
+        let source = shader_with_common(include_str!("shader.wgsl"));
+        let module = naga::front::wgsl::parse_str(&source)
+            .unwrap_or_else(|e| panic!("{}", e.emit_to_string(&source)));
+        naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::default(),
+        )
+        .validate(&module)
+        .unwrap();
     }
 }
@@ -0,0 +1,363 @@
+// Appended to common.wgsl of engine-wgpu-wrapper, which declares the buffers, the scene
+// intersection, the sampler and the camera rays used below, and the entry point `main` that
+// calls `trace_ray`.
+
+// Light arriving at a point from one light, as if all of it left the light's center
+struct LightRay {
+    dir: vec3<f32>,
+    // Distance the shadow ray has to cover, 1e20 for directional lights
+    dist: f32,
+    // Irradiance on a surface facing the light, before shadowing
+    irradiance: vec3<f32>,
+};
+
+// Whitted lights are points. Sphere and spot lights send the power of their sphere from the
+// center, rect and disc lights are a Lambertian emitter of their area at the center and
+// directional lights are infinitely far away, so every light casts a hard shadow.
+fn light_ray(pos: vec3<f32>, light: PointLight) -> LightRay {
+    let emissive = light.material.emissive;
+    if (light.light_type == LIGHT_DIRECTIONAL) {
+        return LightRay(-normalize(light.direction), 1e20, emissive);
+    }
+    let to_light = light.center - pos;
+    let dist2 = max(dot(to_light, to_light), 1e-8);
+    let dist = sqrt(dist2);
+    let dir = to_light / dist;
+    switch (light.light_type) {
+        case LIGHT_RECT, LIGHT_DISC: {
+            // One-sided, the shadow ray ends just before the light's plane
+            let intensity = emissive * planar_light_area(light) * max(dot(-dir, light.direction), 0.0);
+            return LightRay(dir, dist - 0.001, intensity / dist2);
+        }
+        case LIGHT_SPOT: {
+            let intensity = emissive * PI * light.radius * light.radius * spot_falloff(light, -dir);
+            return LightRay(dir, max(dist - light.radius - 0.001, 0.0), intensity / dist2);
+        }
+        default: {
+            let intensity = emissive * PI * light.radius * light.radius;
+            return LightRay(dir, max(dist - light.radius - 0.001, 0.0), intensity / dist2);
+        }
+    }
+}
+
+// Emissive mesh triangles light the scene like small rect lights: a two-sided Lambertian emitter
+// of the triangle's area at its centroid, with the emission map read there
+fn emissive_triangle_ray(pos: vec3<f32>, tri: EmissiveTriangle) -> LightRay {
+    var emission = tri.emission;
+    let mesh_tri = bvh_triangles[tri.triangle];
+    let emissive_texture = meshes[mesh_tri.mesh_index].material.emissive_texture;
+    if (emissive_texture >= 0) {
+        emission *= sample_texture(emissive_texture, triangle_uv(mesh_tri, 1.0 / 3.0, 1.0 / 3.0), 0.0);
+    }
+
+    let to_light = (tri.v0 + tri.v1 + tri.v2) / 3.0 - pos;
+    let dist2 = max(dot(to_light, to_light), 1e-8);
+    let dist = sqrt(dist2);
+    let dir = to_light / dist;
+    let face_normal = normalize(cross(tri.v1 - tri.v0, tri.v2 - tri.v0));
+    let intensity = emission * tri.area * abs(dot(dir, face_normal));
+    return LightRay(dir, dist - 0.001, intensity / dist2);
+}
+
+// Emissive triangles picked by power at every shaded point, a mesh light may have thousands
+const EMISSIVE_TRIANGLE_SAMPLES: u32 = 4u;
+
+fn scene_light_count() -> u32 {
+    return arrayLength(&point_lights) + select(0u, EMISSIVE_TRIANGLE_SAMPLES, emissive_triangles_enabled());
+}
+
+// Light `k` of the lights, followed by `EMISSIVE_TRIANGLE_SAMPLES` emissive triangles picked by
+// their power. A picked triangle's irradiance is divided by its probability and the number of
+// picks, so on average the picks add up to the light of all triangles.
+fn scene_light_ray(pos: vec3<f32>, k: u32, rng: ptr<function, Sampler>) -> LightRay {
+    let light_count = arrayLength(&point_lights);
+    if (k < light_count) {
+        return light_ray(pos, point_lights[k]);
+    }
+    let tri = pick_emissive_triangle(sample_1d(rng));
+    let probability = luminance(tri.emission) * tri.area / emissive_total_power();
+    var ray = emissive_triangle_ray(pos, tri);
+    ray.irradiance *= select(0.0, 1.0 / (probability * f32(EMISSIVE_TRIANGLE_SAMPLES)), probability > 0.0);
+    return ray;
+}
+
+// Radiance of the directional lights seen along an escaping ray, their irradiance spread over
+// the sun disc. Perfectly sharp suns are invisible.
+fn sun_radiance(direction: vec3<f32>) -> vec3<f32> {
+    var radiance = vec3<f32>(0.0);
+    for (var k = 0u; k < arrayLength(&point_lights); k = k + 1u) {
+        let light = point_lights[k];
+        let extent = directional_extent(light);
+        if (light.light_type != LIGHT_DIRECTIONAL || extent <= 0.0) {
+            continue;
+        }
+        if (dot(direction, -light.direction) >= light.cos_outer) {
+            radiance += light.material.emissive / (2.0 * PI * extent);
+        }
+    }
+    return radiance;
+}
+
+// Phong parameters of a surface, resolved from the material and its texture maps
+struct Phong {
+    diffuse: vec3<f32>,
+    specular: vec3<f32>,
+    exponent: f32,
+};
+
+// The diffuse color is the principled base color without the metallic part. The specular
+// color (Ks) is scaled by the specular map and tinted by the base color like metals are in
+// the path tracer, the exponent (Ns) is scaled by the specular exponent map.
+fn phong_from_hit(hit: HitRecord, p: Principled) -> Phong {
+    var specular = hit.material.specular;
+    if (hit.material.specular_texture >= 0) {
+        specular *= sample_texture(hit.material.specular_texture, hit.uv, hit.footprint);
+    }
+    var exponent = hit.material.shininess;
+    if (hit.material.shininess_texture >= 0) {
+        exponent *= fetch_texel(hit.material.shininess_texture, hit.uv, hit.footprint).x;
+    }
+    return Phong(
+        p.base_color * (1.0 - p.metallic),
+        mix(specular, p.base_color, p.metallic),
+        max(exponent, 0.0)
+    );
+}
+
+// Direct light of every light and a few emissive triangles at a surface with normalized Phong
+// shading, each behind a shadow ray. `normal` faces `wo`, `offset_normal` is the surface normal on the
+// same side. The light before shadowing is added to `unoccluded` for the shadow catcher.
+// Every call site adds a copy of the shadow ray loop.
+fn shade_phong(
+    pos: vec3<f32>,
+    normal: vec3<f32>,
+    offset_normal: vec3<f32>,
+    wo: vec3<f32>,
+    phong: Phong,
+    medium: Medium,
+    rng: ptr<function, Sampler>,
+    unoccluded: ptr<function, vec3<f32>>
+) -> vec3<f32> {
+    let origin = pos + 0.001 * offset_normal;
+    let lobe_scale = (phong.exponent + 2.0) / (2.0 * PI);
+    var color = vec3<f32>(0.0);
+    for (var k = 0u; k < scene_light_count(); k = k + 1u) {
+        let light = scene_light_ray(origin, k, rng);
+        let cos_light = dot(normal, light.dir);
+        if (cos_light <= 0.0 || dot(offset_normal, light.dir) <= 0.0 || luminance(light.irradiance) <= 0.0) {
+            continue;
+        }
+        let mirrored = reflect_vector(-light.dir, normal);
+        let lobe = lobe_scale * pow(max(dot(mirrored, wo), 0.0), phong.exponent);
+        let lit = (phong.diffuse / PI + phong.specular * lobe) * light.irradiance * cos_light;
+        *unoccluded += lit;
+        color += lit * shadow_transmittance(origin, light.dir, light.dist, medium);
+    }
+    return color;
+}
+
+// Light scattered towards the viewer inside a medium along a segment of length `t`, the fog
+// thickens exponentially with the distance. The medium is lit by the environment and, without
+// shadows, by the lights and emissive triangles seen from a point inside the first mean free path.
+fn fog_radiance(
+    m: Medium,
+    origin: vec3<f32>,
+    direction: vec3<f32>,
+    t: f32,
+    rng: ptr<function, Sampler>
+) -> vec3<f32> {
+    let sigma_t = m.absorption + m.scattering;
+    let max_sigma = max(max(sigma_t.x, sigma_t.y), sigma_t.z);
+    let albedo = select(vec3<f32>(0.0), m.scattering / sigma_t, sigma_t > vec3<f32>(0.0));
+
+    let pos = origin + min(t, 1.0 / max_sigma) * 0.5 * direction;
+    var in_scattered = environment_radiance(direction) + sun_radiance(direction);
+    for (var k = 0u; k < scene_light_count(); k = k + 1u) {
+        let light = scene_light_ray(pos, k, rng);
+        in_scattered += light.irradiance * hg_phase(dot(light.dir, direction), m.g);
+    }
+    return albedo * (vec3<f32>(1.0) - medium_transmittance(m, t)) * in_scattered;
+}
+
+// Reflection and refraction rays waiting to be traced
+const RAY_STACK_SIZE: u32 = 16u;
+// Rays contributing less than this are dropped
+const MIN_RAY_WEIGHT: f32 = 1e-3;
+
+struct RayEntry {
+    origin: vec3<f32>,
+    // Segments before this one, the camera ray has depth 0
+    depth: u32,
+    direction: vec3<f32>,
+    // Width of the ray cone at the origin, for texture filtering
+    cone_width: f32,
+    // Fraction of its radiance that reaches the pixel
+    weight: vec3<f32>,
+    // Medium the ray travels in
+    medium: Medium,
+};
+
+// Pushes a secondary ray of `parent` unless it is too deep, too dim or the stack is full
+fn push_ray(
+    stack: ptr<function, array<RayEntry, RAY_STACK_SIZE>>,
+    sp: ptr<function, u32>,
+    parent: RayEntry,
+    origin: vec3<f32>,
+    direction: vec3<f32>,
+    cone_width: f32,
+    weight: vec3<f32>,
+    medium: Medium
+) {
+    let depth = parent.depth + 1u;
+    if (depth >= uniforms.max_depth || *sp >= RAY_STACK_SIZE || luminance(weight) < MIN_RAY_WEIGHT) {
+        return;
+    }
+    (*stack)[*sp] = RayEntry(origin, depth, normalize(direction), cone_width, weight, medium);
+    *sp = *sp + 1u;
+}
+
+// Radiance along a camera ray and its alpha, which is only below 1 over a shadow catcher.
+// The color is premultiplied by the alpha.
+//
+// Whitted ray tracing: every hit is shaded with direct light only and spawns a mirror ray,
+// glass splits into a reflected and a refracted ray weighted by Fresnel. The rays are kept on
+// an explicit stack, depth first, and no ray goes deeper than `max_depth` segments.
+fn trace_ray(
+    origin0: vec3<f32>,
+    direction0: vec3<f32>,
+    rng0: Sampler,
+    pixel_spread: f32
+) -> vec4<f32> {
+    var rng = rng0;
+    var stack: array<RayEntry, RAY_STACK_SIZE>;
+    stack[0] = RayEntry(origin0, 0u, direction0, 0.0, vec3<f32>(1.0), global_medium());
+    var sp = 1u;
+
+    var color = vec3<f32>(0.0);
+    var alpha = 1.0;
+
+    while (sp > 0u) {
+        sp = sp - 1u;
+        var ray = stack[sp];
+        let hit = intersect_scene(ray.origin, ray.direction);
+        // Only the camera ray sees the shadow catcher, its reflections show the ground as usual
+        let hit_catcher = ray.depth == 0u && shadow_catcher_enabled() && hit.object.x == OBJECT_GROUND;
+
+        // Fog up to the hit, rays escaping to the sky are fully extinguished
+        if (has_medium(ray.medium)) {
+            color += ray.weight * fog_radiance(ray.medium, ray.origin, ray.direction, hit.t, &rng);
+            ray.weight *= medium_transmittance(ray.medium, hit.t);
+        }
+
+        // Sky
+        if (!hit.hit) {
+            if (ray.depth == 0u && shadow_catcher_enabled()) {
+                alpha = 0.0;
+                continue;
+            }
+            color += ray.weight * (environment_radiance(ray.direction) + sun_radiance(ray.direction));
+            continue;
+        }
+
+        let cone_width = ray.cone_width + pixel_spread * hit.t;
+
+        // Medium boundaries have no surface, the ray continues in the medium behind them
+        if (is_medium_boundary(hit)) {
+            let origin = hit.pos + 0.001 * ray.direction;
+            push_ray(&stack, &sp, ray, origin, ray.direction, cone_width, ray.weight, medium_behind(hit, ray.direction));
+            continue;
+        }
+
+        // Lights are seen but not shaded
+        if (hit.object.x == OBJECT_LIGHT) {
+            color += ray.weight * light_emission(point_lights[hit.object.y], ray.origin, ray.direction);
+            continue;
+        }
+
+        var surface = hit;
+        let cos_incident = max(abs(dot(ray.direction, hit.normal)), 0.05);
+        surface.footprint = cone_width * hit.uv_density / cos_incident;
+        if (!hit_catcher) {
+            color += ray.weight * surface_emission(surface);
+        }
+
+        let front_face = dot(ray.direction, hit.normal) < 0.0;
+        let offset_normal = select(-hit.normal, hit.normal, front_face);
+        var normal = shading_normal(surface);
+        if (!front_face) {
+            normal = -normal;
+        }
+
+        if (is_dielectric(hit.material)) {
+            // Glass / water: Fresnel-weighted reflection and refraction
+            var weight = ray.weight;
+            var eta = 1.0 / max(hit.material.ior, 1e-3);
+            let interior = material_medium(hit.material);
+            if (!front_face) {
+                // Leaving the medium, the segment that just ended ran inside it. An interior
+                // medium replaces the absorption derived from Kd and was applied as fog.
+                eta = hit.material.ior;
+                if (!has_medium(interior)) {
+                    weight *= dielectric_absorption(hit.material, hit.t);
+                }
+            }
+
+            let cos_theta = min(dot(-ray.direction, normal), 1.0);
+            let sin2_transmitted = eta * eta * max(0.0, 1.0 - cos_theta * cos_theta);
+            var fresnel = 1.0;
+            if (sin2_transmitted <= 1.0) {
+                // Schlick is only valid for the angle on the optically thinner side
+                let cos_fresnel = select(cos_theta, sqrt(1.0 - sin2_transmitted), eta > 1.0);
+                fresnel = reflectance(cos_fresnel, eta);
+                let refracted = refract(ray.direction, normal, eta);
+                // Refraction enters the interior medium or leaves to the global one
+                var behind = global_medium();
+                if (front_face) {
+                    behind = interior;
+                }
+                push_ray(&stack, &sp, ray, hit.pos - 0.001 * offset_normal, refracted, cone_width, weight * (1.0 - fresnel), behind);
+            }
+            let reflected = reflect_vector(ray.direction, normal);
+            push_ray(&stack, &sp, ray, hit.pos + 0.001 * offset_normal, reflected, cone_width, weight * fresnel, ray.medium);
+            continue;
+        }
+
+        // Dissolve (d < 1): the surface covers its opacity, the rest of the ray passes through
+        let opacity = clamp(hit.material.opacity, 0.0, 1.0);
+        if (opacity < 1.0 && !hit_catcher) {
+            push_ray(&stack, &sp, ray, hit.pos + 0.001 * ray.direction, ray.direction, cone_width, ray.weight * (1.0 - opacity), ray.medium);
+        }
+
+        let wo = -ray.direction;
+        // A mapped normal facing away from the viewer would get no light, use the surface normal
+        if (dot(normal, wo) <= 1e-4) {
+            normal = offset_normal;
+        }
+        let p = principled_from_hit(surface, surface_base_color(surface));
+        let phong = phong_from_hit(surface, p);
+
+        var unoccluded = vec3<f32>(0.0);
+        let direct = shade_phong(hit.pos, normal, offset_normal, wo, phong, ray.medium, &rng, &unoccluded);
+        if (hit_catcher) {
+            // The catcher keeps the light the objects take away, as the alpha of a black shadow
+            let lit = luminance(unoccluded);
+            alpha = select(0.0, clamp(1.0 - luminance(direct) / lit, 0.0, 1.0), lit > 0.0);
+            continue;
+        }
+        // The environment stands in for the indirect light, without occlusion
+        let ambient = phong.diffuse * environment_radiance(normal);
+        color += ray.weight * opacity * (direct + ambient);
+
+        // Mirror reflection of the specular and clearcoat layers, fading out with their roughness
+        let n_dot_v = max(dot(normal, wo), 0.0);
+        let gloss = 1.0 - sqrt(p.alpha);
+        let clearcoat_gloss = 1.0 - sqrt(p.clearcoat_alpha);
+        let mirror = fresnel_schlick(specular_f0(p), n_dot_v) * gloss
+            + vec3<f32>(0.25 * p.clearcoat * fresnel_schlick(vec3<f32>(0.04), n_dot_v).x * clearcoat_gloss);
+        let reflected = reflect_vector(ray.direction, normal);
+        if (dot(reflected, offset_normal) > 0.0) {
+            push_ray(&stack, &sp, ray, hit.pos + 0.001 * offset_normal, reflected, cone_width, ray.weight * opacity * mirror, ray.medium);
+        }
+    }
+    return vec4<f32>(color, alpha);
+}
@@ -1,9 +1,13 @@
-use engine_config::{Mesh, RenderConfig, Sphere, Uniforms, PointLight, TextureData};
+use engine_config::{
+    AovSet, EmissiveTriangle, EnvironmentMap, Mesh, RenderConfig, Sphere, Uniforms, PointLight,
+    TextureData,
+};
 use engine_config::render_config::Change;
 use wgpu::util::DeviceExt;
 use wgpu::{Buffer, Device};
 use crate::ProgressiveRenderHelper;
 use engine_bvh::bvh::BVHNode;
+use engine_bvh::instance::Instance;
 use engine_bvh::triangle::GPUTriangle;
 use bytemuck::{Pod, Zeroable};
 
@@ -11,6 +15,8 @@ use bytemuck::{Pod, Zeroable};
 ///
 /// Since all textures are flattened into a single byte array, this struct
 /// provides the necessary information to index into that array.
+/// The mip levels of a texture follow its full resolution image, each level
+/// has half the size of the previous one (rounded down, at least 1).
 #[repr(C)]
 #[derive(Copy, Clone, Debug, Pod, Zeroable)]
 pub struct TextureInfo {
@@ -20,8 +26,16 @@ pub struct TextureInfo {
     pub width: u32,
     /// Height of the texture in pixels.
     pub height: u32,
+    /// [`engine_config::WrapMode`] of the texture.
+    pub wrap_mode: u32,
+    /// Number of mip levels, including the full resolution image.
+    pub mip_levels: u32,
+    /// 1 if any texel is not fully opaque. Opacity maps read the alpha channel of such
+    /// textures and the red channel of all others.
+    pub has_alpha: u32,
     /// Padding to ensure 16-byte alignment (required for some GPU structures).
-    pub _pad: u32,
+    pub _pad1: u32,
+    pub _pad2: u32,
 }
 
 /// Manages all GPU buffers used by the rendering engine.
@@ -44,6 +58,8 @@ pub struct GpuBuffers {
     pub meshes: Buffer,
     /// Storage buffer for accumulating samples over multiple frames (progressive rendering).
     pub accumulation: Buffer,
+    /// Staging buffer for reading back the accumulated samples to the CPU.
+    pub accumulation_staging: Buffer,
     /// Uniform buffer for progressive rendering state (pass count, etc.).
     pub progressive_render: Buffer,
     /// Storage buffer containing point light definitions.
@@ -54,10 +70,25 @@ pub struct GpuBuffers {
     pub bvh_indices: Buffer,
     /// Storage buffer for BVH triangles (geometry data).
     pub bvh_triangles: Buffer,
+    /// Storage buffer for the mesh instances of the top-level BVH.
+    pub instances: Buffer,
     /// Storage buffer containing raw texture pixel data (flattened).
     pub texture_data: Buffer,
     /// Storage buffer containing metadata for accessing textures in `texture_data`.
     pub texture_info: Buffer,
+    /// Storage buffer containing the environment map and its sampling CDFs.
+    pub environment: Buffer,
+    /// Storage buffer for the emissive mesh triangles sampled as area lights.
+    pub emissive_triangles: Buffer,
+    /// Storage buffer for the AOV layers, one `vec4<f32>` per pixel and requested layer.
+    pub aovs: Buffer,
+    /// Staging buffer for reading back the AOV layers to the CPU.
+    pub aov_staging: Buffer,
+    /// Storage buffer for adaptive sampling: the count of unconverged pixels,
+    /// followed by the luminance sum of squares and the sample count of every pixel.
+    pub sample_stats: Buffer,
+    /// Staging buffer for reading back the sample statistics to the CPU.
+    pub sample_stats_staging: Buffer,
 }
 
 impl GpuBuffers {
@@ -70,7 +101,7 @@ impl GpuBuffers {
     ///
     /// * `rc` - The initial render configuration.
     /// * `device` - The WGPU device used to create the buffers.
-    /// * `prh` - Helper for progressive rendering state.
+    /// * `prh` - Helper for progressive rendering state, its tile sets the size of the pixel buffers.
     pub fn new(rc: &RenderConfig, device: &Device, prh: &ProgressiveRenderHelper) -> Self {
         let uniforms = match &rc.uniforms {
             Change::Create(u) => u,
@@ -110,16 +141,29 @@ impl GpuBuffers {
             Change::Create(t) => t.as_slice(),
             _ => &[],
         };
+        let instances = match &rc.instances {
+            Change::Create(i) => i.as_slice(),
+            _ => &[],
+        };
+        let environment = match &rc.environment {
+            Change::Create(e) => Self::process_environment(e),
+            _ => vec![],
+        };
+        let emissive_triangles = match &rc.emissive_triangles {
+            Change::Create(t) => t.as_slice(),
+            _ => &[],
+        };
 
-        let size = (uniforms.width * uniforms.height * 4) as u64;
+        let aovs = match &rc.aovs {
+            Change::Create(a) => *a,
+            _ => AovSet::empty(),
+        };
 
-        // Add this when creating buffers
-        let accumulation_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("Accumulation Buffer"),
-            size: (uniforms.width * uniforms.height * 16) as u64, // vec4<f32> = 16 bytes
-            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
+        // The pixel buffers only cover one tile of the image
+        let (tile_width, tile_height) = (prh.tile_width, prh.tile_height);
+        let size = (tile_width as u64) * (tile_height as u64) * 4;
+        let aov_size = Self::aov_buffer_size(tile_width, tile_height, aovs);
+        let sample_stats_size = Self::sample_stats_buffer_size(tile_width, tile_height);
 
         Self {
             spheres: Self::create_storage_buffer(device, "Spheres Buffer", spheres),
@@ -128,7 +172,9 @@ impl GpuBuffers {
             staging: Self::create_staging_buffer(device, size),
             uvs: Self::create_storage_buffer(device, "UVs Buffer", uvs),
             meshes: Self::create_storage_buffer(device, "Meshes Buffer", meshes),
-            accumulation: accumulation_buffer,
+            // vec4<f32> = 16 bytes per pixel
+            accumulation: Self::create_accumulation_buffer(device, size * 4),
+            accumulation_staging: Self::create_accumulation_staging_buffer(device, size * 4),
             progressive_render: Self::create_uniform_buffer(
                 device,
                 "Progressive Render Buffer",
@@ -142,41 +188,143 @@ impl GpuBuffers {
                 "BVH Triangles Buffer",
                 bvh_triangles,
             ),
+            instances: Self::create_storage_buffer(device, "Instances Buffer", instances),
             texture_data: Self::create_storage_buffer(device, "Texture Data Buffer", &tex_data),
             texture_info: Self::create_storage_buffer(device, "Texture Info Buffer", &tex_info),
+            environment: Self::create_storage_buffer(device, "Environment Buffer", &environment),
+            emissive_triangles: Self::create_storage_buffer(
+                device,
+                "Emissive Triangles Buffer",
+                emissive_triangles,
+            ),
+            aovs: Self::create_aov_buffer(device, aov_size),
+            aov_staging: Self::create_aov_staging_buffer(device, aov_size),
+            sample_stats: Self::create_sample_stats_buffer(device, sample_stats_size),
+            sample_stats_staging: Self::create_sample_stats_staging_buffer(
+                device,
+                sample_stats_size,
+            ),
         }
     }
 
-    /// Flattens a list of textures into a single data vector and a corresponding info vector.
+    /// Returns the size in bytes of the sample statistics buffer for the given resolution.
+    ///
+    /// The counter takes 16 bytes to align the per-pixel `vec4<f32>` entries.
+    pub fn sample_stats_buffer_size(width: u32, height: u32) -> u64 {
+        16 + (width as u64) * (height as u64) * 16
+    }
+
+    /// Returns the size in bytes of the AOV buffer for the given resolution and layers.
+    ///
+    /// An empty set still gets a single `vec4<f32>`, since bindings can't be zero sized.
+    pub fn aov_buffer_size(width: u32, height: u32, aovs: AovSet) -> u64 {
+        let size = (width as u64) * (height as u64) * (aovs.len() as u64) * 16;
+        size.max(16)
+    }
+
+    /// Flattens a list of textures and their generated mip chains into a single data vector
+    /// and a corresponding info vector.
     pub fn process_textures(textures: &[TextureData]) -> (Vec<u32>, Vec<TextureInfo>) {
         let mut data = Vec::new();
         let mut info = Vec::new();
         let mut offset = 0;
 
         for tex in textures {
+            let (mip_data, mip_levels) = Self::build_mip_chain(tex);
             info.push(TextureInfo {
                 offset,
                 width: tex.width,
                 height: tex.height,
-                _pad: 0,
+                wrap_mode: tex.wrap_mode as u32,
+                mip_levels,
+                has_alpha: tex.rgba_data.iter().any(|p| p >> 24 != 255) as u32,
+                _pad1: 0,
+                _pad2: 0,
             });
-            data.extend_from_slice(&tex.rgba_data);
-            offset += tex.width * tex.height;
+            offset += mip_data.len() as u32;
+            data.extend(mip_data);
         }
 
         (data, info)
     }
 
-    /// Recreates the output, staging, and accumulation buffers to match a new resolution.
+    /// Builds the mip chain of a texture, see [`TextureData::mip_chain`].
+    ///
+    /// # Returns
+    ///
+    /// The texel data of all levels, starting with the full resolution image, and the number of levels.
+    fn build_mip_chain(tex: &TextureData) -> (Vec<u32>, u32) {
+        let levels = tex.mip_chain();
+        let count = levels.len() as u32;
+        (levels.concat(), count)
+    }
+
+    /// Packs an environment map together with the CDFs used to importance sample it.
+    ///
+    /// Every pixel is weighted by its luminance times the sine of its polar angle, which is
+    /// proportional to the solid angle it covers.
+    ///
+    /// # Returns
+    ///
+    /// `width * height` entries holding the radiance of a pixel and, in `w`, the conditional CDF
+    /// of its row up to and including the pixel, followed by `height` entries holding the
+    /// marginal CDF over the rows in `x`, the weight of the row in `y` and the total weight in `z`.
+    pub fn process_environment(environment: &EnvironmentMap) -> Vec<[f32; 4]> {
+        if !environment.is_valid() {
+            return vec![];
+        }
+        let (width, height) = (environment.width as usize, environment.height as usize);
+        let mut data = Vec::with_capacity(width * height + height);
+        let mut row_weights = Vec::with_capacity(height);
+
+        for (y, row) in environment.rgb_data.chunks_exact(width * 3).enumerate() {
+            let sin_theta = (std::f32::consts::PI * (y as f32 + 0.5) / height as f32).sin();
+            let weights: Vec<f32> = row
+                .chunks_exact(3)
+                .map(|c| (0.2126 * c[0] + 0.7152 * c[1] + 0.0722 * c[2]).max(0.0) * sin_theta)
+                .collect();
+            let row_weight: f32 = weights.iter().sum();
+
+            let mut cumulative = 0.0;
+            for (x, (c, weight)) in row.chunks_exact(3).zip(&weights).enumerate() {
+                cumulative += weight;
+                let cdf = if row_weight > 0.0 {
+                    cumulative / row_weight
+                } else {
+                    (x + 1) as f32 / width as f32
+                };
+                data.push([c[0], c[1], c[2], cdf]);
+            }
+            row_weights.push(row_weight);
+        }
+
+        let total: f32 = row_weights.iter().sum();
+        let mut cumulative = 0.0;
+        for (y, row_weight) in row_weights.iter().enumerate() {
+            cumulative += row_weight;
+            let cdf = if total > 0.0 {
+                cumulative / total
+            } else {
+                (y + 1) as f32 / height as f32
+            };
+            data.push([cdf, *row_weight, total, 0.0]);
+        }
+        data
+    }
+
+    /// Recreates the output, accumulation and sample statistics buffers and their staging
+    /// buffers to match a new tile size.
+    ///
+    /// `size` is the size of the RGBA8 output of a tile in bytes.
     pub fn grow_resolution(&mut self, device: &Device, size: u64) {
+        let sample_stats_size = 16 + size * 4;
+        self.sample_stats = Self::create_sample_stats_buffer(device, sample_stats_size);
+        self.sample_stats_staging =
+            Self::create_sample_stats_staging_buffer(device, sample_stats_size);
         self.output = Self::create_output_buffer(device, size);
         self.staging = Self::create_staging_buffer(device, size);
-        self.accumulation = device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("Accumulation Buffer"),
-            size: size * 4,
-            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
+        self.accumulation = Self::create_accumulation_buffer(device, size * 4);
+        self.accumulation_staging = Self::create_accumulation_staging_buffer(device, size * 4);
     }
 
     /// Recreates the spheres buffer with new data.
@@ -214,6 +362,11 @@ impl GpuBuffers {
         self.bvh_triangles = Self::create_storage_buffer(device, "BVH Triangles Buffer", triangles);
     }
 
+    /// Recreates the instances buffer with new data.
+    pub fn grow_instances(&mut self, device: &Device, instances: &[Instance]) {
+        self.instances = Self::create_storage_buffer(device, "Instances Buffer", instances);
+    }
+
     /// Recreates the texture data and info buffers with new texture data.
     pub fn grow_textures(&mut self, device: &Device, textures: &[TextureData]) {
         let (tex_data, tex_info) = Self::process_textures(textures);
@@ -221,6 +374,18 @@ impl GpuBuffers {
         self.texture_info = Self::create_storage_buffer(device, "Texture Info Buffer", &tex_info);
     }
 
+    /// Recreates the emissive triangles buffer with new data.
+    pub fn grow_emissive_triangles(&mut self, device: &Device, triangles: &[EmissiveTriangle]) {
+        self.emissive_triangles =
+            Self::create_storage_buffer(device, "Emissive Triangles Buffer", triangles);
+    }
+
+    /// Recreates the environment buffer with a new environment map.
+    pub fn grow_environment(&mut self, device: &Device, environment: &EnvironmentMap) {
+        let data = Self::process_environment(environment);
+        self.environment = Self::create_storage_buffer(device, "Environment Buffer", &data);
+    }
+
     fn create_uniform_buffer<T: bytemuck::Pod>(device: &Device, label: &str, data: &T) -> Buffer {
         device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some(label),
@@ -256,6 +421,64 @@ impl GpuBuffers {
         })
     }
 
+    fn create_aov_buffer(device: &Device, size: u64) -> Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("AOV Buffer"),
+            size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        })
+    }
+
+    fn create_aov_staging_buffer(device: &Device, size: u64) -> Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("AOV Staging Buffer"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    fn create_accumulation_buffer(device: &Device, size: u64) -> Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Accumulation Buffer"),
+            size,
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_SRC
+                | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    fn create_accumulation_staging_buffer(device: &Device, size: u64) -> Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Accumulation Staging Buffer"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    fn create_sample_stats_buffer(device: &Device, size: u64) -> Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Sample Stats Buffer"),
+            size,
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_SRC
+                | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    fn create_sample_stats_staging_buffer(device: &Device, size: u64) -> Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Sample Stats Staging Buffer"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
     fn create_staging_buffer(device: &Device, size: u64) -> Buffer {
         device.create_buffer(&wgpu::BufferDescriptor {
             label: Some("Staging Buffer"),
@@ -306,6 +529,11 @@ impl GpuBuffers {
         self.bvh_triangles = Self::create_storage_buffer(device, "BVH Triangles Buffer", triangles);
     }
 
+    /// Initializes the instances buffer.
+    pub fn init_instances(&mut self, device: &Device, instances: &[Instance]) {
+        self.instances = Self::create_storage_buffer(device, "Instances Buffer", instances);
+    }
+
     /// Initializes the texture buffers.
     pub fn init_textures(&mut self, device: &Device, textures: &[TextureData]) {
         let (tex_data, tex_info) = Self::process_textures(textures);
@@ -313,6 +541,18 @@ impl GpuBuffers {
         self.texture_info = Self::create_storage_buffer(device, "Texture Info Buffer", &tex_info);
     }
 
+    /// Initializes the emissive triangles buffer.
+    pub fn init_emissive_triangles(&mut self, device: &Device, triangles: &[EmissiveTriangle]) {
+        self.emissive_triangles =
+            Self::create_storage_buffer(device, "Emissive Triangles Buffer", triangles);
+    }
+
+    /// Initializes the environment buffer.
+    pub fn init_environment(&mut self, device: &Device, environment: &EnvironmentMap) {
+        let data = Self::process_environment(environment);
+        self.environment = Self::create_storage_buffer(device, "Environment Buffer", &data);
+    }
+
     // Update methods for existing buffers
     /// Updates the uniforms buffer by recreating it.
     pub fn update_uniforms(&mut self, device: &Device, uniforms: &Uniforms) {
@@ -354,6 +594,11 @@ impl GpuBuffers {
         self.bvh_triangles = Self::create_storage_buffer(device, "BVH Triangles Buffer", triangles);
     }
 
+    /// Updates the instances buffer by recreating it.
+    pub fn update_instances(&mut self, device: &Device, instances: &[Instance]) {
+        self.instances = Self::create_storage_buffer(device, "Instances Buffer", instances);
+    }
+
     /// Updates the texture buffers by recreating them.
     pub fn update_textures(&mut self, device: &Device, textures: &[TextureData]) {
         let (tex_data, tex_info) = Self::process_textures(textures);
@@ -361,6 +606,25 @@ impl GpuBuffers {
         self.texture_info = Self::create_storage_buffer(device, "Texture Info Buffer", &tex_info);
     }
 
+    /// Updates the emissive triangles buffer by recreating it.
+    pub fn update_emissive_triangles(&mut self, device: &Device, triangles: &[EmissiveTriangle]) {
+        self.emissive_triangles =
+            Self::create_storage_buffer(device, "Emissive Triangles Buffer", triangles);
+    }
+
+    /// Updates the environment buffer by recreating it.
+    pub fn update_environment(&mut self, device: &Device, environment: &EnvironmentMap) {
+        let data = Self::process_environment(environment);
+        self.environment = Self::create_storage_buffer(device, "Environment Buffer", &data);
+    }
+
+    /// Recreates the AOV buffers for a new resolution or set of layers.
+    pub fn update_aovs(&mut self, device: &Device, width: u32, height: u32, aovs: AovSet) {
+        let size = Self::aov_buffer_size(width, height, aovs);
+        self.aovs = Self::create_aov_buffer(device, size);
+        self.aov_staging = Self::create_aov_staging_buffer(device, size);
+    }
+
     // Delete methods (create minimal empty buffers)
     /// Replaces the uniforms buffer with a default/dummy one.
     pub fn delete_uniforms(&mut self, device: &Device) {
@@ -412,6 +676,12 @@ impl GpuBuffers {
         );
     }
 
+    /// Replaces the instances buffer with an empty one.
+    pub fn delete_instances(&mut self, device: &Device) {
+        self.instances =
+            Self::create_storage_buffer(device, "Instances Buffer (deleted)", &[] as &[Instance]);
+    }
+
     /// Replaces the texture buffers with empty ones.
     pub fn delete_textures(&mut self, device: &Device) {
         self.texture_data =
@@ -422,4 +692,80 @@ impl GpuBuffers {
             &[] as &[TextureInfo],
         );
     }
+
+    /// Replaces the emissive triangles buffer with an empty one.
+    pub fn delete_emissive_triangles(&mut self, device: &Device) {
+        self.emissive_triangles = Self::create_storage_buffer(
+            device,
+            "Emissive Triangles Buffer (deleted)",
+            &[] as &[EmissiveTriangle],
+        );
+    }
+
+    /// Replaces the environment buffer with an empty one.
+    pub fn delete_environment(&mut self, device: &Device) {
+        self.environment =
+            Self::create_storage_buffer(device, "Environment Buffer (deleted)", &[] as &[[f32; 4]]);
+    }
+
+    /// Replaces the AOV buffers with minimal ones.
+    pub fn delete_aovs(&mut self, device: &Device) {
+        self.update_aovs(device, 0, 0, AovSet::empty());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use engine_config::{ColorSpace, EnvironmentMap, TextureData, WrapMode};
+
+    use super::GpuBuffers;
+
+    #[test]
+    fn process_textures_appends_mip_chains() {
+        let white = u32::from_le_bytes([255, 255, 255, 255]);
+        let black = u32::from_le_bytes([0, 0, 0, 255]);
+        // 3x2 texture: the odd column is averaged with itself on the next level
+        let checker = TextureData::new(3, 2, vec![white, black, white, black, white, black])
+            .with_color_space(ColorSpace::Linear);
+        let clamped = TextureData::new(1, 1, vec![u32::from_le_bytes([0, 0, 0, 128])])
+            .with_wrap_mode(WrapMode::Clamp);
+
+        let (data, info) = GpuBuffers::process_textures(&[checker, clamped]);
+
+        // 3x2 -> 1x1
+        assert_eq!(info[0].mip_levels, 2);
+        assert_eq!(data[6].to_le_bytes(), [128, 128, 128, 255]);
+        assert_eq!(info[1].offset, 7);
+        assert_eq!(info[1].mip_levels, 1);
+        assert_eq!(info[1].wrap_mode, WrapMode::Clamp as u32);
+        assert_eq!(data.len(), 8);
+        // Only the translucent texel marks a texture with an alpha channel
+        assert_eq!(info[0].has_alpha, 0);
+        assert_eq!(info[1].has_alpha, 1);
+    }
+
+    #[test]
+    fn process_environment_builds_luminance_cdfs() {
+        // 2x2 map, the upper right pixel is four times as bright as the others
+        let environment = EnvironmentMap::new(
+            2,
+            2,
+            vec![1.0, 1.0, 1.0, 4.0, 4.0, 4.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0],
+        );
+
+        let data = GpuBuffers::process_environment(&environment);
+
+        assert_eq!(data.len(), 6);
+        assert_eq!(data[1][..3], [4.0, 4.0, 4.0]);
+        // Conditional CDFs of the rows
+        assert!((data[0][3] - 0.2).abs() < 1e-6);
+        assert!((data[1][3] - 1.0).abs() < 1e-6);
+        assert!((data[2][3] - 0.5).abs() < 1e-6);
+        // Both rows are at the same polar angle, so only the luminance decides
+        assert!((data[4][0] - 5.0 / 7.0).abs() < 1e-6);
+        assert!((data[5][0] - 1.0).abs() < 1e-6);
+        assert!((data[4][2] - data[4][1] - data[5][1]).abs() < 1e-6);
+
+        assert!(GpuBuffers::process_environment(&EnvironmentMap::new(2, 2, vec![])).is_empty());
+    }
 }
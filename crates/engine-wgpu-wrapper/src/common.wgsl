@@ -0,0 +1,1712 @@
+// WGSL shared by the compute shaders of engine-raytracer and engine-pathtracer: the buffer
+// layouts and bindings, tone mapping, textures and patterns, the intersection of the scene
+// through the BVHs, the Sobol sampler, materials, media, lights as seen by rays, AOVs,
+// adaptive sampling, the camera rays and the entry point. Each engine appends its `trace_ray`.
+
+struct Camera {
+    pane_distance: f32,
+    pane_width: f32,
+    aperture_radius: f32,
+    focus_distance: f32,
+    pos: vec3<f32>,
+    aperture_blades: u32,
+    dir: vec3<f32>,
+    _pad2: f32,
+    projection: u32,
+    ortho_width: f32,
+    interocular_distance: f32,
+    _pad3: u32,
+};
+
+struct ProgressiveRenderHelper {
+    total_passes: u32,
+    current_pass: u32,
+    total_samples: u32,
+    samples_per_pass: u32,
+    // Image region rendered by this dispatch, the pixel buffers only cover the tile
+    tile_x: u32,
+    tile_y: u32,
+    tile_width: u32,
+    tile_height: u32,
+}
+
+struct Uniforms {
+    width: u32,
+    height: u32,
+    total_passes: u32,
+    color_hash_enabled: u32,
+    camera: Camera,
+    spheres_count: u32,
+    triangles_count: u32,
+    bvh_node_count: u32,
+    bvh_triangle_count: u32,
+    bvh_root: u32,
+    ground_height: f32,
+    ground_enabled: u32,
+    checkerboard_enabled: u32,
+    sky_color: vec3<f32>,
+    max_depth: u32,
+    checkerboard_color_1: vec3<f32>,
+    _pad1: u32,
+    checkerboard_color_2: vec3<f32>,
+    _pad2: u32,
+    environment_width: u32,
+    environment_height: u32,
+    environment_rotation: f32,
+    environment_intensity: f32,
+    exposure: f32,
+    tone_mapping: u32,
+    aov_flags: u32,
+    adaptive_threshold: f32,
+    adaptive_min_samples: u32,
+    emissive_triangle_count: u32,
+    // Only read on the CPU, which schedules the tiles
+    tile_size: u32,
+    tile_order: u32,
+    // Global medium the scene is filled with
+    medium_absorption: vec3<f32>,
+    medium_anisotropy: f32,
+    medium_scattering: vec3<f32>,
+    // Nonzero if any object has an interior medium
+    interior_media: u32,
+    ground_material: Material,
+    shadow_catcher: u32,
+    // Only read on the CPU, which reads the accumulation back
+    radiance_layer: u32,
+    _pad4: u32,
+    _pad5: u32,
+};
+
+struct Sphere {
+    center: vec3<f32>,
+    radius: f32,
+    material: Material,
+    // Rotation of the texture mapping, columns are the local axes in world space
+    orientation: mat3x3<f32>,
+};
+
+struct Mesh {
+    triangle_index_start: u32,
+    triangle_count: u32,
+    _pad: vec2<u32>,
+    material: Material,
+}
+
+struct Material {
+    ambient: vec3<f32>,
+    _pad0: f32,
+    diffuse: vec3<f32>,
+    _pad1: f32,
+    specular: vec3<f32>,
+    shininess: f32,
+    emissive: vec3<f32>,
+    ior: f32,
+    opacity: f32,
+    illum: u32,
+    texture_index: i32,
+    _pad2: u32,
+    metallic: f32,
+    roughness: f32,
+    specular_level: f32,
+    sheen: f32,
+    clearcoat: f32,
+    clearcoat_roughness: f32,
+    roughness_texture: i32,
+    metallic_texture: i32,
+    normal_texture: i32,
+    bump_texture: i32,
+    bump_scale: f32,
+    _pad3: u32,
+    // Interior medium, all zero for none
+    medium_absorption: vec3<f32>,
+    medium_anisotropy: f32,
+    medium_scattering: vec3<f32>,
+    _pad4: u32,
+    // Procedural pattern (PATTERN_*) replacing the texture lookup
+    pattern_color_a: vec3<f32>,
+    pattern_scale: f32,
+    pattern_color_b: vec3<f32>,
+    pattern_kind: u32,
+    pattern_octaves: u32,
+    pattern_sharpness: f32,
+    // Alpha cutout (map_d), specular color (map_Ks), emission (map_Ke) and exponent (map_Ns) maps
+    opacity_texture: i32,
+    specular_texture: i32,
+    emissive_texture: i32,
+    shininess_texture: i32,
+    _pad5: u32,
+    _pad6: u32,
+}
+
+struct HitRecord {
+    hit: bool,
+    t: f32,
+    pos: vec3<f32>,
+    normal: vec3<f32>,
+    // Face normal of mesh triangles, zero for other objects
+    geometric_normal: vec3<f32>,
+    uv: vec2<f32>,
+    // Tangent and bitangent sign, zero if the surface has no tangent frame
+    tangent: vec4<f32>,
+    // Texture space units per world unit at the hit, 0 if unknown
+    uv_density: f32,
+    // Width of the ray cone in texture space, used to pick the mip level
+    footprint: f32,
+    use_texture: bool,
+    // Kind of the hit object (OBJECT_*) and its index within that kind
+    object: vec2<u32>,
+    material: Material,
+}
+
+struct PointLight {
+    center: vec3<f32>,
+    radius: f32,
+    material: Material,
+    // Spot axis, normal of rect and disc lights, direction sunlight travels
+    direction: vec3<f32>,
+    light_type: u32,
+    // Direction of a rect light's width
+    tangent: vec3<f32>,
+    cos_inner: f32,
+    half_size: vec2<f32>,
+    // Spot cone, or angular radius of a directional light
+    cos_outer: f32,
+    _pad0: u32,
+};
+
+const LIGHT_POINT: u32 = 0u;
+const LIGHT_DIRECTIONAL: u32 = 1u;
+const LIGHT_SPOT: u32 = 2u;
+const LIGHT_RECT: u32 = 3u;
+const LIGHT_DISC: u32 = 4u;
+
+struct GPUTriangle {
+    v0: vec3<f32>,
+    v0_index: u32,
+    v1: vec3<f32>,
+    v1_index: u32,
+    v2: vec3<f32>,
+    v2_index: u32,
+    n0: vec3<f32>,
+    mesh_index: u32,
+    n1: vec3<f32>,
+    _pad0: u32,
+    n2: vec3<f32>,
+    _pad1: u32,
+    t0: vec4<f32>,
+    t1: vec4<f32>,
+    t2: vec4<f32>,
+};
+
+struct BVHNode {
+    aabb_min: vec3<f32>,
+    _pad0: u32,
+    aabb_max: vec3<f32>,
+    _pad1: u32,
+    left: u32,
+    right: u32,
+    first_primitive: u32,
+    primitive_count: u32,
+};
+
+// Placement of a mesh's bottom-level BVH in the scene
+struct Instance {
+    object_to_world: mat4x4<f32>,
+    world_to_object: mat4x4<f32>,
+    blas_root: u32,
+    _pad0: u32,
+    _pad1: u32,
+    _pad2: u32,
+};
+
+struct TextureInfo {
+    offset: u32,
+    width: u32,
+    height: u32,
+    wrap_mode: u32,
+    mip_levels: u32,
+    // Opacity maps read the alpha channel if set, the red channel otherwise
+    has_alpha: u32,
+    _pad1: u32,
+    _pad2: u32,
+}
+
+struct EmissiveTriangle {
+    v0: vec3<f32>,
+    // Running sum of the power, the last entry holds the total
+    cdf: f32,
+    v1: vec3<f32>,
+    area: f32,
+    v2: vec3<f32>,
+    // Index of the mesh triangle
+    triangle: u32,
+    emission: vec3<f32>,
+    _pad1: u32,
+}
+
+struct SampleStats {
+    // Pixels of the current pass that still need samples
+    unconverged_pixels: atomic<u32>,
+    _pad0: u32,
+    _pad1: u32,
+    _pad2: u32,
+    // Luminance sum of squares, sample count and alpha sum per pixel
+    pixels: array<vec4<f32>>,
+}
+
+const PI: f32 = 3.14159265358979;
+
+@group(0) @binding(0) var<uniform> uniforms: Uniforms;
+@group(0) @binding(1) var<storage, read_write> output: array<u32>;
+@group(0) @binding(2) var<storage, read> spheres: array<Sphere>;
+@group(0) @binding(3) var<storage, read_write> accumulation: array<vec4<f32>>;
+@group(0) @binding(4) var<uniform> prh: ProgressiveRenderHelper;
+@group(0) @binding(5) var<storage, read> point_lights: array<PointLight>;
+@group(0) @binding(6) var<storage, read> meshes: array<Mesh>;
+@group(0) @binding(7) var<storage, read> bvh_nodes: array<BVHNode>;
+@group(0) @binding(8) var<storage, read> bvh_indices: array<u32>;
+@group(0) @binding(9) var<storage, read> bvh_triangles: array<GPUTriangle>;
+@group(0) @binding(10) var<storage, read> uvs: array<f32>;
+@group(0) @binding(11) var<storage, read> texture_data: array<u32>;
+@group(0) @binding(12) var<storage, read> texture_info: array<TextureInfo>;
+// Environment pixels (rgb, row CDF in w), followed by one entry per row (marginal CDF, row weight, total weight)
+@group(0) @binding(13) var<storage, read> environment: array<vec4<f32>>;
+// Auxiliary output layers of the enabled AOVs, one vec4 per pixel and layer
+@group(0) @binding(14) var<storage, read_write> aovs: array<vec4<f32>>;
+@group(0) @binding(15) var<storage, read_write> sample_stats: SampleStats;
+@group(0) @binding(16) var<storage, read> emissive_triangles: array<EmissiveTriangle>;
+@group(0) @binding(17) var<storage, read> instances: array<Instance>;
+
+fn ground_enabled() -> bool {
+    if (uniforms.ground_enabled > 0) {
+        return true;
+    } else {
+        return false;
+    }
+}
+
+// The ground only keeps the shadows and reflections of the other objects, over a transparent background
+fn shadow_catcher_enabled() -> bool {
+    return ground_enabled() && uniforms.shadow_catcher > 0u;
+}
+
+fn linear_to_gamma(lin_color: f32) -> f32 {
+    if (lin_color > 0.0) {
+        return sqrt(lin_color);
+    }
+    return 0.0;
+}
+
+fn color_map(color: vec3<f32>, alpha: f32) -> u32 {
+    let r: u32 = u32(clamp(linear_to_gamma(color.x), 0.0, 1.0) * 255.999);
+    let g: u32 = u32(clamp(linear_to_gamma(color.y), 0.0, 1.0) * 255.999);
+    let b: u32 = u32(clamp(linear_to_gamma(color.z), 0.0, 1.0) * 255.999);
+    let a: u32 = u32(clamp(alpha, 0.0, 1.0) * 255.999);
+
+    return (a << 24u) | (b << 16u) | (g << 8u) | r;
+}
+
+const TONE_MAPPING_NONE: u32 = 0u;
+const TONE_MAPPING_REINHARD: u32 = 1u;
+const TONE_MAPPING_ACES: u32 = 2u;
+const TONE_MAPPING_AGX: u32 = 3u;
+
+// Stephen Hill's fit of the ACES reference rendering and output device transforms
+fn tone_map_aces(color: vec3<f32>) -> vec3<f32> {
+    let input_matrix = mat3x3<f32>(
+        vec3<f32>(0.59719, 0.07600, 0.02840),
+        vec3<f32>(0.35458, 0.90834, 0.13383),
+        vec3<f32>(0.04823, 0.01566, 0.83777),
+    );
+    let output_matrix = mat3x3<f32>(
+        vec3<f32>(1.60475, -0.10208, -0.00327),
+        vec3<f32>(-0.53108, 1.10813, -0.07276),
+        vec3<f32>(-0.07367, -0.00605, 1.07602),
+    );
+    let v = input_matrix * color;
+    let a = v * (v + 0.0245786) - 0.000090537;
+    let b = v * (0.983729 * v + 0.4329510) + 0.238081;
+    return output_matrix * (a / b);
+}
+
+// Polynomial fit of the AgX default contrast curve
+fn agx_contrast(x: vec3<f32>) -> vec3<f32> {
+    let x2 = x * x;
+    let x4 = x2 * x2;
+    return 15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x - 0.00232;
+}
+
+// AgX base transform: log encoding in the AgX working space, sigmoid, back to linear sRGB
+fn tone_map_agx(color: vec3<f32>) -> vec3<f32> {
+    let inset = mat3x3<f32>(
+        vec3<f32>(0.842479062253094, 0.0423282422610123, 0.0423756549057051),
+        vec3<f32>(0.0784335999999992, 0.878468636469772, 0.0784336),
+        vec3<f32>(0.0792237451477643, 0.0791661274605434, 0.879142973793104),
+    );
+    let outset = mat3x3<f32>(
+        vec3<f32>(1.19687900512017, -0.0528968517574562, -0.0529716355144438),
+        vec3<f32>(-0.0980208811401368, 1.15190312990417, -0.0980434501171241),
+        vec3<f32>(-0.0990297440797205, -0.0989611768448433, 1.15107367264116),
+    );
+    let min_ev = -12.47393;
+    let max_ev = 4.026069;
+    let v = inset * max(color, vec3<f32>(1e-10));
+    let encoded = clamp((log2(v) - min_ev) / (max_ev - min_ev), vec3<f32>(0.0), vec3<f32>(1.0));
+    // The curve outputs display encoded values, undo the display gamma so color_map can apply it again
+    let display = outset * agx_contrast(encoded);
+    return pow(max(display, vec3<f32>(0.0)), vec3<f32>(2.2));
+}
+
+// Exposure and tone mapping of linear radiance, the result is still linear and gamma encoded in color_map
+fn tone_map(color: vec3<f32>) -> vec3<f32> {
+    let exposed = max(color, vec3<f32>(0.0)) * exp2(uniforms.exposure);
+    switch (uniforms.tone_mapping) {
+        case TONE_MAPPING_REINHARD: {
+            return exposed / (exposed + vec3<f32>(1.0));
+        }
+        case TONE_MAPPING_ACES: {
+            return tone_map_aces(exposed);
+        }
+        case TONE_MAPPING_AGX: {
+            return tone_map_agx(exposed);
+        }
+        case TONE_MAPPING_NONE, default: {
+            return exposed;
+        }
+    }
+}
+
+fn sample_texture(index: i32, uv: vec2<f32>, footprint: f32) -> vec3<f32> {
+    if (index < 0) {
+        if (uniforms.checkerboard_enabled > 0){
+            // Missing texture pattern (Magenta/Black checkerboard), box filtered over the footprint
+            let n = 10.0;
+            let p = uv * n;
+            let w = vec2<f32>(max(footprint * n, 1e-4));
+            let i = 2.0 * (abs(fract((p - 0.5 * w) * 0.5) - 0.5) - abs(fract((p + 0.5 * w) * 0.5) - 0.5)) / w;
+            let odd = 0.5 - 0.5 * i.x * i.y;
+            return mix(uniforms.checkerboard_color_1, uniforms.checkerboard_color_2, odd);
+        } else {
+            return vec3(1.0, 1.0, 1.0);
+        }
+        
+    }
+    let texel = fetch_texel(index, uv, footprint);
+
+    // Convert sRGB to Linear
+    return vec3<f32>(pow(texel.x, 2.2), pow(texel.y, 2.2), pow(texel.z, 2.2));
+}
+
+// Raw texel lookup without color conversion, for data maps such as roughness or metallic.
+// Filters trilinearly between the two mip levels closest to the footprint.
+fn fetch_texel(index: i32, uv: vec2<f32>, footprint: f32) -> vec3<f32> {
+    let info = texture_info[u32(index)];
+
+    let texels = footprint * f32(max(info.width, info.height));
+    let lod = clamp(log2(max(texels, 1e-8)), 0.0, f32(info.mip_levels - 1u));
+    let level = u32(lod);
+
+    let fine = sample_level(info, level, uv).xyz;
+    if (level + 1u >= info.mip_levels) {
+        return fine;
+    }
+    return mix(fine, sample_level(info, level + 1u, uv).xyz, fract(lod));
+}
+
+// Opacity of an alpha cutout map, looked up at full resolution so distant cutouts keep
+// their shape instead of fading with the mip chain
+fn fetch_opacity(index: i32, uv: vec2<f32>) -> f32 {
+    let info = texture_info[u32(index)];
+    let texel = sample_level(info, 0u, uv);
+    return select(texel.x, texel.w, info.has_alpha != 0u);
+}
+
+// Bilinear lookup in one mip level
+fn sample_level(info: TextureInfo, level: u32, uv: vec2<f32>) -> vec4<f32> {
+    // Levels are stored one after another, each half the size of the previous one
+    var offset = info.offset;
+    var width = info.width;
+    var height = info.height;
+    for (var l = 0u; l < level; l = l + 1u) {
+        offset = offset + width * height;
+        width = max(width / 2u, 1u);
+        height = max(height / 2u, 1u);
+    }
+
+    // Map to pixel coordinates, texel centers are at +0.5
+    // Flip V because standard UVs have (0,0) at bottom-left, but image data is top-left
+    let p = vec2<f32>(uv.x * f32(width), (1.0 - uv.y) * f32(height)) - 0.5;
+    let base = floor(p);
+    let f = p - base;
+
+    let x0 = u32(wrap_texel(i32(base.x), i32(width), info.wrap_mode));
+    let x1 = u32(wrap_texel(i32(base.x) + 1, i32(width), info.wrap_mode));
+    let y0 = u32(wrap_texel(i32(base.y), i32(height), info.wrap_mode));
+    let y1 = u32(wrap_texel(i32(base.y) + 1, i32(height), info.wrap_mode));
+
+    let t00 = unpack_texel(texture_data[offset + y0 * width + x0]);
+    let t10 = unpack_texel(texture_data[offset + y0 * width + x1]);
+    let t01 = unpack_texel(texture_data[offset + y1 * width + x0]);
+    let t11 = unpack_texel(texture_data[offset + y1 * width + x1]);
+
+    return mix(mix(t00, t10, f.x), mix(t01, t11, f.x), f.y);
+}
+
+// Maps a texel coordinate into [0, size) for the wrap mode: 0 repeat, 1 clamp, 2 mirror
+fn wrap_texel(i: i32, size: i32, wrap_mode: u32) -> i32 {
+    switch wrap_mode {
+        case 1u: {
+            return clamp(i, 0, size - 1);
+        }
+        case 2u: {
+            let period = 2 * size;
+            let m = ((i % period) + period) % period;
+            return select(m, period - 1 - m, m >= size);
+        }
+        default: {
+            return ((i % size) + size) % size;
+        }
+    }
+}
+
+// Unpack RGBA8 (Little Endian: A B G R)
+fn unpack_texel(pixel: u32) -> vec4<f32> {
+    let r = f32(pixel & 255u) / 255.0;
+    let g = f32((pixel >> 8u) & 255u) / 255.0;
+    let b = f32((pixel >> 16u) & 255u) / 255.0;
+    let a = f32(pixel >> 24u) / 255.0;
+    return vec4<f32>(r, g, b, a);
+}
+
+const PATTERN_NONE: u32 = 0u;
+const PATTERN_TRIPLANAR: u32 = 1u;
+const PATTERN_CHECKER: u32 = 2u;
+const PATTERN_GRADIENT: u32 = 3u;
+const PATTERN_NOISE: u32 = 4u;
+const PATTERN_VORONOI: u32 = 5u;
+
+// Base color of a hit: the diffuse color times the procedural pattern or the texture
+fn surface_base_color(hit: HitRecord) -> vec3<f32> {
+    if (hit.material.pattern_kind != PATTERN_NONE) {
+        return hit.material.diffuse * sample_pattern(hit);
+    }
+    if (hit.use_texture) {
+        return hit.material.diffuse * sample_texture(hit.material.texture_index, hit.uv, hit.footprint);
+    }
+    return hit.material.diffuse;
+}
+
+// Procedural pattern of the material at the world position of the hit
+fn sample_pattern(hit: HitRecord) -> vec3<f32> {
+    let m = hit.material;
+    let p = hit.pos * m.pattern_scale;
+    // Width of the ray cone in pattern space, 0 if the surface has no UV density
+    let width = m.pattern_scale * select(0.0, hit.footprint / hit.uv_density, hit.uv_density > 0.0);
+
+    var t = 0.0;
+    switch m.pattern_kind {
+        case PATTERN_TRIPLANAR: {
+            return sample_triplanar(m, p, hit.normal, width);
+        }
+        case PATTERN_CHECKER: {
+            // Not filtered along the normal, flat faces on a cell boundary would turn grey
+            let n = hit.normal;
+            t = checker_pattern(p, width * sqrt(max(vec3<f32>(1.0) - n * n, vec3<f32>(0.0))));
+        }
+        case PATTERN_GRADIENT: {
+            t = clamp(p.y, 0.0, 1.0);
+        }
+        case PATTERN_NOISE: {
+            t = clamp(0.5 + fbm(p, m.pattern_octaves, width), 0.0, 1.0);
+        }
+        case PATTERN_VORONOI: {
+            t = clamp(voronoi_distance(p), 0.0, 1.0);
+        }
+        default: {}
+    }
+    return mix(m.pattern_color_a, m.pattern_color_b, t);
+}
+
+// Diffuse texture projected along the x, y and z axes, blended by the normal
+fn sample_triplanar(m: Material, p: vec3<f32>, normal: vec3<f32>, width: f32) -> vec3<f32> {
+    var w = pow(abs(normal), vec3<f32>(m.pattern_sharpness));
+    w = w / max(w.x + w.y + w.z, 1e-6);
+    var color = vec3<f32>(0.0);
+    if (w.x > 1e-3) {
+        color += w.x * sample_texture(m.texture_index, p.zy, width);
+    }
+    if (w.y > 1e-3) {
+        color += w.y * sample_texture(m.texture_index, p.xz, width);
+    }
+    if (w.z > 1e-3) {
+        color += w.z * sample_texture(m.texture_index, p.xy, width);
+    }
+    return color;
+}
+
+// 3D checkerboard, 0 or 1 per unit cell, box filtered over `width` per axis
+fn checker_pattern(p: vec3<f32>, width: vec3<f32>) -> f32 {
+    let w = max(width, vec3<f32>(1e-3));
+    // The offset keeps faces on integer coordinates inside one cell
+    let q = p + 1e-3;
+    let i = 2.0 * (abs(fract((q - 0.5 * w) * 0.5) - 0.5) - abs(fract((q + 0.5 * w) * 0.5) - 0.5)) / w;
+    return 0.5 - 0.5 * i.x * i.y * i.z;
+}
+
+// Random point in [0, 1)^3 for a lattice cell
+fn lattice_hash(cell: vec3<f32>) -> vec3<f32> {
+    let c = bitcast<vec3<u32>>(vec3<i32>(cell));
+    let h = hash(hash_combine(hash_combine(hash(c.x), c.y), c.z));
+    let h2 = hash(h);
+    let h3 = hash(h2);
+    return vec3<f32>(f32(h >> 8u), f32(h2 >> 8u), f32(h3 >> 8u)) / 16777216.0;
+}
+
+// Contribution of one lattice corner to the gradient noise
+fn noise_corner(cell: vec3<f32>, f: vec3<f32>, corner: vec3<f32>) -> f32 {
+    let gradient = lattice_hash(cell + corner) * 2.0 - 1.0;
+    return dot(gradient, f - corner);
+}
+
+// Perlin gradient noise, roughly in [-1, 1]
+fn gradient_noise(p: vec3<f32>) -> f32 {
+    let cell = floor(p);
+    let f = p - cell;
+    let u = f * f * f * (f * (f * 6.0 - 15.0) + 10.0);
+    let x00 = mix(noise_corner(cell, f, vec3<f32>(0.0, 0.0, 0.0)), noise_corner(cell, f, vec3<f32>(1.0, 0.0, 0.0)), u.x);
+    let x10 = mix(noise_corner(cell, f, vec3<f32>(0.0, 1.0, 0.0)), noise_corner(cell, f, vec3<f32>(1.0, 1.0, 0.0)), u.x);
+    let x01 = mix(noise_corner(cell, f, vec3<f32>(0.0, 0.0, 1.0)), noise_corner(cell, f, vec3<f32>(1.0, 0.0, 1.0)), u.x);
+    let x11 = mix(noise_corner(cell, f, vec3<f32>(0.0, 1.0, 1.0)), noise_corner(cell, f, vec3<f32>(1.0, 1.0, 1.0)), u.x);
+    return mix(mix(x00, x10, u.y), mix(x01, x11, u.y), u.z);
+}
+
+// Fractal sum of noise octaves, each with twice the frequency and half the amplitude.
+// Octaves finer than the filter width fade out to their mean. Normalized so that the spread,
+// about 0.2, does not depend on the number of octaves.
+fn fbm(p: vec3<f32>, octaves: u32, width: f32) -> f32 {
+    var sum = 0.0;
+    var total = 0.0;
+    var amplitude = 1.0;
+    var frequency = 1.0;
+    for (var i = 0u; i < octaves; i = i + 1u) {
+        let fade = clamp(1.5 - frequency * width * 2.0, 0.0, 1.0);
+        sum += amplitude * fade * gradient_noise(p * frequency);
+        total += amplitude * amplitude;
+        amplitude *= 0.5;
+        frequency *= 2.0;
+    }
+    return sum / sqrt(total);
+}
+
+// Distance to the closest of the random feature points, one per unit cell
+fn voronoi_distance(p: vec3<f32>) -> f32 {
+    let cell = floor(p);
+    var closest = 8.0;
+    for (var z = -1; z <= 1; z = z + 1) {
+        for (var y = -1; y <= 1; y = y + 1) {
+            for (var x = -1; x <= 1; x = x + 1) {
+                let neighbour = cell + vec3<f32>(f32(x), f32(y), f32(z));
+                let feature = neighbour + lattice_hash(neighbour);
+                closest = min(closest, distance(p, feature));
+            }
+        }
+    }
+    return closest;
+}
+
+fn intersect_sphere(ray_origin: vec3<f32>, ray_dir: vec3<f32>, sphere: Sphere) -> f32 {
+    let oc = ray_origin - sphere.center;
+    let a = dot(ray_dir, ray_dir);
+    let half_b = dot(oc, ray_dir);
+    let c = dot(oc, oc) - sphere.radius * sphere.radius;
+    let discriminant = half_b * half_b - a * c;
+
+    if discriminant < 0.0 {
+        return -1.0;
+    }
+
+    let sqrtd = sqrt(discriminant);
+    var root = (-half_b - sqrtd) / a;
+
+    if root <= 0.001 {
+        root = (-half_b + sqrtd) / a;
+        if root <= 0.001 {
+            return -1.0;
+        }
+    }
+
+    return root;
+}
+
+fn intersect_pointlight(ray_origin: vec3<f32>, ray_dir: vec3<f32>, pointlight: PointLight) -> f32 {
+    let oc = ray_origin - pointlight.center;
+    let a = dot(ray_dir, ray_dir);
+    let half_b = dot(oc, ray_dir);
+    let c = dot(oc, oc) - pointlight.radius * pointlight.radius;
+    let discriminant = half_b * half_b - a * c;
+
+    if discriminant < 0.0 {
+        return -1.0;
+    }
+
+    let sqrtd = sqrt(discriminant);
+    var root = (-half_b - sqrtd) / a;
+
+    if root <= 0.001 {
+        root = (-half_b + sqrtd) / a;
+        if root <= 0.001 {
+            return -1.0;
+        }
+    }
+
+    return root;
+}
+
+// Hit distance on the plane of a rect or disc light, -1 outside of its shape. Both sides are solid.
+fn intersect_planar_light(ray_origin: vec3<f32>, ray_dir: vec3<f32>, light: PointLight) -> f32 {
+    let denom = dot(ray_dir, light.direction);
+    if (abs(denom) < 1e-8) {
+        return -1.0;
+    }
+    let t = dot(light.center - ray_origin, light.direction) / denom;
+    if (t <= 0.001) {
+        return -1.0;
+    }
+
+    let local = ray_origin + t * ray_dir - light.center;
+    if (light.light_type == LIGHT_RECT) {
+        let bitangent = cross(light.direction, light.tangent);
+        if (abs(dot(local, light.tangent)) > light.half_size.x || abs(dot(local, bitangent)) > light.half_size.y) {
+            return -1.0;
+        }
+    } else if (dot(local, local) > light.radius * light.radius) {
+        return -1.0;
+    }
+    return t;
+}
+
+// Hit distance on the geometry of any light, -1 if missed. Directional lights have no geometry.
+fn intersect_light(ray_origin: vec3<f32>, ray_dir: vec3<f32>, light: PointLight) -> f32 {
+    switch (light.light_type) {
+        case LIGHT_POINT, LIGHT_SPOT: {
+            return intersect_pointlight(ray_origin, ray_dir, light);
+        }
+        case LIGHT_RECT, LIGHT_DISC: {
+            return intersect_planar_light(ray_origin, ray_dir, light);
+        }
+        default: {
+            return -1.0;
+        }
+    }
+}
+
+struct TriangleData {
+    v0: vec3<f32>,
+    v1: vec3<f32>,
+    v2: vec3<f32>,
+    _pad: u32,
+};
+
+fn intersect_triangle(ray_origin: vec3<f32>, ray_dir: vec3<f32>, tri: TriangleData) -> vec3<f32> {
+    let edge1 = tri.v1 - tri.v0;
+    let edge2 = tri.v2 - tri.v0;
+    let h = cross(ray_dir, edge2);
+    let a = dot(edge1, h);
+
+    if abs(a) < 1e-6 {
+        return vec3<f32>(-1.0, 0.0, 0.0);
+    }
+
+    let f = 1.0 / a;
+    let s = ray_origin - tri.v0;
+    let u = f * dot(s, h);
+
+    if u < 0.0 || u > 1.0 {
+        return vec3<f32>(-1.0, 0.0, 0.0);
+    }
+
+    let q = cross(s, edge1);
+    let v = f * dot(ray_dir, q);
+
+    if v < 0.0 || u + v > 1.0 {
+        return vec3<f32>(-1.0, 0.0, 0.0);
+    }
+
+    let t = f * dot(edge2, q);
+
+    if t > 0.0 {
+        return vec3<f32>(t, u, v);
+    }
+
+    return vec3<f32>(-1.0, 0.0, 0.0);
+}
+
+// Interpolates the vertex normals of a triangle at the barycentric hit position.
+// Triangles without vertex normals (all zero) fall back to the flat face normal.
+fn triangle_normal(tri: GPUTriangle, u: f32, v: f32, w: f32) -> vec3<f32> {
+    let shading = w * tri.n0 + u * tri.n1 + v * tri.n2;
+    if (dot(shading, shading) < 1e-12) {
+        return normalize(cross(tri.v1 - tri.v0, tri.v2 - tri.v0));
+    }
+    return normalize(shading);
+}
+
+// Interpolates the vertex UVs of a triangle at the barycentric position (u, v)
+fn triangle_uv(tri: GPUTriangle, u: f32, v: f32) -> vec2<f32> {
+    let uv0 = vec2<f32>(uvs[tri.v0_index * 2u], uvs[tri.v0_index * 2u + 1u]);
+    let uv1 = vec2<f32>(uvs[tri.v1_index * 2u], uvs[tri.v1_index * 2u + 1u]);
+    let uv2 = vec2<f32>(uvs[tri.v2_index * 2u], uvs[tri.v2_index * 2u + 1u]);
+    return (1.0 - u - v) * uv0 + u * uv1 + v * uv2;
+}
+
+// Whether the opacity map of the triangle's material cuts the point (u, v) away
+fn is_cut_out(tri: GPUTriangle, u: f32, v: f32) -> bool {
+    let index = meshes[tri.mesh_index].material.opacity_texture;
+    return index >= 0 && fetch_opacity(index, triangle_uv(tri, u, v)) < 0.5;
+}
+
+const BVH_STACK_SIZE: u32 = 64u;
+// Instance of stack entries in the top-level BVH, which is traversed in world space
+const NO_INSTANCE: u32 = 0xffffffffu;
+
+// Traverses the top-level BVH over the mesh instances and, from its leaves, the bottom-level
+// BVHs of the instanced meshes. Bottom-level nodes are tested with the ray moved into the
+// space of their instance.
+fn intersect_bvh(ray_origin: vec3<f32>, ray_dir: vec3<f32>) -> HitRecord {
+    var hit = empty_hit();
+
+    if (uniforms.bvh_node_count == 0u) {
+        return hit;
+    }
+
+    // Node index and instance of each entry
+    var stack: array<vec2<u32>, BVH_STACK_SIZE>;
+    stack[0] = vec2<u32>(uniforms.bvh_root, NO_INSTANCE);
+    var sp = 1u;
+
+    var hit_triangle = 0u;
+    var hit_instance = 0u;
+    var hit_barycentrics = vec2<f32>(0.0);
+
+    loop {
+        if sp == 0u {
+            break;
+        }
+        sp = sp - 1u;
+        let entry = stack[sp];
+
+        if (entry.x >= uniforms.bvh_node_count) {
+            continue;
+        }
+
+        // The direction is not normalized in mesh space, so t is the same in both spaces
+        var origin = ray_origin;
+        var dir = ray_dir;
+        if (entry.y != NO_INSTANCE) {
+            let world_to_object = instances[entry.y].world_to_object;
+            origin = (world_to_object * vec4<f32>(ray_origin, 1.0)).xyz;
+            dir = (world_to_object * vec4<f32>(ray_dir, 0.0)).xyz;
+        }
+
+        let node = bvh_nodes[entry.x];
+
+        if (!intersect_aabb(origin, dir, node.aabb_min, node.aabb_max)) {
+            continue;
+        }
+
+        if node.primitive_count == 0u {
+            if (node.left < uniforms.bvh_node_count && sp < BVH_STACK_SIZE) {
+                stack[sp] = vec2<u32>(node.left, entry.y);
+                sp = sp + 1u;
+            }
+            if (node.right < uniforms.bvh_node_count && sp < BVH_STACK_SIZE) {
+                stack[sp] = vec2<u32>(node.right, entry.y);
+                sp = sp + 1u;
+            }
+            continue;
+        }
+
+        let index_count = arrayLength(&bvh_indices);
+        for (var i: u32 = 0u; i < node.primitive_count; i = i + 1u) {
+            let index = node.first_primitive + i;
+            if (index >= index_count) {
+                continue;
+            }
+            let primitive = bvh_indices[index];
+
+            // Top-level leaves hold instances, continue in their bottom-level BVH
+            if (entry.y == NO_INSTANCE) {
+                if (sp < BVH_STACK_SIZE) {
+                    stack[sp] = vec2<u32>(instances[primitive].blas_root, primitive);
+                    sp = sp + 1u;
+                }
+                continue;
+            }
+
+            if (primitive >= uniforms.bvh_triangle_count) {
+                continue;
+            }
+
+            let tri = bvh_triangles[primitive];
+            let hit_data = intersect_triangle(origin, dir, TriangleData(tri.v0, tri.v1, tri.v2, 0u));
+            let t = hit_data.x;
+
+            // Cut out texels are skipped like misses, which also lets shadow rays through
+            if (t > 0.001 && t < hit.t && !is_cut_out(tri, hit_data.y, hit_data.z)) {
+                hit.hit = true;
+                hit.t = t;
+                hit_triangle = primitive;
+                hit_instance = entry.y;
+                hit_barycentrics = hit_data.yz;
+            }
+        }
+    }
+
+    if (!hit.hit) {
+        return hit;
+    }
+
+    let tri = bvh_triangles[hit_triangle];
+    let instance = instances[hit_instance];
+    let u = hit_barycentrics.x;
+    let v = hit_barycentrics.y;
+    let w = 1.0 - u - v;
+
+    hit.pos = ray_origin + hit.t * ray_dir;
+    // Normals move to world space with the inverse transpose of the instance transform
+    let face = cross(tri.v1 - tri.v0, tri.v2 - tri.v0);
+    hit.normal = normalize((vec4<f32>(triangle_normal(tri, u, v, w), 0.0) * instance.world_to_object).xyz);
+    hit.geometric_normal = normalize((vec4<f32>(face, 0.0) * instance.world_to_object).xyz);
+    hit.object = vec2<u32>(OBJECT_MESH, tri.mesh_index);
+
+    let uv0 = vec2<f32>(uvs[tri.v0_index * 2u], uvs[tri.v0_index * 2u + 1u]);
+    let uv1 = vec2<f32>(uvs[tri.v1_index * 2u], uvs[tri.v1_index * 2u + 1u]);
+    let uv2 = vec2<f32>(uvs[tri.v2_index * 2u], uvs[tri.v2_index * 2u + 1u]);
+
+    hit.uv = w * uv0 + u * uv1 + v * uv2;
+    let uv_area = abs((uv1.x - uv0.x) * (uv2.y - uv0.y) - (uv2.x - uv0.x) * (uv1.y - uv0.y));
+    let edge1 = (instance.object_to_world * vec4<f32>(tri.v1 - tri.v0, 0.0)).xyz;
+    let edge2 = (instance.object_to_world * vec4<f32>(tri.v2 - tri.v0, 0.0)).xyz;
+    let world_area = length(cross(edge1, edge2));
+    hit.uv_density = sqrt(uv_area / max(world_area, 1e-12));
+    let tangent = w * tri.t0 + u * tri.t1 + v * tri.t2;
+    hit.tangent = vec4<f32>((instance.object_to_world * vec4<f32>(tangent.xyz, 0.0)).xyz, tangent.w);
+
+    if (uniforms.color_hash_enabled != 0u) {
+        hit.material.diffuse = hash_to_color(hit_triangle + 1u);
+        hit.material.ambient = vec3<f32>(0.0);
+        hit.material.specular = vec3<f32>(0.0);
+        hit.material.pattern_kind = PATTERN_NONE;
+        hit.use_texture = false;
+    } else {
+        hit.material = meshes[tri.mesh_index].material;
+        // Use texture if material has a valid texture index
+        hit.use_texture = hit.material.texture_index >= 0;
+    }
+
+    return hit;
+}
+
+fn hash_to_color(n: u32) -> vec3<f32> {
+    let h = n * 2654435761u;
+    let r = f32(h % 41u) / 40.0;
+    let g = f32(h % 29u) / 28.0;
+    let b = f32(h % 19u) / 18.0;
+    return vec3<f32>(r, g, b);
+}
+
+fn intersect_ground(ray_origin: vec3<f32>, ray_dir: vec3<f32>) -> f32 {
+    if (abs(ray_dir.y) < 1e-6) {
+        return -1.0;
+    }
+
+    let t = (uniforms.ground_height - ray_origin.y) / ray_dir.y;
+
+    if (t > 0.0) {
+        return t;
+    }
+
+    return -1.0;
+}
+
+// PCG hash
+fn hash(seed: u32) -> u32 {
+    var state = seed * 747796405u + 2891336453u;
+    var word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+fn hash_combine(seed: u32, value: u32) -> u32 {
+    return seed ^ (value + (seed << 6u) + (seed >> 2u));
+}
+
+// Low-discrepancy sampler with Owen-scrambled Sobol points, after Burley,
+// "Practical Hash-based Owen Scrambling" (JCGT 2020).
+//
+// Every draw takes its own dimension, the bounce in the upper and a running slot in the lower
+// 16 bits. Per dimension the sample index is shuffled and the 2D Sobol point is scrambled with
+// seeds hashed from the pixel and the dimension. Each 1D and 2D projection stays stratified,
+// while dimensions and neighbouring pixels are decorrelated. The ray tracer only samples the
+// pixel jitter and the lens, in bounce 0.
+struct Sampler {
+    // Index of the sample within the pixel
+    index: u32,
+    // Hash of the pixel
+    seed: u32,
+    // Dimension of the next draw
+    dimension: u32,
+}
+
+fn sampler_new(pixel_index: u32, sample_index: u32) -> Sampler {
+    return Sampler(sample_index, hash(pixel_index), 0u);
+}
+
+fn laine_karras_permutation(x0: u32, seed: u32) -> u32 {
+    var x = x0 + seed;
+    x ^= x * 0x6c50b47cu;
+    x ^= x * 0xb82f1e52u;
+    x ^= x * 0xc7afe638u;
+    x ^= x * 0x8d22f6e6u;
+    return x;
+}
+
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    return reverseBits(laine_karras_permutation(reverseBits(x), seed));
+}
+
+// First two Sobol dimensions: van der Corput and its (0,2)-sequence partner
+fn sobol_2d(index: u32) -> vec2<u32> {
+    var y = 0u;
+    var v = 0x80000000u;
+    var i = index;
+    while (i != 0u) {
+        if ((i & 1u) != 0u) {
+            y ^= v;
+        }
+        i = i >> 1u;
+        v ^= v >> 1u;
+    }
+    return vec2<u32>(reverseBits(index), y);
+}
+
+fn sample_2d(rng: ptr<function, Sampler>) -> vec2<f32> {
+    let seed = hash(hash_combine((*rng).seed, (*rng).dimension));
+    (*rng).dimension += 1u;
+
+    let index = nested_uniform_scramble((*rng).index, seed);
+    let p = sobol_2d(index);
+    let x = nested_uniform_scramble(p.x, hash_combine(seed, 0u));
+    let y = nested_uniform_scramble(p.y, hash_combine(seed, 1u));
+    // The upper 24 bits fit into the mantissa, so the result stays below 1.0
+    return vec2<f32>(vec2<u32>(x, y) >> vec2<u32>(8u)) / 16777216.0;
+}
+
+fn sample_1d(rng: ptr<function, Sampler>) -> f32 {
+    return sample_2d(rng).x;
+}
+
+// Point on the unit aperture: a disk, or a regular polygon with `blades` corners on the unit circle
+fn sample_aperture(blades: u32, rng: ptr<function, Sampler>) -> vec2<f32> {
+    let u = sample_2d(rng);
+    let u1 = u.x;
+    let u2 = u.y;
+    if (blades < 3u) {
+        let r = sqrt(u1);
+        let phi = 2.0 * PI * u2;
+        return r * vec2<f32>(cos(phi), sin(phi));
+    }
+
+    // One of the triangles fanning out from the center, then a uniform point inside it
+    let n = f32(blades);
+    let k = min(floor(sample_1d(rng) * n), n - 1.0);
+    let a0 = 2.0 * PI * k / n;
+    let a1 = 2.0 * PI * (k + 1.0) / n;
+    var s = u1;
+    var t = u2;
+    if (s + t > 1.0) {
+        s = 1.0 - s;
+        t = 1.0 - t;
+    }
+    return s * vec2<f32>(cos(a0), sin(a0)) + t * vec2<f32>(cos(a1), sin(a1));
+}
+
+fn reflect_vector(v: vec3<f32>, n: vec3<f32>) -> vec3<f32> {
+    return v - 2.0 * dot(v, n) * n;
+}
+
+// Shading normal with the normal map and height map of the material applied.
+// Only hits with a tangent frame are perturbed, other surfaces keep their normal.
+fn shading_normal(hit: HitRecord) -> vec3<f32> {
+    let material = hit.material;
+    if ((material.normal_texture < 0 && material.bump_texture < 0) || dot(hit.tangent.xyz, hit.tangent.xyz) < 1e-12) {
+        return hit.normal;
+    }
+    let n = hit.normal;
+    let t = normalize(hit.tangent.xyz - n * dot(n, hit.tangent.xyz));
+    let b = cross(n, t) * select(1.0, -1.0, hit.tangent.w < 0.0);
+
+    var mapped = n;
+    if (material.normal_texture >= 0) {
+        // Tangent space normal, OpenGL convention (green = +V)
+        let m = fetch_texel(material.normal_texture, hit.uv, hit.footprint) * 2.0 - 1.0;
+        let tilted = vec3<f32>(m.xy * material.bump_scale, max(m.z, 1e-3));
+        mapped = normalize(t * tilted.x + b * tilted.y + n * tilted.z);
+    }
+    if (material.bump_texture >= 0) {
+        // Height differences to the neighbouring texels tilt the normal
+        let info = texture_info[u32(material.bump_texture)];
+        let texel = vec2<f32>(1.0 / f32(info.width), 1.0 / f32(info.height));
+        let h = fetch_texel(material.bump_texture, hit.uv, hit.footprint).x;
+        let dh_du = fetch_texel(material.bump_texture, hit.uv + vec2<f32>(texel.x, 0.0), hit.footprint).x - h;
+        let dh_dv = fetch_texel(material.bump_texture, hit.uv + vec2<f32>(0.0, texel.y), hit.footprint).x - h;
+        mapped = normalize(mapped - material.bump_scale * (dh_du * t + dh_dv * b));
+    }
+    return mapped;
+}
+
+struct Principled {
+    base_color: vec3<f32>,
+    metallic: f32,
+    alpha: f32,
+    specular: f32,
+    sheen: f32,
+    clearcoat: f32,
+    clearcoat_alpha: f32,
+};
+
+// Resolves the principled BSDF parameters at a hit, `base_color` already includes the texture
+fn principled_from_hit(hit: HitRecord, base_color: vec3<f32>) -> Principled {
+    var metallic = hit.material.metallic;
+    var roughness = hit.material.roughness;
+    var specular = hit.material.specular_level;
+    if (hit.material.metallic_texture >= 0) {
+        metallic *= fetch_texel(hit.material.metallic_texture, hit.uv, hit.footprint).x;
+    }
+    if (hit.material.roughness_texture >= 0) {
+        roughness *= fetch_texel(hit.material.roughness_texture, hit.uv, hit.footprint).x;
+    } else if (hit.material.shininess_texture >= 0) {
+        // Same conversion from the specular exponent as on the scene side
+        let exponent = hit.material.shininess * fetch_texel(hit.material.shininess_texture, hit.uv, hit.footprint).x;
+        roughness = 1.0 - sqrt(clamp(exponent / 1000.0, 0.0, 1.0));
+    }
+    if (hit.material.specular_texture >= 0) {
+        let tint = sample_texture(hit.material.specular_texture, hit.uv, hit.footprint);
+        specular *= (tint.x + tint.y + tint.z) / 3.0;
+    }
+    // alpha is clamped so near-perfect mirrors stay numerically stable
+    return Principled(
+        base_color,
+        clamp(metallic, 0.0, 1.0),
+        max(roughness * roughness, 1e-3),
+        specular,
+        hit.material.sheen,
+        hit.material.clearcoat,
+        max(hit.material.clearcoat_roughness * hit.material.clearcoat_roughness, 1e-3)
+    );
+}
+
+// Emitted radiance of a hit, the emissive color scaled by the emission map
+fn surface_emission(hit: HitRecord) -> vec3<f32> {
+    if (hit.material.emissive_texture < 0) {
+        return hit.material.emissive;
+    }
+    return hit.material.emissive * sample_texture(hit.material.emissive_texture, hit.uv, hit.footprint);
+}
+
+fn luminance(c: vec3<f32>) -> f32 {
+    return dot(c, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+fn fresnel_schlick(f0: vec3<f32>, cos_theta: f32) -> vec3<f32> {
+    return f0 + (vec3<f32>(1.0) - f0) * pow(1.0 - clamp(cos_theta, 0.0, 1.0), 5.0);
+}
+
+fn specular_f0(p: Principled) -> vec3<f32> {
+    return mix(vec3<f32>(0.08 * p.specular), p.base_color, p.metallic);
+}
+
+// Illumination models 4, 6 and 7 describe glass-like surfaces that refract
+fn is_dielectric(material: Material) -> bool {
+    return material.illum == 4u || material.illum == 6u || material.illum == 7u;
+}
+
+// Schlick's approximation of the Fresnel reflectance
+fn reflectance(cos_theta: f32, eta: f32) -> f32 {
+    var r0 = (1.0 - eta) / (1.0 + eta);
+    r0 = r0 * r0;
+    return r0 + (1.0 - r0) * pow(1.0 - cos_theta, 5.0);
+}
+
+// Beer-Lambert absorption for a path segment of length `distance` inside the medium.
+// Kd is read as the transmittance after one scene unit. Exporters often leave Kd black
+// for glass, so a black Kd is treated as clear.
+fn dielectric_absorption(material: Material, distance: f32) -> vec3<f32> {
+    if (max(max(material.diffuse.x, material.diffuse.y), material.diffuse.z) <= 0.0) {
+        return vec3<f32>(1.0);
+    }
+    let sigma_a = -log(clamp(material.diffuse, vec3<f32>(1e-4), vec3<f32>(1.0)));
+    return exp(-sigma_a * distance);
+}
+
+fn collision(origin: vec3<f32>, light_dir: vec3<f32>, max_dist: f32) -> bool {
+    if (ground_enabled()) {
+        let t = intersect_ground(origin, light_dir);
+        if (t > 0.001 && t < max_dist) {
+            return true;
+        }
+    }
+
+    let bvh_hit = intersect_bvh(origin, light_dir);
+    if (bvh_hit.hit && bvh_hit.t < max_dist) {
+        return true;
+    }
+
+    for (var k = 0u; k < uniforms.spheres_count; k = k + 1u) {
+        let t = intersect_sphere(origin, light_dir, spheres[k]);
+        if (t > 0.001 && t < max_dist) {
+            return true;
+        }
+    }
+
+    for (var k = 0u; k < arrayLength(&point_lights); k = k + 1u) {
+            let t = intersect_light(origin, light_dir, point_lights[k]);
+            if (t > 0.001 && t < max_dist) {
+                return true;
+            }
+        }
+
+    return false;
+}
+
+// Homogeneous participating medium, coefficients per unit length
+struct Medium {
+    absorption: vec3<f32>,
+    scattering: vec3<f32>,
+    // Henyey-Greenstein asymmetry
+    g: f32,
+};
+
+fn global_medium() -> Medium {
+    return Medium(uniforms.medium_absorption, uniforms.medium_scattering, uniforms.medium_anisotropy);
+}
+
+fn material_medium(material: Material) -> Medium {
+    return Medium(material.medium_absorption, material.medium_scattering, material.medium_anisotropy);
+}
+
+fn has_medium(m: Medium) -> bool {
+    let sigma_t = m.absorption + m.scattering;
+    return max(max(sigma_t.x, sigma_t.y), sigma_t.z) > 0.0;
+}
+
+// Shadow rays only have to look for medium boundaries if the scene contains any media
+fn media_enabled() -> bool {
+    return has_medium(global_medium()) || uniforms.interior_media != 0u;
+}
+
+fn medium_transmittance(m: Medium, distance: f32) -> vec3<f32> {
+    return exp(-(m.absorption + m.scattering) * distance);
+}
+
+// Non-dielectric objects with an interior medium have no surface of their own (smoke, fog
+// volumes), rays pass through their boundary and only change the medium they travel in
+fn is_medium_boundary(hit: HitRecord) -> bool {
+    let object = hit.object.x == OBJECT_SPHERE || hit.object.x == OBJECT_MESH;
+    return object && !is_dielectric(hit.material) && has_medium(material_medium(hit.material));
+}
+
+// Medium a ray continues in after crossing the boundary of `hit`: the object's interior
+// medium when entering it, the global medium when leaving it
+fn medium_behind(hit: HitRecord, direction: vec3<f32>) -> Medium {
+    if (dot(direction, hit.normal) < 0.0) {
+        return material_medium(hit.material);
+    }
+    return global_medium();
+}
+
+// Henyey-Greenstein phase function for the angle between the propagation directions before
+// and after scattering, `g > 0` scatters forwards
+fn hg_phase(cos_theta: f32, g: f32) -> f32 {
+    let denom = max(1.0 + g * g - 2.0 * g * cos_theta, 1e-6);
+    return (1.0 - g * g) / (4.0 * PI * denom * sqrt(denom));
+}
+
+const MAX_MEDIUM_BOUNDARIES: u32 = 8u;
+
+// Transmittance of a shadow ray starting in `medium` up to `max_dist`, zero if a surface blocks
+// it. The ray passes through medium boundaries and is attenuated by every medium on its way, so
+// lights at infinity are fully extinguished by a global medium.
+fn shadow_transmittance(origin: vec3<f32>, dir: vec3<f32>, max_dist: f32, medium: Medium) -> vec3<f32> {
+    if (!media_enabled()) {
+        return select(vec3<f32>(1.0), vec3<f32>(0.0), collision(origin, dir, max_dist));
+    }
+
+    var transmittance = vec3<f32>(1.0);
+    var current = medium;
+    var pos = origin;
+    var remaining = max_dist;
+    for (var i = 0u; i < MAX_MEDIUM_BOUNDARIES; i = i + 1u) {
+        let hit = intersect_scene(pos, dir);
+        if (!hit.hit || hit.t >= remaining) {
+            return transmittance * medium_transmittance(current, remaining);
+        }
+        if (!is_medium_boundary(hit)) {
+            return vec3<f32>(0.0);
+        }
+        transmittance *= medium_transmittance(current, hit.t);
+        current = medium_behind(hit, dir);
+        pos = hit.pos + 0.001 * dir;
+        remaining -= hit.t + 0.001;
+    }
+    return vec3<f32>(0.0);
+}
+
+fn planar_light_area(light: PointLight) -> f32 {
+    if (light.light_type == LIGHT_RECT) {
+        return 4.0 * light.half_size.x * light.half_size.y;
+    }
+    return PI * light.radius * light.radius;
+}
+
+// Smooth spot falloff between the inner and the outer cone for light leaving along `out_dir`
+fn spot_falloff(light: PointLight, out_dir: vec3<f32>) -> f32 {
+    let cos_angle = dot(out_dir, light.direction);
+    if (light.cos_inner <= light.cos_outer) {
+        return select(0.0, 1.0, cos_angle >= light.cos_outer);
+    }
+    return smoothstep(light.cos_outer, light.cos_inner, cos_angle);
+}
+
+// Radiance of a light seen from `origin` along `dir`. The spot falloff depends on the direction
+// from the center to `origin`, so it doesn't depend on how `dir` was chosen.
+fn light_emission(light: PointLight, origin: vec3<f32>, dir: vec3<f32>) -> vec3<f32> {
+    let emissive = light.material.emissive;
+    switch (light.light_type) {
+        case LIGHT_SPOT: {
+            return emissive * spot_falloff(light, normalize(origin - light.center));
+        }
+        case LIGHT_RECT, LIGHT_DISC: {
+            // One-sided
+            return select(vec3<f32>(0.0), emissive, dot(dir, light.direction) < 0.0);
+        }
+        default: {
+            return emissive;
+        }
+    }
+}
+
+// 1 - cos of the angular radius of a directional light, 0 for a perfectly sharp one
+fn directional_extent(light: PointLight) -> f32 {
+    let cos_max = clamp(light.cos_outer, -1.0, 1.0);
+    let sin2_max = 1.0 - cos_max * cos_max;
+    return select(0.0, sin2_max / (1.0 + cos_max), cos_max < 1.0);
+}
+
+fn environment_enabled() -> bool {
+    return uniforms.environment_width > 0u && uniforms.environment_height > 0u;
+}
+
+// Equirectangular coordinates of a direction, v = 0 is the zenith
+fn environment_uv(dir: vec3<f32>) -> vec2<f32> {
+    let phi = atan2(dir.z, dir.x) - uniforms.environment_rotation;
+    let theta = acos(clamp(dir.y, -1.0, 1.0));
+    return vec2<f32>(fract(phi / (2.0 * PI)), theta / PI);
+}
+
+// Pixel of the environment map seen in direction `dir`
+fn environment_index(dir: vec3<f32>) -> u32 {
+    let w = uniforms.environment_width;
+    let h = uniforms.environment_height;
+    let uv = environment_uv(dir);
+    let x = min(u32(uv.x * f32(w)), w - 1u);
+    let y = min(u32(uv.y * f32(h)), h - 1u);
+    return y * w + x;
+}
+
+// Radiance arriving from `dir`, falls back to the sky color.
+// Pixels are not filtered, so the radiance matches the piecewise constant sampling pdf of the
+// path tracer and small bright sources get the full benefit of MIS.
+fn environment_radiance(dir: vec3<f32>) -> vec3<f32> {
+    if (!environment_enabled()) {
+        return uniforms.sky_color;
+    }
+    return environment[environment_index(dir)].xyz * uniforms.environment_intensity;
+}
+
+fn emissive_triangles_enabled() -> bool {
+    return uniforms.emissive_triangle_count > 0u && uniforms.color_hash_enabled == 0u;
+}
+
+fn emissive_total_power() -> f32 {
+    return emissive_triangles[uniforms.emissive_triangle_count - 1u].cdf;
+}
+
+// Picks an emissive triangle proportional to its power `luminance(emission) · area`, `u` in [0, 1)
+fn pick_emissive_triangle(u: f32) -> EmissiveTriangle {
+    let r = u * emissive_total_power();
+    var lo = 0u;
+    var hi = uniforms.emissive_triangle_count - 1u;
+    while (lo < hi) {
+        let mid = (lo + hi) / 2u;
+        if (emissive_triangles[mid].cdf <= r) {
+            lo = mid + 1u;
+        } else {
+            hi = mid;
+        }
+    }
+    return emissive_triangles[lo];
+}
+
+const OBJECT_NONE: u32 = 0u;
+const OBJECT_GROUND: u32 = 1u;
+const OBJECT_SPHERE: u32 = 2u;
+const OBJECT_MESH: u32 = 3u;
+const OBJECT_LIGHT: u32 = 4u;
+
+fn empty_hit() -> HitRecord {
+    return HitRecord(
+        false,
+        1e20,
+        vec3<f32>(0.0),
+        vec3<f32>(0.0),
+        vec3<f32>(0.0),
+        vec2<f32>(0.0),
+        vec4<f32>(0.0),
+        0.0,
+        0.0,
+        false,
+        vec2<u32>(OBJECT_NONE, 0u),
+        Material(
+            vec3<f32>(0.0), 0.0,
+            vec3<f32>(0.0), 0.0,
+            vec3<f32>(0.0), 0.0,
+            vec3<f32>(0.0), 0.0,
+            1.0, 0u, -1, 0u,
+            0.0, 1.0, 0.0, 0.0, 0.0, 0.0, -1, -1,
+            -1, -1, 1.0, 0u,
+            vec3<f32>(0.0), 0.0, vec3<f32>(0.0), 0u,
+            vec3<f32>(0.0), 1.0, vec3<f32>(0.0), PATTERN_NONE,
+            1u, 1.0, -1, -1,
+            -1, -1, 0u, 0u
+        )
+    );
+}
+
+// Spherical UVs of a sphere hit with the tangent frame along them.
+// In the local frame of the sphere v runs from the south pole (-y) to the north pole (+y)
+// and u runs around the y axis, starting and ending at the seam at -z.
+fn sphere_mapping(hit: ptr<function, HitRecord>, sphere: Sphere) {
+    let d = transpose(sphere.orientation) * (*hit).normal;
+    let ring = length(d.xz);
+    (*hit).uv = vec2<f32>(0.5 + atan2(d.x, d.z) / (2.0 * PI), 0.5 + asin(clamp(d.y, -1.0, 1.0)) / PI);
+    // dP/du points along the ring, any direction at the poles
+    let tangent = select(vec3<f32>(1.0, 0.0, 0.0), vec3<f32>(d.z, 0.0, -d.x) / ring, ring > 1e-6);
+    (*hit).tangent = vec4<f32>(sphere.orientation * tangent, 1.0);
+    // u covers 2 pi r cos(latitude) and v covers pi r
+    (*hit).uv_density = 1.0 / (PI * sphere.radius * sqrt(2.0 * max(ring, 1e-3)));
+}
+
+// Closest hit along the ray over the ground, the BVH triangles, the spheres and the lights
+fn intersect_scene(origin: vec3<f32>, direction: vec3<f32>) -> HitRecord {
+    var closest_hit = empty_hit();
+
+    // Ground
+    if (ground_enabled()) {
+        let t = intersect_ground(origin, direction);
+        if (t > 0.001 && t < closest_hit.t) {
+            closest_hit.hit = true;
+            closest_hit.t = t;
+            closest_hit.pos = origin + t * direction;
+            closest_hit.normal = vec3<f32>(0.0, 1.0, 0.0);
+            closest_hit.material = uniforms.ground_material;
+            closest_hit.uv = closest_hit.pos.xz;
+            // u runs along x and v along z
+            closest_hit.tangent = vec4<f32>(1.0, 0.0, 0.0, -1.0);
+            closest_hit.uv_density = 1.0;
+            closest_hit.use_texture = true;
+            closest_hit.object = vec2<u32>(OBJECT_GROUND, 0u);
+        }
+    }
+
+    // BVH Triangles
+    let bvh_hit = intersect_bvh(origin, direction);
+    if bvh_hit.hit && bvh_hit.t < closest_hit.t {
+        closest_hit = bvh_hit;
+    }
+
+    // Spheres
+    for (var k = 0u; k < uniforms.spheres_count; k = k + 1u) {
+        let sphere = spheres[k];
+        let t = intersect_sphere(origin, direction, sphere);
+
+        if (t > 0.001 && t < closest_hit.t) {
+            closest_hit.hit = true;
+            closest_hit.t = t;
+            closest_hit.pos = origin + t * direction;
+            closest_hit.normal = normalize(closest_hit.pos - sphere.center);
+            closest_hit.material = sphere.material;
+            closest_hit.use_texture = closest_hit.material.texture_index >= 0;
+            closest_hit.object = vec2<u32>(OBJECT_SPHERE, k);
+        }
+    }
+    if (closest_hit.object.x == OBJECT_SPHERE) {
+        sphere_mapping(&closest_hit, spheres[closest_hit.object.y]);
+    }
+
+    // Lights
+    for (var k = 0u; k < arrayLength(&point_lights); k = k + 1u) {
+        let point_light = point_lights[k];
+        let t = intersect_light(origin, direction, point_light);
+
+        if (t > 0.001 && t < closest_hit.t) {
+            closest_hit.hit = true;
+            closest_hit.t = t;
+            closest_hit.pos = origin + t * direction;
+            closest_hit.normal = normalize(closest_hit.pos - point_light.center);
+            if (point_light.light_type == LIGHT_RECT || point_light.light_type == LIGHT_DISC) {
+                closest_hit.normal = point_light.direction;
+            }
+            closest_hit.tangent = vec4<f32>(0.0);
+            closest_hit.uv_density = 0.0;
+            closest_hit.material = point_light.material;
+            closest_hit.object = vec2<u32>(OBJECT_LIGHT, k);
+        }
+    }
+
+    return closest_hit;
+}
+
+fn intersect_aabb(ray_origin: vec3<f32>, ray_dir: vec3<f32>, aabb_min: vec3<f32>, aabb_max: vec3<f32>) -> bool {
+    let inv_dir = 1.0 / ray_dir;
+    let t0s = (aabb_min - ray_origin) * inv_dir;
+    let t1s = (aabb_max - ray_origin) * inv_dir;
+    let tmin = max(max(min(t0s.x, t1s.x), min(t0s.y, t1s.y)), min(t0s.z, t1s.z));
+    let tmax = min(min(max(t0s.x, t1s.x), max(t0s.y, t1s.y)), max(t0s.z, t1s.z));
+    return tmax >= max(tmin, 0.0);
+}
+
+const AOV_DEPTH: u32 = 0u;
+const AOV_NORMAL: u32 = 1u;
+const AOV_ALBEDO: u32 = 2u;
+const AOV_OBJECT_ID: u32 = 3u;
+const AOV_UV: u32 = 4u;
+
+fn aov_enabled(aov: u32) -> bool {
+    return (uniforms.aov_flags & (1u << aov)) != 0u;
+}
+
+// Layers of the enabled AOVs are packed in the order of their flags
+fn aov_index(aov: u32, pixel_index: u32) -> u32 {
+    let layer = countOneBits(uniforms.aov_flags & ((1u << aov) - 1u));
+    return layer * prh.tile_width * prh.tile_height + pixel_index;
+}
+
+// Writes the first hit of a camera ray to the enabled AOV layers, `w` holds the coverage
+fn write_aovs(pixel_index: u32, origin: vec3<f32>, direction: vec3<f32>, camera_forward: vec3<f32>) {
+    let hit = intersect_scene(origin, direction);
+    let coverage = select(0.0, 1.0, hit.hit);
+
+    var depth = 0.0;
+    var normal = vec3<f32>(0.0);
+    var albedo = vec3<f32>(0.0);
+    if (hit.hit) {
+        // Distance along the view axis, not along the ray
+        depth = hit.t * dot(direction, camera_forward);
+        normal = shading_normal(hit);
+        if (dot(normal, direction) > 0.0) {
+            normal = -normal;
+        }
+        if (is_dielectric(hit.material)) {
+            albedo = vec3<f32>(1.0);
+        } else {
+            albedo = surface_base_color(hit);
+        }
+    }
+
+    if (aov_enabled(AOV_DEPTH)) {
+        aovs[aov_index(AOV_DEPTH, pixel_index)] = vec4<f32>(vec3<f32>(depth), coverage);
+    }
+    if (aov_enabled(AOV_NORMAL)) {
+        aovs[aov_index(AOV_NORMAL, pixel_index)] = vec4<f32>(normal, coverage);
+    }
+    if (aov_enabled(AOV_ALBEDO)) {
+        aovs[aov_index(AOV_ALBEDO, pixel_index)] = vec4<f32>(albedo, coverage);
+    }
+    if (aov_enabled(AOV_OBJECT_ID)) {
+        aovs[aov_index(AOV_OBJECT_ID, pixel_index)] = vec4<f32>(f32(hit.object.x), f32(hit.object.y), 0.0, coverage);
+    }
+    if (aov_enabled(AOV_UV)) {
+        aovs[aov_index(AOV_UV, pixel_index)] = vec4<f32>(hit.uv, 0.0, coverage);
+    }
+}
+
+// Keeps the error of nearly black pixels finite
+const ADAPTIVE_ERROR_FLOOR: f32 = 0.05;
+
+fn adaptive_enabled() -> bool {
+    return uniforms.adaptive_threshold > 0.0;
+}
+
+// Standard error of the mean luminance, scaled to the square root encoding of the display.
+// Uniforms::sample_error in engine-config computes the same on the CPU
+fn sample_error(color_sum: vec3<f32>, stats: vec4<f32>) -> f32 {
+    let n = stats.y;
+    let scale = exp2(uniforms.exposure);
+    let mean = luminance(color_sum) / n * scale;
+    let variance = max(stats.x / n * scale * scale - mean * mean, 0.0);
+    return sqrt(variance / n) / (2.0 * sqrt(mean) + ADAPTIVE_ERROR_FLOOR);
+}
+
+fn pixel_converged(color_sum: vec3<f32>, stats: vec4<f32>) -> bool {
+    return stats.y >= f32(uniforms.adaptive_min_samples)
+        && sample_error(color_sum, stats) < uniforms.adaptive_threshold;
+}
+
+const PROJECTION_PERSPECTIVE: u32 = 0u;
+const PROJECTION_ORTHOGRAPHIC: u32 = 1u;
+const PROJECTION_EQUIRECTANGULAR: u32 = 2u;
+const PROJECTION_STEREO_SIDE_BY_SIDE: u32 = 3u;
+const PROJECTION_STEREO_TOP_BOTTOM: u32 = 4u;
+
+struct CameraRay {
+    origin: vec3<f32>,
+    direction: vec3<f32>,
+    // Angle between the rays of neighbouring pixels, the initial spread of the ray cone
+    spread: f32,
+    // Perspective rays may start on the lens aperture instead
+    lens: bool,
+}
+
+// Primary ray through image pixel (x, y), offset by `jitter` pixels.
+// The frames are mirrored horizontally, `camera_right` points to the left of the frame.
+fn camera_ray(x: u32, y: u32, jitter: vec2<f32>) -> CameraRay {
+    let camera_forward = normalize(uniforms.camera.dir);
+    let world_up = vec3<f32>(0.0, 1.0, 0.0);
+    let camera_right = normalize(cross(world_up, camera_forward));
+    let camera_up = cross(camera_forward, camera_right);
+    let projection = uniforms.camera.projection;
+
+    if (projection == PROJECTION_EQUIRECTANGULAR) {
+        // Longitude around the up axis with the view direction in the center, latitude from the top
+        let phi = ((f32(x) + 0.5 + jitter.x) / f32(uniforms.width) * 2.0 - 1.0) * PI;
+        let theta = (0.5 - (f32(y) + 0.5 + jitter.y) / f32(uniforms.height)) * PI;
+        let direction = cos(theta) * (sin(phi) * camera_right + cos(phi) * camera_forward)
+            + sin(theta) * camera_up;
+        return CameraRay(uniforms.camera.pos, direction, 2.0 * PI / f32(uniforms.width), false);
+    }
+
+    if (projection == PROJECTION_ORTHOGRAPHIC) {
+        let aspect = f32(uniforms.width) / f32(uniforms.height);
+        let u = ((f32(x) + jitter.x) / f32(uniforms.width - 1u)) * 2.0 - 1.0;
+        let v = 1.0 - ((f32(y) + jitter.y) / f32(uniforms.height - 1u)) * 2.0;
+        let half_width = uniforms.camera.ortho_width * 0.5;
+        let origin = uniforms.camera.pos + u * half_width * camera_right + v * half_width / aspect * camera_up;
+        return CameraRay(origin, camera_forward, 0.0, false);
+    }
+
+    // Stereo pairs split the image into one perspective view per eye, the left eye is shown
+    // on the left (the upper x range of the buffers) or on top
+    var view_x = x;
+    var view_y = y;
+    var view_width = uniforms.width;
+    var view_height = uniforms.height;
+    var eye = 0.0;
+    if (projection == PROJECTION_STEREO_SIDE_BY_SIDE) {
+        let half = uniforms.width / 2u;
+        let left = x >= half;
+        view_x = select(x, x - half, left);
+        view_width = select(half, uniforms.width - half, left);
+        eye = select(-0.5, 0.5, left);
+    } else if (projection == PROJECTION_STEREO_TOP_BOTTOM) {
+        let half = uniforms.height / 2u;
+        let left = y < half;
+        view_y = select(y - half, y, left);
+        view_height = select(uniforms.height - half, half, left);
+        eye = select(-0.5, 0.5, left);
+    }
+
+    let view_aspect = f32(view_width) / f32(view_height);
+    let fov = uniforms.camera.pane_width / (2.0 * uniforms.camera.pane_distance * view_aspect);
+    let view_u = (((f32(view_x) + jitter.x) / f32(view_width - 1u)) * 2.0 - 1.0) * view_aspect;
+    let view_v = 1.0 - ((f32(view_y) + jitter.y) / f32(view_height - 1u)) * 2.0;
+    let origin = uniforms.camera.pos + eye * uniforms.camera.interocular_distance * camera_right;
+    let direction = normalize(fov * view_u * camera_right + fov * view_v * camera_up + camera_forward);
+    let spread = uniforms.camera.pane_width / (uniforms.camera.pane_distance * f32(view_width));
+    return CameraRay(origin, direction, spread, true);
+}
+
+// Entry point of both engines: every invocation renders the samples of one pixel of the tile.
+// The engine's `trace_ray(origin, direction, rng, pixel_spread)` returns the radiance of a
+// camera ray premultiplied by its alpha.
+@compute @workgroup_size(16, 16, 1)
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    if (global_id.x >= prh.tile_width || global_id.y >= prh.tile_height) {
+        return;
+    }
+
+    // Image coordinates for the camera and the sampler, tile coordinates for the pixel buffers
+    let x: u32 = prh.tile_x + global_id.x;
+    let y: u32 = prh.tile_y + global_id.y;
+    let pixel_index = global_id.y * prh.tile_width + global_id.x;
+
+    // Load previous accumulation
+    var accumulated_color = accumulation[pixel_index].xyz;
+    var total_samples = u32(accumulation[pixel_index].w);
+    var stats = sample_stats.pixels[pixel_index];
+
+    let camera_forward = normalize(uniforms.camera.dir);
+    let world_up = vec3<f32>(0.0, 1.0, 0.0);
+    let camera_right = normalize(cross(world_up, camera_forward));
+    let camera_up = cross(camera_forward, camera_right);
+
+    // Auxiliary outputs come from a single pinhole ray through the pixel center
+    if (prh.current_pass == 0u && uniforms.aov_flags != 0u) {
+        let ray = camera_ray(x, y, vec2<f32>(0.0));
+        // Panoramas have no view axis, their depth is the distance along the ray
+        let depth_axis = select(camera_forward, ray.direction, uniforms.camera.projection == PROJECTION_EQUIRECTANGULAR);
+        write_aovs(pixel_index, ray.origin, ray.direction, depth_axis);
+    }
+
+    // Converged pixels keep their output from an earlier pass
+    if (adaptive_enabled() && pixel_converged(accumulated_color, stats)) {
+        return;
+    }
+
+    // Render new samples for this pass
+    for (var sample: u32 = 0u; sample < prh.samples_per_pass; sample = sample + 1u) {
+        // The pixel's own sample count keeps its sequence free of gaps under adaptive sampling
+        var rng = sampler_new(y * uniforms.width + x, total_samples);
+
+        let offset = sample_2d(&rng) - 0.5;
+        let ray = camera_ray(x, y, offset);
+
+        var ray_origin = ray.origin;
+        var ray_dir = ray.direction;
+
+        // Thin lens: start on the aperture and pass through the point the pinhole ray hits on the focus plane
+        if (ray.lens && uniforms.camera.aperture_radius > 0.0) {
+            let focus_point = ray.origin + ray_dir * (uniforms.camera.focus_distance / dot(ray_dir, camera_forward));
+            let lens = sample_aperture(uniforms.camera.aperture_blades, &rng) * uniforms.camera.aperture_radius;
+            ray_origin = ray.origin + lens.x * camera_right + lens.y * camera_up;
+            ray_dir = normalize(focus_point - ray_origin);
+        }
+
+        let sample = trace_ray(ray_origin, ray_dir, rng, ray.spread);
+        accumulated_color = accumulated_color + sample.rgb;
+        total_samples = total_samples + 1u;
+        let sample_luminance = luminance(sample.rgb);
+        stats = stats + vec4<f32>(sample_luminance * sample_luminance, 1.0, sample.a, 0.0);
+    }
+
+    // Store accumulated result
+    accumulation[pixel_index] = vec4<f32>(accumulated_color, f32(total_samples));
+    sample_stats.pixels[pixel_index] = stats;
+    if (adaptive_enabled() && !pixel_converged(accumulated_color, stats)) {
+        atomicAdd(&sample_stats.unconverged_pixels, 1u);
+    }
+
+    // Write final color (averaged + exposure and tone mapping), the alpha is stored unpremultiplied
+    let final_alpha = stats.z / f32(total_samples);
+    let final_color = accumulated_color / f32(total_samples) / max(final_alpha, 1e-4);
+    output[pixel_index] = color_map(tone_map(final_color), final_alpha);
+}
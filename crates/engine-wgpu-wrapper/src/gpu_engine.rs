@@ -0,0 +1,226 @@
+//! The [`Renderer`] shared by the GPU engines, which differ only in their compute shader.
+
+use crate::{GpuWrapper, shader_with_common};
+use anyhow::Result;
+use chrono::Local;
+use engine_config::{RenderConfig, Renderer};
+use frame_buffer::frame_iterator::{Frame, FrameIterator, FrameLayer, RADIANCE_LAYER, samples_layer};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// A wgpu-based renderer running one compute shader.
+///
+/// `GpuEngine` holds a thread-safe reference to the [`GpuWrapper`] and implements the
+/// [`Renderer`] trait to integrate with the rest of the application. The engine crates
+/// create it with their shader source and a name for the log.
+pub struct GpuEngine {
+    /// Shared access to the GPU wrapper, managing device, queue, and resources.
+    gpu_wrapper: Arc<Mutex<GpuWrapper>>,
+    /// Prefix of the log messages, e.g. `ENGINE-RAYTRACER`.
+    name: &'static str,
+}
+
+impl GpuEngine {
+    /// Creates a new `GpuEngine` instance.
+    ///
+    /// # Arguments
+    ///
+    /// * `rc` - The initial render configuration.
+    /// * `shader_source` - The WGSL source of the compute shader, without the [`COMMON_SHADER`](crate::COMMON_SHADER).
+    /// * `name` - Prefix of the log messages.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Self>` - The engine, or an error if the GPU resources can't be created.
+    pub fn new(rc: RenderConfig, shader_source: &str, name: &'static str) -> Result<Self> {
+        let wrapper = GpuWrapper::new(rc, &shader_with_common(shader_source))?;
+        Ok(Self {
+            gpu_wrapper: Arc::new(Mutex::new(wrapper)),
+            name,
+        })
+    }
+}
+
+impl Renderer for GpuEngine {
+    /// Renders a scene synchronously.
+    ///
+    /// This method blocks until the entire rendering process (all passes) is complete.
+    /// It updates the GPU resources with the provided configuration, dispatches the compute shader,
+    /// and returns the final frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `rc` - The render configuration containing the scene description and settings.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Frame>` - The rendered frame containing pixel data, or an error if rendering fails.
+    fn render(&mut self, rc: RenderConfig) -> Result<Frame> {
+        let mut gpu_wrapper = self.gpu_wrapper.lock().unwrap();
+
+        gpu_wrapper.update(rc)?;
+        gpu_wrapper.update_uniforms();
+        gpu_wrapper.dispatch_compute()?;
+        read_frame(&gpu_wrapper)
+    }
+
+    /// Creates a frame iterator for progressive rendering.
+    ///
+    /// This method prepares the GPU for a new rendering session and returns an iterator
+    /// that yields frames progressively. This is useful for interactive viewing where
+    /// intermediate results are displayed while the image converges.
+    ///
+    /// # Arguments
+    ///
+    /// * `rc` - The render configuration containing the scene description and settings.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Box<dyn FrameIterator>>` - A boxed iterator yielding frames, or an error if initialization fails.
+    fn frame_iterator(&mut self, rc: RenderConfig) -> Result<Box<dyn FrameIterator>> {
+        {
+            let mut gpu_wrapper = self.gpu_wrapper.lock().unwrap();
+            gpu_wrapper.update(rc)?;
+            gpu_wrapper.update_uniforms();
+            gpu_wrapper.begin_render();
+        }
+        Ok(Box::new(GpuFrameIterator::new(
+            Arc::clone(&self.gpu_wrapper),
+            self.name,
+        )))
+    }
+}
+
+/// Reads the pixels of the last render with its AOV layers, named after their
+/// [`engine_config::Aov`], with adaptive sampling the samples layer and, if requested, the
+/// radiance layer.
+fn read_frame(gpu_wrapper: &GpuWrapper) -> Result<Frame> {
+    let mut layers: Vec<FrameLayer> = gpu_wrapper
+        .read_aovs()?
+        .into_iter()
+        .map(|(aov, data)| FrameLayer::new(aov.name(), data))
+        .collect();
+    if gpu_wrapper.is_adaptive() {
+        layers.push(samples_layer(&gpu_wrapper.read_sample_stats()?.samples));
+    }
+    if gpu_wrapper.has_radiance_layer() {
+        layers.push(FrameLayer::new(RADIANCE_LAYER, gpu_wrapper.read_radiance()));
+    }
+
+    Ok(Frame::new(
+        gpu_wrapper.get_width() as usize,
+        gpu_wrapper.get_height() as usize,
+        gpu_wrapper.read_pixels()?,
+    )
+    .with_layers(layers))
+}
+
+/// A frame iterator for progressive rendering on the GPU.
+///
+/// This struct manages the state of a progressive rendering session. It handles
+/// multi-pass rendering where each `next()` call computes a portion of the total samples,
+/// accumulating results over time. Large images are rendered tile by tile,
+/// the frames show the finished tiles and the passes of the current one.
+pub struct GpuFrameIterator {
+    /// Shared access to the GPU wrapper.
+    gpu_wrapper: Arc<Mutex<GpuWrapper>>,
+    /// Prefix of the log messages.
+    name: &'static str,
+    /// Flag indicating if the rendering session has been initialized (e.g., render timer started).
+    initialized: bool,
+    /// Timer to track the duration of the rendering process.
+    render_time: Option<Instant>,
+}
+
+impl GpuFrameIterator {
+    /// Creates a new `GpuFrameIterator`.
+    ///
+    /// # Arguments
+    ///
+    /// * `gpu_wrapper` - Shared access to the GPU wrapper.
+    /// * `name` - Prefix of the log messages.
+    fn new(gpu_wrapper: Arc<Mutex<GpuWrapper>>, name: &'static str) -> Self {
+        Self {
+            gpu_wrapper,
+            name,
+            initialized: false,
+            render_time: None,
+        }
+    }
+}
+
+impl FrameIterator for GpuFrameIterator {
+    /// Checks if there are more passes to render.
+    fn has_next(&self) -> bool {
+        self.gpu_wrapper.lock().unwrap().has_next_pass()
+    }
+
+    /// Computes the next frame in the progressive sequence.
+    ///
+    /// This method:
+    /// 1. Starts the render timer on the first call.
+    /// 2. Renders the next pass of the current tile.
+    /// 3. Returns the stitched frame with the finished tiles and the current one.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Frame>` - The current accumulated frame.
+    fn next(&mut self) -> Result<Frame> {
+        if !self.has_next() {
+            // Stop render time
+            if let Some(timer) = self.render_time {
+                let duration = timer.elapsed();
+                log::info!("Render finished in {:?}", duration);
+            }
+            anyhow::bail!("No more frames available");
+        }
+
+        let mut gpu_wrapper = self.gpu_wrapper.lock().unwrap();
+
+        if !self.initialized {
+            // Start render time
+            self.render_time = Some(Instant::now());
+            log::info!("Render started at {}", Local::now());
+            self.initialized = true;
+        }
+
+        let tile = gpu_wrapper.tile_index();
+        let sample = gpu_wrapper.prh().current_pass + 1;
+        gpu_wrapper.render_pass()?;
+
+        // The AOV layers of a tile are written in its first pass and don't change afterwards,
+        // every frame carries them so progressive previews can be denoised
+        let frame = read_frame(&gpu_wrapper)?;
+
+        if gpu_wrapper.tile_count() > 1 {
+            log::info!(
+                "[{}] Tile: {} / {}, Sample: {} / {}",
+                self.name,
+                tile + 1,
+                gpu_wrapper.tile_count(),
+                sample,
+                gpu_wrapper.prh().total_passes,
+            );
+        } else {
+            log::info!(
+                "[{}] Sample: {} / {}",
+                self.name,
+                sample,
+                gpu_wrapper.prh().total_passes,
+            );
+        }
+
+        if !gpu_wrapper.has_next_pass()
+            && let Some(timer) = self.render_time
+        {
+            let duration = timer.elapsed();
+            log::info!("[{}] Render finished in {:?}", self.name, duration);
+        }
+        Ok(frame)
+    }
+
+    /// Cancels the current rendering iterator.
+    fn destroy(&mut self) {
+        log::info!("[{}] Cancelled Render Iterator.", self.name)
+    }
+}
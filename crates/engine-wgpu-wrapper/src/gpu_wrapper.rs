@@ -1,21 +1,22 @@
 use crate::bind_group;
-use crate::{GpuDevice, buffers, pipeline};
+use crate::{GpuDevice, buffers, pipeline, tiles};
 use anyhow::{Ok, Result, anyhow};
 use bind_group::{BindGroup, BindGroupLayout};
 use buffers::GpuBuffers;
 use bytemuck::{Pod, Zeroable};
 use engine_config::render_config::{Change, Validate, ValidateInit};
-use engine_config::{RenderConfig, Uniforms};
+use engine_config::{Aov, AovSet, RenderConfig, TileOrder, Uniforms};
 use log::info;
 use pipeline::ComputePipeline;
+use tiles::{Tile, max_tile_pixels, split_tiles, tile_extent};
 
 const SAMPLES_PER_PASS: u32 = 1;
 
 /// Helper struct for managing progressive rendering state.
 ///
 /// It tracks the current pass, total passes, and sample counts to allow splitting
-/// a heavy rendering task into smaller, manageable work units. This struct is passed
-/// to the GPU as a uniform.
+/// a heavy rendering task into smaller, manageable work units, and the tile of the
+/// image the passes render. This struct is passed to the GPU as a uniform.
 #[repr(C)]
 #[derive(Copy, Clone, Debug, Pod, Zeroable)]
 pub struct ProgressiveRenderHelper {
@@ -27,6 +28,14 @@ pub struct ProgressiveRenderHelper {
     pub total_samples: u32,
     /// Number of samples computed per pass.
     pub samples_per_pass: u32,
+    /// Left edge of the rendered tile in the GPU buffers.
+    pub tile_x: u32,
+    /// Top edge of the rendered tile.
+    pub tile_y: u32,
+    /// Width of the rendered tile, the pixel buffers have this row stride.
+    pub tile_width: u32,
+    /// Height of the rendered tile.
+    pub tile_height: u32,
 }
 
 impl ProgressiveRenderHelper {
@@ -41,9 +50,21 @@ impl ProgressiveRenderHelper {
             current_pass: 0,
             total_samples,
             samples_per_pass: SAMPLES_PER_PASS,
+            tile_x: 0,
+            tile_y: 0,
+            tile_width: 0,
+            tile_height: 0,
         }
     }
 
+    /// Sets the tile rendered by the following passes.
+    pub fn set_tile(&mut self, tile: Tile) {
+        self.tile_x = tile.x;
+        self.tile_y = tile.y;
+        self.tile_width = tile.width;
+        self.tile_height = tile.height;
+    }
+
     /// Updates the total sample count and recalculates the total passes.
     pub fn update(&mut self, total_samples: u32) -> Self {
         self.total_samples = total_samples;
@@ -52,11 +73,25 @@ impl ProgressiveRenderHelper {
     }
 }
 
+/// Adaptive sampling state read back after a pass.
+#[derive(Clone, Debug)]
+pub struct SampleStats {
+    /// Number of pixels of the current tile that still need samples after the pass.
+    pub unconverged_pixels: u32,
+    /// Samples taken per pixel, in the same pixel order as [`GpuWrapper::read_pixels`].
+    /// Pixels of tiles that weren't rendered yet have no samples.
+    pub samples: Vec<f32>,
+}
+
 /// The main interface for WGPU-based rendering engines.
 ///
 /// `GpuWrapper` orchestrates the interaction between the `RenderConfig` and the GPU.
 /// It manages the lifecycle of GPU resources (buffers, bind groups), the compute pipeline,
 /// and the execution of compute passes.
+///
+/// Images that don't fit into the storage buffer limits, or that ask for smaller tiles,
+/// are rendered tile by tile. Every tile takes all of its passes before the next one
+/// starts and is stitched into the frame on the CPU.
 pub struct GpuWrapper {
     buffer_wrapper: GpuBuffers,
     bind_group_wrapper: BindGroup,
@@ -67,6 +102,24 @@ pub struct GpuWrapper {
     prh: ProgressiveRenderHelper,
     pipeline_wrapper: ComputePipeline,
     initialized: bool,
+    aovs: AovSet,
+    /// Width and height of the tiles the pixel buffers are sized for.
+    tile_extent: (u32, u32),
+    /// Tiles of the current render, in render order.
+    tiles: Vec<Tile>,
+    /// Index of the tile the next pass renders.
+    tile_index: usize,
+    /// Stitched RGBA8 frame of the current render.
+    image: Vec<u8>,
+    /// Stitched AOV layers of the current render.
+    aov_layers: Vec<(Aov, Vec<f32>)>,
+    /// Stitched per-pixel sample counts of the current render, empty without adaptive sampling.
+    samples: Vec<f32>,
+    /// Stitched linear radiance and alpha of the current render, four floats per pixel, empty
+    /// unless the uniforms request the radiance layer.
+    radiance: Vec<f32>,
+    /// Unconverged pixels of the current tile after the last pass.
+    unconverged_pixels: u32,
 }
 
 impl GpuWrapper {
@@ -86,7 +139,18 @@ impl GpuWrapper {
             Change::Keep => Uniforms::default(),
             Change::Delete => panic!("Cannot create GpuWrapper with deleted uniforms"),
         };
-        let prh = ProgressiveRenderHelper::new(initial_uniforms.total_samples);
+        let mut prh = ProgressiveRenderHelper::new(initial_uniforms.total_samples);
+        let aovs = match rc.aovs {
+            Change::Create(a) => a,
+            _ => AovSet::empty(),
+        };
+        let extent = Self::tile_extent_for(&gpu.device, &initial_uniforms, aovs);
+        prh.set_tile(Tile {
+            x: 0,
+            y: 0,
+            width: extent.0,
+            height: extent.1,
+        });
         let buffers = GpuBuffers::new(&rc, &gpu.device, &prh);
         let layout = BindGroupLayout::new(&gpu.device);
         let groups = BindGroup::new(&gpu.device, &buffers, &layout.bind_group_layout);
@@ -101,13 +165,22 @@ impl GpuWrapper {
             prh,
             pipeline_wrapper: pipeline,
             initialized: false,
+            aovs,
+            tile_extent: extent,
+            tiles: Vec::new(),
+            tile_index: 0,
+            image: Vec::new(),
+            aov_layers: Vec::new(),
+            samples: Vec::new(),
+            radiance: Vec::new(),
+            unconverged_pixels: 0,
         })
     }
 
     /// Updates the GPU resources based on the new render configuration.
     ///
     /// This method handles:
-    /// - Resizing buffers if the resolution changes.
+    /// - Resizing the pixel buffers if the tile size changes.
     /// - Updating, deleting, or recreating buffers for scene objects (spheres, meshes, etc.).
     /// - Recreating the bind group if buffers are changed.
     ///
@@ -119,18 +192,8 @@ impl GpuWrapper {
             new_rc.validate_init()?;
             // Initialize all resources
             if let Change::Create(uniforms) = &new_rc.uniforms {
-                // Check if resolution changed from engine initialization and resize buffers
-                let old_size = self.get_image_buffer_size() * 4;
-                let new_size = (uniforms.width as u64) * (uniforms.height as u64) * 4;
                 self.buffer_wrapper.init_uniforms(&self.device, uniforms);
-                if old_size != new_size {
-                    info!(
-                        "Resolution changed during first render, resizing buffers from {} to {} bytes",
-                        old_size, new_size
-                    );
-                    self.prh.update(uniforms.total_samples);
-                    self.buffer_wrapper.grow_resolution(&self.device, new_size);
-                }
+                self.prh.update(uniforms.total_samples);
             }
             if let Change::Create(spheres) = &new_rc.spheres {
                 self.buffer_wrapper.init_spheres(&self.device, spheres);
@@ -155,9 +218,33 @@ impl GpuWrapper {
                 self.buffer_wrapper
                     .init_bvh_triangles(&self.device, bvh_triangles);
             }
+            if let Change::Create(instances) = &new_rc.instances {
+                self.buffer_wrapper.init_instances(&self.device, instances);
+            }
             if let Change::Create(textures) = &new_rc.textures {
                 self.buffer_wrapper.init_textures(&self.device, textures);
             }
+            if let Change::Create(environment) = &new_rc.environment {
+                self.buffer_wrapper
+                    .init_environment(&self.device, environment);
+            }
+            if let Change::Create(triangles) = &new_rc.emissive_triangles {
+                self.buffer_wrapper
+                    .init_emissive_triangles(&self.device, triangles);
+            }
+            if let Change::Create(aovs) = &new_rc.aovs {
+                self.aovs = *aovs;
+            }
+            if let Change::Create(uniforms) = &new_rc.uniforms {
+                // Check if the tile size changed from engine initialization and resize buffers
+                self.resize_tiles(uniforms);
+                self.buffer_wrapper.update_aovs(
+                    &self.device,
+                    self.tile_extent.0,
+                    self.tile_extent.1,
+                    self.aovs,
+                );
+            }
             // Recreate bind group with new buffers
             self.recreate_bind_group();
             self.initialized = true;
@@ -168,16 +255,6 @@ impl GpuWrapper {
             match &new_rc.uniforms {
                 Change::Keep => info!("Not updating Uniforms."),
                 Change::Update(uniforms) => {
-                    // Check if resolution changed and resize output/staging buffers if needed
-                    let old_size = self.get_image_buffer_size() * 4;
-                    let new_size = (uniforms.width as u64) * (uniforms.height as u64) * 4;
-                    if old_size != new_size {
-                        info!(
-                            "Resolution changed, resizing buffers from {} to {} bytes",
-                            old_size, new_size
-                        );
-                        self.buffer_wrapper.grow_resolution(&self.device, new_size);
-                    }
                     self.prh.update(uniforms.total_samples);
                     self.buffer_wrapper.update_uniforms(&self.device, uniforms);
                 }
@@ -278,6 +355,16 @@ impl GpuWrapper {
                         .update_bvh_triangles(&self.device, triangles);
                 }
             }
+            match &new_rc.instances {
+                Change::Keep => info!("Not updating Instances Buffer."),
+                Change::Update(instances) | Change::Create(instances) => {
+                    self.buffer_wrapper
+                        .update_instances(&self.device, instances);
+                }
+                Change::Delete => {
+                    self.buffer_wrapper.delete_instances(&self.device);
+                }
+            }
 
             match &new_rc.textures {
                 Change::Keep => info!("Not updating Textures Buffer."),
@@ -291,6 +378,56 @@ impl GpuWrapper {
                     log::warn!("Create not allowed after initialization for textures.");
                 }
             }
+
+            match &new_rc.emissive_triangles {
+                Change::Keep => info!("Not updating Emissive Triangles Buffer."),
+                Change::Update(triangles) | Change::Create(triangles) => {
+                    self.buffer_wrapper
+                        .update_emissive_triangles(&self.device, triangles);
+                }
+                Change::Delete => {
+                    self.buffer_wrapper.delete_emissive_triangles(&self.device);
+                }
+            }
+
+            match &new_rc.environment {
+                Change::Keep => info!("Not updating Environment Buffer."),
+                Change::Update(environment) | Change::Create(environment) => {
+                    self.buffer_wrapper
+                        .update_environment(&self.device, environment);
+                }
+                Change::Delete => {
+                    self.buffer_wrapper.delete_environment(&self.device);
+                }
+            }
+
+            let mut aovs_changed = false;
+            match &new_rc.aovs {
+                Change::Keep => {}
+                Change::Update(aovs) | Change::Create(aovs) => {
+                    self.aovs = *aovs;
+                    aovs_changed = true;
+                }
+                Change::Delete => {
+                    self.aovs = AovSet::empty();
+                    self.buffer_wrapper.delete_aovs(&self.device);
+                }
+            }
+
+            // The tile size depends on the resolution and on the number of AOV layers
+            let uniforms = match (&new_rc.uniforms, &self.rc.uniforms) {
+                (Change::Update(u), _) | (_, Change::Create(u) | Change::Update(u)) => Some(*u),
+                _ => None,
+            };
+            let resized = uniforms.is_some_and(|u| self.resize_tiles(&u));
+            if resized || aovs_changed {
+                self.buffer_wrapper.update_aovs(
+                    &self.device,
+                    self.tile_extent.0,
+                    self.tile_extent.1,
+                    self.aovs,
+                );
+            }
             // Recreate bind group after any buffer updates
             self.recreate_bind_group();
         }
@@ -299,6 +436,35 @@ impl GpuWrapper {
         Ok(())
     }
 
+    /// Returns the size of the tiles for the given settings on this device.
+    fn tile_extent_for(device: &wgpu::Device, uniforms: &Uniforms, aovs: AovSet) -> (u32, u32) {
+        tile_extent(
+            uniforms.width,
+            uniforms.height,
+            uniforms.tile_size,
+            max_tile_pixels(&device.limits(), aovs),
+        )
+    }
+
+    /// Recreates the output, staging, accumulation and sample statistics buffers if the
+    /// tile size changed. The AOV buffers are left to the caller.
+    ///
+    /// Returns `true` if the buffers were recreated.
+    fn resize_tiles(&mut self, uniforms: &Uniforms) -> bool {
+        let extent = Self::tile_extent_for(&self.device, uniforms, self.aovs);
+        if extent == self.tile_extent {
+            return false;
+        }
+        info!(
+            "Tile size changed, resizing buffers from {}x{} to {}x{} pixels",
+            self.tile_extent.0, self.tile_extent.1, extent.0, extent.1
+        );
+        self.tile_extent = extent;
+        self.buffer_wrapper
+            .grow_resolution(&self.device, (extent.0 as u64) * (extent.1 as u64) * 4);
+        true
+    }
+
     fn recreate_bind_group(&mut self) {
         self.bind_group_wrapper = BindGroup::new(
             &self.device,
@@ -356,13 +522,45 @@ impl GpuWrapper {
         &mut self.prh
     }
 
-    /// Dispatches a single compute pass for progressive rendering.
+    /// Returns `true` if pixels stop taking samples once they are converged.
+    pub fn is_adaptive(&self) -> bool {
+        match &self.rc.uniforms {
+            Change::Create(u) | Change::Update(u) => u.adaptive_threshold > 0.0,
+            Change::Keep | Change::Delete => false,
+        }
+    }
+
+    /// Returns `true` if the frames carry the linear radiance layer.
+    pub fn has_radiance_layer(&self) -> bool {
+        match &self.rc.uniforms {
+            Change::Create(u) | Change::Update(u) => u.radiance_layer != 0,
+            Change::Keep | Change::Delete => false,
+        }
+    }
+
+    /// Clears the accumulated samples and the adaptive sampling statistics before a new tile.
+    pub fn clear_accumulation(&self) {
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Clear Accumulation"),
+            });
+        encoder.clear_buffer(&self.buffer_wrapper.accumulation, 0, None);
+        encoder.clear_buffer(&self.buffer_wrapper.sample_stats, 0, None);
+        self.queue.submit(Some(encoder.finish()));
+    }
+
+    /// Dispatches a single compute pass over the current tile.
     ///
     /// # Arguments
     ///
     /// * `pass_index` - The current pass number.
     /// * `total_passes` - The total number of passes.
     pub fn dispatch_compute_progressive(&self, pass_index: u32, total_passes: u32) -> Result<()> {
+        // Every pass counts its unconverged pixels from zero
+        self.queue
+            .write_buffer(&self.buffer_wrapper.sample_stats, 0, &[0u8; 4]);
+
         let mut encoder = self
             .device
             .create_command_encoder(&wgpu::CommandEncoderDescriptor {
@@ -378,8 +576,8 @@ impl GpuWrapper {
             pass.set_pipeline(self.get_pipeline());
             pass.set_bind_group(0, self.get_bind_group(), &[]);
             pass.dispatch_workgroups(
-                self.get_width().div_ceil(16),
-                self.get_height().div_ceil(16),
+                self.prh.tile_width.div_ceil(16),
+                self.prh.tile_height.div_ceil(16),
                 1,
             );
         }
@@ -389,9 +587,41 @@ impl GpuWrapper {
             0,
             &self.buffer_wrapper.staging,
             0,
-            self.get_image_buffer_size() * 4,
+            (self.prh.tile_width as u64) * (self.prh.tile_height as u64) * 4,
         );
 
+        // The AOV layers are only written by the first pass
+        if pass_index == 0 && !self.aovs.is_empty() {
+            encoder.copy_buffer_to_buffer(
+                &self.buffer_wrapper.aovs,
+                0,
+                &self.buffer_wrapper.aov_staging,
+                0,
+                self.buffer_wrapper.aovs.size(),
+            );
+        }
+
+        // The radiance layer takes the alpha sums from the statistics
+        if self.has_radiance_layer() {
+            encoder.copy_buffer_to_buffer(
+                &self.buffer_wrapper.accumulation,
+                0,
+                &self.buffer_wrapper.accumulation_staging,
+                0,
+                (self.prh.tile_width as u64) * (self.prh.tile_height as u64) * 16,
+            );
+        }
+
+        if self.is_adaptive() || self.has_radiance_layer() {
+            encoder.copy_buffer_to_buffer(
+                &self.buffer_wrapper.sample_stats,
+                0,
+                &self.buffer_wrapper.sample_stats_staging,
+                0,
+                self.buffer_wrapper.sample_stats.size(),
+            );
+        }
+
         self.queue.submit(Some(encoder.finish()));
 
         let _ = self.device.poll(wgpu::PollType::wait_indefinitely());
@@ -399,67 +629,250 @@ impl GpuWrapper {
         Ok(())
     }
 
-    /// Dispatches all compute passes sequentially.
+    /// Starts a new render.
     ///
-    /// This is a blocking operation that executes the full rendering process.
-    /// It clears the accumulation buffer before starting.
-    pub fn dispatch_compute(&mut self) -> Result<()> {
+    /// Splits the image into tiles in the configured [`TileOrder`], clears the stitched
+    /// frame and prepares the first tile. Must be called before the first
+    /// [`render_pass`](Self::render_pass) of a render.
+    pub fn begin_render(&mut self) {
+        let uniforms = match &self.rc.uniforms {
+            Change::Create(u) | Change::Update(u) => *u,
+            Change::Keep | Change::Delete => panic!("Uniforms must be initialized"),
+        };
+        let order = TileOrder::from_u32(uniforms.tile_order).unwrap_or_default();
+        self.tiles = split_tiles(uniforms.width, uniforms.height, self.tile_extent, order);
+        self.tile_index = 0;
+        if self.tiles.len() > 1 {
+            info!(
+                "Rendering {}x{} image in {} tiles of {}x{} pixels ({})",
+                uniforms.width,
+                uniforms.height,
+                self.tiles.len(),
+                self.tile_extent.0,
+                self.tile_extent.1,
+                order
+            );
+        }
+
+        let pixels = self.get_image_buffer_size() as usize;
+        self.image = [0, 0, 0, 255].repeat(pixels);
+        self.aov_layers = self
+            .aovs
+            .iter()
+            .map(|aov| (aov, vec![0.0; pixels * 4]))
+            .collect();
+        self.samples = if self.is_adaptive() {
+            vec![0.0; pixels]
+        } else {
+            Vec::new()
+        };
+        self.radiance = if self.has_radiance_layer() {
+            vec![0.0; pixels * 4]
+        } else {
+            Vec::new()
+        };
+        self.unconverged_pixels = 0;
+        self.start_tile();
+    }
+
+    /// Points the passes at the current tile and clears its accumulation.
+    fn start_tile(&mut self) {
+        if let Some(tile) = self.tiles.get(self.tile_index) {
+            self.prh.set_tile(*tile);
+        }
+        self.prh.current_pass = 0;
+        self.clear_accumulation();
+    }
+
+    /// Returns `true` while the current render has passes left.
+    pub fn has_next_pass(&self) -> bool {
+        self.prh.total_passes > 0 && self.tile_index < self.tiles.len()
+    }
+
+    /// Returns the index of the tile the next pass renders.
+    pub fn tile_index(&self) -> usize {
+        self.tile_index
+    }
+
+    /// Returns the number of tiles of the current render.
+    pub fn tile_count(&self) -> usize {
+        self.tiles.len()
+    }
+
+    /// Renders the next pass of the current tile and stitches the result into the frame.
+    ///
+    /// Moves on to the next tile once the tile took all of its samples or, with adaptive
+    /// sampling, once all of its pixels converged.
+    pub fn render_pass(&mut self) -> Result<()> {
+        let pass = self.prh.current_pass;
         self.queue.write_buffer(
-            &self.buffer_wrapper.accumulation,
+            &self.buffer_wrapper.progressive_render,
             0,
-            &vec![0u8; (self.get_width() * self.get_height() * 16) as usize],
+            bytemuck::cast_slice(&[self.prh]),
         );
 
-        for pass in 0..self.prh.total_passes {
-            info!("Rendering pass {}/{}", pass + 1, self.prh.total_passes);
-            self.prh.current_pass = pass;
-            self.queue.write_buffer(
-                &self.buffer_wrapper.progressive_render,
-                0,
-                bytemuck::cast_slice(&[self.prh]),
+        self.dispatch_compute_progressive(pass, self.prh.total_passes)?;
+        self.read_tile(pass == 0)?;
+        self.prh.current_pass += 1;
+
+        let converged = self.is_adaptive() && self.unconverged_pixels == 0;
+        if converged {
+            info!(
+                "All pixels of tile {}/{} converged after {} samples",
+                self.tile_index + 1,
+                self.tiles.len(),
+                self.prh.current_pass
             );
+        }
+        if converged || self.prh.current_pass >= self.prh.total_passes {
+            self.tile_index += 1;
+            if self.tile_index < self.tiles.len() {
+                self.start_tile();
+            }
+        }
+        Ok(())
+    }
+
+    /// Dispatches all compute passes of all tiles sequentially.
+    ///
+    /// This is a blocking operation that executes the full rendering process.
+    /// Every tile stops early once adaptive sampling has converged all of its pixels.
+    pub fn dispatch_compute(&mut self) -> Result<()> {
+        self.begin_render();
 
-            self.dispatch_compute_progressive(pass, self.prh.total_passes)?;
+        while self.has_next_pass() {
+            info!(
+                "Rendering tile {}/{}, pass {}/{}",
+                self.tile_index + 1,
+                self.tiles.len(),
+                self.prh.current_pass + 1,
+                self.prh.total_passes
+            );
+            self.render_pass()?;
         }
 
         Ok(())
     }
 
-    /// Reads the rendered pixels from the staging buffer.
-    ///
-    /// This method maps the staging buffer, reads the data, and returns it as a `Vec<u8>`.
-    /// The returned data is in RGBA8 format (although the alpha channel is manually set to 255).
-    pub fn read_pixels(&self) -> Result<Vec<u8>> {
-        let buffer_slice = self.buffer_wrapper.staging.slice(..);
-        let (sender, receiver) = std::sync::mpsc::channel();
-        buffer_slice.map_async(wgpu::MapMode::Read, move |res| {
-            let _ = sender.send(res);
-        });
-        self.device
-            .poll(wgpu::PollType::wait_indefinitely())
-            .map_err(|e| anyhow!("Device poll failed: {:?}", e))?;
-        receiver
-            .recv()
-            .map_err(|_| anyhow!("Failed to receive map_async result"))??;
-
-        let data_slice = buffer_slice.get_mapped_range();
+    /// Copies the output of the last pass over the current tile into the stitched frame,
+    /// along with the AOV layers after the first pass, the adaptive sampling statistics and
+    /// the radiance layer.
+    fn read_tile(&mut self, first_pass: bool) -> Result<()> {
+        let tile = self.prh;
         let width = self.get_width() as usize;
-        let height = self.get_height() as usize;
-        let mut result = Vec::with_capacity(width * height * 4);
+        let (tile_width, tile_height) = (tile.tile_width as usize, tile.tile_height as usize);
+        // The frames are mirrored horizontally relative to the GPU buffers
+        let frame_index = |tx: usize, ty: usize| {
+            (tile.tile_y as usize + ty) * width + width - 1 - (tile.tile_x as usize + tx)
+        };
 
-        for y in 0..height {
-            for x in (0..width).rev() {
-                let idx = (y * width + x) * 4;
-                result.push(data_slice[idx]);
-                result.push(data_slice[idx + 1]);
-                result.push(data_slice[idx + 2]);
-                result.push(255u8);
+        let image = &mut self.image;
+        read_staging(&self.device, &self.buffer_wrapper.staging, |data| {
+            for ty in 0..tile_height {
+                for tx in 0..tile_width {
+                    let src = (ty * tile_width + tx) * 4;
+                    let dst = frame_index(tx, ty) * 4;
+                    image[dst..dst + 4].copy_from_slice(&data[src..src + 4]);
+                }
             }
+        })?;
+
+        if first_pass && !self.aovs.is_empty() {
+            let layers = &mut self.aov_layers;
+            read_staging(&self.device, &self.buffer_wrapper.aov_staging, |data| {
+                let data: &[[f32; 4]] = bytemuck::cast_slice(data);
+                for (layer, (_, pixels)) in layers.iter_mut().enumerate() {
+                    let offset = layer * tile_width * tile_height;
+                    for ty in 0..tile_height {
+                        for tx in 0..tile_width {
+                            let dst = frame_index(tx, ty) * 4;
+                            pixels[dst..dst + 4]
+                                .copy_from_slice(&data[offset + ty * tile_width + tx]);
+                        }
+                    }
+                }
+            })?;
         }
 
-        drop(data_slice);
-        self.buffer_wrapper.staging.unmap();
-        Ok(result)
+        if self.is_adaptive() {
+            let samples = &mut self.samples;
+            let unconverged_pixels = read_staging(
+                &self.device,
+                &self.buffer_wrapper.sample_stats_staging,
+                |data| {
+                    let pixels: &[[f32; 4]] = bytemuck::cast_slice(&data[16..]);
+                    for ty in 0..tile_height {
+                        for tx in 0..tile_width {
+                            samples[frame_index(tx, ty)] = pixels[ty * tile_width + tx][1];
+                        }
+                    }
+                    bytemuck::cast_slice::<u8, u32>(&data[..16])[0]
+                },
+            )?;
+            self.unconverged_pixels = unconverged_pixels;
+        }
+
+        if self.has_radiance_layer() {
+            let accumulation = read_staging(
+                &self.device,
+                &self.buffer_wrapper.accumulation_staging,
+                |data| bytemuck::cast_slice::<u8, [f32; 4]>(data).to_vec(),
+            )?;
+            let radiance = &mut self.radiance;
+            read_staging(
+                &self.device,
+                &self.buffer_wrapper.sample_stats_staging,
+                |data| {
+                    let stats: &[[f32; 4]] = bytemuck::cast_slice(&data[16..]);
+                    for ty in 0..tile_height {
+                        for tx in 0..tile_width {
+                            let src = ty * tile_width + tx;
+                            let dst = frame_index(tx, ty) * 4;
+                            radiance[dst..dst + 4]
+                                .copy_from_slice(&mean_radiance(accumulation[src], stats[src][2]));
+                        }
+                    }
+                },
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the rendered pixels of the current render.
+    ///
+    /// The returned data is in RGBA8 format. The alpha channel is 255 except over a shadow
+    /// catcher, where the colors are not premultiplied.
+    /// Tiles that weren't rendered yet are black, the current tile holds the passes so far.
+    pub fn read_pixels(&self) -> Result<Vec<u8>> {
+        Ok(self.image.clone())
+    }
+
+    /// Returns the AOV layers of the current render.
+    ///
+    /// Returns one entry per requested layer with four floats per pixel, in the same
+    /// pixel order as [`read_pixels`](Self::read_pixels). The layers of a tile are
+    /// copied after its first pass, so this can be called at any point of a progressive render.
+    pub fn read_aovs(&self) -> Result<Vec<(Aov, Vec<f32>)>> {
+        Ok(self.aov_layers.clone())
+    }
+
+    /// Returns the adaptive sampling statistics of the last pass.
+    ///
+    /// The statistics are only copied back while [`is_adaptive`](Self::is_adaptive) is `true`.
+    pub fn read_sample_stats(&self) -> Result<SampleStats> {
+        Ok(SampleStats {
+            unconverged_pixels: self.unconverged_pixels,
+            samples: self.samples.clone(),
+        })
+    }
+
+    /// Returns the linear radiance and alpha of the current render, four floats per pixel in
+    /// the same pixel order as [`read_pixels`](Self::read_pixels).
+    ///
+    /// Empty unless [`has_radiance_layer`](Self::has_radiance_layer) is `true`.
+    pub fn read_radiance(&self) -> Vec<f32> {
+        self.radiance.clone()
     }
 
     /// Updates the data in the GPU buffers with the values from the current `RenderConfig`.
@@ -494,6 +907,28 @@ impl GpuWrapper {
             Change::Keep => {}
         }
 
+        match &self.rc.environment {
+            Change::Create(e) | Change::Update(e) => {
+                uniforms.environment_width = e.width;
+                uniforms.environment_height = e.height;
+            }
+            Change::Delete => {
+                uniforms.environment_width = 0;
+                uniforms.environment_height = 0;
+            }
+            Change::Keep => {}
+        }
+
+        match &self.rc.emissive_triangles {
+            Change::Create(t) | Change::Update(t) => {
+                uniforms.emissive_triangle_count = t.len() as u32;
+            }
+            Change::Delete => uniforms.emissive_triangle_count = 0,
+            Change::Keep => {}
+        }
+
+        uniforms.aov_flags = self.aovs.bits();
+
         info!(
             "Writing uniforms to GPU: camera_pos={:?}, camera_dir={:?}, pane_distance={}, pane_width={}, size={}x{}, spheres={}, triangles={}",
             uniforms.camera.pos,
@@ -560,6 +995,14 @@ impl GpuWrapper {
             );
         }
 
+        if let Change::Create(instances) | Change::Update(instances) = &self.rc.instances {
+            self.queue.write_buffer(
+                &self.buffer_wrapper.instances,
+                0,
+                bytemuck::cast_slice(instances),
+            );
+        }
+
         if let Change::Create(textures) | Change::Update(textures) = &self.rc.textures {
             let (tex_data, tex_info) = GpuBuffers::process_textures(textures);
             self.queue.write_buffer(
@@ -573,5 +1016,56 @@ impl GpuWrapper {
                 bytemuck::cast_slice(&tex_info),
             );
         }
+
+        if let Change::Create(triangles) | Change::Update(triangles) = &self.rc.emissive_triangles {
+            self.queue.write_buffer(
+                &self.buffer_wrapper.emissive_triangles,
+                0,
+                bytemuck::cast_slice(triangles),
+            );
+        }
+
+        if let Change::Create(environment) | Change::Update(environment) = &self.rc.environment {
+            let data = GpuBuffers::process_environment(environment);
+            self.queue.write_buffer(
+                &self.buffer_wrapper.environment,
+                0,
+                bytemuck::cast_slice(&data),
+            );
+        }
     }
 }
+
+/// Mean radiance and alpha of a pixel from its accumulation, which holds the radiance sum and
+/// the sample count, and its alpha sum. The radiance is not premultiplied, like the output.
+fn mean_radiance(accumulation: [f32; 4], alpha_sum: f32) -> [f32; 4] {
+    let samples = accumulation[3].max(1.0);
+    let alpha = alpha_sum / samples;
+    let [r, g, b] = [0, 1, 2].map(|c| accumulation[c] / samples / alpha.max(1e-4));
+    [r, g, b, alpha]
+}
+
+/// Maps a staging buffer, passes its contents to `read` and unmaps it again.
+fn read_staging<R>(
+    device: &wgpu::Device,
+    buffer: &wgpu::Buffer,
+    read: impl FnOnce(&[u8]) -> R,
+) -> Result<R> {
+    let buffer_slice = buffer.slice(..);
+    let (sender, receiver) = std::sync::mpsc::channel();
+    buffer_slice.map_async(wgpu::MapMode::Read, move |res| {
+        let _ = sender.send(res);
+    });
+    device
+        .poll(wgpu::PollType::wait_indefinitely())
+        .map_err(|e| anyhow!("Device poll failed: {:?}", e))?;
+    receiver
+        .recv()
+        .map_err(|_| anyhow!("Failed to receive map_async result"))??;
+
+    let data_slice = buffer_slice.get_mapped_range();
+    let result = read(&data_slice);
+    drop(data_slice);
+    buffer.unmap();
+    Ok(result)
+}
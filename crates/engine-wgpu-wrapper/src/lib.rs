@@ -6,15 +6,27 @@
 //!
 //! ## Key Components
 //!
-//! - [`GpuWrapper`]: The main entry point, managing the device, queue, pipeline, and resources.
+//! - [`GpuEngine`]: The [`engine_config::Renderer`] of the engines, which only pass in their shader source.
+//! - [`GpuFrameIterator`]: Renders one pass per frame for progressive rendering.
+//! - [`GpuWrapper`]: Manages the device, queue, pipeline, and resources.
 //! - [`GpuBuffers`]: Manages all GPU buffers (uniforms, geometry, textures, accumulation, etc.).
 //! - [`BindGroup`] & [`BindGroupLayout`]: Defines and creates the bind groups used by the compute shaders.
 //! - [`ComputePipeline`]: Handles the creation of the wgpu compute pipeline and shader module loading.
+//! - [`COMMON_SHADER`]: The WGSL shared by the engines (`common.wgsl`), the engine shaders are appended to it.
 //! - [`GpuDevice`]: Provides a singleton-like access to the `wgpu::Device` and `wgpu::Queue`.
+//! - [`Tile`]: A region of the image; images larger than the buffer limits are rendered tile by tile.
 //!
 //! ## Usage
 //!
-//! Engines typically instantiate `GpuWrapper` with a `RenderConfig` and the path to their specific shader file.
+//! Engines typically instantiate `GpuEngine` with a `RenderConfig` and the source of their specific shader.
+//!
+//! ```rust,ignore
+//! use engine_wgpu_wrapper::GpuEngine;
+//!
+//! let engine = GpuEngine::new(rc, include_str!("shader.wgsl"), "ENGINE-RAYTRACER")?;
+//! ```
+//!
+//! The `GpuWrapper` beneath it can also be driven directly:
 //!
 //! ```rust,ignore
 //! use engine_wgpu_wrapper::GpuWrapper;
@@ -37,14 +49,18 @@
 mod bind_group;
 mod buffers;
 pub mod gpu_device;
+mod gpu_engine;
 mod gpu_wrapper;
 mod pipeline;
+mod tiles;
 
 pub use bind_group::*;
 pub use buffers::*;
 pub use gpu_device::*;
+pub use gpu_engine::*;
 pub use gpu_wrapper::*;
 pub use pipeline::*;
+pub use tiles::*;
 
 pub use anyhow::Result;
 pub use engine_config::RenderConfig;
@@ -1,3 +1,12 @@
+/// WGSL shared by the compute shaders of all engines, including the entry point `main`. The
+/// engines only add the `trace_ray` it calls.
+pub const COMMON_SHADER: &str = include_str!("common.wgsl");
+
+/// Appends the shader of an engine to the [`COMMON_SHADER`].
+pub fn shader_with_common(shader_source: &str) -> String {
+    format!("{COMMON_SHADER}\n{shader_source}")
+}
+
 /// Wrapper for the `wgpu::ComputePipeline`.
 ///
 /// This struct handles the loading of the WGSL shader source code and the creation
@@ -0,0 +1,220 @@
+//! Splitting of large images into tiles that fit into the GPU buffers.
+//!
+//! The output, accumulation, AOV and sample statistics buffers only cover one tile.
+//! [`GpuWrapper`](crate::GpuWrapper) renders the tiles one after another and stitches
+//! them into the full frame.
+
+use engine_config::{AovSet, TileOrder};
+
+/// A rectangular region of the image, in the pixel coordinates of the GPU buffers.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Tile {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Tile {
+    /// Returns the number of pixels in the tile.
+    pub fn pixels(&self) -> u64 {
+        (self.width as u64) * (self.height as u64)
+    }
+}
+
+/// Returns the largest number of pixels a tile may have on a device with the given limits.
+///
+/// The largest per-pixel buffers are the accumulation, sample statistics and AOV buffers,
+/// with one `vec4<f32>` per pixel and layer. The statistics start with a 16 byte header.
+pub fn max_tile_pixels(limits: &wgpu::Limits, aovs: AovSet) -> u64 {
+    let max_bytes = (limits.max_storage_buffer_binding_size as u64).min(limits.max_buffer_size);
+    max_bytes.saturating_sub(16) / (16 * aovs.len().max(1) as u64)
+}
+
+/// Returns the width and height of the tiles an image is split into.
+///
+/// # Arguments
+///
+/// * `width`, `height` - Resolution of the image
+/// * `tile_size` - Requested edge length, 0 renders the image in one tile if it fits
+/// * `max_pixels` - Largest number of pixels in a tile, see [`max_tile_pixels`]
+pub fn tile_extent(width: u32, height: u32, tile_size: u32, max_pixels: u64) -> (u32, u32) {
+    if tile_size == 0 && (width as u64) * (height as u64) <= max_pixels {
+        return (width, height);
+    }
+    // Multiples of the workgroup size don't leave idle invocations
+    let max_side = ((max_pixels as f64).sqrt() as u32 / 16 * 16).max(16);
+    let side = if tile_size == 0 {
+        max_side
+    } else {
+        tile_size.min(max_side)
+    };
+    (side.min(width), side.min(height))
+}
+
+/// Splits an image into tiles of the given extent, in the order they are rendered.
+///
+/// The order refers to the frames returned by [`GpuWrapper`](crate::GpuWrapper), which
+/// are mirrored horizontally relative to the GPU buffers. Tiles on the right and bottom
+/// edge are cut to the image.
+pub fn split_tiles(width: u32, height: u32, extent: (u32, u32), order: TileOrder) -> Vec<Tile> {
+    let (tile_width, tile_height) = (extent.0.max(1), extent.1.max(1));
+    let columns = width.div_ceil(tile_width);
+    let rows = height.div_ceil(tile_height);
+
+    let cells: Vec<(u32, u32)> = match order {
+        TileOrder::Rows => (0..rows)
+            .flat_map(|row| (0..columns).map(move |column| (column, row)))
+            .collect(),
+        TileOrder::Columns => (0..columns)
+            .flat_map(|column| (0..rows).map(move |row| (column, row)))
+            .collect(),
+        TileOrder::Spiral => spiral(columns, rows),
+    };
+
+    cells
+        .into_iter()
+        .map(|(column, row)| {
+            let display_x = column * tile_width;
+            let y = row * tile_height;
+            let tile_width = tile_width.min(width - display_x);
+            Tile {
+                x: width - display_x - tile_width,
+                y,
+                width: tile_width,
+                height: tile_height.min(height - y),
+            }
+        })
+        .collect()
+}
+
+/// Walks a grid of cells in a square spiral, starting at the center cell.
+fn spiral(columns: u32, rows: u32) -> Vec<(u32, u32)> {
+    let count = (columns * rows) as usize;
+    let mut cells = Vec::with_capacity(count);
+    let (mut column, mut row) = ((columns as i64 - 1) / 2, (rows as i64 - 1) / 2);
+    let visit = |column: i64, row: i64, cells: &mut Vec<(u32, u32)>| {
+        if (0..columns as i64).contains(&column) && (0..rows as i64).contains(&row) {
+            cells.push((column as u32, row as u32));
+        }
+    };
+    visit(column, row, &mut cells);
+
+    // right, down, left, up, the run length grows after every second turn
+    let directions = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+    let mut run = 1;
+    let mut direction = 0;
+    while cells.len() < count {
+        for _ in 0..2 {
+            let (dx, dy) = directions[direction];
+            for _ in 0..run {
+                column += dx;
+                row += dy;
+                visit(column, row, &mut cells);
+            }
+            direction = (direction + 1) % 4;
+        }
+        run += 1;
+    }
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use engine_config::TileOrder;
+
+    use super::{Tile, split_tiles, tile_extent};
+
+    #[test]
+    fn tile_extent_keeps_images_that_fit_whole() {
+        assert_eq!(tile_extent(400, 300, 0, 1 << 20), (400, 300));
+        // 1000 pixels allow 31x31, rounded down to the workgroup size
+        assert_eq!(tile_extent(400, 300, 0, 1000), (16, 16));
+        assert_eq!(tile_extent(400, 300, 64, 1 << 20), (64, 64));
+        assert_eq!(tile_extent(400, 300, 512, 1 << 20), (400, 300));
+        assert_eq!(tile_extent(400, 300, 512, 128 * 128), (128, 128));
+    }
+
+    #[test]
+    fn split_tiles_covers_the_image_in_order() {
+        let rows = split_tiles(5, 3, (2, 2), TileOrder::Rows);
+        // The first tile is on the left of the frame, which is the right of the buffers
+        assert_eq!(
+            rows[..4],
+            [
+                Tile {
+                    x: 3,
+                    y: 0,
+                    width: 2,
+                    height: 2
+                },
+                Tile {
+                    x: 1,
+                    y: 0,
+                    width: 2,
+                    height: 2
+                },
+                Tile {
+                    x: 0,
+                    y: 0,
+                    width: 1,
+                    height: 2
+                },
+                Tile {
+                    x: 3,
+                    y: 2,
+                    width: 2,
+                    height: 1
+                },
+            ]
+        );
+        assert_eq!(rows.len(), 6);
+        assert_eq!(rows.iter().map(Tile::pixels).sum::<u64>(), 15);
+
+        let columns = split_tiles(5, 3, (2, 2), TileOrder::Columns);
+        assert_eq!(
+            columns[1],
+            Tile {
+                x: 3,
+                y: 2,
+                width: 2,
+                height: 1
+            }
+        );
+
+        // 3x3 grid: center first, then right, down and around
+        let spiral = split_tiles(6, 6, (2, 2), TileOrder::Spiral);
+        assert_eq!(spiral.len(), 9);
+        assert_eq!(
+            spiral[0],
+            Tile {
+                x: 2,
+                y: 2,
+                width: 2,
+                height: 2
+            }
+        );
+        assert_eq!(
+            spiral[1],
+            Tile {
+                x: 0,
+                y: 2,
+                width: 2,
+                height: 2
+            }
+        );
+        assert_eq!(
+            spiral[2],
+            Tile {
+                x: 0,
+                y: 4,
+                width: 2,
+                height: 2
+            }
+        );
+        let mut sorted = spiral.clone();
+        sorted.sort_by_key(|t| (t.y, t.x));
+        sorted.dedup();
+        assert_eq!(sorted.len(), 9);
+    }
+}
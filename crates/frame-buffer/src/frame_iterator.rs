@@ -1,3 +1,37 @@
+/// A named auxiliary layer of a [`Frame`], such as depth or normals.
+#[derive(Debug, Clone)]
+pub struct FrameLayer {
+    /// Name of the layer.
+    pub name: String,
+    /// Four floats per pixel, in the same pixel order as [`Frame::pixels`].
+    pub data: Vec<f32>,
+}
+
+impl FrameLayer {
+    /// Creates a new [`FrameLayer`].
+    pub fn new(name: impl Into<String>, data: Vec<f32>) -> Self {
+        Self {
+            name: name.into(),
+            data,
+        }
+    }
+}
+
+/// Name of the layer holding the number of samples taken per pixel by adaptive sampling.
+pub const SAMPLES_LAYER: &str = "samples";
+
+/// Builds the [`SAMPLES_LAYER`] from the per-pixel sample counts.
+pub fn samples_layer(samples: &[f32]) -> FrameLayer {
+    FrameLayer::new(
+        SAMPLES_LAYER,
+        samples.iter().flat_map(|n| [*n, *n, *n, 1.0]).collect(),
+    )
+}
+
+/// Name of the layer holding the linear radiance of every pixel, averaged over its samples,
+/// before exposure and tone mapping. The colors are not premultiplied, `w` holds the alpha.
+pub const RADIANCE_LAYER: &str = "radiance";
+
 /// A Frame is a single image rendered by the render engine.
 #[derive(Debug, Clone)]
 pub struct Frame {
@@ -7,6 +41,8 @@ pub struct Frame {
     pub height: usize,
     /// Pixels of the image as RGBA8 data.
     pub pixels: Vec<u8>, // RGBA8 data
+    /// Auxiliary layers rendered alongside the image, empty if none were requested.
+    pub layers: Vec<FrameLayer>,
 }
 
 impl Frame {
@@ -16,9 +52,21 @@ impl Frame {
             width,
             height,
             pixels,
+            layers: vec![],
         }
     }
 
+    /// Returns the frame with the given auxiliary layers attached.
+    pub fn with_layers(mut self, layers: Vec<FrameLayer>) -> Self {
+        self.layers = layers;
+        self
+    }
+
+    /// Returns the layer with the given name, if present.
+    pub fn layer(&self, name: &str) -> Option<&FrameLayer> {
+        self.layers.iter().find(|l| l.name == name)
+    }
+
     /// Returns the size of the image in bytes.
     pub fn expected_size(&self) -> usize {
         self.width * self.height * 4
@@ -34,6 +82,16 @@ impl Frame {
                 self.pixels.len()
             );
         }
+        for layer in &self.layers {
+            if layer.data.len() != self.width * self.height * 4 {
+                anyhow::bail!(
+                    "Frame layer {} size mismatch: expected {} floats, got {}",
+                    layer.name,
+                    self.width * self.height * 4,
+                    layer.data.len()
+                );
+            }
+        }
         Ok(())
     }
 }
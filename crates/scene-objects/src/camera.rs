@@ -1,4 +1,66 @@
 use glam::Vec3;
+/// How the camera maps the image to rays, with the mode specific parameters
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Projection {
+    /// Pinhole or thin lens camera looking through the pane
+    #[default]
+    Perspective,
+    /// Parallel rays
+    /// 'width': width of the viewed area in scene units
+    Orthographic { width: f32 },
+    /// 360x180 degree panorama around the camera position, the pane is ignored
+    Equirectangular,
+    /// Left eye in the left half of the image, right eye in the right half
+    /// 'interocular_distance': distance between the eyes in scene units
+    StereoSideBySide { interocular_distance: f32 },
+    /// Left eye in the top half of the image, right eye in the bottom half
+    /// 'interocular_distance': distance between the eyes in scene units
+    StereoTopBottom { interocular_distance: f32 },
+}
+impl Projection {
+    /// Names of all projections, in the order they are presented to the user
+    pub const NAMES: [&'static str; 5] = [
+        "perspective",
+        "orthographic",
+        "equirectangular",
+        "stereo_side_by_side",
+        "stereo_top_bottom",
+    ];
+    /// ## Returns
+    /// Lowercase name used in scene files
+    pub fn name(&self) -> &'static str {
+        match self {
+            Projection::Perspective => "perspective",
+            Projection::Orthographic { .. } => "orthographic",
+            Projection::Equirectangular => "equirectangular",
+            Projection::StereoSideBySide { .. } => "stereo_side_by_side",
+            Projection::StereoTopBottom { .. } => "stereo_top_bottom",
+        }
+    }
+    /// ## Parameter
+    /// 'name': Projection name as used in scene files, ignoring case
+    /// ## Returns
+    /// The projection with default parameters, None if the name is unknown
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "perspective" => Some(Projection::Perspective),
+            "orthographic" | "ortho" => Some(Projection::Orthographic { width: 10.0 }),
+            "equirectangular" | "panorama" => Some(Projection::Equirectangular),
+            "stereo_side_by_side" => Some(Projection::StereoSideBySide {
+                interocular_distance: 0.065,
+            }),
+            "stereo_top_bottom" => Some(Projection::StereoTopBottom {
+                interocular_distance: 0.065,
+            }),
+            _ => None,
+        }
+    }
+}
+impl std::fmt::Display for Projection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
 /// Camera that is used to render scenes
 #[allow(dead_code)]
 #[derive(Debug, Clone, Copy)]
@@ -11,9 +73,16 @@ pub struct Camera {
     up: Vec3,
     resolution: Resolution,
     ray_samples: u32, // todo move to scene
+    aperture_radius: f32,
+    focus_distance: Option<f32>,
+    aperture_blades: u32,
+    projection: Projection,
 }
 #[allow(dead_code)]
 impl Camera {
+    /// Scene units per pane unit. The pane distance is read as focal length in millimetres
+    /// and one scene unit as one metre when converting f-stops.
+    pub const PANE_UNIT: f32 = 0.001;
     /// sets the position of the camera
     /// ## Parameter
     /// 'position': glam::Vec3 of the new position
@@ -109,6 +178,99 @@ impl Camera {
         self.ray_samples = samples;
         // here could be a check for values [1, 100] or so
     }
+    /// ## Returns
+    /// Radius of the lens aperture in scene units, 0 for a pinhole camera
+    pub fn get_aperture_radius(&self) -> f32 {
+        self.aperture_radius
+    }
+    /// Sets the radius of the lens aperture. 0 disables depth of field
+    /// ## Parameter
+    /// 'radius': new aperture radius in scene units, negative values are ignored
+    pub fn set_aperture_radius(&mut self, radius: f32) {
+        if radius >= 0.0 {
+            self.aperture_radius = radius;
+        }
+    }
+    /// ## Returns
+    /// The f-number of the lens, None for a pinhole camera
+    pub fn get_f_stop(&self) -> Option<f32> {
+        if self.aperture_radius > 0.0 {
+            Some(self.pane_distance * Self::PANE_UNIT / (2.0 * self.aperture_radius))
+        } else {
+            None
+        }
+    }
+    /// Sets the aperture radius from an f-number, using the pane distance as focal length
+    /// ## Parameter
+    /// 'f_stop': f-number of the lens, e.g. 2.8. Non positive values are ignored
+    pub fn set_f_stop(&mut self, f_stop: f32) {
+        if f_stop > 0.0 {
+            self.aperture_radius = self.pane_distance * Self::PANE_UNIT / (2.0 * f_stop);
+        }
+    }
+    /// ## Returns
+    /// Distance of the plane in focus along the viewing direction.
+    /// Defaults to the distance of the look at point
+    pub fn get_focus_distance(&self) -> f32 {
+        self.focus_distance
+            .unwrap_or_else(|| (self.look_at - self.position).length())
+    }
+    /// ## Returns
+    /// The focus distance if it was set explicitly
+    pub fn get_focus_distance_override(&self) -> Option<f32> {
+        self.focus_distance
+    }
+    /// Sets the distance of the plane in focus
+    /// ## Parameter
+    /// 'distance': new focus distance, non positive values are ignored
+    pub fn set_focus_distance(&mut self, distance: f32) {
+        if distance > 0.0 {
+            self.focus_distance = Some(distance);
+        }
+    }
+    /// Focuses the camera on the given world position. Points behind the camera are ignored
+    /// ## Parameter
+    /// 'point': glam::Vec3 that should be in focus
+    pub fn focus_on(&mut self, point: Vec3) {
+        let forward = (self.look_at - self.position).normalize_or_zero();
+        self.set_focus_distance((point - self.position).dot(forward));
+    }
+    /// ## Returns
+    /// Number of aperture blades, 0 for a circular aperture
+    pub fn get_aperture_blades(&self) -> u32 {
+        self.aperture_blades
+    }
+    /// Sets the number of aperture blades, which shapes the bokeh
+    /// ## Parameter
+    /// 'blades': 0 for a circular aperture or at least 3 for a polygonal one. Other values are ignored
+    pub fn set_aperture_blades(&mut self, blades: u32) {
+        if blades == 0 || blades >= 3 {
+            self.aperture_blades = blades;
+        }
+    }
+    /// ## Returns
+    /// Projection of the camera and its parameters
+    pub fn get_projection(&self) -> Projection {
+        self.projection
+    }
+    /// Sets the projection of the camera
+    /// ## Parameter
+    /// 'projection': new projection. Non positive ortho widths and negative interocular distances are ignored
+    pub fn set_projection(&mut self, projection: Projection) {
+        let valid = match projection {
+            Projection::Orthographic { width } => width > 0.0,
+            Projection::StereoSideBySide {
+                interocular_distance,
+            }
+            | Projection::StereoTopBottom {
+                interocular_distance,
+            } => interocular_distance >= 0.0,
+            _ => true,
+        };
+        if valid {
+            self.projection = projection;
+        }
+    }
 }
 
 impl Default for Camera {
@@ -126,6 +288,10 @@ impl Default for Camera {
             pane_distance: 35.0,
             pane_width,
             pane_height,
+            aperture_radius: 0.0,
+            focus_distance: None,
+            aperture_blades: 0,
+            projection: Projection::Perspective,
         }
     }
 }
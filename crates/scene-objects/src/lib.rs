@@ -3,6 +3,7 @@ pub mod geometric_object;
 pub mod light_source;
 pub mod material;
 pub mod mesh;
+pub mod mesh_instance;
 pub mod sphere;
 
 #[cfg(test)]
@@ -11,9 +12,11 @@ mod tests {
     use glam::Vec3;
 
     use crate::{
+        camera,
         geometric_object::{GeometricObject, SceneObject},
-        material::Material,
+        material::{Material, MaterialPresets, PbrProperties},
         mesh::Mesh,
+        mesh_instance::MeshInstance,
         sphere::Sphere,
     };
 
@@ -34,6 +37,18 @@ mod tests {
         sphere.translate(translation);
         assert_eq!(sphere.get_center(), translation);
         assert_eq!(sphere.get_translation(), translation);
+
+        // Relative rotations compose like the absolute one
+        sphere.rotate(Vec3::new(0.0, 45.0, 0.0));
+        sphere.rotate(Vec3::new(0.0, 45.0, 0.0));
+        let mut rotated = Sphere::new(Vec3::default(), 1.0, Material::default(), [1.0; 3]);
+        rotated.rotate_to(Vec3::new(0.0, 90.0, 0.0));
+        assert!(
+            sphere
+                .get_orientation()
+                .abs_diff_eq(rotated.get_orientation(), 1e-5)
+        );
+        assert_eq!(sphere.get_center(), translation);
     }
     #[test]
     fn mesh_test() {
@@ -79,4 +94,167 @@ mod tests {
             Err(_) => panic!("Failed to create new mesh"),
         }
     }
+    #[test]
+    fn mesh_instance_test() {
+        let tetrahedron = || {
+            Mesh::new(
+                vec![0.0, 0.0, 0.0, 2.0, 0.0, 0.0, 0.0, 3.0, 0.0, 0.0, 0.0, 4.0],
+                vec![0, 1, 2, 0, 1, 3, 0, 2, 3, 1, 2, 3],
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .unwrap()
+        };
+        let place = |mesh: &mut dyn GeometricObject| {
+            mesh.scale(0.5);
+            mesh.rotate(Vec3::new(30.0, 0.0, 45.0));
+            mesh.translate(Vec3::new(-4.0, 0.0, 1.0));
+        };
+
+        let mut source = tetrahedron();
+        source.scale(2.0);
+        source.rotate(Vec3::new(0.0, 90.0, 0.0));
+        source.translate(Vec3::new(1.0, 2.0, 3.0));
+
+        // an instance ends up where a separately loaded copy with the same transforms would be
+        let mut copy = tetrahedron();
+        place(&mut copy);
+        let mut instance = MeshInstance::new(0, "copy".to_owned());
+        place(&mut instance);
+
+        let transform = instance.get_transform(&source);
+        for (p, expected) in source
+            .get_vertices()
+            .chunks_exact(3)
+            .zip(copy.get_vertices().chunks_exact(3))
+        {
+            let p = transform.transform_point3(Vec3::from_slice(p));
+            assert!((p - Vec3::from_slice(expected)).length() < 1e-4);
+        }
+        assert_eq!(instance.get_scale(), copy.get_scale());
+        assert!((instance.get_rotation() - copy.get_rotation()).length() < 1e-3);
+    }
+    #[test]
+    fn vertex_normals_test() {
+        // two triangles of a shallow roof, sharing the edge 1-2
+        let roof = vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.1, 1.0, 1.0, 0.1, 2.0, 1.0, 0.0];
+        let tris = vec![0, 1, 2, 1, 3, 2];
+        let normals = Mesh::calculate_vertex_normals(&roof, &tris);
+        assert_eq!(normals.len(), tris.len() * 3);
+        // corners on the shared edge are weighted by their interior angle at vertex 1
+        let left = Vec3::new(-0.1, 0.0, 1.0).normalize();
+        let right = Vec3::new(0.1, 0.0, 1.0).normalize();
+        let right_angle = Vec3::new(1.0, 1.0, -0.1).angle_between(Vec3::Y);
+        let expected = (left * std::f32::consts::FRAC_PI_2 + right * right_angle).normalize();
+        let shared = Vec3::from_slice(&normals[3..6]);
+        assert!((shared - expected).length() < 1e-5);
+        assert_eq!(&normals[3..6], &normals[9..12]);
+        // unshared corners keep their face normal
+        let face = Vec3::from_slice(&normals[0..3]);
+        assert!(face.x < 0.0 && face.z > 0.9);
+
+        // faces meeting at 90 degrees are not smoothed
+        let corner = vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0];
+        let tris = vec![0, 1, 2, 0, 1, 3];
+        let normals = Mesh::calculate_vertex_normals(&corner, &tris);
+        assert_eq!(&normals[0..3], &[0.0, 0.0, 1.0]);
+        assert_eq!(&normals[9..12], &[0.0, -1.0, 0.0]);
+
+        // normals follow the mesh rotation
+        let mut mesh = Mesh::new(corner, vec![0, 1, 2], None, None, None, None, None)
+            .unwrap()
+            .with_normals(vec![
+                0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0,
+            ]);
+        mesh.rotate(Vec3::new(90.0, 0.0, 0.0));
+        let rotated = Vec3::from_slice(&mesh.get_normals().unwrap()[0..3]);
+        assert!((rotated - Vec3::new(0.0, -1.0, 0.0)).length() < 1e-5);
+    }
+    #[test]
+    fn tangents_test() {
+        // a quad in the xy plane, the left half mirrored in u
+        let vertices = vec![
+            0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0,
+            0.0,
+        ];
+        let uvs = vec![0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 1.0, 0.0, 0.0, 1.0, 1.0, 1.0];
+        let normals = [0.0, 0.0, 1.0].repeat(6);
+        let tangents = Mesh::calculate_tangents(&vertices, &uvs, &normals, &[0, 1, 2, 3, 4, 5]);
+        assert_eq!(tangents.len(), 24);
+        assert_eq!(&tangents[0..4], &[1.0, 0.0, 0.0, 1.0]);
+        // u runs against x on the second triangle, the bitangent still follows +v
+        assert_eq!(&tangents[12..16], &[-1.0, 0.0, 0.0, -1.0]);
+
+        // degenerate uvs give no tangent
+        let tangents =
+            Mesh::calculate_tangents(&vertices[..9], &[0.0; 6], &normals[..9], &[0, 1, 2]);
+        assert_eq!(tangents, vec![0.0; 12]);
+    }
+    #[test]
+    fn camera_lens_test() {
+        let mut camera = camera::Camera::new(Vec3::ZERO, Vec3::new(0.0, 0.0, 4.0));
+        assert_eq!(camera.get_aperture_radius(), 0.0);
+        assert_eq!(camera.get_f_stop(), None);
+        assert_eq!(camera.get_focus_distance(), 4.0);
+
+        // 35 mm at f/2 is an aperture of 17.5 mm, radius 8.75 mm
+        camera.set_pane_distance(35.0);
+        camera.set_f_stop(2.0);
+        assert!((camera.get_aperture_radius() - 0.00875).abs() < 1e-6);
+        assert!((camera.get_f_stop().unwrap() - 2.0).abs() < 1e-5);
+
+        // Only the distance along the viewing direction counts
+        camera.focus_on(Vec3::new(3.0, 1.0, 2.5));
+        assert!((camera.get_focus_distance() - 2.5).abs() < 1e-6);
+        camera.focus_on(Vec3::new(0.0, 0.0, -1.0));
+        assert!((camera.get_focus_distance() - 2.5).abs() < 1e-6);
+
+        camera.set_aperture_blades(2);
+        assert_eq!(camera.get_aperture_blades(), 0);
+        camera.set_aperture_blades(6);
+        assert_eq!(camera.get_aperture_blades(), 6);
+
+        assert_eq!(camera.get_projection(), camera::Projection::Perspective);
+        camera.set_projection(camera::Projection::Orthographic { width: 0.0 });
+        assert_eq!(camera.get_projection(), camera::Projection::Perspective);
+        for name in camera::Projection::NAMES {
+            let projection = camera::Projection::from_name(name).unwrap();
+            assert_eq!(projection.name(), name);
+            camera.set_projection(projection);
+            assert_eq!(camera.get_projection(), projection);
+        }
+    }
+
+    #[test]
+    fn principled_conversion_test() {
+        // Phong metal: Ks set, Kd black
+        let metal = Material::from(MaterialPresets::Metal).principled();
+        assert_eq!(metal.metallic, 1.0);
+        assert_eq!(metal.base_color, [0.5, 0.5, 0.5]);
+        assert!((metal.roughness - (1.0 - 0.5f64.sqrt())).abs() < 1e-9);
+
+        let mirror = Material::from(MaterialPresets::Mirror).principled();
+        assert_eq!(mirror.roughness, 0.0);
+
+        let plastic = Material::from(MaterialPresets::Plastic).principled();
+        assert_eq!(plastic.metallic, 0.0);
+        assert_eq!(plastic.base_color, [1.0, 1.0, 1.0]);
+        assert_eq!(plastic.specular, 0.0);
+
+        // Authored PBR values win over the Phong heuristic
+        let authored = Material::from(MaterialPresets::Metal).with_pbr(PbrProperties {
+            roughness: Some(0.2),
+            metallic: Some(0.0),
+            clearcoat: Some(1.0),
+            ..Default::default()
+        });
+        let authored = authored.principled();
+        assert_eq!(authored.metallic, 0.0);
+        assert_eq!(authored.roughness, 0.2);
+        assert_eq!(authored.base_color, [0.0, 0.0, 0.0]);
+        assert_eq!(authored.clearcoat, 1.0);
+    }
 }
@@ -1,4 +1,61 @@
-use glam::Vec3;
+use glam::{EulerRot, Mat3, Vec3};
+/// Kind of a LightSource and its type specific parameters
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum LightType {
+    /// Spherical light emitting in all directions
+    #[default]
+    Point,
+    /// Infinitely distant sun, the position is ignored
+    /// 'angle': angular diameter in degrees, 0 gives sharp shadows
+    Directional { angle: f32 },
+    /// Spherical light emitting into a cone, the intensity falls off between the angles
+    /// 'inner_angle', 'outer_angle': angles between the axis and the cone in degrees
+    Spot { inner_angle: f32, outer_angle: f32 },
+    /// One-sided rectangle
+    Rect { width: f32, height: f32 },
+    /// One-sided disc
+    Disc { radius: f32 },
+}
+impl LightType {
+    /// Names of all types, in the order they are presented to the user
+    pub const NAMES: [&'static str; 5] = ["point", "directional", "spot", "rect", "disc"];
+    /// ## Returns
+    /// Lowercase name used in scene files
+    pub fn name(&self) -> &'static str {
+        match self {
+            LightType::Point => "point",
+            LightType::Directional { .. } => "directional",
+            LightType::Spot { .. } => "spot",
+            LightType::Rect { .. } => "rect",
+            LightType::Disc { .. } => "disc",
+        }
+    }
+    /// ## Parameter
+    /// 'name': Type name as used in scene files, ignoring case
+    /// ## Returns
+    /// The type with default parameters, None if the name is unknown
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "point" => Some(LightType::Point),
+            "directional" | "sun" => Some(LightType::Directional { angle: 0.5 }),
+            "spot" => Some(LightType::Spot {
+                inner_angle: 20.0,
+                outer_angle: 30.0,
+            }),
+            "rect" => Some(LightType::Rect {
+                width: 1.0,
+                height: 1.0,
+            }),
+            "disc" => Some(LightType::Disc { radius: 0.5 }),
+            _ => None,
+        }
+    }
+}
+impl std::fmt::Display for LightType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
 /// Defines light sources for the scene
 #[allow(dead_code)]
 #[derive(Clone, Debug)]
@@ -7,8 +64,10 @@ pub struct LightSource {
     luminosity: f32,
     pub name: String,
     pub color: [f32; 3],
+    /// Euler angles in degrees (x = roll, y = pitch, z = yaw).
+    /// Without rotation directional, spot and area lights point down (-y).
     pub rotation: Vec3,
-    // pub light_type: LightType,
+    pub light_type: LightType,
 }
 #[allow(dead_code)]
 impl LightSource {
@@ -44,8 +103,47 @@ impl LightSource {
     /// 'vec': Rotation vector
     /// ## Returns:
     /// New Rotation as glam::Vec3
-    pub fn rotate(&mut self, _vec: Vec3) -> Vec3 {
-        todo!()
+    pub fn rotate(&mut self, vec: Vec3) -> Vec3 {
+        self.rotation += vec;
+        self.rotation
+    }
+    /// Sets the rotation
+    /// ## Parameter
+    /// 'rotation': New rotation as Euler angles in degrees
+    pub fn set_rotation(&mut self, rotation: Vec3) {
+        self.rotation = rotation
+    }
+    /// ## Returns
+    /// Rotation matrix of the Euler angles, using the same convention as meshes
+    fn rotation_matrix(&self) -> Mat3 {
+        Mat3::from_euler(
+            EulerRot::ZYX,
+            self.rotation.z.to_radians(),
+            self.rotation.y.to_radians(),
+            self.rotation.x.to_radians(),
+        )
+    }
+    /// ## Returns
+    /// Normalized direction the light emits into: the spot axis, the normal of area lights
+    /// and the direction sunlight travels
+    pub fn get_direction(&self) -> Vec3 {
+        (self.rotation_matrix() * Vec3::NEG_Y).normalize()
+    }
+    /// ## Returns
+    /// Normalized direction of a rect light's width, perpendicular to the direction
+    pub fn get_tangent(&self) -> Vec3 {
+        (self.rotation_matrix() * Vec3::X).normalize()
+    }
+    /// ## Returns
+    /// Type of the LightSource with its parameters
+    pub fn get_light_type(&self) -> &LightType {
+        &self.light_type
+    }
+    /// Sets the type of the LightSource
+    /// ## Parameter
+    /// 'light_type': New type with its parameters
+    pub fn set_light_type(&mut self, light_type: LightType) {
+        self.light_type = light_type
     }
     /// ## Returns
     /// LightSource color as rgb array of f32, values in \[0, 1]
@@ -76,15 +174,15 @@ impl LightSource {
         color: [f32; 3],
         name: String,
         rotation: Vec3,
-        //light_type: LightType,
+        light_type: LightType,
     ) -> Self {
         LightSource {
             position,
             luminosity,
             name,
             color,
-            rotation, // some types have no ratation
-                      //light_type,
+            rotation, // point lights ignore the rotation
+            light_type,
         }
     }
 }
@@ -93,8 +191,8 @@ impl std::fmt::Display for LightSource {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "LightSource {} at {}",
-            //self.get_light_type(),
+            "{} LightSource {} at {}",
+            self.get_light_type(),
             self.name,
             self.get_position()
         )
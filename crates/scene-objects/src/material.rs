@@ -6,6 +6,7 @@ static PLASTIC: LazyLock<Material> = LazyLock::new(|| Material::from(MaterialPre
 static METAL: LazyLock<Material> = LazyLock::new(|| Material::from(MaterialPresets::Metal));
 static MIRROR: LazyLock<Material> = LazyLock::new(|| Material::from(MaterialPresets::Mirror));
 static LIGHT: LazyLock<Material> = LazyLock::new(|| Material::from(MaterialPresets::Light));
+static GLASS: LazyLock<Material> = LazyLock::new(|| Material::from(MaterialPresets::Glass));
 
 #[derive(Debug, PartialEq)]
 pub struct Material {
@@ -16,8 +17,152 @@ pub struct Material {
     pub emissive: Vec<f64>,              //Ke
     pub shininess: f64,                  //Ns
     pub transparency: f64,               //d
+    pub ior: f64,                        //Ni
+    pub illum: u32,                      //illum
     pub texture_path: Option<String>,    //map_Kd
     pub ref_path: Option<String>,
+    pub pbr: PbrProperties,
+    pub bump: BumpProperties,
+    pub maps: MapProperties,
+    pub medium: MediumProperties,
+    pub clamped_textures: Vec<String>,  //maps with -clamp on
+    pub mirrored_textures: Vec<String>, //maps with -mirror on
+    pub pattern: Option<Pattern>,       //proc
+}
+
+/// Values of the MTL PBR extension. `None` means the key was not present in the file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PbrProperties {
+    pub roughness: Option<f64>,           //Pr
+    pub metallic: Option<f64>,            //Pm
+    pub sheen: Option<f64>,               //Ps
+    pub clearcoat: Option<f64>,           //Pc
+    pub clearcoat_roughness: Option<f64>, //Pcr
+    pub roughness_path: Option<String>,   //map_Pr
+    pub metallic_path: Option<String>,    //map_Pm
+}
+
+/// Surface detail maps. `None` means the map was not present in the file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BumpProperties {
+    pub normal_path: Option<String>, //norm, or map_Bump of a normal map
+    pub height_path: Option<String>, //bump / map_Bump
+    pub multiplier: Option<f64>,     //-bm
+}
+
+/// Texture maps besides the diffuse, PBR and bump maps. `None` means the map was not present
+/// in the file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MapProperties {
+    pub opacity_path: Option<String>,   //map_d, alpha cutout
+    pub specular_path: Option<String>,  //map_Ks
+    pub emissive_path: Option<String>,  //map_Ke
+    pub shininess_path: Option<String>, //map_Ns
+}
+
+/// Homogeneous medium filling the interior of an object, coefficients per unit length.
+/// `None` means the key was not present in the file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MediumProperties {
+    pub absorption: Option<[f64; 3]>, //Ma
+    pub scattering: Option<[f64; 3]>, //Ms
+    pub anisotropy: Option<f64>,      //Mg
+}
+
+impl MediumProperties {
+    /// Whether the medium absorbs or scatters light at all
+    pub fn is_present(&self) -> bool {
+        [self.absorption, self.scattering]
+            .iter()
+            .flatten()
+            .any(|c| c.iter().any(|v| *v > 0.0))
+    }
+}
+
+/// Kind of a procedural [`Pattern`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatternKind {
+    /// `map_Kd` projected along the three world axes, for meshes without texture coordinates
+    Triplanar,
+    Checker,
+    /// Blend along the world y axis
+    Gradient,
+    /// Fractal Perlin noise
+    Noise,
+    Voronoi,
+}
+impl PatternKind {
+    /// All kinds, in the order they are presented to the user
+    pub const ALL: [PatternKind; 5] = [
+        PatternKind::Triplanar,
+        PatternKind::Checker,
+        PatternKind::Gradient,
+        PatternKind::Noise,
+        PatternKind::Voronoi,
+    ];
+    /// ## Returns
+    /// Lowercase name used in scene files and MTL files
+    pub fn name(&self) -> &'static str {
+        match self {
+            PatternKind::Triplanar => "triplanar",
+            PatternKind::Checker => "checker",
+            PatternKind::Gradient => "gradient",
+            PatternKind::Noise => "noise",
+            PatternKind::Voronoi => "voronoi",
+        }
+    }
+    /// ## Parameter
+    /// 'name': Kind name as used in scene files, ignoring case
+    /// ## Returns
+    /// The kind, None if the name is unknown
+    pub fn from_name(name: &str) -> Option<Self> {
+        PatternKind::ALL
+            .into_iter()
+            .find(|k| k.name().eq_ignore_ascii_case(name))
+    }
+}
+impl std::fmt::Display for PatternKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Procedural base color input that takes the place of the `map_Kd` lookup.
+/// Evaluated at the world position, `scale` is the number of repetitions per scene unit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern {
+    pub kind: PatternKind,
+    pub scale: f64,
+    pub colors: [[f64; 3]; 2], //not used by triplanar
+    pub octaves: u32,          //noise
+    pub sharpness: f64,        //triplanar blend exponent
+}
+
+impl Pattern {
+    /// Constructor for a pattern with scale 1, light and dark grey, 4 octaves and sharpness 4
+    /// ## Parameter
+    /// 'kind': Kind of the pattern
+    pub fn new(kind: PatternKind) -> Self {
+        Self {
+            kind,
+            scale: 1.0,
+            colors: [[0.8; 3], [0.2; 3]],
+            octaves: 4,
+            sharpness: 4.0,
+        }
+    }
+}
+
+/// Parameters of the principled (metallic/roughness) BSDF, see [`Material::principled`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Principled {
+    pub base_color: [f64; 3],
+    pub metallic: f64,
+    pub roughness: f64,
+    pub specular: f64,
+    pub sheen: f64,
+    pub clearcoat: f64,
+    pub clearcoat_roughness: f64,
 }
 
 #[allow(dead_code)]
@@ -31,6 +176,8 @@ impl Material {
         emissive: Vec<f64>,
         shininess: f64,
         transparency: f64,
+        ior: f64,
+        illum: u32,
         texture_path: Option<String>,
         ref_path: Option<String>,
     ) -> Self {
@@ -42,8 +189,148 @@ impl Material {
             emissive,
             shininess,
             transparency,
+            ior,
+            illum,
             texture_path,
             ref_path,
+            pbr: PbrProperties::default(),
+            bump: BumpProperties::default(),
+            maps: MapProperties::default(),
+            medium: MediumProperties::default(),
+            clamped_textures: Vec::new(),
+            mirrored_textures: Vec::new(),
+            pattern: None,
+        }
+    }
+
+    pub fn with_pbr(mut self, pbr: PbrProperties) -> Self {
+        self.pbr = pbr;
+        self
+    }
+
+    pub fn with_bump(mut self, bump: BumpProperties) -> Self {
+        self.bump = bump;
+        self
+    }
+
+    pub fn with_maps(mut self, maps: MapProperties) -> Self {
+        self.maps = maps;
+        self
+    }
+
+    /// Fills the interior of objects with this material with a homogeneous medium
+    pub fn with_medium(mut self, medium: MediumProperties) -> Self {
+        self.medium = medium;
+        self
+    }
+
+    /// Replaces the `map_Kd` lookup with a procedural pattern, None for the texture
+    pub fn with_pattern(mut self, pattern: Option<Pattern>) -> Self {
+        self.pattern = pattern;
+        self
+    }
+
+    /// Sets the texture files that are clamped instead of repeated
+    pub fn with_clamped_textures(mut self, clamped_textures: Vec<String>) -> Self {
+        self.clamped_textures = clamped_textures;
+        self
+    }
+
+    /// Sets the texture files that are mirrored on every other tile instead of repeated
+    pub fn with_mirrored_textures(mut self, mirrored_textures: Vec<String>) -> Self {
+        self.mirrored_textures = mirrored_textures;
+        self
+    }
+
+    /// All texture files referenced by this material.
+    pub fn texture_paths(&self) -> Vec<&String> {
+        [
+            &self.texture_path,
+            &self.pbr.roughness_path,
+            &self.pbr.metallic_path,
+            &self.bump.normal_path,
+            &self.bump.height_path,
+            &self.maps.opacity_path,
+            &self.maps.specular_path,
+            &self.maps.emissive_path,
+            &self.maps.shininess_path,
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+
+    /// Whether the former "metal" heuristic applies: Ks is set, Kd is black and no metallic
+    /// value was authored
+    fn is_derived_metal(&self) -> bool {
+        let mean = |v: &Vec<f64>| v.iter().take(3).sum::<f64>() / 3.0;
+        self.pbr.metallic.is_none()
+            && self.pbr.metallic_path.is_none()
+            && mean(&self.specular_reflectivity) > 0.01
+            && mean(&self.diffuse_reflectivity) < 0.01
+    }
+
+    /// The map of the principled base color: `map_Ks` for derived metals, whose base color is
+    /// Ks, `map_Kd` otherwise
+    pub fn base_color_path(&self) -> &Option<String> {
+        match &self.maps.specular_path {
+            Some(_) if self.is_derived_metal() => &self.maps.specular_path,
+            _ => &self.texture_path,
+        }
+    }
+
+    /// The map of the specular level, None for derived metals that use `map_Ks` as base color
+    pub fn specular_level_path(&self) -> &Option<String> {
+        match self.is_derived_metal() {
+            true => &None,
+            false => &self.maps.specular_path,
+        }
+    }
+
+    /// Resolves the principled BSDF parameters.
+    ///
+    /// Values given through the PBR extension take precedence. Missing values are
+    /// derived from the Phong fields:
+    /// - metallic: 1.0 if Ks is set and Kd is black (the former "metal" heuristic), else 0.0
+    /// - base color: Ks for such derived metals, Kd otherwise
+    /// - roughness: `1 - sqrt(Ns / 1000)`, the inverse of Blender's MTL export
+    /// - specular: mean of Ks for non-metals, 0.5 corresponds to an F0 of 0.04
+    ///
+    /// A roughness or metallic map without a scalar value uses 1.0 as scalar,
+    /// so the map is used as is.
+    pub fn principled(&self) -> Principled {
+        let channel = |v: &Vec<f64>, i: usize| v.get(i).copied().unwrap_or(0.0);
+        let mean = |v: &Vec<f64>| (channel(v, 0) + channel(v, 1) + channel(v, 2)) / 3.0;
+
+        let derived_metal = self.is_derived_metal();
+        let metallic = match (self.pbr.metallic, &self.pbr.metallic_path) {
+            (Some(m), _) => m,
+            (None, Some(_)) => 1.0,
+            (None, None) if derived_metal => 1.0,
+            (None, None) => 0.0,
+        };
+        let (base, specular) = if derived_metal {
+            (&self.specular_reflectivity, 0.5)
+        } else {
+            (
+                &self.diffuse_reflectivity,
+                mean(&self.specular_reflectivity),
+            )
+        };
+        let roughness = match (self.pbr.roughness, &self.pbr.roughness_path) {
+            (Some(r), _) => r,
+            (None, Some(_)) => 1.0,
+            (None, None) => 1.0 - (self.shininess / 1000.0).clamp(0.0, 1.0).sqrt(),
+        };
+
+        Principled {
+            base_color: [channel(base, 0), channel(base, 1), channel(base, 2)],
+            metallic: metallic.clamp(0.0, 1.0),
+            roughness: roughness.clamp(0.0, 1.0),
+            specular: specular.clamp(0.0, 1.0),
+            sheen: self.pbr.sheen.unwrap_or(0.0).max(0.0),
+            clearcoat: self.pbr.clearcoat.unwrap_or(0.0).clamp(0.0, 1.0),
+            clearcoat_roughness: self.pbr.clearcoat_roughness.unwrap_or(0.03).clamp(0.0, 1.0),
         }
     }
 }
@@ -58,8 +345,17 @@ impl Clone for Material {
             emissive: self.emissive.clone(),
             shininess: self.shininess,
             transparency: self.transparency,
+            ior: self.ior,
+            illum: self.illum,
             texture_path: self.texture_path.clone(),
             ref_path: self.ref_path.clone(),
+            pbr: self.pbr.clone(),
+            bump: self.bump.clone(),
+            maps: self.maps.clone(),
+            medium: self.medium.clone(),
+            clamped_textures: self.clamped_textures.clone(),
+            mirrored_textures: self.mirrored_textures.clone(),
+            pattern: self.pattern.clone(),
         }
     }
 }
@@ -77,19 +373,21 @@ pub enum MaterialPresets {
     #[default]
     Mirror,
     Light,
+    Glass,
 }
 
 impl MaterialPresets {
-    pub const fn list() -> [&'static str; 4] {
-        ["plastic", "metal", "mirror", "light"]
+    pub const fn list() -> [&'static str; 5] {
+        ["plastic", "metal", "mirror", "light", "glass"]
     }
 
-    pub const fn list_enum() -> [MaterialPresets; 4] {
+    pub const fn list_enum() -> [MaterialPresets; 5] {
         [
             MaterialPresets::Plastic,
             MaterialPresets::Metal,
             MaterialPresets::Mirror,
             MaterialPresets::Light,
+            MaterialPresets::Glass,
         ]
     }
 }
@@ -101,6 +399,7 @@ impl From<MaterialPresets> for String {
             MaterialPresets::Metal => "metal",
             MaterialPresets::Mirror => "mirror",
             MaterialPresets::Light => "light",
+            MaterialPresets::Glass => "glass",
         }
         .to_string()
     }
@@ -115,6 +414,7 @@ impl TryFrom<&str> for MaterialPresets {
             "metal" => Ok(MaterialPresets::Metal),
             "mirror" => Ok(MaterialPresets::Mirror),
             "light" => Ok(MaterialPresets::Light),
+            "glass" => Ok(MaterialPresets::Glass),
             _ => Err(anyhow!("Invalid material preset: {}", string)),
         }
     }
@@ -131,6 +431,8 @@ impl From<MaterialPresets> for Material {
                 vec![0.0, 0.0, 0.0],
                 0.0,
                 1.0,
+                1.0,
+                2,
                 None,
                 None,
             ),
@@ -142,6 +444,8 @@ impl From<MaterialPresets> for Material {
                 vec![100.0, 100.0, 100.0],
                 0.0,
                 1.0,
+                1.0,
+                2,
                 None,
                 None,
             ),
@@ -152,7 +456,9 @@ impl From<MaterialPresets> for Material {
                 vec![1.0, 1.0, 1.0],
                 vec![0.0, 0.0, 0.0],
                 1000.0,
-                0.0,
+                1.0,
+                1.0,
+                2,
                 None,
                 None,
             ),
@@ -164,6 +470,21 @@ impl From<MaterialPresets> for Material {
                 vec![0.0, 0.0, 0.0],
                 500.0,
                 1.0,
+                1.0,
+                2,
+                None,
+                None,
+            ),
+            MaterialPresets::Glass => Material::new(
+                "glass".to_string(),
+                vec![0.0, 0.0, 0.0],
+                vec![1.0, 1.0, 1.0],
+                vec![0.0, 0.0, 0.0],
+                vec![0.0, 0.0, 0.0],
+                0.0,
+                1.0,
+                1.5,
+                7,
                 None,
                 None,
             ),
@@ -185,6 +506,7 @@ impl TryFrom<&Material> for MaterialPresets {
 }
 
 #[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
 pub enum MaterialRef {
     Preset(MaterialPresets),
     Custom(Material),
@@ -204,6 +526,7 @@ impl MaterialRef {
                 MaterialPresets::Metal => &METAL,
                 MaterialPresets::Mirror => &MIRROR,
                 MaterialPresets::Light => &LIGHT,
+                MaterialPresets::Glass => &GLASS,
             },
             MaterialRef::Custom(mtl) => mtl,
         }
@@ -219,7 +542,16 @@ impl PartialEq<Material> for &Material {
             && self.emissive == other.emissive
             && self.shininess == other.shininess
             && self.transparency == other.transparency
+            && self.ior == other.ior
+            && self.illum == other.illum
             && self.texture_path == other.texture_path
+            && self.pbr == other.pbr
+            && self.bump == other.bump
+            && self.maps == other.maps
+            && self.medium == other.medium
+            && self.clamped_textures == other.clamped_textures
+            && self.mirrored_textures == other.mirrored_textures
+            && self.pattern == other.pattern
     }
 }
 
@@ -1,14 +1,19 @@
+use std::collections::HashMap;
 use std::fmt::Display;
 use std::path::PathBuf;
-use glam::{Vec3, Mat3, EulerRot};
+use glam::{Vec3, Mat3, Mat4, EulerRot};
 use anyhow::Error;
 use log::debug;
 
 use crate::{
     geometric_object::{GeometricObject, SceneObject},
-    material::Material,
+    material::{Material, Pattern},
 };
 
+/// Angle in degrees between two face normals above which generated vertex normals are not averaged,
+/// so hard edges (e.g. of a box) stay sharp
+pub const NORMAL_CREASE_ANGLE: f32 = 60.0;
+
 /// Mesh represents the main type of geometry: It is made up from vertices and tris
 #[allow(dead_code)]
 #[derive(Debug)]
@@ -16,8 +21,11 @@ pub struct Mesh {
     vertices: Vec<f32>,
     tris: Vec<u32>,
     uvs: Option<Vec<f32>>,
+    normals: Option<Vec<f32>>,
+    tangents: Option<Vec<f32>>,
     materials: Option<Vec<Material>>,
     material_index: Option<Vec<usize>>,
+    pattern: Option<Pattern>,
     path: Option<PathBuf>,
     name: String,
     scale: Vec3,
@@ -44,8 +52,11 @@ impl Mesh {
                 vertices,
                 tris,
                 uvs,
+                normals: None,
+                tangents: None,
                 materials,
                 material_index,
+                pattern: None,
                 path: _path,
                 name: name.unwrap_or("unnamed mesh".to_owned()),
                 scale: Vec3::new(1.0, 1.0, 1.0),
@@ -57,11 +68,40 @@ impl Mesh {
             Err(error) => Err(Error::msg(format!("Failed to create new mesh: {error}"))),
         }
     }
+    /// Sets per vertex normals, three entries per vertex in the same order as the vertices
+    /// ## Parameter
+    /// 'normals': Vec<f32> of normals, empty or wrongly sized input is ignored
+    pub fn with_normals(mut self, normals: Vec<f32>) -> Self {
+        if !normals.is_empty() && normals.len() == self.vertices.len() {
+            self.normals = Some(normals);
+        }
+        self
+    }
+    /// Sets per vertex tangents, four entries per vertex: the tangent and the handedness of the bitangent
+    /// ## Parameter
+    /// 'tangents': Vec<f32> of tangents, empty or wrongly sized input is ignored
+    pub fn with_tangents(mut self, tangents: Vec<f32>) -> Self {
+        if !tangents.is_empty() && tangents.len() == self.vertices.len() / 3 * 4 {
+            self.tangents = Some(tangents);
+        }
+        self
+    }
     /// ## Returns
     /// The materials of the mesh as an Option of Reference to a Vector of Materials
     pub fn get_materials(&self) -> Option<&Vec<Material>> {
         self.materials.as_ref()
     }
+    /// ## Returns
+    /// The procedural pattern replacing the patterns and textures of all materials of the mesh, if any
+    pub fn get_pattern(&self) -> Option<&Pattern> {
+        self.pattern.as_ref()
+    }
+    /// Sets a procedural pattern for all materials of the mesh, None keeps the materials as loaded
+    /// ## Parameter
+    /// 'pattern': the pattern, applied in place of the patterns of the materials
+    pub fn set_pattern(&mut self, pattern: Option<Pattern>) {
+        self.pattern = pattern;
+    }
     /// Calculates the centroid based on self.vertices
     /// Should be called after all changes to self.vertices
     /// ## Returns
@@ -121,6 +161,18 @@ impl Mesh {
             self.vertices[i * 3 + 1] = p_final.y;
             self.vertices[i * 3 + 2] = p_final.z;
         }
+        if let Some(normals) = self.normals.as_mut() {
+            for n in normals.chunks_exact_mut(3) {
+                let rotated = matrix * Vec3::new(n[0], n[1], n[2]);
+                n.copy_from_slice(&rotated.to_array());
+            }
+        }
+        if let Some(tangents) = self.tangents.as_mut() {
+            for t in tangents.chunks_exact_mut(4) {
+                let rotated = matrix * Vec3::new(t[0], t[1], t[2]);
+                t[..3].copy_from_slice(&rotated.to_array());
+            }
+        }
         self.update_centroid();
     }
     /// scales the mesh so that the given scale is the new scale
@@ -164,6 +216,132 @@ impl Mesh {
         let len = len as f32;
         Ok(Vec3::new(x_sum / len, y_sum / len, z_sum / len))
     }
+    /// Calculates angle weighted vertex normals for indexed triangles.
+    /// Faces that meet at an angle above [`NORMAL_CREASE_ANGLE`] do not contribute to each other's normals.
+    /// ## Parameter
+    /// 'vertices': slice of f32 representing the vertices
+    /// 'tris': slice of u32, where three entries are the vertex indices of one triangle
+    /// ## Returns
+    /// Vec<f32> with one normal (three entries) per entry in tris
+    pub fn calculate_vertex_normals(vertices: &[f32], tris: &[u32]) -> Vec<f32> {
+        let point = |i: u32| {
+            let i = i as usize * 3;
+            vertices
+                .get(i..i + 3)
+                .map(Vec3::from_slice)
+                .unwrap_or(Vec3::ZERO)
+        };
+        let cos_crease = NORMAL_CREASE_ANGLE.to_radians().cos();
+
+        // face normal and the interior angle at each corner
+        let mut faces = Vec::with_capacity(tris.len() / 3);
+        let mut adjacent: HashMap<u32, Vec<(usize, f32)>> = HashMap::new();
+        for (face, corners) in tris.chunks_exact(3).enumerate() {
+            let p = [point(corners[0]), point(corners[1]), point(corners[2])];
+            faces.push((p[1] - p[0]).cross(p[2] - p[0]).normalize_or_zero());
+            for k in 0..3 {
+                let a = (p[(k + 1) % 3] - p[k]).normalize_or_zero();
+                let b = (p[(k + 2) % 3] - p[k]).normalize_or_zero();
+                let angle = a.dot(b).clamp(-1.0, 1.0).acos();
+                adjacent.entry(corners[k]).or_default().push((face, angle));
+            }
+        }
+
+        let mut normals = Vec::with_capacity(faces.len() * 9);
+        for (face, corners) in tris.chunks_exact(3).enumerate() {
+            let face_normal = faces[face];
+            for corner in corners {
+                let n = adjacent[corner]
+                    .iter()
+                    .filter(|(other, _)| faces[*other].dot(face_normal) >= cos_crease)
+                    .fold(Vec3::ZERO, |sum, (other, angle)| {
+                        sum + faces[*other] * *angle
+                    })
+                    .normalize_or(face_normal);
+                normals.extend_from_slice(&n.to_array());
+            }
+        }
+        normals
+    }
+    /// Calculates per vertex tangents from the texture coordinates.
+    /// Triangle tangents are accumulated over vertices with equal position, uv and normal,
+    /// then made orthogonal to the vertex normal.
+    /// ## Parameter
+    /// 'vertices': slice of f32 representing the vertices
+    /// 'uvs': slice of f32, two entries per vertex
+    /// 'normals': slice of f32, three entries per vertex
+    /// 'tris': slice of u32, where three entries are the vertex indices of one triangle
+    /// ## Returns
+    /// Vec<f32> with four entries per vertex: the tangent and the sign of the bitangent.
+    /// Vertices without usable texture coordinates get a zero tangent
+    pub fn calculate_tangents(
+        vertices: &[f32],
+        uvs: &[f32],
+        normals: &[f32],
+        tris: &[u32],
+    ) -> Vec<f32> {
+        let vertex_count = vertices.len() / 3;
+        let read3 = |data: &[f32], i: usize| {
+            data.get(i * 3..i * 3 + 3)
+                .map(Vec3::from_slice)
+                .unwrap_or(Vec3::ZERO)
+        };
+        let read_uv = |i: usize| {
+            uvs.get(i * 2..i * 2 + 2)
+                .map(|uv| (uv[0], uv[1]))
+                .unwrap_or_default()
+        };
+
+        // vertices that only differ by their triangle share one tangent
+        let mut groups: HashMap<[u32; 8], usize> = HashMap::new();
+        let group_of: Vec<usize> = (0..vertex_count)
+            .map(|i| {
+                let (p, n, (u, v)) = (read3(vertices, i), read3(normals, i), read_uv(i));
+                let key = [p.x, p.y, p.z, u, v, n.x, n.y, n.z].map(f32::to_bits);
+                let next = groups.len();
+                *groups.entry(key).or_insert(next)
+            })
+            .collect();
+
+        let mut tangent_sum = vec![Vec3::ZERO; groups.len()];
+        let mut bitangent_sum = vec![Vec3::ZERO; groups.len()];
+        for corners in tris.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|k| corners[k] as usize);
+            let (e1, e2) = (
+                read3(vertices, b) - read3(vertices, a),
+                read3(vertices, c) - read3(vertices, a),
+            );
+            let ((u0, v0), (u1, v1), (u2, v2)) = (read_uv(a), read_uv(b), read_uv(c));
+            let (du1, dv1, du2, dv2) = (u1 - u0, v1 - v0, u2 - u0, v2 - v0);
+            let det = du1 * dv2 - du2 * dv1;
+            if det.abs() < 1e-12 {
+                continue;
+            }
+            // unnormalized, so larger triangles weigh more
+            let tangent = (e1 * dv2 - e2 * dv1) / det;
+            let bitangent = (e2 * du1 - e1 * du2) / det;
+            for i in [a, b, c] {
+                if let Some(&group) = group_of.get(i) {
+                    tangent_sum[group] += tangent;
+                    bitangent_sum[group] += bitangent;
+                }
+            }
+        }
+
+        let mut tangents = Vec::with_capacity(vertex_count * 4);
+        for (i, group) in group_of.iter().enumerate() {
+            let n = read3(normals, i);
+            let t = tangent_sum[*group];
+            let t = (t - n * n.dot(t)).normalize_or_zero();
+            let w = if n.cross(t).dot(bitangent_sum[*group]) < 0.0 {
+                -1.0
+            } else {
+                1.0
+            };
+            tangents.extend_from_slice(&[t.x, t.y, t.z, if t == Vec3::ZERO { 0.0 } else { w }]);
+        }
+        tangents
+    }
 
     pub fn get_name(&self) -> String {
         self.name.clone()
@@ -179,6 +357,16 @@ impl Mesh {
         self.uvs.as_ref()
     }
     /// ## Returns
+    /// Vertex normals of the Mesh, three entries per vertex
+    pub fn get_normals(&self) -> Option<&Vec<f32>> {
+        self.normals.as_ref()
+    }
+    /// ## Returns
+    /// Vertex tangents of the Mesh, four entries per vertex
+    pub fn get_tangents(&self) -> Option<&Vec<f32>> {
+        self.tangents.as_ref()
+    }
+    /// ## Returns
     /// Reference to Vec<u32>, where three entries define the indices of the vertices that make up one triangle
     pub fn get_tri_indices(&self) -> &Vec<u32> {
         &self.tris
@@ -190,6 +378,29 @@ impl Mesh {
     pub fn get_material_indices(&self) -> Option<&Vec<usize>> {
         self.material_index.as_ref()
     }
+    /// ## Returns
+    /// The centroid the mesh had when it was created, before any translation
+    pub fn get_origin(&self) -> Vec3 {
+        self.centroid - self.translation
+    }
+    /// ## Returns
+    /// The transform from the vertices the mesh was created with to its current vertices
+    pub fn get_transform(&self) -> Mat4 {
+        Self::placement(
+            self.get_origin(),
+            self.scale.x,
+            self.accumulated_rotation,
+            self.translation,
+        )
+    }
+    /// Builds the transform of a scale and rotation around 'origin', followed by a translation,
+    /// which is how the scale, rotation and translation of a mesh are applied
+    pub(crate) fn placement(origin: Vec3, scale: f32, rotation: Mat3, translation: Vec3) -> Mat4 {
+        Mat4::from_translation(origin + translation)
+            * Mat4::from_mat3(rotation)
+            * Mat4::from_scale(Vec3::splat(scale))
+            * Mat4::from_translation(-origin)
+    }
 }
 
 #[allow(unused)]
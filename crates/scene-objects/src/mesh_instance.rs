@@ -0,0 +1,154 @@
+use std::fmt::Display;
+use std::path::PathBuf;
+use glam::{EulerRot, Mat3, Mat4, Vec3};
+use log::debug;
+
+use crate::{
+    geometric_object::{GeometricObject, SceneObject},
+    mesh::Mesh,
+};
+
+/// Another placement of the geometry of a Mesh in the scene.
+/// The instance shares the vertices, materials and BVH of its mesh and only stores its own transform.
+/// Scale, rotation and translation are applied to the mesh as it was created, like they would be to a freshly loaded copy
+#[derive(Debug)]
+pub struct MeshInstance {
+    mesh_index: usize,
+    name: String,
+    scale: Vec3,
+    translation: Vec3,
+    rotation: Vec3,
+    accumulated_rotation: Mat3,
+}
+
+#[allow(unused)]
+impl MeshInstance {
+    /// Constructor for a new MeshInstance without transformation
+    /// ## Parameter
+    /// 'mesh_index': Index of the instanced mesh in the scene <br>
+    /// 'name': Name of the instance
+    pub fn new(mesh_index: usize, name: String) -> Self {
+        Self {
+            mesh_index,
+            name,
+            scale: Vec3::new(1.0, 1.0, 1.0),
+            translation: Vec3::default(),
+            rotation: Vec3::default(),
+            accumulated_rotation: Mat3::IDENTITY,
+        }
+    }
+    /// ## Returns
+    /// Index of the instanced mesh in the scene
+    pub fn get_mesh_index(&self) -> usize {
+        self.mesh_index
+    }
+    /// Sets the index of the instanced mesh, e.g. after a mesh before it was removed
+    /// ## Parameter
+    /// 'mesh_index': New index of the instanced mesh in the scene
+    pub fn set_mesh_index(&mut self, mesh_index: usize) {
+        self.mesh_index = mesh_index;
+    }
+    pub fn get_name(&self) -> String {
+        self.name.clone()
+    }
+    /// scales the instance so that the given scale is the new scale
+    /// ## Parameter
+    /// 'scale': new absolute scale
+    pub fn scale_to(&mut self, scale: f32) {
+        let factor = scale.abs() / self.scale.x;
+        if factor != 0.0 {
+            self.scale(factor);
+        }
+    }
+    /// translates the instance so that the given translation is the new absolute translation
+    /// ## Parameter
+    /// 'translation': new absolute translation as glam::Vec3
+    pub fn translate_to(&mut self, translation: Vec3) {
+        self.translation = translation;
+    }
+    /// rotates the instance so that the given vector is the new rotation
+    /// ## Parameter
+    /// 'rotation': new absolute rotation as glam::Vec3 (Euler angles in degrees)
+    pub fn rotate_to(&mut self, rotation: Vec3) {
+        self.accumulated_rotation = Mat3::from_euler(
+            EulerRot::ZYX,
+            rotation.z.to_radians(),
+            rotation.y.to_radians(),
+            rotation.x.to_radians(),
+        );
+        self.rotation = rotation;
+    }
+    /// ## Parameter
+    /// 'mesh': The instanced mesh
+    /// ## Returns
+    /// The transform from the current vertices of the mesh to the vertices of this instance
+    pub fn get_transform(&self, mesh: &Mesh) -> Mat4 {
+        let placement = Mesh::placement(
+            mesh.get_origin(),
+            self.scale.x,
+            self.accumulated_rotation,
+            self.translation,
+        );
+        placement * mesh.get_transform().inverse()
+    }
+}
+
+impl GeometricObject for MeshInstance {
+    /// scales the instance by the given factor
+    /// ## Parameter
+    /// 'factor': factor for scale
+    fn scale(&mut self, factor: f32) {
+        self.scale *= factor;
+    }
+    /// translates the instance by the direction given
+    /// ## Parameter
+    /// 'vec': Vector by which the instance is translated
+    fn translate(&mut self, vec: Vec3) {
+        self.translation += vec;
+    }
+    /// Rotates the instance around its centroid
+    /// ## Parameter
+    /// 'vec': Rotation: Euler angles in degree (Z, Y, X) = yaw, pitch, roll
+    fn rotate(&mut self, vec: Vec3) {
+        debug!("MeshInstance {}: rotate relative {:?}", self.name, vec);
+        let delta_rot = Mat3::from_euler(
+            EulerRot::ZYX,
+            vec.z.to_radians(),
+            vec.y.to_radians(),
+            vec.x.to_radians(),
+        );
+        self.accumulated_rotation = delta_rot * self.accumulated_rotation;
+
+        let (z, y, x) = self.accumulated_rotation.to_euler(EulerRot::ZYX);
+        self.rotation = Vec3::new(x.to_degrees(), y.to_degrees(), z.to_degrees());
+    }
+}
+
+impl SceneObject for MeshInstance {
+    /// Instances have no file of their own, the path is the one of the instanced mesh
+    fn get_path(&self) -> Option<PathBuf> {
+        None
+    }
+
+    fn get_scale(&self) -> Vec3 {
+        self.scale
+    }
+
+    fn get_translation(&self) -> Vec3 {
+        self.translation
+    }
+
+    fn get_rotation(&self) -> Vec3 {
+        self.rotation
+    }
+}
+
+impl Display for MeshInstance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Mesh instance {} of mesh {}: translation: {:?}",
+            self.name, self.mesh_index, self.translation
+        )
+    }
+}
@@ -1,4 +1,4 @@
-use glam::Vec3;
+use glam::{EulerRot, Mat3, Vec3};
 use crate::{
     geometric_object::{GeometricObject, SceneObject},
     material::Material,
@@ -36,6 +36,24 @@ impl Sphere {
     pub fn translate_to(&mut self, translation: Vec3) {
         self.translate(self.translation - translation);
     }
+    /// rotates the sphere so that the given vector is the new rotation
+    /// ## Parameter
+    /// 'rotation': new absolute rotation as glam::Vec3 (Euler angles in degrees)
+    pub fn rotate_to(&mut self, rotation: Vec3) {
+        self.rotation = rotation;
+    }
+    /// ## Returns
+    /// Orientation of the sphere's texture mapping as rotation matrix.
+    /// Applied to the local frame whose v axis runs from the south to the north pole (+y)
+    /// and whose seam lies at -z.
+    pub fn get_orientation(&self) -> Mat3 {
+        Mat3::from_euler(
+            EulerRot::ZYX,
+            self.rotation.z.to_radians(),
+            self.rotation.y.to_radians(),
+            self.rotation.x.to_radians(),
+        )
+    }
     /// Sets the LightSource color
     /// ## Parameter
     /// 'color': New LightSource color as array of f32, values in \[0, 1]
@@ -138,8 +156,17 @@ impl GeometricObject for Sphere {
         self.center += vec;
         self.translation += vec;
     }
-    /// Rotates the sphere. Currently not needed,  it could be usefull if sphere is extended to have a texture
+    /// Rotates the sphere around its center, which turns its texture mapping
+    /// ## Parameter
+    /// 'vec': Rotation: Euler angles in degree (Z, Y, X) = yaw, pitch, roll
     fn rotate(&mut self, vec: Vec3) {
-        self.rotation += vec;
+        let delta_rot = Mat3::from_euler(
+            EulerRot::ZYX,
+            vec.z.to_radians(),
+            vec.y.to_radians(),
+            vec.x.to_radians(),
+        );
+        let (z, y, x) = (delta_rot * self.get_orientation()).to_euler(EulerRot::ZYX);
+        self.rotation = Vec3::new(x.to_degrees(), y.to_degrees(), z.to_degrees());
     }
 }
@@ -2,7 +2,7 @@
 //!
 //! This module provides the [`Engine`] struct, which serves as a unified interface
 //! for all rendering backends in the RenderBaby system. It wraps individual engine
-//! implementations (raytracer, pathtracer) and provides a consistent API with
+//! implementations (raytracer, pathtracer, CPU) and provides a consistent API with
 //! additional features like automatic timing, logging, and runtime engine switching.
 
 use anyhow::Result;
@@ -42,7 +42,7 @@ use crate::compute_plane::render_engine::RenderEngine;
 /// let frame = engine.render(config)?;
 /// ```
 pub struct Engine {
-    /// The wrapped renderer implementation (raytracer or pathtracer).
+    /// The wrapped renderer implementation (raytracer, pathtracer or CPU).
     renderer: Box<dyn Renderer + Sync>,
     /// The type of the current rendering engine.
     engine_type: RenderEngine,
@@ -112,7 +112,7 @@ impl RendererIterable for Engine {
 impl Engine {
     /// Creates a new rendering engine with the specified backend.
     ///
-    /// Initializes the appropriate rendering backend (raytracer or pathtracer) based
+    /// Initializes the appropriate rendering backend (raytracer, pathtracer or CPU) based
     /// on the `engine_type` parameter. The backend is initialized with the provided
     /// configuration.
     ///
@@ -128,6 +128,7 @@ impl Engine {
         let renderer: Box<dyn Renderer + Sync> = match engine_type {
             RenderEngine::Raytracer => Box::new(engine_raytracer::Engine::new(rc)),
             RenderEngine::Pathtracer => Box::new(engine_pathtracer::Engine::new(rc)),
+            RenderEngine::Cpu => Box::new(engine_cpu::Engine::new(rc)),
         };
 
         Self {
@@ -187,6 +188,7 @@ impl Engine {
         self.renderer = match engine_type {
             RenderEngine::Raytracer => Box::new(engine_raytracer::Engine::new(rc)),
             RenderEngine::Pathtracer => Box::new(engine_pathtracer::Engine::new(rc)),
+            RenderEngine::Cpu => Box::new(engine_cpu::Engine::new(rc)),
         };
         self.engine_type = engine_type;
     }
@@ -11,11 +11,12 @@
 #[allow(dead_code)]
 #[derive(Debug, Clone, Copy, Default)]
 pub enum RenderEngine {
-    /// Classical ray tracing with direct lighting (not implemented).
+    /// Whitted-style ray tracing with direct lighting.
     ///
     /// Features:
     /// - Fast rendering
-    /// - Direct illumination
+    /// - Direct illumination with hard shadows and Phong shading
+    /// - Mirror reflections and glass refraction
     /// - No global illumination
     /// - Good for previews
     Raytracer,
@@ -29,4 +30,12 @@ pub enum RenderEngine {
     /// - Slower but higher quality
     #[default]
     Pathtracer,
+
+    /// Path tracing on the CPU.
+    ///
+    /// Features:
+    /// - Same light transport as the pathtracer
+    /// - Runs without a GPU, parallelized across tiles
+    /// - Much slower, meant for testing and as a reference
+    Cpu,
 }
@@ -1,4 +1,5 @@
 use clap::Parser;
+use engine_config::{Aov, AovSet, TileOrder, ToneMapping};
 use std::path::PathBuf;
 use log::{error, info};
 use crate::control_plane::app::App;
@@ -19,6 +20,62 @@ pub struct Args {
 
     #[arg(long, default_value = "png", value_parser = ["png", "jpg"], help = "Image file format.")]
     pub filetype: String,
+
+    #[arg(
+        long,
+        allow_hyphen_values = true,
+        help = "Exposure in stops, overrides the scene."
+    )]
+    pub exposure: Option<f32>,
+
+    #[arg(
+        long,
+        help = "Tone mapping operator (none, reinhard, aces, agx), overrides the scene."
+    )]
+    pub tone_mapping: Option<ToneMapping>,
+
+    #[arg(
+        long,
+        value_delimiter = ',',
+        help = "AOV layers (depth, normal, albedo, object_id, uv) saved next to the image."
+    )]
+    pub aovs: Vec<Aov>,
+
+    #[arg(
+        long,
+        help = "Denoise the image, the noisy input is saved next to it with the suffix _raw."
+    )]
+    pub denoise: bool,
+
+    #[arg(
+        long,
+        help = "Stop sampling pixels whose noise falls below this level (0 disables adaptive sampling), overrides the scene."
+    )]
+    pub adaptive_threshold: Option<f32>,
+
+    #[arg(
+        long,
+        help = "Samples every pixel takes before adaptive sampling may stop it, overrides the scene."
+    )]
+    pub adaptive_min_samples: Option<u32>,
+
+    #[arg(
+        long,
+        help = "Render in square tiles with this edge length in pixels (0 splits the image only when it exceeds the GPU limits), overrides the scene."
+    )]
+    pub tile_size: Option<u32>,
+
+    #[arg(
+        long,
+        help = "Order in which the tiles are rendered (rows, columns, spiral), overrides the scene."
+    )]
+    pub tile_order: Option<TileOrder>,
+
+    #[arg(
+        long,
+        help = "Render the ground as shadow catcher, only its shadows and reflections are kept over a transparent background (png only)."
+    )]
+    pub shadow_catcher: bool,
 }
 
 pub struct CliStaticApp {
@@ -56,6 +113,34 @@ impl App for CliStaticApp {
             }
         }
 
+        if let Some(exposure) = self.args.exposure {
+            scene.set_exposure(exposure);
+        }
+        if let Some(tone_mapping) = self.args.tone_mapping {
+            scene.set_tone_mapping(tone_mapping);
+        }
+        if !self.args.aovs.is_empty() {
+            scene.set_aovs(self.args.aovs.iter().copied().collect::<AovSet>());
+        }
+        if self.args.denoise {
+            scene.set_denoise(true);
+        }
+        if let Some(threshold) = self.args.adaptive_threshold {
+            scene.set_adaptive_threshold(threshold);
+        }
+        if let Some(min_samples) = self.args.adaptive_min_samples {
+            scene.set_adaptive_min_samples(min_samples);
+        }
+        if let Some(tile_size) = self.args.tile_size {
+            scene.set_tile_size(tile_size);
+        }
+        if let Some(tile_order) = self.args.tile_order {
+            scene.set_tile_order(tile_order);
+        }
+        if self.args.shadow_catcher {
+            scene.set_shadow_catcher(true);
+        }
+
         match scene.render() {
             Err(e) => {
                 error!("Error rendering scene: {:?}, exiting...", e);
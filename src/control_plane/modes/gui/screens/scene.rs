@@ -10,7 +10,9 @@ use crate::control_plane::modes::gui::screens::Screen;
 use crate::control_plane::modes::gui::screens::start::StartScreen;
 use crate::control_plane::modes::gui::screens::viewable::Viewable;
 use crate::control_plane::modes::is_debug_mode;
+use crate::data_plane::scene_io::img_export::visualize_layer;
 use crate::included_files::AutoPath;
+use frame_buffer::frame_iterator::SAMPLES_LAYER;
 
 static FRAME_DURATION_FPS24: Duration = Duration::from_millis(1000 / 24);
 
@@ -19,6 +21,7 @@ pub struct SceneScreen {
     model: Model,
     bottom_visible: bool,
     render_on_change: bool,
+    show_sample_heatmap: bool,
     file_dialog_obj: ThreadedNativeFileDialog,
     file_dialog_export: ThreadedNativeFileDialog,
     file_dialog_save: ThreadedNativeFileDialog,
@@ -33,6 +36,7 @@ impl SceneScreen {
             model,
             bottom_visible: false,
             render_on_change: false,
+            show_sample_heatmap: false,
             file_dialog_obj: ThreadedNativeFileDialog::new(
                 FileDialog::new().add_filter("OBJ", &["obj"]),
             ),
@@ -223,6 +227,9 @@ impl Screen for SceneScreen {
                 if ui.button("Toggle log-view").clicked() {
                     self.bottom_visible = !self.bottom_visible;
                 }
+
+                ui.checkbox(&mut self.show_sample_heatmap, "Sample Heatmap")
+                    .on_hover_text("Show where adaptive sampling spent its samples");
             })
         });
 
@@ -318,8 +325,12 @@ impl Screen for SceneScreen {
         if let Some(output) = self.model.frame_buffer.try_recv() {
             match output {
                 Ok(output) => {
+                    let pixels = match output.layer(SAMPLES_LAYER) {
+                        Some(samples) if self.show_sample_heatmap => visualize_layer(samples),
+                        _ => output.pixels,
+                    };
                     self.image_area
-                        .set_image(ctx, Image::new(output.width, output.height, output.pixels));
+                        .set_image(ctx, Image::new(output.width, output.height, pixels));
                 }
                 Err(e) => {
                     self.message_popup_pipe.push_message(Message::from_error(e));
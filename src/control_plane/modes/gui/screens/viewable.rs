@@ -1,8 +1,9 @@
 use std::sync::{Arc, Mutex};
+use engine_config::{TileOrder, ToneMapping};
 use egui::{CollapsingHeader, Color32, ComboBox, RichText, Ui};
-use scene_objects::camera::Resolution;
-use scene_objects::light_source::LightSource;
-use scene_objects::material::{Material, MaterialPresets, MaterialRef};
+use scene_objects::camera::{Projection, Resolution};
+use scene_objects::light_source::{LightSource, LightType};
+use scene_objects::material::{Material, MaterialPresets, MaterialRef, Pattern, PatternKind};
 use scene_objects::mesh::Mesh;
 use scene_objects::sphere::Sphere;
 use crate::data_plane::scene::render_scene::Scene;
@@ -83,6 +84,85 @@ fn color_ui(ui: &mut Ui, color: &mut Color) -> bool {
     changed
 }
 
+/// Per channel coefficients of a medium, in units of 1 / length
+fn coefficients_ui(ui: &mut Ui, coefficients: &mut [f32; 3]) -> bool {
+    let mut changed = false;
+
+    ui.horizontal(|ui| {
+        for (label, value) in ["R:", "G:", "B:"].into_iter().zip(coefficients.iter_mut()) {
+            ui.label(label);
+            changed |= ui
+                .add(egui::DragValue::new(value).speed(0.001).range(0.0..=100.0))
+                .changed();
+        }
+    });
+    changed
+}
+
+/// Procedural pattern of a material, None for the texture of the material
+fn pattern_ui(ui: &mut Ui, pattern: &mut Option<Pattern>) -> bool {
+    let mut changed = false;
+
+    ComboBox::from_label("Pattern")
+        .selected_text(pattern.as_ref().map_or("None", |p| p.kind.name()))
+        .show_ui(ui, |ui| {
+            if ui.selectable_label(pattern.is_none(), "None").clicked() {
+                *pattern = None;
+                changed = true;
+            }
+            for kind in PatternKind::ALL {
+                let selected = pattern.as_ref().is_some_and(|p| p.kind == kind);
+                if ui.selectable_label(selected, kind.name()).clicked() && !selected {
+                    // Keep the parameters when switching between kinds
+                    *pattern = Some(match pattern.take() {
+                        Some(previous) => Pattern { kind, ..previous },
+                        None => Pattern::new(kind),
+                    });
+                    changed = true;
+                }
+            }
+        });
+
+    let Some(pattern) = pattern else {
+        return changed;
+    };
+    changed |= ui
+        .add(
+            egui::Slider::new(&mut pattern.scale, 0.01..=100.0)
+                .logarithmic(true)
+                .text("Pattern Scale"),
+        )
+        .on_hover_text("Repetitions per scene unit")
+        .changed();
+    match pattern.kind {
+        PatternKind::Triplanar => {
+            changed |= ui
+                .add(egui::Slider::new(&mut pattern.sharpness, 1.0..=16.0).text("Blend Sharpness"))
+                .changed();
+        }
+        _ => {
+            for (i, color) in pattern.colors.iter_mut().enumerate() {
+                ui.label(format!("Pattern Color {}", i + 1));
+                let mut proxy = Color {
+                    r: color[0] as f32,
+                    g: color[1] as f32,
+                    b: color[2] as f32,
+                };
+                if color_ui(ui, &mut proxy) {
+                    *color = [proxy.r as f64, proxy.g as f64, proxy.b as f64];
+                    changed = true;
+                }
+            }
+            if pattern.kind == PatternKind::Noise {
+                changed |= ui
+                    .add(egui::Slider::new(&mut pattern.octaves, 1..=8).text("Octaves"))
+                    .changed();
+            }
+        }
+    }
+    changed
+}
+
 impl Vec3d {
     pub fn normalize(&self) -> Self {
         let len = self.length();
@@ -322,6 +402,104 @@ impl Viewable for ProxyCamera {
                 .set_look_at(self.look_at.clone().into());
             changed = true;
         }
+        ui.separator();
+
+        ui.label("Depth of Field:");
+        if ui
+            .add(egui::Slider::new(&mut self.aperture_radius, 0.0..=1.0).text("Aperture Radius"))
+            .changed()
+        {
+            scene
+                .lock()
+                .unwrap()
+                .get_camera_mut()
+                .set_aperture_radius(self.aperture_radius);
+            changed = true;
+        }
+
+        if ui
+            .add(egui::Slider::new(&mut self.aperture_blades, 0..=12).text("Aperture Blades"))
+            .changed()
+        {
+            let mut scene_lock = scene.lock().unwrap();
+            scene_lock
+                .get_camera_mut()
+                .set_aperture_blades(self.aperture_blades);
+            self.aperture_blades = scene_lock.get_camera().get_aperture_blades();
+            changed = true;
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Focus Distance:");
+            if ui
+                .add(
+                    egui::DragValue::new(&mut self.focus_distance)
+                        .speed(0.1)
+                        .range(0.01..=1_000.0),
+                )
+                .changed()
+            {
+                scene
+                    .lock()
+                    .unwrap()
+                    .get_camera_mut()
+                    .set_focus_distance(self.focus_distance);
+                changed = true;
+            }
+            if ui.button("Focus on Look At Point").clicked() {
+                let mut scene_lock = scene.lock().unwrap();
+                let camera = scene_lock.get_camera_mut();
+                camera.focus_on(self.look_at.clone().into());
+                self.focus_distance = camera.get_focus_distance();
+                changed = true;
+            }
+        });
+        ui.separator();
+
+        ComboBox::from_label("Projection")
+            .selected_text(self.projection.name())
+            .show_ui(ui, |ui| {
+                for name in Projection::NAMES {
+                    if ui
+                        .selectable_label(self.projection.name() == name, name)
+                        .clicked()
+                        && self.projection.name() != name
+                    {
+                        self.projection = Projection::from_name(name).unwrap_or_default();
+                        scene
+                            .lock()
+                            .unwrap()
+                            .get_camera_mut()
+                            .set_projection(self.projection);
+                        changed = true;
+                    }
+                }
+            });
+
+        let params_changed = match &mut self.projection {
+            Projection::Perspective | Projection::Equirectangular => false,
+            Projection::Orthographic { width } => ui
+                .add(egui::Slider::new(width, 0.1..=100.0).text("Ortho Width"))
+                .changed(),
+            Projection::StereoSideBySide {
+                interocular_distance,
+            }
+            | Projection::StereoTopBottom {
+                interocular_distance,
+            } => ui
+                .add(
+                    egui::Slider::new(interocular_distance, 0.0..=1.0).text("Interocular Distance"),
+                )
+                .changed(),
+        };
+        if params_changed {
+            scene
+                .lock()
+                .unwrap()
+                .get_camera_mut()
+                .set_projection(self.projection);
+            changed = true;
+        }
         changed
     }
 }
@@ -349,7 +527,7 @@ impl Viewable for Vec<ProxyMesh> {
                     });
 
                 if ui.small_button("remove").clicked() {
-                    scene.lock().unwrap().get_meshes_mut().remove(i);
+                    scene.lock().unwrap().remove_mesh(i);
                     self.remove(i);
                     changed = true;
                     break;
@@ -384,6 +562,11 @@ impl Viewable for ProxyMesh {
             mesh.translate_to(self.translation.clone().into());
             changed = true;
         }
+
+        if pattern_ui(ui, &mut self.pattern) {
+            mesh.set_pattern(self.pattern.clone());
+            changed = true;
+        }
         changed
     }
 }
@@ -446,6 +629,12 @@ impl Viewable for ProxySphere {
             changed = true;
         }
 
+        ui.label("Rotation:");
+        if vec3_ui(ui, &mut self.rotation) {
+            sphere.rotate_to(self.rotation.clone().into());
+            changed = true;
+        }
+
         ui.label("Color:");
         if color_ui(ui, &mut self.color) {
             sphere.set_color(self.color.into());
@@ -470,12 +659,27 @@ impl Viewable for ProxySphere {
                         .clicked()
                     {
                         self.material_ref = MaterialRef::Preset(preset);
-                        sphere.set_material(self.material_ref.get_material().clone());
+                        sphere.set_material(
+                            self.material_ref
+                                .get_material()
+                                .clone()
+                                .with_pattern(self.pattern.clone()),
+                        );
                         changed = true;
                     }
                 }
             });
 
+        if pattern_ui(ui, &mut self.pattern) {
+            sphere.set_material(
+                self.material_ref
+                    .get_material()
+                    .clone()
+                    .with_pattern(self.pattern.clone()),
+            );
+            changed = true;
+        }
+
         changed
     }
 }
@@ -512,6 +716,113 @@ impl Viewable for Misc {
             changed = true;
         }
 
+        if ui
+            .add(egui::Slider::new(&mut self.render_param.exposure, -10.0..=10.0).text("Exposure"))
+            .changed()
+        {
+            scene
+                .lock()
+                .unwrap()
+                .set_exposure(self.render_param.exposure);
+            changed = true;
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Tone Mapping:");
+            ComboBox::from_id_salt("tone_mapping")
+                .selected_text(format!("{:?}", self.render_param.tone_mapping))
+                .show_ui(ui, |ui| {
+                    for tone_mapping in ToneMapping::ALL {
+                        if ui
+                            .selectable_value(
+                                &mut self.render_param.tone_mapping,
+                                tone_mapping,
+                                format!("{:?}", tone_mapping),
+                            )
+                            .clicked()
+                        {
+                            scene.lock().unwrap().set_tone_mapping(tone_mapping);
+                            changed = true;
+                        }
+                    }
+                });
+        });
+
+        if ui
+            .checkbox(&mut self.render_param.denoise, "Denoise")
+            .changed()
+        {
+            scene.lock().unwrap().set_denoise(self.render_param.denoise);
+            changed = true;
+        }
+
+        if ui
+            .add(
+                egui::Slider::new(&mut self.render_param.adaptive_threshold, 0.0..=0.1)
+                    .text("Adaptive Threshold"),
+            )
+            .on_hover_text(
+                "Pixels stop taking samples below this noise level, 0 disables adaptive sampling",
+            )
+            .changed()
+        {
+            scene
+                .lock()
+                .unwrap()
+                .set_adaptive_threshold(self.render_param.adaptive_threshold);
+            changed = true;
+        }
+
+        if self.render_param.adaptive_threshold > 0.0
+            && ui
+                .add(
+                    egui::Slider::new(&mut self.render_param.adaptive_min_samples, 1..=256)
+                        .text("Min Samples"),
+                )
+                .changed()
+        {
+            scene
+                .lock()
+                .unwrap()
+                .set_adaptive_min_samples(self.render_param.adaptive_min_samples);
+            changed = true;
+        }
+
+        if ui
+            .add(egui::Slider::new(&mut self.render_param.tile_size, 0..=2048).text("Tile Size"))
+            .on_hover_text(
+                "Edge length of the render tiles in pixels, 0 splits the image only when it exceeds the GPU limits",
+            )
+            .changed()
+        {
+            scene
+                .lock()
+                .unwrap()
+                .set_tile_size(self.render_param.tile_size);
+            changed = true;
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Tile Order:");
+            ComboBox::from_id_salt("tile_order")
+                .selected_text(format!("{:?}", self.render_param.tile_order))
+                .show_ui(ui, |ui| {
+                    for tile_order in TileOrder::ALL {
+                        if ui
+                            .selectable_value(
+                                &mut self.render_param.tile_order,
+                                tile_order,
+                                format!("{:?}", tile_order),
+                            )
+                            .clicked()
+                        {
+                            scene.lock().unwrap().set_tile_order(tile_order);
+                            changed = true;
+                        }
+                    }
+                });
+        });
+
         ui.separator();
 
         ui.label("Sky Color:");
@@ -523,6 +834,57 @@ impl Viewable for Misc {
             changed = true;
         }
 
+        if let Some(environment) = &self.environment {
+            ui.label(format!("Environment: {environment}"));
+            if ui
+                .add(
+                    egui::Slider::new(&mut self.render_param.environment_rotation, -180.0..=180.0)
+                        .text("Environment Rotation"),
+                )
+                .changed()
+            {
+                scene
+                    .lock()
+                    .unwrap()
+                    .set_environment_rotation(self.render_param.environment_rotation);
+                changed = true;
+            }
+            if ui
+                .add(
+                    egui::Slider::new(&mut self.render_param.environment_intensity, 0.0..=10.0)
+                        .text("Environment Intensity"),
+                )
+                .changed()
+            {
+                scene
+                    .lock()
+                    .unwrap()
+                    .set_environment_intensity(self.render_param.environment_intensity);
+                changed = true;
+            }
+        }
+
+        ui.separator();
+
+        let mut medium_changed = false;
+        ui.label("Medium Absorption:");
+        medium_changed |= coefficients_ui(ui, &mut self.render_param.medium.absorption);
+        ui.label("Medium Scattering:");
+        medium_changed |= coefficients_ui(ui, &mut self.render_param.medium.scattering);
+        medium_changed |= ui
+            .add(
+                egui::Slider::new(&mut self.render_param.medium.anisotropy, -0.95..=0.95)
+                    .text("Medium Anisotropy"),
+            )
+            .on_hover_text(
+                "Henyey-Greenstein asymmetry: negative scatters backwards, positive forwards",
+            )
+            .changed();
+        if medium_changed {
+            scene.lock().unwrap().set_medium(self.render_param.medium);
+            changed = true;
+        }
+
         if ui
             .add(
                 egui::Slider::new(&mut self.render_param.max_depth, 1..=10)
@@ -593,6 +955,70 @@ impl Viewable for Misc {
                     changed = true;
                 }
             }
+
+            // The pattern is edited separately and kept when switching presets
+            let pattern = self
+                .render_param
+                .ground_material
+                .as_ref()
+                .and_then(|m| m.pattern.clone());
+            let current_label = match &self.render_param.ground_material {
+                Some(material) => match MaterialRef::from(material.clone().with_pattern(None)) {
+                    MaterialRef::Preset(p) => format!("{:?}", p),
+                    MaterialRef::Custom(_) => "Custom".to_string(),
+                },
+                None => "Default".to_string(),
+            };
+            ComboBox::from_label("Ground Material")
+                .selected_text(current_label)
+                .show_ui(ui, |ui| {
+                    if ui
+                        .selectable_label(self.render_param.ground_material.is_none(), "Default")
+                        .clicked()
+                    {
+                        self.render_param.ground_material = None;
+                        scene.lock().unwrap().set_ground_material(None);
+                        changed = true;
+                    }
+                    for preset in MaterialPresets::list_enum() {
+                        let material = Material::from(preset).with_pattern(pattern.clone());
+                        let selected = self
+                            .render_param
+                            .ground_material
+                            .as_ref()
+                            .is_some_and(|m| m == material);
+                        if ui
+                            .selectable_label(selected, format!("{:?}", preset))
+                            .clicked()
+                        {
+                            self.render_param.ground_material = Some(material.clone());
+                            scene.lock().unwrap().set_ground_material(Some(material));
+                            changed = true;
+                        }
+                    }
+                });
+
+            if let Some(material) = &self.render_param.ground_material {
+                let mut pattern = pattern;
+                if pattern_ui(ui, &mut pattern) {
+                    let material = material.clone().with_pattern(pattern);
+                    self.render_param.ground_material = Some(material.clone());
+                    scene.lock().unwrap().set_ground_material(Some(material));
+                    changed = true;
+                }
+            }
+
+            if ui
+                .checkbox(&mut self.render_param.shadow_catcher, "Shadow Catcher")
+                .on_hover_text("Keep only the shadows and reflections on the ground, over a transparent background")
+                .changed()
+            {
+                scene
+                    .lock()
+                    .unwrap()
+                    .set_shadow_catcher(self.render_param.shadow_catcher);
+                changed = true;
+            }
         }
 
         ui.separator();
@@ -654,9 +1080,70 @@ impl Viewable for ProxyLight {
 
     fn ui(&mut self, ui: &mut Ui, light: &mut LightSource) -> bool {
         let mut changed = false;
-        ui.label("Position:");
-        if vec3_ui(ui, &mut self.position) {
-            light.set_position(self.position.clone().into());
+        ComboBox::from_label("Type")
+            .selected_text(self.light_type.name())
+            .show_ui(ui, |ui| {
+                for name in LightType::NAMES {
+                    if ui
+                        .selectable_label(self.light_type.name() == name, name)
+                        .clicked()
+                        && self.light_type.name() != name
+                    {
+                        self.light_type = LightType::from_name(name).unwrap_or_default();
+                        light.set_light_type(self.light_type);
+                        changed = true;
+                    }
+                }
+            });
+
+        if !matches!(self.light_type, LightType::Directional { .. }) {
+            ui.label("Position:");
+            if vec3_ui(ui, &mut self.position) {
+                light.set_position(self.position.clone().into());
+                changed = true;
+            }
+        }
+        if self.light_type != LightType::Point {
+            ui.label("Rotation:");
+            if vec3_ui(ui, &mut self.rotation) {
+                light.set_rotation(self.rotation.clone().into());
+                changed = true;
+            }
+        }
+
+        let params_changed = match &mut self.light_type {
+            LightType::Point => false,
+            LightType::Directional { angle } => ui
+                .add(egui::Slider::new(angle, 0.0..=20.0).text("Angle"))
+                .changed(),
+            LightType::Spot {
+                inner_angle,
+                outer_angle,
+            } => {
+                let outer = ui
+                    .add(egui::Slider::new(outer_angle, 1.0..=90.0).text("Outer Angle"))
+                    .changed();
+                let inner = ui
+                    .add(egui::Slider::new(inner_angle, 0.0..=*outer_angle).text("Inner Angle"))
+                    .changed();
+                *inner_angle = inner_angle.min(*outer_angle);
+                outer || inner
+            }
+            LightType::Rect { width, height } => {
+                let w = ui
+                    .add(egui::Slider::new(width, 0.05..=20.0).text("Width"))
+                    .changed();
+                let h = ui
+                    .add(egui::Slider::new(height, 0.05..=20.0).text("Height"))
+                    .changed();
+                w || h
+            }
+            LightType::Disc { radius } => ui
+                .add(egui::Slider::new(radius, 0.05..=10.0).text("Radius"))
+                .changed(),
+        };
+        if params_changed {
+            light.set_light_type(self.light_type);
             changed = true;
         }
         ui.label("Color:");
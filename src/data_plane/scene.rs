@@ -1,3 +1,4 @@
+pub mod environment;
 pub mod render_parameter;
 pub mod render_scene;
 pub mod scene_engine_adapter;
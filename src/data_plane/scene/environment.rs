@@ -0,0 +1,29 @@
+use std::path::PathBuf;
+use engine_config::EnvironmentMap;
+
+/// HDR image surrounding the scene. It is shown as background and lights the scene.
+/// Rotation and intensity are part of the RenderParameter
+#[derive(Clone, Debug)]
+pub struct Environment {
+    path: PathBuf,
+    map: EnvironmentMap,
+}
+
+impl Environment {
+    /// ## Parameter
+    /// 'path': path of the image the map was loaded from, used when exporting the scene
+    /// 'map': loaded image data
+    pub fn new(path: PathBuf, map: EnvironmentMap) -> Self {
+        Self { path, map }
+    }
+    /// ## Returns
+    /// Path of the image the environment was loaded from
+    pub fn get_path(&self) -> &PathBuf {
+        &self.path
+    }
+    /// ## Returns
+    /// Image data of the environment
+    pub fn get_map(&self) -> &EnvironmentMap {
+        &self.map
+    }
+}
@@ -1,8 +1,9 @@
-use engine_config::Uniforms;
+use engine_config::{AovSet, Medium, TileOrder, ToneMapping, Uniforms};
+use scene_objects::material::Material;
 use serde::{Deserialize, Serialize};
 use crate::data_plane::scene_proxy::color::Color;
 
-#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 /// RenderParameter: Holds render parameters for the scene
 pub(crate) struct RenderParameter {
     pub(crate) ground_height: f32,
@@ -12,6 +13,49 @@ pub(crate) struct RenderParameter {
     pub(crate) max_depth: u32,
     pub(crate) sky_color: Color,
     pub(crate) color_hash_enabled: bool,
+    /// Rotation of the environment map around the y axis in degrees
+    #[serde(default)]
+    pub(crate) environment_rotation: f32,
+    #[serde(default = "default_environment_intensity")]
+    pub(crate) environment_intensity: f32,
+    /// Exposure in stops
+    #[serde(default)]
+    pub(crate) exposure: f32,
+    #[serde(default)]
+    pub(crate) tone_mapping: ToneMapping,
+    /// Auxiliary layers rendered alongside the image
+    #[serde(default)]
+    pub(crate) aovs: AovSet,
+    /// Denoise the rendered frames, guided by the albedo and normal AOVs
+    #[serde(default)]
+    pub(crate) denoise: bool,
+    /// Noise level at which a pixel stops taking samples, 0 disables adaptive sampling
+    #[serde(default)]
+    pub(crate) adaptive_threshold: f32,
+    #[serde(default = "default_adaptive_min_samples")]
+    pub(crate) adaptive_min_samples: u32,
+    /// Homogeneous medium filling the space outside of objects
+    #[serde(default)]
+    pub(crate) medium: Medium,
+    /// Edge length of the render tiles in pixels, 0 renders in as few tiles as the GPU allows
+    #[serde(default)]
+    pub(crate) tile_size: u32,
+    #[serde(default)]
+    pub(crate) tile_order: TileOrder,
+    /// Material of the ground plane, None for the default grey
+    #[serde(skip)]
+    pub(crate) ground_material: Option<Material>,
+    /// Render only the shadows and reflections on the ground, over a transparent background
+    #[serde(default)]
+    pub(crate) shadow_catcher: bool,
+}
+
+fn default_environment_intensity() -> f32 {
+    Uniforms::default().environment_intensity
+}
+
+fn default_adaptive_min_samples() -> u32 {
+    Uniforms::default().adaptive_min_samples
 }
 
 impl Default for RenderParameter {
@@ -28,6 +72,19 @@ impl Default for RenderParameter {
             max_depth: uniform.max_depth,
             sky_color: Color::from(uniform.sky_color),
             color_hash_enabled: true,
+            environment_rotation: uniform.environment_rotation.to_degrees(),
+            environment_intensity: uniform.environment_intensity,
+            exposure: uniform.exposure,
+            tone_mapping: ToneMapping::default(),
+            aovs: AovSet::empty(),
+            denoise: false,
+            adaptive_threshold: uniform.adaptive_threshold,
+            adaptive_min_samples: uniform.adaptive_min_samples,
+            medium: uniform.medium(),
+            tile_size: uniform.tile_size,
+            tile_order: TileOrder::default(),
+            ground_material: None,
+            shadow_catcher: uniform.shadow_catcher != 0,
         }
     }
 }
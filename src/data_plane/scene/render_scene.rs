@@ -1,27 +1,41 @@
 use std::path::PathBuf;
+use std::str::FromStr;
 use anyhow::Error;
-use engine_config::{RenderConfigBuilder, Uniforms};
+use engine_config::{
+    Aov, AovSet, ColorSpace, Medium, RenderConfigBuilder, TileOrder, ToneMapping, Uniforms,
+    WrapMode,
+};
 use glam::Vec3;
 use log::{debug, error, info, warn};
 use frame_buffer::frame_iterator::Frame;
 use scene_objects::{
     camera::{Camera, Resolution},
     geometric_object::GeometricObject,
-    light_source::{LightSource},
+    light_source::{LightSource, LightType},
     material::Material,
     mesh::Mesh,
+    mesh_instance::MeshInstance,
     sphere::Sphere,
 };
 use crate::{
     compute_plane::{engine::Engine, render_engine::RenderEngine},
     data_plane::{
-        scene::{render_parameter::RenderParameter, scene_graph::SceneGraph},
-        scene_io::{img_export::export_img_png, obj_parser::load_obj, scene_importer::parse_scene},
+        scene::{
+            environment::Environment, render_parameter::RenderParameter, scene_graph::SceneGraph,
+        },
+        scene_io::{
+            environment_loader::load_environment,
+            img_export::{export_img_png, export_layer},
+            obj_parser::load_obj,
+            scene_importer::parse_scene,
+        },
     },
     included_files::AutoPath,
 };
 use crate::data_plane::scene_io::scene_exporter;
-use crate::data_plane::scene_io::texture_loader::TextureCache;
+use crate::data_plane::scene_io::texture_loader::{
+    TextureCache, material_color_space, material_wrap_mode,
+};
 use crate::data_plane::scene_proxy::color::Color;
 
 /// The scene holds all relevant objects, lightsources, camera
@@ -34,6 +48,7 @@ pub struct Scene {
     pub(crate) texture_cache: TextureCache,
     output_path: Option<PathBuf>,
     render_params: RenderParameter,
+    environment: Option<Environment>,
 }
 impl Default for Scene {
     fn default() -> Self {
@@ -54,6 +69,8 @@ impl Scene {
         let rotation = loaded_data.rotations;
         let translation = loaded_data.translations;
         let scale = loaded_data.scales;
+        let instances = loaded_data.instances;
+        let patterns = loaded_data.patterns;
 
         debug!("Scene: Loading {} objects...", paths.len());
         for (i, p_str) in paths.iter().enumerate() {
@@ -70,6 +87,31 @@ impl Scene {
                 rotation[i],
                 scale[i],
             )?;
+            // further placements share the geometry that was just loaded
+            let mesh_index = scene.get_meshes().len() - 1;
+            scene.get_meshes_mut()[mesh_index].set_pattern(patterns[i].clone());
+            for instance in instances[i].iter() {
+                scene.add_mesh_instance(
+                    mesh_index,
+                    Vec3::new(
+                        instance.translation.x,
+                        instance.translation.y,
+                        instance.translation.z,
+                    ),
+                    Vec3::new(
+                        instance.rotation.x,
+                        instance.rotation.y,
+                        instance.rotation.z,
+                    ),
+                    instance.scale.x,
+                )?;
+            }
+        }
+
+        if let Some(environment) = loaded_data.environment {
+            let p = AutoPath::try_from(environment.path)?;
+            debug!("Scene: Loading environment map from {:?}", p);
+            scene.load_environment(p, environment.rotation, environment.intensity)?;
         }
 
         info!("Scene: Successfully loaded scene.");
@@ -250,7 +292,7 @@ impl Scene {
             [1.0, 1.0, 1.0],
             "proto_light".to_owned(),
             Vec3::default(),
-            //LightType::Ambient,
+            LightType::Point,
         );
         let point_light = LightSource::new(
             Vec3::new(2.0, 4.0, 1.0),
@@ -258,7 +300,7 @@ impl Scene {
             [1.0, 0.9, 0.8],
             "point_light".to_owned(),
             Vec3::default(),
-            //LightType::Point,
+            LightType::Point,
         );
         self.add_sphere(sphere0);
         self.add_sphere(sphere1);
@@ -309,7 +351,7 @@ impl Scene {
         Self {
             scene_graph: SceneGraph::new(),
             name: "scene".to_owned(),
-            render_params: render_param,
+            render_params: render_param.clone(),
             render_engine: if load_engine {
                 Option::from(Engine::new(
                     RenderConfigBuilder::new()
@@ -344,6 +386,7 @@ impl Scene {
             last_frame: None,
             texture_cache: TextureCache::new(),
             output_path: None,
+            environment: None,
         }
     }
     /// adds an sphere to the scene
@@ -351,8 +394,35 @@ impl Scene {
     /// 'sphere': GeometricObject that is to be added to the scene
     pub fn add_sphere(&mut self, sphere: Sphere) {
         info!("{self}: adding {:?}", sphere);
+        self.load_material_textures(sphere.get_material());
         self.scene_graph.add_sphere(sphere);
     }
+    /// Loads the textures of a material that is not part of a mesh into the texture cache.
+    /// Textures that cannot be loaded are skipped with a warning
+    /// ## Arguments
+    /// 'material': Material whose textures are loaded
+    pub(crate) fn load_material_textures(&mut self, material: &Material) {
+        for path in material.texture_paths() {
+            match AutoPath::try_from(path.clone()) {
+                Ok(auto_path) => {
+                    if let Err(e) = self.texture_cache.load(auto_path.clone()) {
+                        warn!("{self}: could not load texture {path}: {e}");
+                        continue;
+                    }
+                    let wrap_mode = material_wrap_mode(material, path);
+                    if wrap_mode != WrapMode::Repeat {
+                        self.texture_cache
+                            .set_wrap_mode(auto_path.clone(), wrap_mode);
+                    }
+                    let color_space = material_color_space(material, path);
+                    if color_space != ColorSpace::Srgb {
+                        self.texture_cache.set_color_space(auto_path, color_space);
+                    }
+                }
+                Err(e) => warn!("{self}: could not load texture {path}: {e}"),
+            }
+        }
+    }
     /// adds an object to the scene
     /// ## Arguments
     /// 'mesh': GeometricObject that is to be added to the scene
@@ -360,6 +430,52 @@ impl Scene {
         info!("{self}: adding {:?}", mesh.get_name());
         self.scene_graph.add_mesh(mesh);
     }
+    /// adds another placement of an existing mesh to the scene, sharing its geometry.
+    /// The transformation is applied to the mesh as it was loaded, like for a new copy of it
+    /// ## Arguments
+    /// 'mesh_index': Index of the instanced mesh <br>
+    /// 'translation': Translation to be applied, as glam::Vec3 <br>
+    /// 'rotation': Rotation to be applied, as glam::Vec3 <br>
+    /// 'scale': Scale to be applied
+    pub fn add_mesh_instance(
+        &mut self,
+        mesh_index: usize,
+        translation: Vec3,
+        rotation: Vec3,
+        scale: f32,
+    ) -> Result<(), Error> {
+        let Some(mesh) = self.get_meshes().get(mesh_index) else {
+            return Err(Error::msg(format!(
+                "Cannot instance mesh {mesh_index}: the scene has {} meshes",
+                self.get_meshes().len()
+            )));
+        };
+        let count = self
+            .get_mesh_instances()
+            .iter()
+            .filter(|instance| instance.get_mesh_index() == mesh_index)
+            .count();
+        let mut instance =
+            MeshInstance::new(mesh_index, format!("{} {}", mesh.get_name(), count + 1));
+        instance.scale(scale);
+        instance.rotate(rotation);
+        instance.translate(translation);
+        info!("{self}: adding {instance}");
+        self.scene_graph.add_mesh_instance(instance);
+        Ok(())
+    }
+    /// removes the mesh at the given index together with its instances
+    /// ## Arguments
+    /// 'index': Index of the mesh
+    pub fn remove_mesh(&mut self, index: usize) {
+        self.scene_graph.remove_mesh(index);
+    }
+    /// removes the mesh instance at the given index
+    /// ## Arguments
+    /// 'index': Index of the mesh instance
+    pub fn remove_mesh_instance(&mut self, index: usize) {
+        self.scene_graph.remove_mesh_instance(index);
+    }
     /// adds an LightSource to the scene
     /// ## Arguments
     /// 'light': LightSource that is to be added
@@ -403,6 +519,16 @@ impl Scene {
     pub fn get_meshes_mut(&mut self) -> &mut Vec<Mesh> {
         self.scene_graph.get_meshes_mut()
     }
+    /// ##  Returns
+    /// a reference to a vector of all mesh instances
+    pub fn get_mesh_instances(&self) -> &Vec<MeshInstance> {
+        self.scene_graph.get_mesh_instances()
+    }
+    /// ##  Returns
+    /// a mutable reference to a vector of all mesh instances
+    pub fn get_mesh_instances_mut(&mut self) -> &mut Vec<MeshInstance> {
+        self.scene_graph.get_mesh_instances_mut()
+    }
     /// ## Returns
     /// Reference to a vector that holds all LightSources of the scene
     pub fn get_light_sources(&self) -> &Vec<LightSource> {
@@ -472,6 +598,189 @@ impl Scene {
             color.r, color.g, color.b
         );
     }
+    /// Loads an equirectangular .hdr / .exr image that replaces the background color and lights the scene
+    /// ## Parameter
+    /// 'auto_path': AutoPath to the image
+    /// 'rotation': rotation around the y axis in degrees
+    /// 'intensity': multiplier of the image radiance
+    pub fn load_environment(
+        &mut self,
+        auto_path: AutoPath,
+        rotation: f32,
+        intensity: f32,
+    ) -> Result<(), Error> {
+        match load_environment(auto_path.clone()) {
+            Ok(map) => {
+                self.environment = Some(Environment::new(auto_path.path_buf(), map));
+                self.set_environment_rotation(rotation);
+                self.set_environment_intensity(intensity);
+                info!("{self}: Loaded environment map from {}", auto_path);
+                Ok(())
+            }
+            Err(error) => {
+                error!(
+                    "{self}: Loading environment map from {} resulted in error: {error}",
+                    auto_path
+                );
+                Err(error)
+            }
+        }
+    }
+    /// Removes the environment map, the background color is used again
+    pub fn clear_environment(&mut self) {
+        self.environment = None;
+        info!("{self}: Removed environment map");
+    }
+    /// ## Returns
+    /// Reference to the environment map, if one is loaded
+    pub fn get_environment(&self) -> Option<&Environment> {
+        self.environment.as_ref()
+    }
+    /// ## Returns
+    /// Rotation of the environment map around the y axis in degrees
+    pub fn get_environment_rotation(&self) -> f32 {
+        self.render_params.environment_rotation
+    }
+    /// ## Parameters
+    /// 'rotation': new rotation of the environment map around the y axis in degrees
+    pub fn set_environment_rotation(&mut self, rotation: f32) {
+        self.render_params.environment_rotation = rotation;
+        info!("Scene {self}: set environment rotation to {}", rotation);
+    }
+    /// ## Returns
+    /// Radiance multiplier of the environment map
+    pub fn get_environment_intensity(&self) -> f32 {
+        self.render_params.environment_intensity
+    }
+    /// ## Parameters
+    /// 'intensity': new radiance multiplier of the environment map, negative values are ignored
+    pub fn set_environment_intensity(&mut self, intensity: f32) {
+        if intensity >= 0.0 {
+            self.render_params.environment_intensity = intensity;
+            info!("Scene {self}: set environment intensity to {}", intensity);
+        } else {
+            warn!("{self}: ignoring invalid environment intensity {intensity}")
+        }
+    }
+    /// ## Returns
+    /// Exposure of the rendered image in stops
+    pub fn get_exposure(&self) -> f32 {
+        self.render_params.exposure
+    }
+    /// ## Parameters
+    /// 'exposure': new exposure in stops, the radiance is scaled by 2^exposure before tone mapping
+    pub fn set_exposure(&mut self, exposure: f32) {
+        if exposure.is_finite() {
+            self.render_params.exposure = exposure;
+            info!("Scene {self}: set exposure to {}", exposure);
+        } else {
+            warn!("{self}: ignoring invalid exposure {exposure}")
+        }
+    }
+    /// ## Returns
+    /// Tone mapping operator applied before 8-bit quantization
+    pub fn get_tone_mapping(&self) -> ToneMapping {
+        self.render_params.tone_mapping
+    }
+    /// ## Parameters
+    /// 'tone_mapping': new tone mapping operator
+    pub fn set_tone_mapping(&mut self, tone_mapping: ToneMapping) {
+        self.render_params.tone_mapping = tone_mapping;
+        info!("Scene {self}: set tone mapping to {}", tone_mapping);
+    }
+    /// ## Returns
+    /// AOV layers rendered alongside the image
+    pub fn get_aovs(&self) -> AovSet {
+        self.render_params.aovs
+    }
+    /// ## Parameters
+    /// 'aovs': AOV layers to render alongside the image, exported next to it by export_render_img
+    pub fn set_aovs(&mut self, aovs: AovSet) {
+        self.render_params.aovs = aovs;
+        info!(
+            "Scene {self}: set AOVs to {:?}",
+            aovs.iter().collect::<Vec<_>>()
+        );
+    }
+    /// ## Returns
+    /// Whether the rendered frames are denoised
+    pub fn get_denoise(&self) -> bool {
+        self.render_params.denoise
+    }
+    /// ## Parameters
+    /// 'denoise': denoise the rendered frames, the engine then also renders the albedo, normal and depth AOVs
+    pub fn set_denoise(&mut self, denoise: bool) {
+        self.render_params.denoise = denoise;
+        info!("Scene {self}: set denoise to {}", denoise);
+    }
+    /// ## Returns
+    /// Noise level at which a pixel stops taking samples, 0 if adaptive sampling is disabled
+    pub fn get_adaptive_threshold(&self) -> f32 {
+        self.render_params.adaptive_threshold
+    }
+    /// ## Parameters
+    /// 'threshold': noise level at which a pixel stops taking samples, 0 disables adaptive sampling.
+    /// Negative values are ignored
+    pub fn set_adaptive_threshold(&mut self, threshold: f32) {
+        if threshold.is_finite() && threshold >= 0.0 {
+            self.render_params.adaptive_threshold = threshold;
+            info!("Scene {self}: set adaptive threshold to {}", threshold);
+        } else {
+            warn!("{self}: ignoring invalid adaptive threshold {threshold}")
+        }
+    }
+    /// ## Returns
+    /// Samples every pixel takes before adaptive sampling may stop it
+    pub fn get_adaptive_min_samples(&self) -> u32 {
+        self.render_params.adaptive_min_samples
+    }
+    /// ## Parameters
+    /// 'min_samples': samples every pixel takes before adaptive sampling may stop it, 0 is ignored
+    pub fn set_adaptive_min_samples(&mut self, min_samples: u32) {
+        if min_samples > 0 {
+            self.render_params.adaptive_min_samples = min_samples;
+            info!("Scene {self}: set adaptive min samples to {}", min_samples);
+        } else {
+            warn!("{self}: ignoring invalid adaptive min samples {min_samples}")
+        }
+    }
+    /// ## Returns
+    /// Edge length of the render tiles in pixels, 0 if the image is split only when it exceeds the GPU limits
+    pub fn get_tile_size(&self) -> u32 {
+        self.render_params.tile_size
+    }
+    /// ## Parameters
+    /// 'tile_size': edge length of the render tiles in pixels, 0 splits the image only when it exceeds the GPU limits
+    pub fn set_tile_size(&mut self, tile_size: u32) {
+        self.render_params.tile_size = tile_size;
+        info!("Scene {self}: set tile size to {}", tile_size);
+    }
+    /// ## Returns
+    /// Order in which the render tiles are rendered
+    pub fn get_tile_order(&self) -> TileOrder {
+        self.render_params.tile_order
+    }
+    /// ## Parameters
+    /// 'tile_order': order in which the render tiles are rendered
+    pub fn set_tile_order(&mut self, tile_order: TileOrder) {
+        self.render_params.tile_order = tile_order;
+        info!("Scene {self}: set tile order to {}", tile_order);
+    }
+    /// ## Returns
+    /// Homogeneous medium filling the space outside of objects
+    pub fn get_medium(&self) -> Medium {
+        self.render_params.medium
+    }
+    /// ## Parameters
+    /// 'medium': new global medium, a medium with negative or non-finite coefficients is ignored
+    pub fn set_medium(&mut self, medium: Medium) {
+        if medium.is_valid() {
+            self.render_params.medium = medium;
+            info!("Scene {self}: set medium to {:?}", medium);
+        } else {
+            warn!("{self}: ignoring invalid medium {:?}", medium)
+        }
+    }
     /// ## Returns
     /// Scene ground height as f32
     pub fn get_ground_height(&self) -> f32 {
@@ -517,6 +826,35 @@ impl Scene {
         info!("Scene {self}: set ground enabled  to {:?}", colors);
     }
     /// ## Returns
+    /// Material of the ground plane, None for the default grey
+    pub fn get_ground_material(&self) -> Option<&Material> {
+        self.render_params.ground_material.as_ref()
+    }
+    /// Sets the material of the ground plane and loads its textures
+    /// ## Parameters
+    /// 'material': new ground material, None restores the default grey
+    pub fn set_ground_material(&mut self, material: Option<Material>) {
+        if let Some(material) = &material {
+            self.load_material_textures(material);
+        }
+        info!(
+            "Scene {self}: set ground material to {}",
+            material.as_ref().map_or("default", |m| m.name.as_str())
+        );
+        self.render_params.ground_material = material;
+    }
+    /// ## Returns
+    /// If the ground is rendered as shadow catcher
+    pub fn get_shadow_catcher(&self) -> bool {
+        self.render_params.shadow_catcher
+    }
+    /// ## Parameters
+    /// 'enabled': whether the ground only keeps shadows and reflections over a transparent background
+    pub fn set_shadow_catcher(&mut self, enabled: bool) {
+        self.render_params.shadow_catcher = enabled;
+        info!("Scene {self}: set shadow catcher to {}", enabled);
+    }
+    /// ## Returns
     /// Max depth of render recursion
     pub fn get_max_depth(&self) -> u32 {
         self.render_params.max_depth
@@ -535,13 +873,13 @@ impl Scene {
     /// ## Returns
     /// RenderParameter of the scene
     pub fn get_render_parameter(&self) -> RenderParameter {
-        self.render_params
+        self.render_params.clone()
     }
     /// ## Parameters
     /// 'param': new RenderParameter
     pub fn set_render_parameter(&mut self, param: RenderParameter) {
-        self.render_params = param;
         info!("Scene {self}: set render parameter  to {:?}", param);
+        self.render_params = param;
     }
     /// Sets the value of field last render to the given Frame
     /// ## Parameter
@@ -567,7 +905,11 @@ impl Scene {
     pub fn get_output_path(&self) -> Option<PathBuf> {
         self.output_path.clone()
     }
-    /// Exports the last render result to the given path
+    /// Exports the last render result to the given path.
+    /// Every requested AOV layer of the render is saved next to it as <name>_<layer>.<extension>,
+    /// .exr keeps the raw values of the layers. A denoised render also saves the noisy input as
+    /// <name>_raw.<extension> and the filtered linear radiance as <name>_radiance.<extension>,
+    /// adaptive sampling the samples per pixel as <name>_samples.<extension>.
     /// ## Parameter
     /// 'path': std::path::BathBuf of where the image will be saved
     pub fn export_render_img(&self, path: PathBuf) -> anyhow::Result<()> {
@@ -577,12 +919,31 @@ impl Scene {
             ))
         })?;
 
+        // Guide layers only rendered for the denoiser are not exported
+        let requested =
+            |name: &str| Aov::from_str(name).map_or(true, |aov| self.get_aovs().contains(aov));
+        for layer in render.layers.iter().filter(|l| requested(&l.name)) {
+            let layer_path = aov_path(&path, &layer.name);
+            export_layer(layer_path.clone(), &render, layer)?;
+            info!("{self}: Saved {} layer to {:?}", layer.name, layer_path);
+        }
+
         info!("{self}: Saved image to {:?}", path.clone());
         export_img_png(path, render)?;
         Ok(())
     }
 }
 
+/// Path of an AOV layer next to the image at `path`, e.g. render.png -> render_depth.png
+fn aov_path(path: &std::path::Path, layer: &str) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let file_name = match path.extension() {
+        Some(extension) => format!("{stem}_{layer}.{}", extension.to_string_lossy()),
+        None => format!("{stem}_{layer}"),
+    };
+    path.with_file_name(file_name)
+}
+
 impl std::fmt::Display for Scene {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "Scene {}", self.get_name())
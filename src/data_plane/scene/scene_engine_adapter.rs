@@ -1,49 +1,89 @@
 /// Serves as an adpter between the scene plane and the render engine.
 use std::collections::HashMap;
+use std::ops::Range;
 use anyhow::{Error, Result};
-use engine_config::{RenderConfig, RenderConfigBuilder};
-use glam::Vec3;
+use denoiser::{DenoiseSettings, DenoisingFrameIterator, GUIDE_AOVS, denoise};
+use engine_config::{AovSet, EmissiveTriangle, Medium, RenderConfig, RenderConfigBuilder};
+use glam::{Mat4, Vec3, Vec4};
 use log::{debug, error, info};
 use engine_config::renderer::RendererIterable;
 use frame_buffer::frame_iterator::{Frame, FrameIterator};
 use scene_objects::{
-    camera::{Camera, Resolution},
-    light_source::LightSource,
+    camera::{Camera, Projection, Resolution},
+    light_source::{LightSource, LightType},
+    material::{Pattern, PatternKind},
     mesh::Mesh,
     sphere::Sphere,
 };
 use crate::data_plane::scene::{render_parameter::RenderParameter, render_scene::Scene};
 use engine_bvh::triangle::GPUTriangle;
-use engine_bvh::bvh::BVH;
+use engine_bvh::instance::TwoLevelBVH;
 
 type RenderSphere = engine_config::Sphere;
 type RenderUniforms = engine_config::Uniforms;
 type RenderMesh = engine_config::Mesh;
 pub type RenderCamera = engine_config::Camera;
 type RenderLight = engine_config::PointLight;
-type RenderGeometry = (Vec<f32>, Vec<u32>, Vec<f32>, engine_config::Material);
-type SubMeshGeometry = (Vec<f32>, Vec<u32>, Vec<f32>);
-/// Converts the given LightSource to a engine_config::PointLight if has the type Point
+type RenderProjection = engine_config::Projection;
+type RenderPattern = engine_config::Pattern;
+type RenderGeometry = (
+    Vec<f32>,
+    Vec<u32>,
+    Vec<f32>,
+    Vec<f32>,
+    Vec<f32>,
+    engine_config::Material,
+);
+type SubMeshGeometry = (Vec<f32>, Vec<u32>, Vec<f32>, Vec<f32>, Vec<f32>);
+/// Converts the given LightSource to a engine_config::PointLight of the matching type
 /// ## Parameter:
 /// 'light': LightSource that is to be converted
 /// ## Returns
-/// Options of engine_config::PointLight: Some if light has Type Point
+/// Options of engine_config::PointLight, always Some since every LightType can be rendered
 fn light_to_render_point_light(light: &LightSource) -> Option<RenderLight> {
-    Some(RenderLight::new(
-        light.get_position().into(),
-        0.5,
-        light.get_luminositoy(),
-        light.get_color(),
-    ))
+    let position = light.get_position().into();
+    let direction = light.get_direction().into();
+    let luminosity = light.get_luminositoy();
+    let color = light.get_color();
+    Some(match *light.get_light_type() {
+        LightType::Point => RenderLight::new(position, 0.5, luminosity, color),
+        LightType::Directional { angle } => {
+            RenderLight::directional(direction, angle, luminosity, color)
+        }
+        LightType::Spot {
+            inner_angle,
+            outer_angle,
+        } => RenderLight::spot(
+            position,
+            0.5,
+            direction,
+            [inner_angle, outer_angle],
+            luminosity,
+            color,
+        ),
+        LightType::Rect { width, height } => RenderLight::rect(
+            position,
+            direction,
+            light.get_tangent().into(),
+            [width, height],
+            luminosity,
+            color,
+        ),
+        LightType::Disc { radius } => {
+            RenderLight::disc(position, direction, radius, luminosity, color)
+        }
+    })
 }
 /// Converts a given scene_objects::sphere::Sphere to a engine_config::sphere
 /// so it can be passed to the render engine
 /// ## Parameter
-/// scene_objects::sphere::Sphere to be converted
+/// 'sphere': scene_objects::sphere::Sphere to be converted <br>
+/// 'texture_map': Map of texture paths to texture indices
 /// ## Returns
 /// engine_config::Sphere based on the given sphere
-fn sphere_to_render_sphere(sphere: &Sphere) -> RenderSphere {
+fn sphere_to_render_sphere(sphere: &Sphere, texture_map: &HashMap<String, i32>) -> RenderSphere {
     let center = sphere.get_center();
+    let orientation = sphere.get_orientation();
 
     RenderSphere::new(
         engine_config::Vec3::new(center.x, center.y, center.z),
@@ -51,11 +91,12 @@ fn sphere_to_render_sphere(sphere: &Sphere) -> RenderSphere {
         material_to_render_material(
             sphere.get_material(),
             Some(&sphere.get_color()),
-            &HashMap::new(),
+            texture_map,
         ),
     )
     .unwrap()
     //todo error handling
+    .with_orientation(orientation.to_cols_array_2d())
 }
 
 fn vec3_to_array(vec: Vec3) -> [f32; 3] {
@@ -85,7 +126,43 @@ fn camera_to_render_uniforms(
         camera.get_pane_width(),
         vec3_to_array(position),
         vec3_to_array(dir),
+    )
+    .with_lens(
+        camera.get_aperture_radius(),
+        camera.get_focus_distance(),
+        camera.get_aperture_blades(),
+    );
+    let (ortho_width, interocular_distance) = (
+        render_camera.ortho_width,
+        render_camera.interocular_distance,
     );
+    let render_camera = match camera.get_projection() {
+        Projection::Perspective => render_camera,
+        Projection::Orthographic { width } => render_camera.with_projection(
+            RenderProjection::Orthographic,
+            width,
+            interocular_distance,
+        ),
+        Projection::Equirectangular => render_camera.with_projection(
+            RenderProjection::Equirectangular,
+            ortho_width,
+            interocular_distance,
+        ),
+        Projection::StereoSideBySide {
+            interocular_distance,
+        } => render_camera.with_projection(
+            RenderProjection::StereoSideBySide,
+            ortho_width,
+            interocular_distance,
+        ),
+        Projection::StereoTopBottom {
+            interocular_distance,
+        } => render_camera.with_projection(
+            RenderProjection::StereoTopBottom,
+            ortho_width,
+            interocular_distance,
+        ),
+    };
 
     let Resolution { width, height } = camera.get_resolution();
     let uniforms = RenderUniforms::new(
@@ -124,6 +201,27 @@ fn vec3_to_f32_with_color(v: &[f64], color: Option<&[f32; 3]>) -> [f32; 3] {
         vec3_from_slice_f32(v)
     }
 }
+/// Converts the given procedural pattern of a material to the engine pattern
+/// ## Parameter
+/// 'pattern': Pattern from the scene
+/// ## Returns
+/// engine_config::Pattern with the same parameters
+fn pattern_to_render_pattern(pattern: &Pattern) -> RenderPattern {
+    let kind = match pattern.kind {
+        PatternKind::Triplanar => engine_config::PatternKind::Triplanar,
+        PatternKind::Checker => engine_config::PatternKind::Checker,
+        PatternKind::Gradient => engine_config::PatternKind::Gradient,
+        PatternKind::Noise => engine_config::PatternKind::Noise,
+        PatternKind::Voronoi => engine_config::PatternKind::Voronoi,
+    };
+    RenderPattern::new(
+        kind,
+        pattern.scale as f32,
+        pattern.colors.map(|c| c.map(|v| v as f32)),
+    )
+    .with_octaves(pattern.octaves.clamp(1, RenderPattern::MAX_OCTAVES))
+    .with_sharpness((pattern.sharpness as f32).max(1.0))
+}
 /// Converts the material to the form that the GPU needs
 /// ## Parameter
 /// 'mat': Reference to a Material from the scene
@@ -134,12 +232,14 @@ fn material_to_render_material(
     color: Option<&[f32; 3]>,
     texture_map: &HashMap<String, i32>,
 ) -> engine_config::Material {
+    let principled = mat.principled();
     let ambient = vec3_from_slice_f32(&mat.ambient_reflectivity);
-    let diffuse = vec3_to_f32_with_color(&mat.diffuse_reflectivity, color);
+    // The engine reads the diffuse color as base color of the principled BSDF
+    let diffuse = vec3_to_f32_with_color(&principled.base_color, color);
     let diffuse = engine_config::Vec3::new(diffuse[0], diffuse[1], diffuse[2]);
 
     let specular = vec3_to_f32_with_color(&mat.specular_reflectivity, color);
-    let emissive = if let Some(color) = color {
+    let mut emissive = if let Some(color) = color {
         vec3_to_f32_with_color(
             &mat.emissive,
             Some(&[color[0] * 500.0, color[1] * 500.0, color[2] * 500.0]),
@@ -148,24 +248,75 @@ fn material_to_render_material(
         vec3_from_slice_f32(&mat.emissive)
     };
 
-    let texture_index = if let Some(path) = &mat.texture_path {
-        *texture_map.get(path).unwrap_or(&-1)
-    } else {
-        -1
+    let lookup_texture = |path: &Option<String>| match path {
+        Some(path) => *texture_map.get(path).unwrap_or(&-1),
+        None => -1,
+    };
+    let texture_index = lookup_texture(mat.base_color_path());
+    let emissive_texture = lookup_texture(&mat.maps.emissive_path);
+    // Like roughness and metallic maps, a map without a scalar value is used as is
+    if emissive_texture >= 0 && emissive == [0.0; 3] {
+        emissive = [1.0; 3];
+    }
+    // Authored roughness takes precedence over the specular exponent
+    let shininess_texture = match (mat.pbr.roughness, &mat.pbr.roughness_path) {
+        (None, None) => lookup_texture(&mat.maps.shininess_path),
+        _ => -1,
     };
+    let shininess = match shininess_texture >= 0 && mat.shininess <= 0.0 {
+        true => 1000.0,
+        false => mat.shininess as f32,
+    };
+    let pattern = mat
+        .pattern
+        .as_ref()
+        .map(pattern_to_render_pattern)
+        .unwrap_or_default();
+
+    let coefficients = |c: Option<[f64; 3]>| c.unwrap_or_default().map(|v| v.max(0.0) as f32);
+    let medium = Medium::new(
+        coefficients(mat.medium.absorption),
+        coefficients(mat.medium.scattering),
+        mat.medium.anisotropy.unwrap_or(0.0).clamp(-0.99, 0.99) as f32,
+    );
 
     engine_config::Material::new(
         ambient,
         diffuse,
         specular,
-        mat.shininess as f32,
+        shininess,
         emissive,
-        1.0,
-        1.0 - mat.transparency as f32,
-        2,
+        mat.ior as f32,
+        mat.transparency as f32,
+        mat.illum,
         texture_index,
     )
     .unwrap_or_default()
+    .with_principled(
+        principled.metallic as f32,
+        principled.roughness as f32,
+        principled.specular as f32,
+        principled.sheen as f32,
+        principled.clearcoat as f32,
+        principled.clearcoat_roughness as f32,
+    )
+    .with_pbr_textures(
+        lookup_texture(&mat.pbr.roughness_path),
+        lookup_texture(&mat.pbr.metallic_path),
+    )
+    .with_bump_textures(
+        lookup_texture(&mat.bump.normal_path),
+        lookup_texture(&mat.bump.height_path),
+        mat.bump.multiplier.unwrap_or(1.0) as f32,
+    )
+    .with_map_textures(
+        lookup_texture(&mat.maps.opacity_path),
+        lookup_texture(mat.specular_level_path()),
+        emissive_texture,
+        shininess_texture,
+    )
+    .with_medium(medium)
+    .with_pattern(pattern)
 }
 /// Extracts vertices and point references from the given mesh
 /// ## Parameter
@@ -175,9 +326,24 @@ fn mesh_to_render_data(mesh: &Mesh, texture_map: &HashMap<String, i32>) -> Vec<R
     let original_vertices = mesh.get_vertices();
     let original_indices = mesh.get_tri_indices();
     let original_uvs = mesh.get_uvs();
+    let original_normals = mesh.get_normals();
+    let original_tangents = mesh.get_tangents();
 
     let materials = mesh.get_materials();
     let material_indices = mesh.get_material_indices();
+    // A pattern of the mesh replaces the patterns of its materials
+    let render_material = |mat: &scene_objects::material::Material| match mesh.get_pattern() {
+        Some(pattern) => material_to_render_material(
+            &mat.clone().with_pattern(Some(pattern.clone())),
+            None,
+            texture_map,
+        ),
+        None => material_to_render_material(mat, None, texture_map),
+    };
+    let default_material = || match mesh.get_pattern() {
+        Some(_) => render_material(&scene_objects::material::Material::default()),
+        None => engine_config::Material::default(),
+    };
 
     if let (Some(mats), Some(mat_indices)) = (materials, material_indices)
         && !mats.is_empty()
@@ -194,10 +360,14 @@ fn mesh_to_render_data(mesh: &Mesh, texture_map: &HashMap<String, i32>) -> Vec<R
                 0
             };
 
-            let entry = sub_meshes
-                .entry(mat_idx)
-                .or_insert((Vec::new(), Vec::new(), Vec::new()));
-            let (verts, inds, uvs) = entry;
+            let entry = sub_meshes.entry(mat_idx).or_insert((
+                Vec::new(),
+                Vec::new(),
+                Vec::new(),
+                Vec::new(),
+                Vec::new(),
+            ));
+            let (verts, inds, uvs, normals, tangents) = entry;
 
             let current_v_count = (verts.len() / 3) as u32;
 
@@ -249,6 +419,22 @@ fn mesh_to_render_data(mesh: &Mesh, texture_map: &HashMap<String, i32>) -> Vec<R
                 }
             }
 
+            // Add normals, zero normals are shaded flat
+            for idx in [idx0, idx1, idx2] {
+                match original_normals.and_then(|n| n.get(idx * 3..idx * 3 + 3)) {
+                    Some(n) => normals.extend_from_slice(n),
+                    None => normals.extend_from_slice(&[0.0, 0.0, 0.0]),
+                }
+            }
+
+            // Add tangents, zero tangents disable normal and bump maps
+            for idx in [idx0, idx1, idx2] {
+                match original_tangents.and_then(|t| t.get(idx * 4..idx * 4 + 4)) {
+                    Some(t) => tangents.extend_from_slice(t),
+                    None => tangents.extend_from_slice(&[0.0, 0.0, 0.0, 0.0]),
+                }
+            }
+
             // Add indices
             inds.push(current_v_count);
             inds.push(current_v_count + 1);
@@ -256,13 +442,13 @@ fn mesh_to_render_data(mesh: &Mesh, texture_map: &HashMap<String, i32>) -> Vec<R
         }
 
         let mut result = Vec::new();
-        for (mat_idx, (verts, inds, uvs)) in sub_meshes {
+        for (mat_idx, (verts, inds, uvs, normals, tangents)) in sub_meshes {
             let material = if mat_idx < mats.len() {
-                material_to_render_material(&mats[mat_idx], None, texture_map)
+                render_material(&mats[mat_idx])
             } else {
-                engine_config::Material::default()
+                default_material()
             };
-            result.push((verts, inds, uvs, material));
+            result.push((verts, inds, uvs, normals, tangents, material));
         }
         return result;
     }
@@ -274,24 +460,34 @@ fn mesh_to_render_data(mesh: &Mesh, texture_map: &HashMap<String, i32>) -> Vec<R
     } else {
         vec![0.0; (vertices.len() / 3) * 2]
     };
+    let normals = match original_normals {
+        Some(normals) => normals.clone(),
+        None => vec![0.0; vertices.len()],
+    };
+    let tangents = match original_tangents {
+        Some(tangents) => tangents.clone(),
+        None => vec![0.0; vertices.len() / 3 * 4],
+    };
 
     let material = if let Some(mats) = materials {
         if !mats.is_empty() {
-            material_to_render_material(&mats[0], None, texture_map)
+            render_material(&mats[0])
         } else {
-            engine_config::Material::default()
+            default_material()
         }
     } else {
-        engine_config::Material::default()
+        default_material()
     };
 
-    vec![(vertices, indices, uvs, material)]
+    vec![(vertices, indices, uvs, normals, tangents, material)]
 }
 
 /// Converts a given Mesh to a triangle as it will be used on the GPU
 fn mesh_to_gpu_triangles(
     mesh: &RenderMesh,
     verts: &[f32],
+    normals: &[f32],
+    tangents: &[f32],
     indices: &[u32],
     mesh_index: u32,
 ) -> Vec<GPUTriangle> {
@@ -316,15 +512,65 @@ fn mesh_to_gpu_triangles(
             v0_index: v0i as u32,
             v1_index: v1i as u32,
             v2_index: v2i as u32,
+            n0: Vec3::new(normals[i0], normals[i0 + 1], normals[i0 + 2]),
+            n1: Vec3::new(normals[i1], normals[i1 + 1], normals[i1 + 2]),
+            n2: Vec3::new(normals[i2], normals[i2 + 1], normals[i2 + 2]),
             mesh_index,
             _pad0: 0,
             _pad1: 0,
-            _pad2: 0,
+            t0: Vec4::from_slice(&tangents[v0i * 4..v0i * 4 + 4]),
+            t1: Vec4::from_slice(&tangents[v1i * 4..v1i * 4 + 4]),
+            t2: Vec4::from_slice(&tangents[v2i * 4..v2i * 4 + 4]),
         });
     }
     tris
 }
 
+/// Gathers the triangles with an emissive material into a light list for direct lighting
+/// ## Parameter
+/// 'triangles': All mesh triangles as they are passed to the GPU <br>
+/// 'meshes': The meshes the triangles index into, holding their materials <br>
+/// 'instances': The triangles of each placed mesh and their transform into the scene
+/// ## Returns
+/// The emissive triangles of every instance in world space, with the running sum of their power as distribution to pick them by
+pub(crate) fn collect_emissive_triangles(
+    triangles: &[GPUTriangle],
+    meshes: &[RenderMesh],
+    instances: &[(Range<usize>, Mat4)],
+) -> Vec<EmissiveTriangle> {
+    let mut cdf = 0.0;
+    instances
+        .iter()
+        .flat_map(|(range, transform)| {
+            triangles
+                .get(range.clone())
+                .unwrap_or_default()
+                .iter()
+                .zip(range.clone())
+                .map(move |(tri, index)| (tri, index, transform))
+        })
+        .filter_map(|(tri, index, transform)| {
+            let emissive = meshes.get(tri.mesh_index as usize)?.material.emissive;
+            let light = EmissiveTriangle::new(
+                transform.transform_point3(tri.v0).to_array(),
+                transform.transform_point3(tri.v1).to_array(),
+                transform.transform_point3(tri.v2).to_array(),
+                emissive,
+            );
+            let power = light.power();
+            if power <= 0.0 {
+                return None;
+            }
+            cdf += power;
+            Some(EmissiveTriangle {
+                cdf,
+                triangle: index as u32,
+                ..light
+            })
+        })
+        .collect()
+}
+
 /// Extends scene to offer functionalities needed for rendering with raytracer or pathtracer engine
 impl Scene {
     /// ## Returns
@@ -338,22 +584,38 @@ impl Scene {
         }
         res
     }
+    /// ## Parameter
+    /// 'texture_map': Map of texture paths to texture indices
     /// ## Returns
     /// a Vec that contains all Scene spheres as engine_config::Sphere
-    fn get_render_spheres(&self) -> Vec<RenderSphere> {
+    fn get_render_spheres(&self, texture_map: &HashMap<String, i32>) -> Vec<RenderSphere> {
         self.get_spheres()
             .iter()
-            .map(sphere_to_render_sphere)
+            .map(|sphere| sphere_to_render_sphere(sphere, texture_map))
             .collect()
     }
     /// ## Returns
-    /// RenderUnfiform for the camera of the scene
+    /// RenderUnfiform for the camera, environment map and media of the scene
+    /// ## Parameters
+    /// 'interior_media': whether any sphere or mesh material has an interior medium
     pub(crate) fn get_render_uniforms(
         &self,
         spheres_count: u32,
         bvh_node_count: u32,
         bvh_triangle_count: u32,
+        interior_media: bool,
+        texture_map: &HashMap<String, i32>,
     ) -> RenderUniforms {
+        // The ground is an open plane, it can't hold an interior medium
+        let ground_material = match self.get_ground_material() {
+            Some(material) => material_to_render_material(material, None, texture_map)
+                .with_medium(Medium::default()),
+            None => engine_config::Material::ground(),
+        };
+        let (environment_width, environment_height) = self
+            .get_environment()
+            .map(|e| (e.get_map().width, e.get_map().height))
+            .unwrap_or((0, 0));
         camera_to_render_uniforms(
             self.get_camera(),
             spheres_count,
@@ -363,63 +625,116 @@ impl Scene {
             self.get_render_parameter(),
         )
         .unwrap()
+        .with_environment(
+            environment_width,
+            environment_height,
+            self.get_environment_rotation().to_radians(),
+            self.get_environment_intensity(),
+        )
+        .with_tone_mapping(self.get_exposure(), self.get_tone_mapping())
+        .with_adaptive_sampling(
+            self.get_adaptive_threshold(),
+            self.get_adaptive_min_samples(),
+        )
+        .with_medium(self.get_medium(), interior_media)
+        .with_tiles(self.get_tile_size(), self.get_tile_order())
+        .with_ground(ground_material, self.get_shadow_catcher())
+        .with_radiance_layer(self.get_denoise())
     }
     /// ## Returns
-    /// Vector of touples, with each of the touples representing a TriGeometry defined by the points and the triangles build from the points.
-    fn get_render_tris(&self, texture_map: &HashMap<String, i32>) -> Vec<RenderGeometry> {
+    /// For each mesh a vector of touples, with each of the touples representing a TriGeometry defined by the points and the triangles build from the points.
+    fn get_render_tris(&self, texture_map: &HashMap<String, i32>) -> Vec<Vec<RenderGeometry>> {
         self.get_meshes()
             .iter()
-            .flat_map(|m| mesh_to_render_data(m, texture_map))
+            .map(|m| mesh_to_render_data(m, texture_map))
+            .collect()
+    }
+    /// ## Returns
+    /// The mesh index and transform of every placement of a mesh: each mesh itself, followed by its instances
+    fn get_render_instances(&self) -> Vec<(usize, Mat4)> {
+        let meshes = self.get_meshes();
+        (0..meshes.len())
+            .map(|i| (i, Mat4::IDENTITY))
+            .chain(self.get_mesh_instances().iter().filter_map(|instance| {
+                let mesh = meshes.get(instance.get_mesh_index())?;
+                Some((instance.get_mesh_index(), instance.get_transform(mesh)))
+            }))
             .collect()
     }
 
     fn generate_full_render_command_builder(&mut self) -> RenderConfig {
-        let render_spheres = self.get_render_spheres();
-
         // Collect textures
         let (texture_list, texture_map) = self.texture_cache.get_split_clone();
 
+        let render_spheres = self.get_render_spheres(&texture_map);
+
         let render_tris = self.get_render_tris(&texture_map);
         debug!("Scene mesh data: {:?}", self.get_meshes());
         debug!("Collected mesh data: {:?}", render_tris);
 
+        // Triangles of each mesh, the range its bottom-level BVH is built over
+        let mut mesh_ranges = Vec::with_capacity(render_tris.len());
+        let mut triangle_start = 0;
+        for geometries in render_tris.iter() {
+            let count: usize = geometries.iter().map(|g| g.1.len() / 3).sum();
+            mesh_ranges.push(triangle_start..triangle_start + count);
+            triangle_start += count;
+        }
+        let render_tris: Vec<RenderGeometry> = render_tris.into_iter().flatten().collect();
+
         let spheres_count = render_spheres.len() as u32;
 
         // Collect all vertices, triangles, and mesh into flat vectors
-        let (all_vertices, all_triangles, all_meshes, all_uvs) = if render_tris.is_empty() {
-            (vec![], vec![], vec![], vec![])
-        } else {
-            let mut all_verts = vec![];
-            let mut all_tris = vec![];
-            let mut all_uvs = vec![];
-            let mut mesh_infos = vec![];
-            let mut vertex_offset = 0u32;
-            let mut triangle_offset = 0u32;
-
-            for (verts, tris, uvs, material) in render_tris.iter() {
-                let vertex_count = (verts.len() / 3) as u32;
-                let triangle_count = (tris.len() / 3) as u32;
-
-                // Add mesh metadata
-                mesh_infos.push(RenderMesh::new(triangle_offset, triangle_count, *material));
-
-                // Add triangles with vertex offset
-                for tri_idx in tris {
-                    all_tris.push(tri_idx + vertex_offset);
-                }
+        let (all_vertices, all_triangles, all_meshes, all_uvs, all_normals, all_tangents) =
+            if render_tris.is_empty() {
+                (vec![], vec![], vec![], vec![], vec![], vec![])
+            } else {
+                let mut all_verts = vec![];
+                let mut all_tris = vec![];
+                let mut all_uvs = vec![];
+                let mut all_normals = vec![];
+                let mut all_tangents = vec![];
+                let mut mesh_infos = vec![];
+                let mut vertex_offset = 0u32;
+                let mut triangle_offset = 0u32;
 
-                // Add vertices
-                all_verts.extend(verts);
+                for (verts, tris, uvs, normals, tangents, material) in render_tris.iter() {
+                    let vertex_count = (verts.len() / 3) as u32;
+                    let triangle_count = (tris.len() / 3) as u32;
 
-                // Add UVs
-                all_uvs.extend(uvs);
+                    // Add mesh metadata
+                    mesh_infos.push(RenderMesh::new(triangle_offset, triangle_count, *material));
 
-                vertex_offset += vertex_count;
-                triangle_offset += triangle_count;
-            }
+                    // Add triangles with vertex offset
+                    for tri_idx in tris {
+                        all_tris.push(tri_idx + vertex_offset);
+                    }
 
-            (all_verts, all_tris, mesh_infos, all_uvs)
-        };
+                    // Add vertices
+                    all_verts.extend(verts);
+
+                    // Add UVs
+                    all_uvs.extend(uvs);
+
+                    // Add normals
+                    all_normals.extend(normals);
+
+                    // Add tangents
+                    all_tangents.extend(tangents);
+
+                    vertex_offset += vertex_count;
+                    triangle_offset += triangle_count;
+                }
+
+                (
+                    all_verts,
+                    all_tris,
+                    mesh_infos,
+                    all_uvs,
+                    all_normals,
+                    all_tangents,
+                )
+            };
 
         let mut gpu_triangles: Vec<GPUTriangle> = Vec::new();
 
@@ -427,26 +742,38 @@ impl Scene {
             gpu_triangles.extend(mesh_to_gpu_triangles(
                 mesh,
                 &all_vertices,
+                &all_normals,
+                &all_tangents,
                 &all_triangles,
                 i as u32,
             ));
         }
 
-        let (bvh_nodes, bvh_indices) = if gpu_triangles.is_empty() {
-            (vec![], vec![])
-        } else {
-            let bvh = BVH::new(&gpu_triangles);
-            (bvh.nodes, bvh.indices)
-        };
+        // One bottom-level BVH per mesh, shared by all of its instances
+        let render_instances = self.get_render_instances();
+        let bvh = TwoLevelBVH::new(&gpu_triangles, &mesh_ranges, &render_instances);
+        let instance_triangles: Vec<(Range<usize>, Mat4)> = render_instances
+            .iter()
+            .map(|(mesh, transform)| (mesh_ranges[*mesh].clone(), *transform))
+            .collect();
 
-        let bvh_node_count = bvh_nodes.len();
+        let bvh_node_count = bvh.nodes.len();
         let bvh_triangle_count = gpu_triangles.len();
 
-        let uniforms = self.get_render_uniforms(
-            spheres_count,
-            bvh_node_count as u32,
-            bvh_triangle_count as u32,
-        );
+        let interior_media = render_spheres
+            .iter()
+            .map(|s| &s.material)
+            .chain(all_meshes.iter().map(|m| &m.material))
+            .any(|m| !m.medium().is_vacuum());
+        let uniforms = self
+            .get_render_uniforms(
+                spheres_count,
+                bvh_node_count as u32,
+                bvh_triangle_count as u32,
+                interior_media,
+                &texture_map,
+            )
+            .with_bvh_root(bvh.root);
 
         info!("Collected vertices count: {}", all_vertices.len());
         info!("Collected tris count: {}", all_triangles.len());
@@ -456,48 +783,98 @@ impl Scene {
             bvh_triangle_count,
             all_vertices.len() / 3
         );
+        info!(
+            "{self}: Placing {} meshes as {} instances",
+            mesh_ranges.len(),
+            bvh.instances.len()
+        );
 
         let point_lights = self.get_render_point_lights();
+        let emissive_triangles =
+            collect_emissive_triangles(&gpu_triangles, &all_meshes, &instance_triangles);
+        if !emissive_triangles.is_empty() {
+            info!(
+                "{self}: Sampling {} emissive triangles as area lights",
+                emissive_triangles.len()
+            );
+        }
+        let environment = self.get_environment().map(|e| e.get_map().clone());
+        let aovs = self.get_render_aovs();
 
         // todo: fix this part just in case (|| true)
         if self.get_first_render() {
             self.set_first_render(false);
             // NOTE: *_create is for the first initial render which initializes all the buffers etc.
-            RenderConfigBuilder::new()
+            let builder = RenderConfigBuilder::new()
                 .uniforms_create(uniforms)
                 .spheres_create(render_spheres)
                 .uvs_create(all_uvs)
                 .meshes_create(all_meshes)
-                .bvh_nodes_create(bvh_nodes)
-                .bvh_indices_create(bvh_indices)
+                .bvh_nodes_create(bvh.nodes)
+                .bvh_indices_create(bvh.indices)
                 .bvh_triangles_create(gpu_triangles)
+                .instances_create(bvh.instances)
+                .emissive_triangles_create(emissive_triangles)
                 .lights_create(point_lights)
                 .textures_create(texture_list)
-                .build()
+                .aovs_create(aovs);
+            match environment {
+                Some(environment) => builder.environment_create(environment),
+                None => builder,
+            }
+            .build()
         } else {
             // NOTE: * otherwise the values are updated with the new value an the unchanged fields
             // are kept as is. See: ../../../crates/engine-config/src/render_config.rs - `Change<T>`
-            RenderConfigBuilder::new()
+            let builder = RenderConfigBuilder::new()
                 .uniforms(uniforms)
                 .spheres(render_spheres)
                 .uvs(all_uvs)
                 .meshes(all_meshes)
-                .bvh_nodes_create(bvh_nodes)
-                .bvh_indices_create(bvh_indices)
+                .bvh_nodes_create(bvh.nodes)
+                .bvh_indices_create(bvh.indices)
                 .bvh_triangles_create(gpu_triangles)
+                .instances_create(bvh.instances)
+                .emissive_triangles(emissive_triangles)
                 .lights(point_lights)
                 .textures(texture_list)
-                .build()
+                .aovs(aovs);
+            match environment {
+                Some(environment) => builder.environment(environment),
+                None => builder.environment_delete(),
+            }
+            .build()
+        }
+    }
+    /// ## Returns
+    /// The AOV layers requested from the engine, including the denoiser's guides if denoising is enabled
+    fn get_render_aovs(&self) -> AovSet {
+        let mut aovs = self.get_aovs();
+        if self.get_denoise() {
+            GUIDE_AOVS.into_iter().for_each(|aov| aovs.insert(aov));
         }
+        aovs
     }
     /// ## Returns
-    /// A FrameIterator for the current scene
+    /// The denoiser settings, with the same display transform as the render
+    fn get_denoise_settings(&self) -> DenoiseSettings {
+        DenoiseSettings::default().with_tone_mapping(self.get_exposure(), self.get_tone_mapping())
+    }
+    /// ## Returns
+    /// A FrameIterator for the current scene, denoising every frame if enabled
     pub fn get_frame_iterator(&mut self) -> Result<Box<dyn FrameIterator>> {
         let rc = self.generate_full_render_command_builder();
+        let denoise = self.get_denoise();
+        let settings = self.get_denoise_settings();
 
         let engine = self.get_render_engine_mut();
 
-        engine.get_frame_iterator(rc)
+        let iterator = engine.get_frame_iterator(rc)?;
+        if denoise {
+            Ok(Box::new(DenoisingFrameIterator::new(iterator, settings)))
+        } else {
+            Ok(iterator)
+        }
     }
     /// calls the render engine for the scene self.
     /// ## Returns
@@ -506,10 +883,14 @@ impl Scene {
         info!("{self}: Render has been called. Collecting render parameters");
 
         let rc = self.generate_full_render_command_builder();
+        let settings = self.get_denoise_settings();
 
         let engine = self.get_render_engine_mut();
 
-        let output = engine.render(rc);
+        let mut output = engine.render(rc);
+        if self.get_denoise() {
+            output = output.and_then(|frame| denoise(&frame, &settings));
+        }
         match output {
             Ok(res) => match res.validate() {
                 Ok(_) => {
@@ -1,8 +1,12 @@
-use scene_objects::{camera::Camera, light_source::LightSource, mesh::Mesh, sphere::Sphere};
+use scene_objects::{
+    camera::Camera, light_source::LightSource, mesh::Mesh, mesh_instance::MeshInstance,
+    sphere::Sphere,
+};
 /// The scene graphs holds all elements of the scene
 pub(crate) struct SceneGraph {
     spheres: Vec<Sphere>,
     meshes: Vec<Mesh>,
+    mesh_instances: Vec<MeshInstance>,
     light_sources: Vec<LightSource>,
     camera: Camera,
 }
@@ -14,6 +18,7 @@ impl SceneGraph {
         Self {
             spheres: Vec::new(),
             meshes: Vec::new(),
+            mesh_instances: Vec::new(),
             light_sources: Vec::new(),
             camera: Camera::default(),
         }
@@ -30,6 +35,12 @@ impl SceneGraph {
     pub fn add_mesh(&mut self, mesh: Mesh) {
         self.meshes.push(mesh);
     }
+    /// Adds an instance of a mesh
+    /// ## Parameter
+    /// 'instance': instance to be added, its mesh index has to point to a mesh of the graph
+    pub fn add_mesh_instance(&mut self, instance: MeshInstance) {
+        self.mesh_instances.push(instance);
+    }
     /// adds a LightSource
     /// ## Parameter
     /// 'light': LightSource to be added
@@ -63,6 +74,16 @@ impl SceneGraph {
         &mut self.meshes
     }
     /// ## Returns
+    /// all mesh instances as a reference to a vector of MeshInstance
+    pub fn get_mesh_instances(&self) -> &Vec<MeshInstance> {
+        &self.mesh_instances
+    }
+    /// ## Returns
+    /// all mesh instances as a mutable reference to a vector of MeshInstance
+    pub fn get_mesh_instances_mut(&mut self) -> &mut Vec<MeshInstance> {
+        &mut self.mesh_instances
+    }
+    /// ## Returns
     /// all light sources as a reference to a vector of LightSource
     pub fn get_light_sources(&self) -> &Vec<LightSource> {
         &self.light_sources
@@ -92,15 +113,29 @@ impl SceneGraph {
     pub fn clear_spheres(&mut self) {
         self.spheres.clear();
     }
-    /// Deletes all meshes in the graph
+    /// Deletes all meshes and their instances in the graph
     pub fn clear_meshes(&mut self) {
         self.meshes.clear();
+        self.mesh_instances.clear();
     }
-    /// Removes mesh at given index
+    /// Removes mesh at given index together with its instances
     /// ## Parameter
     /// 'index': Index of the object that will be removed
     pub fn remove_mesh(&mut self, index: usize) {
         self.meshes.remove(index);
+        self.mesh_instances
+            .retain(|instance| instance.get_mesh_index() != index);
+        for instance in self.mesh_instances.iter_mut() {
+            if instance.get_mesh_index() > index {
+                instance.set_mesh_index(instance.get_mesh_index() - 1);
+            }
+        }
+    }
+    /// Removes mesh instance at given index
+    /// ## Parameter
+    /// 'index': Index of the instance that will be removed
+    pub fn remove_mesh_instance(&mut self, index: usize) {
+        self.mesh_instances.remove(index);
     }
     /// Removes light source at given index
     /// ## Parameter
@@ -18,3 +18,57 @@ fn basic_scene_test() {
     }
     assert_eq!(scene.get_spheres().len(), s_count);
 }
+
+/// Triangle in the z = 0 plane with its right angle at the origin
+fn right_triangle(a: f32, b: f32, mesh_index: u32) -> engine_bvh::triangle::GPUTriangle {
+    engine_bvh::triangle::GPUTriangle {
+        v0: Vec3::ZERO,
+        v1: Vec3::new(a, 0.0, 0.0),
+        v2: Vec3::new(0.0, b, 0.0),
+        mesh_index,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn emissive_triangles_are_sampled_by_power() {
+    use crate::data_plane::scene::scene_engine_adapter::collect_emissive_triangles;
+    use glam::Mat4;
+
+    let glowing = engine_config::Material {
+        emissive: [2.0, 2.0, 2.0],
+        ..Default::default()
+    };
+    let meshes = [
+        engine_config::Mesh::new(0, 3, glowing),
+        engine_config::Mesh::new(3, 1, engine_config::Material::default()),
+    ];
+    // The third triangle of the glowing mesh is degenerate and emits no power
+    let triangles = [
+        right_triangle(1.0, 1.0, 0),
+        right_triangle(2.0, 1.0, 0),
+        right_triangle(1.0, 0.0, 0),
+        right_triangle(1.0, 1.0, 1),
+    ];
+    let instances = [
+        (0..3, Mat4::IDENTITY),
+        (3..4, Mat4::IDENTITY),
+        (0..3, Mat4::from_scale(Vec3::splat(2.0))),
+    ];
+
+    let lights = collect_emissive_triangles(&triangles, &meshes, &instances);
+
+    // Only the non-degenerate triangles of both placements of the glowing mesh
+    assert_eq!(
+        lights.iter().map(|l| l.triangle).collect::<Vec<_>>(),
+        [0, 1, 0, 1]
+    );
+    assert_eq!(
+        lights.iter().map(|l| l.area).collect::<Vec<_>>(),
+        [0.5, 1.0, 2.0, 4.0]
+    );
+    assert!(lights.windows(2).all(|w| w[0].cdf < w[1].cdf));
+    let total: f32 = lights.iter().map(|l| l.power()).sum();
+    assert_eq!(lights.last().unwrap().cdf, total);
+    assert_eq!(total, 2.0 * 7.5);
+}
@@ -1,3 +1,4 @@
+pub mod environment_loader;
 pub mod file_manager;
 pub mod img_export;
 pub mod mtl_parser;
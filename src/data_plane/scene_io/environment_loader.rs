@@ -0,0 +1,29 @@
+use image::ImageFormat;
+use engine_config::EnvironmentMap;
+use log::info;
+use crate::included_files::AutoPath;
+
+/// Loads an equirectangular .hdr or .exr image as an environment map
+/// ## Parameter
+/// 'path': AutoPath to the image
+/// ## Returns
+/// The linear radiance of the image as engine_config::EnvironmentMap
+pub fn load_environment(path: AutoPath) -> anyhow::Result<EnvironmentMap> {
+    let format = match path.extension().map(|e| e.to_ascii_lowercase()).as_deref() {
+        Some("hdr") => ImageFormat::Hdr,
+        Some("exr") => ImageFormat::OpenExr,
+        _ => {
+            return Err(anyhow::Error::msg(format!(
+                "Environment map {} is not an .hdr or .exr image",
+                path
+            )));
+        }
+    };
+    let img = image::load(path.reader()?, format)?.to_rgb32f();
+    let (width, height) = img.dimensions();
+    info!(
+        "EnvironmentLoader: Loaded {}x{} environment map from {}",
+        width, height, path
+    );
+    Ok(EnvironmentMap::new(width, height, img.into_raw()))
+}
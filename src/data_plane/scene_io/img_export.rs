@@ -1,6 +1,6 @@
 use std::path::PathBuf;
 use image::{ImageBuffer, Rgba};
-use frame_buffer::frame_iterator::Frame;
+use frame_buffer::frame_iterator::{Frame, FrameLayer, SAMPLES_LAYER};
 
 pub fn export_img_png(path: PathBuf, frame: Frame) -> image::ImageResult<()> {
     let img: ImageBuffer<Rgba<u8>, _> = ImageBuffer::from_raw(
@@ -14,5 +14,90 @@ pub fn export_img_png(path: PathBuf, frame: Frame) -> image::ImageResult<()> {
         ))
     })?;
 
+    if is_exr(&path) {
+        // OpenEXR only stores floats
+        return image::DynamicImage::ImageRgba8(img).to_rgba32f().save(path);
+    }
     img.save(path)
 }
+
+fn is_exr(path: &std::path::Path) -> bool {
+    path.extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("exr"))
+}
+
+/// Saves an AOV layer of a frame. OpenEXR files keep the raw floats, other formats
+/// get an 8-bit visualization of the layer.
+pub fn export_layer(path: PathBuf, frame: &Frame, layer: &FrameLayer) -> image::ImageResult<()> {
+    let dimension_mismatch = || {
+        image::ImageError::Parameter(image::error::ParameterError::from_kind(
+            image::error::ParameterErrorKind::DimensionMismatch,
+        ))
+    };
+    if is_exr(&path) {
+        let img: ImageBuffer<Rgba<f32>, _> =
+            ImageBuffer::from_raw(frame.width as u32, frame.height as u32, layer.data.clone())
+                .ok_or_else(dimension_mismatch)?;
+        img.save(path)
+    } else {
+        let img: ImageBuffer<Rgba<u8>, _> = ImageBuffer::from_raw(
+            frame.width as u32,
+            frame.height as u32,
+            visualize_layer(layer),
+        )
+        .ok_or_else(dimension_mismatch)?;
+        img.save(path)
+    }
+}
+
+/// Maps the floats of an AOV layer to RGBA8, the background (coverage 0) stays black
+pub fn visualize_layer(layer: &FrameLayer) -> Vec<u8> {
+    let pixels = layer.data.chunks_exact(4);
+    let min_depth = pixels
+        .clone()
+        .filter(|p| p[3] > 0.0 && p[0] > 0.0)
+        .map(|p| p[0])
+        .fold(f32::INFINITY, f32::min);
+    let max_samples = pixels.clone().map(|p| p[0]).fold(1.0, f32::max);
+    let to_u8 = |v: f32| (v.clamp(0.0, 1.0) * 255.999) as u8;
+
+    pixels
+        .flat_map(|p| {
+            let rgb = if p[3] <= 0.0 {
+                [0.0; 3]
+            } else {
+                match layer.name.as_str() {
+                    // Inverse depth, the nearest hit is white and a ground plane fades towards the horizon
+                    "depth" => [min_depth / p[0].max(f32::EPSILON); 3],
+                    "normal" => [p[0] * 0.5 + 0.5, p[1] * 0.5 + 0.5, p[2] * 0.5 + 0.5],
+                    "albedo" => [p[0].sqrt(), p[1].sqrt(), p[2].sqrt()],
+                    "object_id" => id_to_color(p[0] as u32, p[1] as u32),
+                    "uv" => [p[0].rem_euclid(1.0), p[1].rem_euclid(1.0), 0.0],
+                    // Heatmap of adaptive sampling, relative to the most sampled pixel
+                    SAMPLES_LAYER => heat_color(p[0] / max_samples),
+                    _ => [p[0], p[1], p[2]],
+                }
+            };
+            [to_u8(rgb[0]), to_u8(rgb[1]), to_u8(rgb[2]), 255]
+        })
+        .collect()
+}
+
+/// Blue for few, green and yellow for more and red for the most samples
+fn heat_color(t: f32) -> [f32; 3] {
+    [
+        1.5 - (4.0 * t - 3.0).abs(),
+        1.5 - (4.0 * t - 2.0).abs(),
+        1.5 - (4.0 * t - 1.0).abs(),
+    ]
+}
+
+/// Distinct color for every object kind and index
+fn id_to_color(kind: u32, index: u32) -> [f32; 3] {
+    let h = (kind.wrapping_mul(73856093) ^ index.wrapping_mul(19349663)).wrapping_mul(2654435761);
+    [
+        0.2 + 0.8 * (h & 0xff) as f32 / 255.0,
+        0.2 + 0.8 * ((h >> 8) & 0xff) as f32 / 255.0,
+        0.2 + 0.8 * ((h >> 16) & 0xff) as f32 / 255.0,
+    ]
+}
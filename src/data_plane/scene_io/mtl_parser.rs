@@ -1,5 +1,7 @@
 use anyhow::anyhow;
-use scene_objects::material::Material;
+use scene_objects::material::{
+    BumpProperties, MapProperties, Material, MediumProperties, Pattern, PatternKind, PbrProperties,
+};
 use crate::included_files::AutoPath;
 
 #[derive(Debug)]
@@ -11,10 +13,30 @@ pub struct MTLParser {
     pub ks: Vec<f32>,
     pub ke: Vec<f32>,
     pub d: f32,
+    pub ni: f32,
     pub ns: f32,
     pub illum: u32,
     pub map_kd: Option<String>,
+    pub map_d: Option<String>,
+    pub map_ks: Option<String>,
+    pub map_ke: Option<String>,
+    pub map_ns: Option<String>,
     pub bump: Option<String>,
+    pub norm: Option<String>,
+    pub bm: Option<f32>,
+    pub clamped: Vec<String>,
+    pub mirrored: Vec<String>,
+    pub pr: Option<f32>,
+    pub pm: Option<f32>,
+    pub ps: Option<f32>,
+    pub pc: Option<f32>,
+    pub pcr: Option<f32>,
+    pub map_pr: Option<String>,
+    pub map_pm: Option<String>,
+    pub ma: Option<[f32; 3]>,
+    pub ms: Option<[f32; 3]>,
+    pub mg: Option<f32>,
+    pub pattern: Option<Pattern>,
 }
 
 impl MTLParser {
@@ -30,11 +52,31 @@ impl MTLParser {
         let mut kd: Vec<f32> = Vec::with_capacity(10);
         let mut ks: Vec<f32> = Vec::with_capacity(10);
         let mut ke: Vec<f32> = Vec::with_capacity(10);
-        let mut d: f32 = 0.0;
+        let mut d: f32 = 1.0;
+        let mut ni: f32 = 1.0;
         let mut ns: f32 = 0.0;
         let mut illum: u32 = 0;
         let mut map_kd: Option<String> = None;
+        let mut map_d: Option<String> = None;
+        let mut map_ks: Option<String> = None;
+        let mut map_ke: Option<String> = None;
+        let mut map_ns: Option<String> = None;
         let mut bump: Option<String> = None;
+        let mut norm: Option<String> = None;
+        let mut bm: Option<f32> = None;
+        let mut clamped: Vec<String> = Vec::new();
+        let mut mirrored: Vec<String> = Vec::new();
+        let mut pr: Option<f32> = None;
+        let mut pm: Option<f32> = None;
+        let mut ps: Option<f32> = None;
+        let mut pc: Option<f32> = None;
+        let mut pcr: Option<f32> = None;
+        let mut map_pr: Option<String> = None;
+        let mut map_pm: Option<String> = None;
+        let mut ma: Option<[f32; 3]> = None;
+        let mut ms: Option<[f32; 3]> = None;
+        let mut mg: Option<f32> = None;
+        let mut pattern: Option<Pattern> = None;
 
         let lineiter = data.lines();
         for l in lineiter {
@@ -50,10 +92,30 @@ impl MTLParser {
                                 ks: ks.clone(),
                                 ke: ke.clone(),
                                 d,
+                                ni,
                                 ns,
                                 illum,
                                 map_kd: map_kd.clone(),
+                                map_d: map_d.clone(),
+                                map_ks: map_ks.clone(),
+                                map_ke: map_ke.clone(),
+                                map_ns: map_ns.clone(),
                                 bump: bump.clone(),
+                                norm: norm.clone(),
+                                bm,
+                                clamped: clamped.clone(),
+                                mirrored: mirrored.clone(),
+                                pr,
+                                pm,
+                                ps,
+                                pc,
+                                pcr,
+                                map_pr: map_pr.clone(),
+                                map_pm: map_pm.clone(),
+                                ma,
+                                ms,
+                                mg,
+                                pattern: pattern.clone(),
                             }
                         });
                     }
@@ -62,11 +124,31 @@ impl MTLParser {
                     kd.clear();
                     ks.clear();
                     ke.clear();
-                    d = 0.0;
+                    d = 1.0;
+                    ni = 1.0;
                     illum = 0;
                     ns = 0.0;
                     map_kd = None;
+                    map_d = None;
+                    map_ks = None;
+                    map_ke = None;
+                    map_ns = None;
                     bump = None;
+                    norm = None;
+                    bm = None;
+                    clamped.clear();
+                    mirrored.clear();
+                    pr = None;
+                    pm = None;
+                    ps = None;
+                    pc = None;
+                    pcr = None;
+                    map_pr = None;
+                    map_pm = None;
+                    ma = None;
+                    ms = None;
+                    mg = None;
+                    pattern = None;
                     name = line.replace("newmtl", "").trim().to_string();
                 }
                 if line.starts_with("Ka") {
@@ -97,13 +179,20 @@ impl MTLParser {
                         ke.push(i.parse::<f32>()?);
                     }
                 }
-                if line.starts_with("d") {
-                    let temp = line.replace("d", "").trim().to_string();
+                if line.starts_with("d ") {
+                    let temp = line.replacen("d", "", 1).trim().to_string();
                     let temp = temp.split_whitespace().collect::<Vec<&str>>();
                     for i in temp {
                         d = i.parse::<f32>()?;
                     }
                 }
+                if line.starts_with("Ni") {
+                    let temp = line.replace("Ni", "").trim().to_string();
+                    let temp = temp.split_whitespace().collect::<Vec<&str>>();
+                    for i in temp {
+                        ni = i.parse::<f32>()?;
+                    }
+                }
                 if line.starts_with("Ns") {
                     let temp = line.replace("Ns", "").trim().to_string();
                     let temp = temp.split_whitespace().collect::<Vec<&str>>();
@@ -125,12 +214,66 @@ impl MTLParser {
                         map_kd = Some(i.to_string());
                     }
                 }
-                if line.starts_with("bump") {
-                    let temp = line.replace("bump", "").trim().to_string();
-                    let temp = temp.split_whitespace().collect::<Vec<&str>>();
-                    for i in temp {
-                        bump = Some(i.to_string());
-                    }
+                if line.starts_with("Pr ") {
+                    pr = line.replacen("Pr", "", 1).trim().parse::<f32>().ok();
+                }
+                if line.starts_with("Pm ") {
+                    pm = line.replacen("Pm", "", 1).trim().parse::<f32>().ok();
+                }
+                if line.starts_with("Ps ") {
+                    ps = line.replacen("Ps", "", 1).trim().parse::<f32>().ok();
+                }
+                if line.starts_with("Pc ") {
+                    pc = line.replacen("Pc", "", 1).trim().parse::<f32>().ok();
+                }
+                if line.starts_with("Pcr ") {
+                    pcr = line.replacen("Pcr", "", 1).trim().parse::<f32>().ok();
+                }
+                if line.starts_with("map_Pr") {
+                    map_pr = line.split_whitespace().last().map(|s| s.to_string());
+                }
+                if line.starts_with("map_Pm") {
+                    map_pm = line.split_whitespace().last().map(|s| s.to_string());
+                }
+                if line.starts_with("map_d ") {
+                    map_d = line.split_whitespace().last().map(|s| s.to_string());
+                }
+                if line.starts_with("map_Ks") {
+                    map_ks = line.split_whitespace().last().map(|s| s.to_string());
+                }
+                if line.starts_with("map_Ke") {
+                    map_ke = line.split_whitespace().last().map(|s| s.to_string());
+                }
+                if line.starts_with("map_Ns") {
+                    map_ns = line.split_whitespace().last().map(|s| s.to_string());
+                }
+                if line.starts_with("Ma ") {
+                    ma = parse_coefficients(line);
+                }
+                if line.starts_with("Ms ") {
+                    ms = parse_coefficients(line);
+                }
+                if line.starts_with("Mg ") {
+                    mg = line.replacen("Mg", "", 1).trim().parse::<f32>().ok();
+                }
+                if line.starts_with("proc ") {
+                    pattern = parse_pattern(line);
+                }
+                if line.starts_with("bump") || line.to_lowercase().starts_with("map_bump") {
+                    let (path, multiplier) = parse_bump_map(line);
+                    bump = path;
+                    bm = multiplier.or(bm);
+                }
+                if let Some(path) = map_with_option(line, "-clamp") {
+                    clamped.push(path);
+                }
+                if let Some(path) = map_with_option(line, "-mirror") {
+                    mirrored.push(path);
+                }
+                if line.starts_with("norm") {
+                    let (path, multiplier) = parse_bump_map(line);
+                    norm = path;
+                    bm = multiplier.or(bm);
                 }
             }
         }
@@ -142,23 +285,44 @@ impl MTLParser {
                 ks: ks.clone(),
                 ke: ke.clone(),
                 d,
+                ni,
                 ns,
                 illum,
                 map_kd: map_kd.clone(),
+                map_d: map_d.clone(),
+                map_ks: map_ks.clone(),
+                map_ke: map_ke.clone(),
+                map_ns: map_ns.clone(),
                 bump: bump.clone(),
+                norm: norm.clone(),
+                bm,
+                clamped: clamped.clone(),
+                mirrored: mirrored.clone(),
+                pr,
+                pm,
+                ps,
+                pc,
+                pcr,
+                map_pr: map_pr.clone(),
+                map_pm: map_pm.clone(),
+                ma,
+                ms,
+                mg,
+                pattern: pattern.clone(),
             }
         });
         Ok(return_mats)
     }
 
     pub fn to_material(&self, auto_path: AutoPath, auto_parent: Option<AutoPath>) -> Material {
-        let texture_path = self.map_kd.as_ref().map(|name| match &auto_parent {
+        let resolve = |name: &String| match &auto_parent {
             Some(p) => p
                 .get_joined(name)
                 .map(|joined| joined.to_string())
                 .unwrap_or_else(|| name.clone()),
             None => name.clone(),
-        });
+        };
+        let texture_path = self.map_kd.as_ref().map(resolve);
 
         Material::new(
             self.name.clone(),
@@ -168,10 +332,124 @@ impl MTLParser {
             self.ke.iter().map(|a| *a as f64).collect(),
             self.ns as f64,
             self.d as f64,
+            self.ni as f64,
+            self.illum,
             texture_path,
             Some(auto_path.to_string()),
         )
+        .with_pbr(PbrProperties {
+            roughness: self.pr.map(|v| v as f64),
+            metallic: self.pm.map(|v| v as f64),
+            sheen: self.ps.map(|v| v as f64),
+            clearcoat: self.pc.map(|v| v as f64),
+            clearcoat_roughness: self.pcr.map(|v| v as f64),
+            roughness_path: self.map_pr.as_ref().map(resolve),
+            metallic_path: self.map_pm.as_ref().map(resolve),
+        })
+        .with_bump(self.bump_properties(&resolve))
+        .with_maps(MapProperties {
+            opacity_path: self.map_d.as_ref().map(resolve),
+            specular_path: self.map_ks.as_ref().map(resolve),
+            emissive_path: self.map_ke.as_ref().map(resolve),
+            shininess_path: self.map_ns.as_ref().map(resolve),
+        })
+        .with_medium(MediumProperties {
+            absorption: self.ma.map(|c| c.map(|v| v as f64)),
+            scattering: self.ms.map(|c| c.map(|v| v as f64)),
+            anisotropy: self.mg.map(|v| v as f64),
+        })
+        .with_clamped_textures(self.clamped.iter().map(&resolve).collect())
+        .with_mirrored_textures(self.mirrored.iter().map(&resolve).collect())
+        .with_pattern(self.pattern.clone())
+    }
+
+    /// `norm` is a tangent space normal map, `bump` and `map_Bump` are height maps.
+    /// Exporters like Blender write normal maps as `map_Bump` too, so without a `norm` line a
+    /// `map_Bump` whose file name contains "normal" or "nrm" is used as normal map.
+    fn bump_properties(&self, resolve: &impl Fn(&String) -> String) -> BumpProperties {
+        let looks_like_normal_map = |path: &String| {
+            let name = path.to_lowercase();
+            name.contains("normal") || name.contains("nrm")
+        };
+        let (normal_path, height_path) = match (&self.norm, &self.bump) {
+            (None, Some(bump)) if looks_like_normal_map(bump) => (Some(bump), None),
+            (norm, bump) => (norm.as_ref(), bump.as_ref()),
+        };
+        BumpProperties {
+            normal_path: normal_path.map(resolve),
+            height_path: height_path.map(resolve),
+            multiplier: self.bm.map(|v| v as f64),
+        }
+    }
+}
+
+/// ## Returns
+/// The file name of a texture map line with an on/off option like `-clamp on` switched on.
+/// `-mirror on` is an extension that mirrors every other tile instead of repeating it
+fn map_with_option(line: &str, option: &str) -> Option<String> {
+    let is_map = ["map_", "bump", "norm", "disp", "decal", "refl"]
+        .iter()
+        .any(|key| line.starts_with(key));
+    let args = line.split_whitespace().collect::<Vec<&str>>();
+    let enabled = args.windows(2).any(|w| w[0] == option && w[1] == "on");
+    match (is_map && enabled, args.last()) {
+        (true, Some(path)) => Some(path.to_string()),
+        _ => None,
+    }
+}
+
+/// Parses the coefficients of an `Ma` (absorption) or `Ms` (scattering) line of the medium
+/// extension. A single value applies to all three channels.
+fn parse_coefficients(line: &str) -> Option<[f32; 3]> {
+    let values = line
+        .split_whitespace()
+        .skip(1)
+        .map(|v| v.parse::<f32>().ok())
+        .collect::<Option<Vec<f32>>>()?;
+    match values[..] {
+        [v] => Some([v; 3]),
+        [r, g, b] => Some([r, g, b]),
+        _ => None,
+    }
+}
+
+/// Parses a `proc` line of the procedural pattern extension:
+/// `proc <kind> [-s scale] [-c1 r g b] [-c2 r g b] [-o octaves] [-blend sharpness]`.
+/// ## Returns
+/// The pattern, None if the kind is unknown or an option value is invalid
+fn parse_pattern(line: &str) -> Option<Pattern> {
+    let mut args = line.split_whitespace().skip(1);
+    let mut pattern = Pattern::new(PatternKind::from_name(args.next()?)?);
+    let number = |args: &mut dyn Iterator<Item = &str>| args.next()?.parse::<f64>().ok();
+    while let Some(arg) = args.next() {
+        match arg {
+            "-s" => pattern.scale = number(&mut args).filter(|s| *s > 0.0)?,
+            "-c1" | "-c2" => {
+                let color = [number(&mut args)?, number(&mut args)?, number(&mut args)?];
+                pattern.colors[usize::from(arg == "-c2")] = color;
+            }
+            "-o" => pattern.octaves = args.next()?.parse::<u32>().ok()?,
+            "-blend" => pattern.sharpness = number(&mut args)?,
+            _ => return None,
+        }
+    }
+    Some(pattern)
+}
+
+/// Parses the file name and the `-bm` multiplier of a `bump`, `map_Bump` or `norm` line.
+/// Other options are skipped, the file name is the last argument.
+fn parse_bump_map(line: &str) -> (Option<String>, Option<f32>) {
+    let mut path = None;
+    let mut multiplier = None;
+    let mut args = line.split_whitespace().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "-bm" {
+            multiplier = args.next().and_then(|v| v.parse::<f32>().ok());
+        } else if !arg.starts_with('-') {
+            path = Some(arg.to_string());
+        }
     }
+    (path, multiplier)
 }
 
 pub fn load_mtl(auto_path: AutoPath) -> anyhow::Result<Vec<Material>> {
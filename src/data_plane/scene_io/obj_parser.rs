@@ -1,9 +1,12 @@
 use std::ffi::OsStr;
+use engine_config::{ColorSpace, WrapMode};
 use log::error;
 use scene_objects::material::Material;
 use scene_objects::mesh::Mesh;
 use crate::data_plane::scene_io::mtl_parser::load_mtl;
-use crate::data_plane::scene_io::texture_loader::TextureCache;
+use crate::data_plane::scene_io::texture_loader::{
+    TextureCache, material_color_space, material_wrap_mode,
+};
 use crate::included_files::AutoPath;
 
 #[derive(Debug)]
@@ -156,7 +159,7 @@ pub fn load_obj(
             match load_mtl(mtl_path.clone()) {
                 Ok(mats) => {
                     material_name_list.extend(mats.iter().map(|m| m.name.clone()));
-                    materials.extend(mats.into_iter());
+                    materials.extend(mats);
                 }
                 Err(_e) => {}
             }
@@ -164,10 +167,17 @@ pub fn load_obj(
     }
 
     for m in &materials {
-        #[allow(clippy::collapsible_if)]
-        if let Some(tex_path_str) = &m.texture_path {
+        for tex_path_str in m.texture_paths() {
             if let Ok(ap) = AutoPath::try_from(tex_path_str.clone()) {
-                let _ = texture_cache.load(ap);
+                let _ = texture_cache.load(ap.clone());
+                let wrap_mode = material_wrap_mode(m, tex_path_str);
+                if wrap_mode != WrapMode::Repeat {
+                    texture_cache.set_wrap_mode(ap.clone(), wrap_mode);
+                }
+                let color_space = material_color_space(m, tex_path_str);
+                if color_space != ColorSpace::Srgb {
+                    texture_cache.set_color_space(ap, color_space);
+                }
             }
         }
     }
@@ -175,6 +185,9 @@ pub fn load_obj(
     let mut new_vertices = Vec::with_capacity(objs.faces.len() * 9);
     let mut new_tris = Vec::with_capacity(objs.faces.len() * 3);
     let mut new_uvs = Vec::with_capacity(objs.faces.len() * 6);
+    let mut new_normals = Vec::with_capacity(objs.faces.len() * 9);
+    let mut position_indices = Vec::with_capacity(objs.faces.len() * 3);
+    let mut missing_normals = Vec::new();
     let mut material_index = Vec::with_capacity(objs.faces.len());
 
     let mut vertex_count: u32 = 0;
@@ -197,6 +210,19 @@ pub fn load_obj(
                 } else {
                     new_vertices.extend_from_slice(&[0.0, 0.0, 0.0]);
                 }
+                position_indices.push(v_idx as u32);
+
+                let vn_val = face.vn.get(idx).copied().unwrap_or_default() as usize;
+                match &objs.normals {
+                    Some(normals) if vn_val > 0 && (vn_val - 1) * 3 + 2 < normals.len() => {
+                        let vn_idx = vn_val - 1;
+                        new_normals.extend_from_slice(&normals[vn_idx * 3..vn_idx * 3 + 3]);
+                    }
+                    _ => {
+                        missing_normals.push(vertex_count as usize);
+                        new_normals.extend_from_slice(&[0.0, 0.0, 0.0]);
+                    }
+                }
 
                 if !face.vt.is_empty() && idx < face.vt.len() {
                     let vt_val = face.vt[idx] as usize;
@@ -226,6 +252,21 @@ pub fn load_obj(
         }
     }
 
+    // corners without a vn get angle weighted normals over the shared OBJ vertices
+    if !missing_normals.is_empty() {
+        let generated = Mesh::calculate_vertex_normals(&objs.vertices, &position_indices);
+        for corner in missing_normals {
+            new_normals[corner * 3..corner * 3 + 3]
+                .copy_from_slice(&generated[corner * 3..corner * 3 + 3]);
+        }
+    }
+
+    let tangents = if objs.texture_coordinate.is_some() {
+        Mesh::calculate_tangents(&new_vertices, &new_uvs, &new_normals, &new_tris)
+    } else {
+        Vec::new()
+    };
+
     let mesh = Mesh::new(
         new_vertices,
         new_tris,
@@ -246,7 +287,9 @@ pub fn load_obj(
         },
         Some(objs.name.clone()),
         Some(auto_path.path_buf()),
-    )?;
+    )?
+    .with_normals(new_normals)
+    .with_tangents(tangents);
 
     Ok(ObjLoadResult { mesh })
 }
@@ -2,6 +2,8 @@ use std::fs::{self, File};
 use std::io::BufRead;
 use std::path::{Path, PathBuf};
 use scene_objects::geometric_object::SceneObject;
+use scene_objects::camera::Projection;
+use scene_objects::light_source::LightType;
 use crate::data_plane::scene::render_scene::Scene;
 use crate::data_plane::scene_io::scene_io_objects::*;
 use crate::data_plane::scene_io::file_manager::FileManager;
@@ -48,7 +50,7 @@ pub fn serialize_scene(path: PathBuf, sc: &Scene, export_misc: bool) -> anyhow::
     let scene_name = sc.get_name().clone();
 
     //objects
-    for object in sc.get_meshes() {
+    for (mesh_index, object) in sc.get_meshes().iter().enumerate() {
         let written_path;
 
         if is_rscn {
@@ -148,24 +150,59 @@ pub fn serialize_scene(path: PathBuf, sc: &Scene, export_misc: bool) -> anyhow::
             scale: object.get_scale().into(),
             translation: object.get_translation().into(),
             rotation: object.get_rotation().into(),
+            instances: sc
+                .get_mesh_instances()
+                .iter()
+                .filter(|instance| instance.get_mesh_index() == mesh_index)
+                .map(|instance| FileInstance {
+                    scale: instance.get_scale().into(),
+                    translation: instance.get_translation().into(),
+                    rotation: instance.get_rotation().into(),
+                })
+                .collect(),
+            pattern: object.get_pattern().map(FilePattern::from),
         });
     }
 
     //lights
     sc.get_light_sources().iter().for_each(|light_source| {
         let colors = light_source.get_color();
-        lightarr.push(FileLightSource {
+        let mut file_light = FileLightSource {
             name: light_source.get_name().clone(),
-            r#type: "point".to_string(),
+            r#type: light_source.get_light_type().name().to_string(),
             position: light_source.get_position().into(),
             luminosity: light_source.get_luminositoy(),
             color: (&colors).into(),
             rotation: Some(light_source.get_rotation().into()),
-        })
+            angle: None,
+            inner_angle: None,
+            outer_angle: None,
+            width: None,
+            height: None,
+            radius: None,
+        };
+        match *light_source.get_light_type() {
+            LightType::Point => {}
+            LightType::Directional { angle } => file_light.angle = Some(angle),
+            LightType::Spot {
+                inner_angle,
+                outer_angle,
+            } => {
+                file_light.inner_angle = Some(inner_angle);
+                file_light.outer_angle = Some(outer_angle);
+            }
+            LightType::Rect { width, height } => {
+                file_light.width = Some(width);
+                file_light.height = Some(height);
+            }
+            LightType::Disc { radius } => file_light.radius = Some(radius),
+        }
+        lightarr.push(file_light)
     });
 
     //camera
     let camera = sc.get_camera();
+    let projection = camera.get_projection();
     let file_camera = FileCamera {
         position: camera.get_position().into(),
         look_at: camera.get_look_at().into(),
@@ -176,11 +213,40 @@ pub fn serialize_scene(path: PathBuf, sc: &Scene, export_misc: bool) -> anyhow::
             x: camera.get_resolution().width,
             y: camera.get_resolution().height,
         },
+        aperture_radius: Some(camera.get_aperture_radius()).filter(|r| *r > 0.0),
+        f_stop: None,
+        focus_distance: camera.get_focus_distance_override(),
+        aperture_blades: Some(camera.get_aperture_blades()).filter(|b| *b > 0),
+        projection: Some(projection.name().to_string())
+            .filter(|_| projection != Projection::Perspective),
+        ortho_width: match projection {
+            Projection::Orthographic { width } => Some(width),
+            _ => None,
+        },
+        interocular_distance: match projection {
+            Projection::StereoSideBySide {
+                interocular_distance,
+            }
+            | Projection::StereoTopBottom {
+                interocular_distance,
+            } => Some(interocular_distance),
+            _ => None,
+        },
     };
 
     //background
     let bg = sc.get_background_color();
 
+    //environment
+    let environment = match sc.get_environment() {
+        Some(environment) => Some(FileEnvironment {
+            path: export_environment(environment.get_path(), is_rscn, &base_dir)?,
+            rotation: sc.get_environment_rotation(),
+            intensity: sc.get_environment_intensity(),
+        }),
+        None => None,
+    };
+
     //spheres
     let spheres = sc.get_spheres();
     let mut file_spheres = Vec::new();
@@ -211,11 +277,24 @@ pub fn serialize_scene(path: PathBuf, sc: &Scene, export_misc: bool) -> anyhow::
             b: bg.b,
             a: None,
         },
+        environment,
         misc: if export_misc {
             Some(SceneFileMisc {
                 spheres: Some(file_spheres),
                 ray_samples: Some(sc.get_camera().get_ray_samples()),
                 hash_color: Some(sc.get_color_hash_enabled()),
+                exposure: Some(sc.get_exposure()),
+                tone_mapping: Some(sc.get_tone_mapping()),
+                denoise: Some(sc.get_denoise()),
+                adaptive_threshold: Some(sc.get_adaptive_threshold()),
+                adaptive_min_samples: Some(sc.get_adaptive_min_samples()),
+                medium: Some(sc.get_medium()),
+                tile_size: Some(sc.get_tile_size()),
+                tile_order: Some(sc.get_tile_order()),
+                ground_material: sc
+                    .get_ground_material()
+                    .and_then(|material| FileMaterialRef::try_from(material).ok()),
+                shadow_catcher: Some(sc.get_shadow_catcher()),
             })
         } else {
             None
@@ -252,6 +331,60 @@ pub fn serialize_scene(path: PathBuf, sc: &Scene, export_misc: bool) -> anyhow::
     Ok(())
 }
 
+/// Copies the environment image into the package (.rscn) or makes its path relative to the scene file.
+/// Returns the path to write into the scene file
+fn export_environment(src: &Path, is_rscn: bool, base_dir: &Path) -> anyhow::Result<String> {
+    if is_rscn {
+        let filename = src
+            .file_name()
+            .ok_or_else(|| anyhow::Error::msg("Invalid environment file name"))?;
+        let dest_rel = PathBuf::from("env").join(filename);
+        let dest_abs = base_dir.join(&dest_rel);
+        fs::create_dir_all(base_dir.join("env"))?;
+
+        if src.exists() {
+            debug!(
+                "SceneExporter: Copying environment map from {:?} to {:?}",
+                src, dest_abs
+            );
+            fs::copy(src, &dest_abs)?;
+        } else {
+            match crate::included_files::AutoPath::try_from(src.to_path_buf()) {
+                Ok(ap) => {
+                    debug!(
+                        "SceneExporter: Writing included environment map {:?} to {:?}",
+                        ap, dest_abs
+                    );
+                    let mut reader = ap.reader()?;
+                    let mut out = File::create(&dest_abs)?;
+                    std::io::copy(&mut reader, &mut out)?;
+                }
+                Err(_) => {
+                    warn!(
+                        "SceneExporter: Environment map not found at {:?}, skipping copy.",
+                        src
+                    );
+                }
+            }
+        }
+
+        let p_str = dest_rel.to_string_lossy().to_string();
+        #[cfg(windows)]
+        let p_str = p_str.replace('\\', "/");
+        Ok(p_str)
+    } else if let Ok(relative_path) = src.strip_prefix(base_dir) {
+        Ok(relative_path.to_string_lossy().to_string())
+    } else if let Some(parent) = base_dir.parent()
+        && let Ok(relative_path) = src.strip_prefix(parent)
+    {
+        let mut path = PathBuf::from("../");
+        path.push(relative_path);
+        Ok(path.to_string_lossy().to_string())
+    } else {
+        Ok(src.to_string_lossy().to_string())
+    }
+}
+
 fn copy_obj_dependencies(src_obj: &Path, dest_obj: &Path) -> anyhow::Result<()> {
     let file = File::open(src_obj)?;
     let reader = std::io::BufReader::new(file);
@@ -1,11 +1,17 @@
 use glam::Vec3;
-use scene_objects::{camera, camera::Camera, light_source::LightSource, sphere::Sphere, material::*};
+use scene_objects::{
+    camera,
+    camera::{Camera, Projection},
+    light_source::{LightSource, LightType},
+    sphere::Sphere,
+    material::*,
+};
 use crate::data_plane::scene::{render_scene::Scene};
 use crate::data_plane::scene_io::scene_io_objects::*;
 use crate::included_files::AutoPath;
 
 use crate::data_plane::scene_io::file_manager::FileManager;
-use log::{info, debug, error};
+use log::{info, debug, error, warn};
 use crate::data_plane::scene_io::mtl_parser::load_mtl_with_name;
 
 pub struct LoadedSceneData {
@@ -14,6 +20,92 @@ pub struct LoadedSceneData {
     pub rotations: Vec<Vec3>,
     pub translations: Vec<Vec3>,
     pub scales: Vec<Vec3>,
+    pub instances: Vec<Vec<FileInstance>>,
+    pub patterns: Vec<Option<Pattern>>,
+    pub environment: Option<FileEnvironment>,
+}
+
+/// Reads the type of a light and its parameters, missing parameters keep their defaults
+fn file_light_type(light: &FileLightSource) -> LightType {
+    let Some(light_type) = LightType::from_name(&light.r#type) else {
+        warn!(
+            "SceneImporter: Light {} has unsupported type {}, loading it as point light",
+            light.name, light.r#type
+        );
+        return LightType::Point;
+    };
+    match light_type {
+        LightType::Point => LightType::Point,
+        LightType::Directional { angle } => LightType::Directional {
+            angle: light.angle.unwrap_or(angle),
+        },
+        LightType::Spot {
+            inner_angle,
+            outer_angle,
+        } => LightType::Spot {
+            inner_angle: light.inner_angle.unwrap_or(inner_angle),
+            outer_angle: light.outer_angle.unwrap_or(outer_angle),
+        },
+        LightType::Rect { width, height } => LightType::Rect {
+            width: light.width.unwrap_or(width),
+            height: light.height.unwrap_or(height),
+        },
+        LightType::Disc { radius } => LightType::Disc {
+            radius: light.radius.unwrap_or(radius),
+        },
+    }
+}
+
+/// Reads the projection of the camera and its parameters, missing parameters keep their defaults
+fn file_projection(camera: &FileCamera) -> Projection {
+    let Some(name) = &camera.projection else {
+        return Projection::Perspective;
+    };
+    let Some(projection) = Projection::from_name(name) else {
+        warn!("SceneImporter: Camera has unsupported projection {name}, using perspective");
+        return Projection::Perspective;
+    };
+    match projection {
+        Projection::Orthographic { width } => Projection::Orthographic {
+            width: camera.ortho_width.unwrap_or(width),
+        },
+        Projection::StereoSideBySide {
+            interocular_distance,
+        } => Projection::StereoSideBySide {
+            interocular_distance: camera.interocular_distance.unwrap_or(interocular_distance),
+        },
+        Projection::StereoTopBottom {
+            interocular_distance,
+        } => Projection::StereoTopBottom {
+            interocular_distance: camera.interocular_distance.unwrap_or(interocular_distance),
+        },
+        projection => projection,
+    }
+}
+
+/// Resolves a preset or loads a material from an mtl file, unknown presets give the default material
+fn file_material(material: &FileMaterialRef) -> anyhow::Result<Material> {
+    let (material, pattern) = match material {
+        FileMaterialRef::Preset { preset, pattern } => (
+            match MaterialPresets::try_from(preset.as_str()) {
+                Ok(preset) => preset.into(),
+                Err(_) => Material::default(),
+            },
+            pattern,
+        ),
+        FileMaterialRef::Path {
+            path,
+            name,
+            pattern,
+        } => (
+            load_mtl_with_name(AutoPath::try_from(path.to_string())?, name.clone())?,
+            pattern,
+        ),
+    };
+    Ok(match pattern {
+        Some(pattern) => material.with_pattern(Some(Pattern::try_from(pattern)?)),
+        None => material,
+    })
 }
 
 #[allow(dead_code)]
@@ -43,6 +135,7 @@ fn transform_to_scene(file: SceneFile) -> anyhow::Result<LoadedSceneData> {
                     Vec3::new(0.0, 0.0, 0.0)
                 }
             },
+            file_light_type(&light),
         ))
     }
 
@@ -71,6 +164,20 @@ fn transform_to_scene(file: SceneFile) -> anyhow::Result<LoadedSceneData> {
     scene
         .get_camera_mut()
         .set_pane_distance(file.camera.pane_distance);
+    // the f-stop depends on the pane distance, so the lens is set up afterwards
+    let camera = scene.get_camera_mut();
+    match (file.camera.aperture_radius, file.camera.f_stop) {
+        (Some(radius), _) => camera.set_aperture_radius(radius),
+        (None, Some(f_stop)) => camera.set_f_stop(f_stop),
+        (None, None) => {}
+    }
+    if let Some(focus_distance) = file.camera.focus_distance {
+        camera.set_focus_distance(focus_distance);
+    }
+    if let Some(blades) = file.camera.aperture_blades {
+        camera.set_aperture_blades(blades);
+    }
+    camera.set_projection(file_projection(&file.camera));
     //Background
     scene.set_background_color(file.background_color.into());
     let paths = file
@@ -93,30 +200,32 @@ fn transform_to_scene(file: SceneFile) -> anyhow::Result<LoadedSceneData> {
         .iter()
         .map(|o| Vec3::new(o.scale.x, o.scale.y, o.scale.z))
         .collect();
+    let instances = file.objects.iter().map(|o| o.instances.clone()).collect();
+    let patterns = file
+        .objects
+        .iter()
+        .map(|o| o.pattern.as_ref().map(Pattern::try_from).transpose())
+        .collect::<anyhow::Result<Vec<_>>>()?;
 
     if let Some(misc) = &file.misc {
         if let Some(spheres) = &misc.spheres {
             for sphere in spheres {
                 let material = match &sphere.material {
-                    Some(material) => match material {
-                        FileMaterialRef::Preset { preset } => {
-                            match MaterialPresets::try_from(preset.as_str()) {
-                                Ok(preset) => preset.into(),
-                                Err(_) => Material::default(),
-                            }
-                        }
-                        FileMaterialRef::Path { path, name } => {
-                            load_mtl_with_name(AutoPath::try_from(path.to_string())?, name.clone())?
-                        }
-                    },
+                    Some(material) => file_material(material)?,
                     None => Material::default(),
                 };
-                scene.add_sphere(Sphere::new(
+                let mut new_sphere = Sphere::new(
                     Vec3::new(sphere.center.x, sphere.center.y, sphere.center.z),
                     sphere.radius,
                     material,
                     sphere.color.into(),
+                );
+                new_sphere.rotate_to(Vec3::new(
+                    sphere.rotation.x,
+                    sphere.rotation.y,
+                    sphere.rotation.z,
                 ));
+                scene.add_sphere(new_sphere);
             }
         }
 
@@ -127,6 +236,46 @@ fn transform_to_scene(file: SceneFile) -> anyhow::Result<LoadedSceneData> {
         if let Some(hash_color) = &misc.hash_color {
             scene.set_color_hash_enabled(*hash_color);
         }
+
+        if let Some(exposure) = misc.exposure {
+            scene.set_exposure(exposure);
+        }
+
+        if let Some(tone_mapping) = misc.tone_mapping {
+            scene.set_tone_mapping(tone_mapping);
+        }
+
+        if let Some(denoise) = misc.denoise {
+            scene.set_denoise(denoise);
+        }
+
+        if let Some(threshold) = misc.adaptive_threshold {
+            scene.set_adaptive_threshold(threshold);
+        }
+
+        if let Some(min_samples) = misc.adaptive_min_samples {
+            scene.set_adaptive_min_samples(min_samples);
+        }
+
+        if let Some(medium) = misc.medium {
+            scene.set_medium(medium);
+        }
+
+        if let Some(tile_size) = misc.tile_size {
+            scene.set_tile_size(tile_size);
+        }
+
+        if let Some(tile_order) = misc.tile_order {
+            scene.set_tile_order(tile_order);
+        }
+
+        if let Some(material) = &misc.ground_material {
+            scene.set_ground_material(Some(file_material(material)?));
+        }
+
+        if let Some(shadow_catcher) = misc.shadow_catcher {
+            scene.set_shadow_catcher(shadow_catcher);
+        }
     }
 
     Ok(LoadedSceneData {
@@ -135,6 +284,9 @@ fn transform_to_scene(file: SceneFile) -> anyhow::Result<LoadedSceneData> {
         rotations: rotation,
         translations: translation,
         scales: scale,
+        instances,
+        patterns,
+        environment: file.environment,
     })
 }
 
@@ -218,6 +370,22 @@ pub fn parse_scene(
             .collect();
         loaded_data.paths = abs_paths;
 
+        if let Some(environment) = &mut loaded_data.environment {
+            match AutoPath::get_absolute_or_join(&environment.path, &base_path) {
+                Ok(abs) => {
+                    debug!(
+                        "SceneImporter: Resolved environment path: {} -> {}",
+                        environment.path, abs
+                    );
+                    environment.path = abs.to_string();
+                }
+                Err(_) => error!(
+                    "SceneImporter: Failed to resolve environment path: {}",
+                    environment.path
+                ),
+            }
+        }
+
         info!("SceneImporter: Scene parsing successful.");
         Ok(loaded_data)
     } else {
@@ -1,6 +1,8 @@
+use engine_config::{Medium, TileOrder, ToneMapping};
 use glam::Vec3;
 use serde::{Deserialize, Serialize};
-use scene_objects::material::{Material, MaterialPresets};
+use anyhow::anyhow;
+use scene_objects::material::{Material, MaterialPresets, Pattern, PatternKind};
 use crate::data_plane::scene_proxy::color::Color;
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -10,10 +12,25 @@ pub struct SceneFile {
     pub lights: Vec<FileLightSource>,
     pub camera: FileCamera,
     pub background_color: FileColor,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub environment: Option<FileEnvironment>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub misc: Option<SceneFileMisc>,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FileEnvironment {
+    pub path: String,
+    #[serde(default)]
+    pub rotation: f32, // degrees around the y axis
+    #[serde(default = "default_intensity")]
+    pub intensity: f32,
+}
+
+fn default_intensity() -> f32 {
+    1.0
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct SceneFileMisc {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -22,6 +39,26 @@ pub struct SceneFileMisc {
     pub ray_samples: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub hash_color: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exposure: Option<f32>, // stops
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tone_mapping: Option<ToneMapping>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub denoise: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub adaptive_threshold: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub adaptive_min_samples: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub medium: Option<Medium>, // coefficients per unit length
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tile_size: Option<u32>, // pixels, 0 = as large as the GPU allows
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tile_order: Option<TileOrder>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ground_material: Option<FileMaterialRef>, // default grey if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shadow_catcher: Option<bool>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -31,6 +68,17 @@ pub struct ParsingObject {
     pub scale: Vec3d,
     pub translation: Vec3d,
     pub rotation: Vec3d, //x = roll, y = pitch, z = yaw
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub instances: Vec<FileInstance>, // further placements sharing the loaded geometry
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<FilePattern>, // replaces the patterns of all materials of the object
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FileInstance {
+    pub scale: Vec3d,
+    pub translation: Vec3d,
+    pub rotation: Vec3d, //x = roll, y = pitch, z = yaw
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -41,6 +89,18 @@ pub struct FileLightSource {
     pub luminosity: f32,
     pub color: FileColor,
     pub rotation: Option<Vec3d>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub angle: Option<f32>, // directional: angular diameter in degrees
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inner_angle: Option<f32>, // spot
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outer_angle: Option<f32>, // spot
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<f32>, // rect
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<f32>, // rect
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub radius: Option<f32>, // disc
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -51,6 +111,20 @@ pub struct FileCamera {
     pub pane_distance: f32,
     pub pane_width: f32,
     pub resolution: Resolution,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aperture_radius: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub f_stop: Option<f32>, // only read if aperture_radius is not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub focus_distance: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aperture_blades: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub projection: Option<String>, // perspective if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ortho_width: Option<f32>, // orthographic
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interocular_distance: Option<f32>, // stereo
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy)]
@@ -116,22 +190,35 @@ pub struct FileSphere {
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(untagged)]
 pub enum FileMaterialRef {
-    Preset { preset: String },
-    Path { path: String, name: String },
+    Preset {
+        preset: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pattern: Option<FilePattern>, // replaces the pattern of the preset
+    },
+    Path {
+        path: String,
+        name: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pattern: Option<FilePattern>, // replaces the pattern of the MTL material
+    },
 }
 
 impl TryFrom<&Material> for FileMaterialRef {
     type Error = anyhow::Error;
     fn try_from(mat: &Material) -> anyhow::Result<Self> {
+        let pattern = mat.pattern.as_ref().map(FilePattern::from);
         if let Some(ref_path) = &mat.ref_path {
             Ok(FileMaterialRef::Path {
                 path: ref_path.clone(),
                 name: mat.name.clone(),
+                pattern,
             })
         } else {
-            match MaterialPresets::try_from(mat) {
+            // Presets have no pattern, it is stored next to the preset name
+            match MaterialPresets::try_from(&mat.clone().with_pattern(None)) {
                 Ok(preset) => Ok(FileMaterialRef::Preset {
                     preset: preset.into(),
+                    pattern,
                 }),
                 Err(e) => Err(e),
             }
@@ -139,6 +226,52 @@ impl TryFrom<&Material> for FileMaterialRef {
     }
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FilePattern {
+    pub kind: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scale: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub colors: Option<[FileColor; 2]>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub octaves: Option<u32>, // noise
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sharpness: Option<f64>, // triplanar
+}
+
+impl From<&Pattern> for FilePattern {
+    fn from(pattern: &Pattern) -> Self {
+        FilePattern {
+            kind: pattern.kind.name().to_owned(),
+            scale: Some(pattern.scale),
+            colors: Some(pattern.colors.map(|c| (&c.map(|v| v as f32)).into())),
+            octaves: Some(pattern.octaves),
+            sharpness: Some(pattern.sharpness),
+        }
+    }
+}
+
+impl TryFrom<&FilePattern> for Pattern {
+    type Error = anyhow::Error;
+    fn try_from(file: &FilePattern) -> anyhow::Result<Self> {
+        let kind = PatternKind::from_name(&file.kind)
+            .ok_or_else(|| anyhow!("Invalid pattern kind: {}", file.kind))?;
+        let mut pattern = Pattern::new(kind);
+        if let Some(scale) = file.scale {
+            if scale <= 0.0 {
+                return Err(anyhow!("Pattern scale must be positive, got {scale}"));
+            }
+            pattern.scale = scale;
+        }
+        if let Some(colors) = file.colors {
+            pattern.colors = colors.map(|c| <[f32; 3]>::from(c).map(|v| v as f64));
+        }
+        pattern.octaves = file.octaves.unwrap_or(pattern.octaves);
+        pattern.sharpness = file.sharpness.unwrap_or(pattern.sharpness);
+        Ok(pattern)
+    }
+}
+
 impl From<Vec3> for Vec3d {
     fn from(v: Vec3) -> Vec3d {
         Vec3d {
@@ -3,9 +3,16 @@ use crate::data_plane::{
     scene_io::file_manager::FileManager,
     scene_io::{scene_exporter, scene_importer},
 };
+use crate::data_plane::scene_io::{obj_parser::load_obj, texture_loader::TextureCache};
+use engine_config::{Aov, AovSet, ColorSpace, Medium, TileOrder, ToneMapping, WrapMode};
+use engine_wgpu_wrapper::GpuBuffers;
+use frame_buffer::frame_iterator::{Frame, FrameLayer};
 use glam::Vec3;
 use scene_objects::{
-    camera::Camera, light_source::LightSource, material::Material, sphere::Sphere,
+    camera::{Camera, Projection},
+    light_source::{LightSource, LightType},
+    material::{MapProperties, Material, MaterialPresets, Pattern, PatternKind},
+    sphere::Sphere,
     geometric_object::SceneObject,
 };
 use std::fs;
@@ -30,7 +37,7 @@ fn create_test_scene(name: &str) -> Scene {
         [1.0, 1.0, 1.0],
         "TestLight".to_string(),
         Vec3::ZERO,
-        //LightType::Point,
+        LightType::Point,
     ));
 
     // Setup camera
@@ -57,6 +64,17 @@ fn setup_temp_dir() -> PathBuf {
     dir
 }
 
+/// Exports the scene with the misc data and imports it again
+fn misc_round_trip(scene: &Scene) -> Scene {
+    let temp_dir = setup_temp_dir();
+    let file_path = temp_dir.join("misc.json");
+    scene_exporter::serialize_scene(file_path.clone(), scene, true).expect("Export failed");
+    let loaded_data = scene_importer::parse_scene(AutoPath::try_from(file_path).unwrap(), None)
+        .expect("Import failed");
+    let _ = fs::remove_dir_all(temp_dir);
+    loaded_data.scene
+}
+
 #[test]
 fn test_json_export_import_integrity() {
     let temp_dir = setup_temp_dir();
@@ -192,7 +210,7 @@ fn test_idempotency() {
         [1.0, 1.0, 1.0],
         "L".into(),
         Vec3::ZERO,
-        //LightType::Ambient,
+        LightType::Point,
     ));
 
     scene.export_scene(export_path.clone(), false).unwrap();
@@ -344,6 +362,107 @@ fn test_export_import_misc_data() {
     assert!(!loaded_scene.get_color_hash_enabled());
 }
 
+#[test]
+fn test_display_transform_round_trip() {
+    let mut scene = Scene::new();
+    scene.set_exposure(-1.5);
+    scene.set_tone_mapping(ToneMapping::AgX);
+
+    let loaded_scene = misc_round_trip(&scene);
+    assert_eq!(loaded_scene.get_exposure(), -1.5);
+    assert_eq!(loaded_scene.get_tone_mapping(), ToneMapping::AgX);
+}
+
+#[test]
+fn test_denoise_round_trip() {
+    let mut scene = Scene::new();
+    assert!(!scene.get_denoise());
+    scene.set_denoise(true);
+
+    assert!(misc_round_trip(&scene).get_denoise());
+}
+
+#[test]
+fn test_adaptive_sampling_round_trip() {
+    let mut scene = Scene::new();
+    scene.set_adaptive_threshold(0.02);
+    scene.set_adaptive_min_samples(32);
+
+    let loaded_scene = misc_round_trip(&scene);
+    assert_eq!(loaded_scene.get_adaptive_threshold(), 0.02);
+    assert_eq!(loaded_scene.get_adaptive_min_samples(), 32);
+}
+
+#[test]
+fn test_medium_round_trip() {
+    let mut scene = Scene::new();
+    let medium = Medium::new([0.01, 0.02, 0.03], [0.1, 0.1, 0.1], 0.4);
+    scene.set_medium(medium);
+
+    assert_eq!(misc_round_trip(&scene).get_medium(), medium);
+}
+
+#[test]
+fn test_tiles_round_trip() {
+    let mut scene = Scene::new();
+    scene.set_tile_size(256);
+    scene.set_tile_order(TileOrder::Spiral);
+
+    let loaded_scene = misc_round_trip(&scene);
+    assert_eq!(loaded_scene.get_tile_size(), 256);
+    assert_eq!(loaded_scene.get_tile_order(), TileOrder::Spiral);
+}
+
+#[test]
+fn test_ground_material_round_trip() {
+    let mut scene = Scene::new();
+    scene.set_ground_material(Some(MaterialPresets::Mirror.into()));
+    scene.set_shadow_catcher(true);
+
+    let loaded_scene = misc_round_trip(&scene);
+    assert!(loaded_scene.get_ground_material().unwrap() == Material::from(MaterialPresets::Mirror));
+    assert!(loaded_scene.get_shadow_catcher());
+}
+
+#[test]
+fn test_sphere_rotation_round_trip() {
+    let mut scene = Scene::new();
+    // The rotation turns the texture mapping of the sphere
+    let mut sphere = Sphere::new(Vec3::ZERO, 1.0, Material::default(), [1.0, 1.0, 1.0]);
+    sphere.rotate_to(Vec3::new(10.0, 20.0, 30.0));
+    scene.add_sphere(sphere);
+
+    let loaded_scene = misc_round_trip(&scene);
+    assert_eq!(
+        loaded_scene.get_spheres()[0].get_rotation(),
+        Vec3::new(10.0, 20.0, 30.0)
+    );
+}
+
+#[test]
+fn test_preset_pattern_round_trip() {
+    let mut scene = Scene::new();
+    // A preset with a procedural pattern
+    let mut pattern = Pattern::new(PatternKind::Voronoi);
+    pattern.scale = 3.0;
+    pattern.colors = [[1.0, 0.5, 0.0], [0.0, 0.25, 1.0]];
+    let patterned: Material = MaterialPresets::Plastic.into();
+    scene.add_sphere(Sphere::new(
+        Vec3::new(-1.0, 0.0, 0.0),
+        0.5,
+        patterned.with_pattern(Some(pattern.clone())),
+        [1.0, 1.0, 1.0],
+    ));
+
+    let loaded_scene = misc_round_trip(&scene);
+    let loaded_material = loaded_scene.get_spheres()[0].get_material();
+    assert_eq!(loaded_material.pattern, Some(pattern));
+    assert_eq!(
+        MaterialPresets::try_from(&loaded_material.clone().with_pattern(None)).unwrap(),
+        MaterialPresets::Plastic
+    );
+}
+
 #[test]
 fn test_export_misc_data_disabled() {
     let temp_dir = setup_temp_dir();
@@ -380,3 +499,573 @@ fn test_export_misc_data_disabled() {
     // Verify hash color (should be default, which is true)
     assert!(loaded_scene.get_color_hash_enabled());
 }
+
+#[test]
+fn test_mtl_parses_dielectric_and_pbr_properties() {
+    let temp_dir = setup_temp_dir();
+    let mtl_path = temp_dir.join("glass.mtl");
+    fs::write(
+        &mtl_path,
+        "newmtl Glass\nKd 0.9 0.95 1.0\nNi 1.52\nd 0.25\nillum 7\nMa 0.2 0.1 0.05\nMs 0.5\nMg 0.3\n\nnewmtl Opaque\nKd 0.5 0.5 0.5\nillum 2\nPr 0.3\nPm 1.0\nPc 0.5\nPcr 0.1\nmap_Pr rough.png\n",
+    )
+    .unwrap();
+
+    let materials =
+        crate::data_plane::scene_io::mtl_parser::load_mtl(AutoPath::try_from(mtl_path).unwrap())
+            .expect("Failed to parse MTL");
+
+    assert_eq!(materials.len(), 2);
+    assert!((materials[0].ior - 1.52).abs() < 1e-6);
+    assert!((materials[0].transparency - 0.25).abs() < 1e-6);
+    assert_eq!(materials[0].illum, 7);
+
+    // Missing Ni/d fall back to air and fully opaque
+    assert_eq!(materials[1].ior, 1.0);
+    assert_eq!(materials[1].transparency, 1.0);
+    assert_eq!(materials[1].illum, 2);
+
+    // Interior medium, a single value is gray
+    assert_eq!(
+        materials[0].medium.absorption,
+        Some([0.2f32 as f64, 0.1f32 as f64, 0.05f32 as f64])
+    );
+    assert_eq!(materials[0].medium.scattering, Some([0.5; 3]));
+    assert_eq!(materials[0].medium.anisotropy, Some(0.3f32 as f64));
+    assert!(!materials[1].medium.is_present());
+
+    // PBR extension keys, Pc must not swallow Pcr
+    assert_eq!(materials[0].pbr.roughness, None);
+    assert_eq!(materials[1].pbr.roughness, Some(0.3f32 as f64));
+    assert_eq!(materials[1].pbr.metallic, Some(1.0));
+    assert_eq!(materials[1].pbr.clearcoat, Some(0.5));
+    assert_eq!(materials[1].pbr.clearcoat_roughness, Some(0.1f32 as f64));
+    assert!(
+        materials[1]
+            .pbr
+            .roughness_path
+            .as_ref()
+            .is_some_and(|p| p.ends_with("rough.png"))
+    );
+
+    let _ = fs::remove_dir_all(temp_dir);
+}
+
+#[test]
+fn test_mtl_parses_bump_and_normal_maps() {
+    let temp_dir = setup_temp_dir();
+    let mtl_path = temp_dir.join("bumpy.mtl");
+    fs::write(
+        &mtl_path,
+        "newmtl Height\nbump -bm 0.5 height.png\n\nnewmtl Both\nnorm detail.png\nmap_Bump -bm 2 -clamp on height.png\n\nnewmtl Blender\nmap_Bump -bm 1.000000 Wall_Normal.png\n",
+    )
+    .unwrap();
+
+    let materials =
+        crate::data_plane::scene_io::mtl_parser::load_mtl(AutoPath::try_from(mtl_path).unwrap())
+            .expect("Failed to parse MTL");
+
+    assert_eq!(materials.len(), 3);
+    let ends_with = |p: &Option<String>, name: &str| p.as_ref().is_some_and(|p| p.ends_with(name));
+
+    assert!(ends_with(&materials[0].bump.height_path, "height.png"));
+    assert_eq!(materials[0].bump.normal_path, None);
+    assert_eq!(materials[0].bump.multiplier, Some(0.5));
+
+    // Options between -bm and the file name are skipped
+    assert!(ends_with(&materials[1].bump.normal_path, "detail.png"));
+    assert!(ends_with(&materials[1].bump.height_path, "height.png"));
+    assert_eq!(materials[1].bump.multiplier, Some(2.0));
+    assert_eq!(materials[1].clamped_textures.len(), 1);
+    assert!(materials[1].clamped_textures[0].ends_with("height.png"));
+    assert!(materials[0].clamped_textures.is_empty());
+
+    // A map_Bump that names a normal map is used as one
+    assert!(ends_with(&materials[2].bump.normal_path, "Wall_Normal.png"));
+    assert_eq!(materials[2].bump.height_path, None);
+
+    let _ = fs::remove_dir_all(temp_dir);
+}
+
+#[test]
+fn test_mtl_parses_texture_maps() {
+    let temp_dir = setup_temp_dir();
+    let mtl_path = temp_dir.join("maps.mtl");
+    fs::write(
+        &mtl_path,
+        "newmtl Leaf\nKd 1 1 1\nKs 0.5 0.5 0.5\nmap_Kd leaf.png\nmap_d -clamp on leaf.png\nmap_Ks gloss.png\nmap_Ke glow.png\nmap_Ns shininess.png\n\nnewmtl Brushed\nKd 0 0 0\nKs 0.9 0.9 0.9\nmap_Ks brushed.png\n\nnewmtl Plain\nKd 0.5 0.5 0.5\n",
+    )
+    .unwrap();
+
+    let materials =
+        crate::data_plane::scene_io::mtl_parser::load_mtl(AutoPath::try_from(mtl_path).unwrap())
+            .expect("Failed to parse MTL");
+
+    assert_eq!(materials.len(), 3);
+    let ends_with = |p: &Option<String>, name: &str| p.as_ref().is_some_and(|p| p.ends_with(name));
+
+    let leaf = &materials[0];
+    assert!(ends_with(&leaf.maps.opacity_path, "leaf.png"));
+    assert!(ends_with(&leaf.maps.specular_path, "gloss.png"));
+    assert!(ends_with(&leaf.maps.emissive_path, "glow.png"));
+    assert!(ends_with(&leaf.maps.shininess_path, "shininess.png"));
+    assert!(leaf.clamped_textures[0].ends_with("leaf.png"));
+    assert_eq!(leaf.texture_paths().len(), 5);
+    assert!(ends_with(leaf.base_color_path(), "leaf.png"));
+    assert!(ends_with(leaf.specular_level_path(), "gloss.png"));
+
+    // Derived metals take their base color from Ks and so from map_Ks
+    let brushed = &materials[1];
+    assert!(ends_with(brushed.base_color_path(), "brushed.png"));
+    assert_eq!(brushed.specular_level_path(), &None);
+
+    assert_eq!(materials[2].maps, MapProperties::default());
+
+    let _ = fs::remove_dir_all(temp_dir);
+}
+
+#[test]
+fn test_mtl_wrap_modes_reach_texture_info() {
+    let temp_dir = setup_temp_dir();
+    image::RgbaImage::from_pixel(2, 2, image::Rgba([255, 0, 0, 255]))
+        .save(temp_dir.join("tile.png"))
+        .expect("Failed to write texture");
+    image::RgbaImage::from_pixel(2, 2, image::Rgba([0, 0, 255, 255]))
+        .save(temp_dir.join("edge.png"))
+        .expect("Failed to write texture");
+    fs::write(
+        temp_dir.join("wrap.mtl"),
+        "newmtl Tiles\nmap_Kd -mirror on tile.png\nmap_Ks -clamp on edge.png\n",
+    )
+    .unwrap();
+    let obj_path = temp_dir.join("wrap.obj");
+    fs::write(
+        &obj_path,
+        "mtllib wrap.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl Tiles\nf 1 2 3\n",
+    )
+    .unwrap();
+
+    let materials = crate::data_plane::scene_io::mtl_parser::load_mtl(
+        AutoPath::try_from(temp_dir.join("wrap.mtl")).unwrap(),
+    )
+    .expect("Failed to parse MTL");
+    assert!(materials[0].mirrored_textures[0].ends_with("tile.png"));
+    assert!(materials[0].clamped_textures[0].ends_with("edge.png"));
+
+    let mut texture_cache = TextureCache::new();
+    load_obj(AutoPath::try_from(obj_path).unwrap(), &mut texture_cache)
+        .expect("Failed to load OBJ");
+    let (textures, indices) = texture_cache.get_split_clone();
+    let (_, info) = GpuBuffers::process_textures(&textures);
+    let wrap_mode = |name: &str| {
+        let (_, index) = indices
+            .iter()
+            .find(|(path, _)| path.ends_with(name))
+            .unwrap();
+        info[*index as usize].wrap_mode
+    };
+    assert_eq!(wrap_mode("tile.png"), WrapMode::Mirror as u32);
+    assert_eq!(wrap_mode("edge.png"), WrapMode::Clamp as u32);
+
+    let _ = fs::remove_dir_all(temp_dir);
+}
+
+#[test]
+fn test_mtl_data_maps_are_linear() {
+    let temp_dir = setup_temp_dir();
+    for name in ["color.png", "rough.png"] {
+        image::RgbaImage::from_pixel(2, 2, image::Rgba([0, 255, 0, 255]))
+            .save(temp_dir.join(name))
+            .expect("Failed to write texture");
+    }
+    fs::write(
+        temp_dir.join("spaces.mtl"),
+        "newmtl Painted\nmap_Kd color.png\nmap_Pr rough.png\n",
+    )
+    .unwrap();
+    let obj_path = temp_dir.join("spaces.obj");
+    fs::write(
+        &obj_path,
+        "mtllib spaces.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl Painted\nf 1 2 3\n",
+    )
+    .unwrap();
+
+    let mut texture_cache = TextureCache::new();
+    load_obj(AutoPath::try_from(obj_path).unwrap(), &mut texture_cache)
+        .expect("Failed to load OBJ");
+    let (textures, indices) = texture_cache.get_split_clone();
+    let color_space = |name: &str| {
+        let (_, index) = indices
+            .iter()
+            .find(|(path, _)| path.ends_with(name))
+            .unwrap();
+        textures[*index as usize].color_space
+    };
+    assert_eq!(color_space("color.png"), ColorSpace::Srgb);
+    assert_eq!(color_space("rough.png"), ColorSpace::Linear);
+
+    let _ = fs::remove_dir_all(temp_dir);
+}
+
+#[test]
+fn test_mtl_parses_procedural_patterns() {
+    let temp_dir = setup_temp_dir();
+    let mtl_path = temp_dir.join("patterns.mtl");
+    fs::write(
+        &mtl_path,
+        "newmtl Noise\nKd 1 1 1\nproc noise -s 2.5 -c1 1 0 0 -c2 0 0 1 -o 6\n\nnewmtl Triplanar\nmap_Kd wood.png\nproc TRIPLANAR -blend 8\n\nnewmtl Broken\nproc marble -s 2\n\nnewmtl Plain\nKd 0.5 0.5 0.5\n",
+    )
+    .unwrap();
+
+    let materials =
+        crate::data_plane::scene_io::mtl_parser::load_mtl(AutoPath::try_from(mtl_path).unwrap())
+            .expect("Failed to parse MTL");
+
+    assert_eq!(materials.len(), 4);
+    let noise = materials[0]
+        .pattern
+        .as_ref()
+        .expect("Noise pattern missing");
+    assert_eq!(noise.kind, PatternKind::Noise);
+    assert_eq!(noise.scale, 2.5);
+    assert_eq!(noise.colors, [[1.0, 0.0, 0.0], [0.0, 0.0, 1.0]]);
+    assert_eq!(noise.octaves, 6);
+
+    // Kinds ignore case, unset options keep their defaults
+    let triplanar = materials[1]
+        .pattern
+        .as_ref()
+        .expect("Triplanar pattern missing");
+    assert_eq!(triplanar.kind, PatternKind::Triplanar);
+    assert_eq!(triplanar.sharpness, 8.0);
+    assert_eq!(triplanar.scale, 1.0);
+
+    // Unknown kinds are ignored and the pattern does not leak into the next material
+    assert_eq!(materials[2].pattern, None);
+    assert_eq!(materials[3].pattern, None);
+
+    let _ = fs::remove_dir_all(temp_dir);
+}
+
+#[test]
+fn test_rscn_export_import_with_environment() {
+    let temp_dir = setup_temp_dir();
+    let hdr_path = temp_dir.join("sky.hdr");
+    let export_path = temp_dir.join("environment.rscn");
+
+    // 4x2 HDR image with a bright "sun" pixel
+    let mut img = image::Rgb32FImage::from_pixel(4, 2, image::Rgb([0.5, 0.6, 0.7]));
+    img.put_pixel(1, 0, image::Rgb([50.0, 45.0, 40.0]));
+    image::DynamicImage::ImageRgb32F(img)
+        .save(&hdr_path)
+        .expect("Failed to write HDR image");
+
+    let mut scene = create_test_scene("EnvironmentTest");
+    scene
+        .load_environment(AutoPath::try_from(hdr_path).unwrap(), 45.0, 2.0)
+        .expect("Failed to load environment map");
+
+    let map = scene.get_environment().unwrap().get_map();
+    assert_eq!((map.width, map.height), (4, 2));
+    assert!(map.rgb_data[3] > 10.0);
+
+    scene
+        .export_scene(export_path.clone(), false)
+        .expect("Failed to export RSCN");
+
+    let imported_scene =
+        Scene::load_scene_from_path(AutoPath::try_from(export_path).unwrap(), false)
+            .expect("Failed to import RSCN");
+
+    let environment = imported_scene
+        .get_environment()
+        .expect("Environment map was not exported");
+    assert!(environment.get_path().ends_with("env/sky.hdr"));
+    assert_eq!(environment.get_map().width, 4);
+    assert_eq!(imported_scene.get_environment_rotation(), 45.0);
+    assert_eq!(imported_scene.get_environment_intensity(), 2.0);
+
+    let _ = fs::remove_dir_all(temp_dir);
+}
+
+#[test]
+fn test_camera_lens_round_trip() {
+    let temp_dir = setup_temp_dir();
+    let file_path = temp_dir.join("lens.json");
+
+    let mut scene = create_test_scene("LensTest");
+    let camera = scene.get_camera_mut();
+    camera.set_aperture_radius(0.05);
+    camera.focus_on(Vec3::new(0.0, 0.0, 0.0));
+    camera.set_aperture_blades(6);
+    let focus_distance = camera.get_focus_distance();
+
+    scene
+        .export_scene(file_path.clone(), false)
+        .expect("Failed to export JSON scene");
+    let imported_scene =
+        Scene::load_scene_from_path(AutoPath::try_from(file_path.clone()).unwrap(), false)
+            .expect("Failed to import JSON scene");
+
+    let camera = imported_scene.get_camera();
+    assert_eq!(camera.get_aperture_radius(), 0.05);
+    assert_eq!(camera.get_focus_distance(), focus_distance);
+    assert_eq!(camera.get_aperture_blades(), 6);
+
+    // An f-stop is converted with the pane distance as focal length
+    let f_stop_path = temp_dir.join("f_stop.json");
+    let json = fs::read_to_string(&file_path)
+        .unwrap()
+        .replace("\"aperture_radius\": 0.05", "\"f_stop\": 2.0");
+    fs::write(&f_stop_path, json).unwrap();
+    let imported_scene =
+        Scene::load_scene_from_path(AutoPath::try_from(f_stop_path).unwrap(), true)
+            .expect("Failed to import JSON scene with f-stop");
+    let f_stop = imported_scene.get_camera().get_f_stop().unwrap();
+    assert!((f_stop - 2.0).abs() < 1e-4);
+
+    let _ = fs::remove_dir_all(temp_dir);
+}
+
+#[test]
+fn test_camera_projection_round_trip() {
+    let temp_dir = setup_temp_dir();
+
+    let projections = [
+        Projection::Perspective,
+        Projection::Orthographic { width: 4.5 },
+        Projection::Equirectangular,
+        Projection::StereoSideBySide {
+            interocular_distance: 0.1,
+        },
+        Projection::StereoTopBottom {
+            interocular_distance: 0.0,
+        },
+    ];
+    for projection in projections {
+        let file_path = temp_dir.join(format!("{}.json", projection.name()));
+        let mut scene = create_test_scene("ProjectionTest");
+        scene.get_camera_mut().set_projection(projection);
+
+        scene
+            .export_scene(file_path.clone(), false)
+            .expect("Failed to export JSON scene");
+        let imported_scene =
+            Scene::load_scene_from_path(AutoPath::try_from(file_path).unwrap(), false)
+                .expect("Failed to import JSON scene");
+        assert_eq!(imported_scene.get_camera().get_projection(), projection);
+    }
+
+    let _ = fs::remove_dir_all(temp_dir);
+}
+
+#[test]
+fn test_light_types_round_trip() {
+    let temp_dir = setup_temp_dir();
+    let file_path = temp_dir.join("lights.json");
+
+    let light_types = [
+        LightType::Directional { angle: 1.5 },
+        LightType::Spot {
+            inner_angle: 10.0,
+            outer_angle: 25.0,
+        },
+        LightType::Rect {
+            width: 2.0,
+            height: 0.5,
+        },
+        LightType::Disc { radius: 0.75 },
+    ];
+    let mut scene = create_test_scene("LightTest");
+    for (i, light_type) in light_types.iter().enumerate() {
+        scene.add_lightsource(LightSource::new(
+            Vec3::new(0.0, 3.0, i as f32),
+            5.0,
+            [1.0, 0.5, 0.25],
+            format!("{light_type}"),
+            Vec3::new(0.0, 30.0, 0.0),
+            *light_type,
+        ));
+    }
+
+    scene
+        .export_scene(file_path.clone(), false)
+        .expect("Failed to export JSON scene");
+    let imported_scene =
+        Scene::load_scene_from_path(AutoPath::try_from(file_path.clone()).unwrap(), false)
+            .expect("Failed to import JSON scene");
+
+    let lights = imported_scene.get_light_sources();
+    assert_eq!(lights.len(), 1 + light_types.len());
+    assert_eq!(*lights[0].get_light_type(), LightType::Point);
+    for (light, light_type) in lights[1..].iter().zip(light_types) {
+        assert_eq!(*light.get_light_type(), light_type);
+        assert_eq!(light.get_rotation(), Vec3::new(0.0, 30.0, 0.0));
+    }
+
+    // Unknown types fall back to point lights, missing parameters to their defaults
+    let json = fs::read_to_string(&file_path)
+        .unwrap()
+        .replace("\"type\": \"directional\"", "\"type\": \"ambient\"")
+        .replace("\"radius\": 0.75", "\"unused\": 0.75");
+    let edited_path = temp_dir.join("lights_edited.json");
+    fs::write(&edited_path, json).unwrap();
+    let imported_scene =
+        Scene::load_scene_from_path(AutoPath::try_from(edited_path).unwrap(), false)
+            .expect("Failed to import edited JSON scene");
+    let lights = imported_scene.get_light_sources();
+    assert_eq!(*lights[1].get_light_type(), LightType::Point);
+    assert_eq!(*lights[4].get_light_type(), LightType::Disc { radius: 0.5 });
+
+    let _ = fs::remove_dir_all(temp_dir);
+}
+
+#[test]
+fn test_mesh_instances_round_trip() {
+    let temp_dir = setup_temp_dir();
+    let file_path = temp_dir.join("instances.json");
+
+    let mut scene = create_test_scene("InstanceTest");
+    scene
+        .load_object_from_file(
+            AutoPath::try_from("$INCLUDED/fixtures/scenes/obj/cube_bare.obj").unwrap(),
+        )
+        .expect("Failed to load fixture mesh");
+    scene
+        .add_mesh_instance(0, Vec3::new(3.0, 0.0, 0.0), Vec3::ZERO, 1.0)
+        .unwrap();
+    scene
+        .add_mesh_instance(0, Vec3::new(0.0, 0.0, -2.0), Vec3::new(0.0, 45.0, 0.0), 0.5)
+        .unwrap();
+    assert!(
+        scene
+            .add_mesh_instance(1, Vec3::ZERO, Vec3::ZERO, 1.0)
+            .is_err()
+    );
+
+    scene
+        .export_scene(file_path.clone(), false)
+        .expect("Failed to export JSON scene");
+
+    // The object file is referenced once, with the instances as further placements
+    let json: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&file_path).unwrap()).unwrap();
+    assert_eq!(json["objects"].as_array().unwrap().len(), 1);
+    assert_eq!(json["objects"][0]["instances"].as_array().unwrap().len(), 2);
+
+    let mut imported_scene =
+        Scene::load_scene_from_path(AutoPath::try_from(file_path).unwrap(), false)
+            .expect("Failed to import JSON scene");
+    assert_eq!(imported_scene.get_meshes().len(), 1);
+    let instances = imported_scene.get_mesh_instances();
+    assert_eq!(instances.len(), 2);
+    assert!(instances.iter().all(|i| i.get_mesh_index() == 0));
+    assert_eq!(instances[0].get_translation(), Vec3::new(3.0, 0.0, 0.0));
+    assert_eq!(instances[1].get_scale(), Vec3::splat(0.5));
+    assert!((instances[1].get_rotation() - Vec3::new(0.0, 45.0, 0.0)).length() < 1e-3);
+
+    // Instances go with the mesh they share
+    imported_scene.remove_mesh(0);
+    assert!(imported_scene.get_mesh_instances().is_empty());
+
+    let _ = fs::remove_dir_all(temp_dir);
+}
+
+#[test]
+fn test_mesh_pattern_round_trip() {
+    let temp_dir = setup_temp_dir();
+    let file_path = temp_dir.join("mesh_pattern.json");
+
+    let mut scene = create_test_scene("MeshPatternTest");
+    for _ in 0..2 {
+        scene
+            .load_object_from_file(
+                AutoPath::try_from("$INCLUDED/fixtures/scenes/obj/cube_bare.obj").unwrap(),
+            )
+            .expect("Failed to load fixture mesh");
+    }
+    let mut pattern = Pattern::new(PatternKind::Noise);
+    pattern.scale = 0.5;
+    pattern.colors = [[0.75, 0.5, 0.25], [0.0, 0.0, 0.5]];
+    pattern.octaves = 3;
+    scene.get_meshes_mut()[1].set_pattern(Some(pattern.clone()));
+
+    scene
+        .export_scene(file_path.clone(), false)
+        .expect("Failed to export JSON scene");
+
+    // Only the mesh with a pattern writes one
+    let json: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&file_path).unwrap()).unwrap();
+    assert!(json["objects"][0].get("pattern").is_none());
+    assert_eq!(json["objects"][1]["pattern"]["kind"], "noise");
+
+    let imported_scene = Scene::load_scene_from_path(AutoPath::try_from(file_path).unwrap(), false)
+        .expect("Failed to import JSON scene");
+    let meshes = imported_scene.get_meshes();
+    assert_eq!(meshes.len(), 2);
+    assert_eq!(meshes[0].get_pattern(), None);
+    assert_eq!(meshes[1].get_pattern(), Some(&pattern));
+
+    let _ = fs::remove_dir_all(temp_dir);
+}
+
+#[test]
+fn test_export_render_img_with_aov_layers() {
+    let temp_dir = setup_temp_dir();
+    let mut scene = Scene::new();
+
+    // 2x1 frame, the left pixel hit an object, the right one is background
+    let frame = Frame::new(2, 1, vec![255; 8]).with_layers(vec![
+        FrameLayer::new("depth", vec![4.0, 4.0, 4.0, 1.0, 0.0, 0.0, 0.0, 0.0]),
+        FrameLayer::new("normal", vec![0.0, 1.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0]),
+        FrameLayer::new("albedo", vec![1.0, 1.0, 1.0, 1.0, 0.0, 0.0, 0.0, 0.0]),
+        FrameLayer::new("raw", vec![1.0; 8]),
+    ]);
+    scene.set_last_render(frame);
+    scene.set_aovs(AovSet::empty().with(Aov::Depth).with(Aov::Normal));
+
+    scene
+        .export_render_img(temp_dir.join("render.png"))
+        .expect("Export failed");
+    scene
+        .export_render_img(temp_dir.join("render.exr"))
+        .expect("EXR export failed");
+
+    assert!(temp_dir.join("render.png").exists());
+    let depth = image::open(temp_dir.join("render_depth.png"))
+        .unwrap()
+        .to_rgba8();
+    assert_eq!(depth.get_pixel(0, 0).0, [255, 255, 255, 255]);
+    assert_eq!(depth.get_pixel(1, 0).0, [0, 0, 0, 255]);
+    let normal = image::open(temp_dir.join("render_normal.png"))
+        .unwrap()
+        .to_rgba8();
+    assert_eq!(normal.get_pixel(0, 0).0, [127, 255, 127, 255]);
+    // Guide layers the user didn't request are skipped, the denoiser's input is always saved
+    assert!(!temp_dir.join("render_albedo.png").exists());
+    assert!(temp_dir.join("render_raw.png").exists());
+
+    // OpenEXR keeps the raw values
+    let depth = image::open(temp_dir.join("render_depth.exr"))
+        .unwrap()
+        .to_rgba32f();
+    assert_eq!(depth.get_pixel(0, 0).0, [4.0, 4.0, 4.0, 1.0]);
+
+    let _ = fs::remove_dir_all(temp_dir);
+}
+
+#[test]
+fn test_sample_heatmap_colors() {
+    use crate::data_plane::scene_io::img_export::visualize_layer;
+    use frame_buffer::frame_iterator::samples_layer;
+
+    // Every pixel converged after the same number of samples
+    let uniform = visualize_layer(&samples_layer(&[16.0; 4]));
+    assert!(uniform.chunks(4).all(|p| p == &uniform[..4]));
+
+    // Few samples are blue, half as many as the maximum green and the most sampled pixel red
+    let heatmap = visualize_layer(&samples_layer(&[0.0, 32.0, 64.0]));
+    assert_eq!(&heatmap[..4], [0, 0, 127, 255]);
+    assert_eq!(&heatmap[8..], [127, 0, 0, 255]);
+    assert!(heatmap[5] > heatmap[4] && heatmap[5] > heatmap[6]);
+}
@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 use image::ImageFormat;
-use engine_config::TextureData;
+use engine_config::{ColorSpace, TextureData, WrapMode};
+use scene_objects::material::Material;
 use crate::included_files::AutoPath;
 
 #[derive(Default)]
@@ -49,4 +50,51 @@ impl TextureCache {
         let idx = keys.iter().position(|k| *k == search_key).unwrap_or(0) as i32;
         Ok(idx)
     }
+
+    /// Sets how UVs outside of [0, 1] are handled for an already loaded texture
+    pub fn set_wrap_mode(&mut self, path: AutoPath, wrap_mode: WrapMode) {
+        if let Ok(key) = AutoPath::try_from(path.path_buf())
+            && let Some(texture) = self.map.get_mut(&key)
+        {
+            texture.wrap_mode = wrap_mode;
+        }
+    }
+
+    /// Sets how the texels of an already loaded texture are encoded
+    pub fn set_color_space(&mut self, path: AutoPath, color_space: ColorSpace) {
+        if let Ok(key) = AutoPath::try_from(path.path_buf())
+            && let Some(texture) = self.map.get_mut(&key)
+        {
+            texture.color_space = color_space;
+        }
+    }
+}
+
+/// ## Returns
+/// The wrap mode a material requests for one of its texture files through the
+/// `-clamp on` and `-mirror on` map options, repeat otherwise
+pub fn material_wrap_mode(material: &Material, path: &String) -> WrapMode {
+    if material.clamped_textures.contains(path) {
+        WrapMode::Clamp
+    } else if material.mirrored_textures.contains(path) {
+        WrapMode::Mirror
+    } else {
+        WrapMode::Repeat
+    }
+}
+
+/// ## Returns
+/// The encoding of one of the texture files of a material: sRGB for the color maps
+/// `map_Kd`, `map_Ks` and `map_Ke`, linear for data maps such as roughness or normals
+pub fn material_color_space(material: &Material, path: &String) -> ColorSpace {
+    let color_maps = [
+        &material.texture_path,
+        &material.maps.specular_path,
+        &material.maps.emissive_path,
+    ];
+    if color_maps.into_iter().flatten().any(|p| p == path) {
+        ColorSpace::Srgb
+    } else {
+        ColorSpace::Linear
+    }
 }
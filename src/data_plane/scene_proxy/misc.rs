@@ -10,6 +10,9 @@ pub(crate) struct Misc {
     pub(crate) ray_samples: u32,
     pub(crate) spheres: Vec<ProxySphere>,
     pub(crate) render_param: RenderParameter,
+    /// File name of the loaded environment map
+    #[serde(default)]
+    pub(crate) environment: Option<String>,
 }
 impl Misc {
     pub fn new_from_scene(scene: &Scene) -> Self {
@@ -21,6 +24,13 @@ impl Misc {
             ray_samples: scene.get_camera().get_ray_samples(),
             spheres,
             render_param: scene.get_render_parameter(),
+            environment: scene.get_environment().map(|e| {
+                e.get_path()
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .to_string()
+            }),
         }
     }
 }
@@ -30,6 +40,7 @@ impl Default for Misc {
             ray_samples: 50,
             spheres: vec![],
             render_param: RenderParameter::default(),
+            environment: None,
         }
     }
 }
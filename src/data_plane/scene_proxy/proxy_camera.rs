@@ -1,4 +1,4 @@
-use scene_objects::camera::{Camera};
+use scene_objects::camera::{Camera, Projection};
 use serde::{Deserialize, Serialize};
 
 use crate::data_plane::scene_proxy::position::Vec3d;
@@ -12,6 +12,11 @@ pub(crate) struct ProxyCamera {
     pub resolution: [u32; 2],
     pub look_at: Vec3d,
     pub up: Vec3d,
+    pub aperture_radius: f32,
+    pub focus_distance: f32,
+    pub aperture_blades: u32,
+    #[serde(skip)]
+    pub projection: Projection,
 }
 
 impl ProxyCamera {
@@ -29,6 +34,10 @@ impl ProxyCamera {
             ],
             look_at: camera.get_look_at().into(),
             up: camera.get_up().into(),
+            aperture_radius: camera.get_aperture_radius(),
+            focus_distance: camera.get_focus_distance(),
+            aperture_blades: camera.get_aperture_blades(),
+            projection: camera.get_projection(),
         }
     }
 }
@@ -42,5 +51,9 @@ impl PartialEq<Camera> for ProxyCamera {
             && self.resolution[1] == other.get_resolution().height
             && self.look_at == other.get_look_at()
             && self.up == other.get_up()
+            && self.aperture_radius == other.get_aperture_radius()
+            && self.focus_distance == other.get_focus_distance()
+            && self.aperture_blades == other.get_aperture_blades()
+            && self.projection == other.get_projection()
     }
 }
@@ -1,4 +1,4 @@
-use scene_objects::light_source::{LightSource};
+use scene_objects::light_source::{LightSource, LightType};
 use serde::{Deserialize, Serialize};
 
 use crate::data_plane::scene_proxy::{color::Color, position::Vec3d};
@@ -8,10 +8,9 @@ pub(crate) struct ProxyLight {
     pub luminosity: f32,
     pub name: String,
     pub color: Color,
-    #[serde(rename = "type")]
-    //pub light_type: String,
-    // todo: 'type' is a rust keyword! rename in serialization
     pub rotation: Vec3d,
+    #[serde(skip)]
+    pub light_type: LightType,
 }
 
 impl ProxyLight {
@@ -21,8 +20,8 @@ impl ProxyLight {
             luminosity: light.get_luminositoy(),
             name: light.get_name().to_string(),
             color: light.get_color().into(),
-            //light_type: (*light.get_light_type()).into(),
             rotation: light.get_rotation().into(),
+            light_type: *light.get_light_type(),
         }
     }
 }
@@ -42,12 +41,12 @@ impl Default for ProxyLight {
                 g: 1.0,
                 b: 1.0,
             },
-            //light_type: LightType::Point.into(),
             rotation: Vec3d {
                 x: 0.0,
                 y: 0.0,
-                z: -1.0,
+                z: 0.0,
             },
+            light_type: LightType::Point,
         }
     }
 }
@@ -60,7 +59,7 @@ impl From<ProxyLight> for LightSource {
             value.color.into(),
             value.name,
             value.rotation.into(),
-            //value.light_type.into(),
+            value.light_type,
         )
     }
 }
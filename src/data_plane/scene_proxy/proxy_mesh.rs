@@ -1,5 +1,5 @@
 use std::path::PathBuf;
-use scene_objects::{geometric_object::SceneObject, mesh::Mesh};
+use scene_objects::{geometric_object::SceneObject, material::Pattern, mesh::Mesh};
 use serde::{Deserialize, Serialize};
 use crate::data_plane::scene_proxy::position::Vec3d;
 
@@ -11,6 +11,8 @@ pub(crate) struct ProxyMesh {
     pub scale: Vec3d,
     pub rotation: Vec3d,
     pub translation: Vec3d,
+    #[serde(skip)]
+    pub pattern: Option<Pattern>, // replaces the patterns of all materials of the mesh
 }
 
 impl ProxyMesh {
@@ -21,6 +23,7 @@ impl ProxyMesh {
             scale: mesh.get_scale().into(),
             rotation: mesh.get_rotation().into(),
             translation: mesh.get_translation().into(),
+            pattern: mesh.get_pattern().cloned(),
         }
     }
 }
@@ -45,6 +48,7 @@ impl Default for ProxyMesh {
                 y: 0.0,
                 z: 0.0,
             },
+            pattern: None,
         }
     }
 }
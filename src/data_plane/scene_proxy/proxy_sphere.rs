@@ -1,14 +1,17 @@
-use scene_objects::sphere::Sphere;
+use scene_objects::{geometric_object::SceneObject, sphere::Sphere};
 use serde::{Deserialize, Serialize};
-use scene_objects::material::{MaterialRef};
+use scene_objects::material::{MaterialRef, Pattern};
 use crate::data_plane::scene_proxy::{color::Color, position::Vec3d};
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ProxySphere {
     pub radius: f32,
     pub center: Vec3d,
+    pub rotation: Vec3d,
     pub color: Color,
     #[serde(skip)]
     pub material_ref: MaterialRef, // make this also optional?
+    #[serde(skip)]
+    pub pattern: Option<Pattern>, // edited separately from the material preset
 }
 
 impl ProxySphere {
@@ -16,8 +19,10 @@ impl ProxySphere {
         Self {
             radius: sphere.get_radius(),
             center: sphere.get_center().into(),
+            rotation: sphere.get_rotation().into(),
             color: sphere.get_color().into(),
-            material_ref: sphere.get_material().clone().into(),
+            material_ref: sphere.get_material().clone().with_pattern(None).into(),
+            pattern: sphere.get_material().pattern.clone(),
         }
     }
 }
@@ -31,12 +36,18 @@ impl Default for ProxySphere {
                 y: 0.0,
                 z: 0.0,
             },
+            rotation: Vec3d {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            },
             color: Color {
                 r: 1.0,
                 g: 1.0,
                 b: 1.0,
             },
             material_ref: MaterialRef::default(),
+            pattern: None,
         }
     }
 }